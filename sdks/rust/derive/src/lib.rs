@@ -0,0 +1,364 @@
+//! Derive macros for the mongo-do SDK.
+//!
+//! Use them through `mongo_do` with the `derive` feature rather than
+//! depending on this crate directly.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::Model;
+//!
+//! #[derive(Serialize, Deserialize, Model)]
+//! #[mongo(collection = "users", index(fields(email), unique))]
+//! #[mongo(index(fields(team, created_at = -1), name = "team_recent"))]
+//! struct User {
+//!     #[serde(rename = "_id")]
+//!     id: ObjectId,
+//!     email: String,
+//!     team: String,
+//!     created_at: DateTime,
+//! }
+//!
+//! db.sync_indexes::<User>().await?;
+//! ```
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::meta::ParseNestedMeta;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, Fields, Lit, LitInt, LitStr, Token, UnOp};
+
+/// Derive `mongo_do::Model` for a struct with named fields.
+///
+/// Struct attributes, under `#[mongo(...)]`:
+///
+/// - `collection = "name"`: the collection, required.
+/// - `index(fields(a, b = -1), unique, sparse, name = "n", expire_after_secs = 60)`:
+///   an index, repeatable. Fields are ascending unless given a direction,
+///   and named as written in Rust. The index is on the name serde stores
+///   them under, following `#[serde(rename)]` and `#[serde(rename_all)]`.
+///
+/// The `_id` field is the one marked `#[mongo(id)]`, else the one serde
+/// stores as `_id`. An `Option<_>` `_id` is set to the generated ID by
+/// `Collection::insert_one_mut`.
+#[proc_macro_derive(Model, attributes(mongo))]
+pub fn derive_model(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// An index declared with `#[mongo(index(...))]`.
+#[derive(Default)]
+struct Index {
+    fields: Vec<(syn::Ident, i32)>,
+    unique: bool,
+    sparse: bool,
+    name: Option<String>,
+    expire_after_secs: Option<u64>,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut collection = None;
+    let mut indexes = Vec::new();
+    for attr in input.attrs.iter().filter(|attr| attr.path().is_ident("mongo")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("collection") {
+                collection = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else if meta.path.is_ident("index") {
+                indexes.push(parse_index(&meta)?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `collection` or `index`"))
+            }
+        })?;
+    }
+    let collection = collection.ok_or_else(|| {
+        syn::Error::new_spanned(
+            &input.ident,
+            "Model needs a collection: #[mongo(collection = \"name\")]",
+        )
+    })?;
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(syn::Error::new_spanned(&input.ident, "Model needs named fields")),
+        },
+        _ => return Err(syn::Error::new_spanned(&input.ident, "Model can only derive structs")),
+    };
+    let rename_all = serde_rename_all(&input.attrs)?;
+    let id = find_id_field(fields, rename_all.as_deref())?.ok_or_else(|| {
+        syn::Error::new_spanned(
+            &input.ident,
+            "Model needs an _id field: rename it with #[serde(rename = \"_id\")] \
+             or mark it with #[mongo(id)]",
+        )
+    })?;
+    let id_ident = &id.ident;
+    let id_ty = &id.ty;
+    let set_id = is_option(id_ty).then(|| {
+        quote! {
+            fn set_id(&mut self, id: ::mongo_do::bson::Bson) {
+                if let ::std::result::Result::Ok(id) = ::mongo_do::bson::from_bson(id) {
+                    self.#id_ident = ::std::option::Option::Some(id);
+                }
+            }
+        }
+    });
+
+    let index_models = indexes
+        .iter()
+        .map(|index| index_model(index, fields, rename_all.as_deref()))
+        .collect::<syn::Result<Vec<_>>>()?;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::mongo_do::model::Model for #ident #ty_generics #where_clause {
+            type Id = #id_ty;
+
+            const COLLECTION: &'static str = #collection;
+
+            fn id(&self) -> &Self::Id {
+                &self.#id_ident
+            }
+
+            fn indexes() -> ::std::vec::Vec<::mongo_do::IndexModel> {
+                ::std::vec![#(#index_models),*]
+            }
+
+            #set_id
+        }
+    })
+}
+
+/// Parse the contents of `index(...)`.
+fn parse_index(meta: &ParseNestedMeta) -> syn::Result<Index> {
+    let mut index = Index::default();
+    meta.parse_nested_meta(|meta| {
+        if meta.path.is_ident("fields") {
+            meta.parse_nested_meta(|field| {
+                let name = field
+                    .path
+                    .get_ident()
+                    .ok_or_else(|| field.error("expected a field name"))?
+                    .clone();
+                let direction = if field.input.peek(Token![=]) {
+                    parse_direction(&field.value()?.parse()?)?
+                } else {
+                    1
+                };
+                index.fields.push((name, direction));
+                Ok(())
+            })
+        } else if meta.path.is_ident("unique") {
+            index.unique = true;
+            Ok(())
+        } else if meta.path.is_ident("sparse") {
+            index.sparse = true;
+            Ok(())
+        } else if meta.path.is_ident("name") {
+            index.name = Some(meta.value()?.parse::<LitStr>()?.value());
+            Ok(())
+        } else if meta.path.is_ident("expire_after_secs") {
+            index.expire_after_secs = Some(meta.value()?.parse::<LitInt>()?.base10_parse()?);
+            Ok(())
+        } else {
+            Err(meta.error("expected `fields`, `unique`, `sparse`, `name` or `expire_after_secs`"))
+        }
+    })?;
+    if index.fields.is_empty() {
+        return Err(meta.error("an index needs `fields(...)`"));
+    }
+    Ok(index)
+}
+
+/// Parse an index direction: `1` or `-1`.
+fn parse_direction(expr: &Expr) -> syn::Result<i32> {
+    let (negative, lit) = match expr {
+        Expr::Unary(unary) if matches!(unary.op, UnOp::Neg(_)) => (true, &*unary.expr),
+        other => (false, other),
+    };
+    let value = match lit {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Int(int) => int.base10_parse::<i32>()?,
+            _ => 0,
+        },
+        _ => 0,
+    };
+    match (negative, value) {
+        (false, 1) => Ok(1),
+        (true, 1) => Ok(-1),
+        _ => Err(syn::Error::new_spanned(expr, "index direction must be 1 or -1")),
+    }
+}
+
+/// Find the field holding the `_id`.
+fn find_id_field<'a>(
+    fields: &'a syn::punctuated::Punctuated<syn::Field, Token![,]>,
+    rename_all: Option<&str>,
+) -> syn::Result<Option<&'a syn::Field>> {
+    for field in fields {
+        for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("mongo")) {
+            let mut is_id = false;
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("id") {
+                    is_id = true;
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `id`"))
+                }
+            })?;
+            if is_id {
+                return Ok(Some(field));
+            }
+        }
+    }
+    for field in fields {
+        if serialized_name(field, rename_all)? == "_id" {
+            return Ok(Some(field));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether `ty` is written as an `Option<_>`.
+fn is_option(ty: &syn::Type) -> bool {
+    match ty {
+        syn::Type::Path(path) => path.qself.is_none()
+            && path.path.segments.last().is_some_and(|segment| {
+                segment.ident == "Option"
+                    && matches!(segment.arguments, syn::PathArguments::AngleBracketed(_))
+            }),
+        _ => false,
+    }
+}
+
+/// The name serde stores `field` under.
+fn serialized_name(field: &syn::Field, rename_all: Option<&str>) -> syn::Result<String> {
+    if let Some(rename) = serde_option(&field.attrs, "rename") {
+        return Ok(rename);
+    }
+    let name = field.ident.as_ref().map(IdentExt::unraw).map(|ident| ident.to_string());
+    let name = name.unwrap_or_default();
+    match rename_all {
+        Some(rule) => rename_field(&name, rule).ok_or_else(|| {
+            syn::Error::new_spanned(field, format!("unknown rename rule `{}`", rule))
+        }),
+        None => Ok(name),
+    }
+}
+
+/// The `#[serde(rename_all = "...")]` rule of a struct, if any.
+fn serde_rename_all(attrs: &[syn::Attribute]) -> syn::Result<Option<String>> {
+    let rule = serde_option(attrs, "rename_all");
+    if let Some(ref rule) = rule {
+        if rename_field("a", rule).is_none() {
+            let attr = attrs.iter().find(|attr| attr.path().is_ident("serde"));
+            return Err(syn::Error::new_spanned(attr, format!("unknown rename rule `{}`", rule)));
+        }
+    }
+    Ok(rule)
+}
+
+/// The value of `#[serde(option = "...")]`, or of its serialized side in
+/// `#[serde(option(serialize = "..."))]`, if any.
+fn serde_option(attrs: &[syn::Attribute], option: &str) -> Option<String> {
+    let mut value = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        // Other serde options are not ours to validate.
+        let _ = attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(option) && meta.input.peek(Token![=]) {
+                value = Some(meta.value()?.parse::<LitStr>()?.value());
+            } else if meta.path.is_ident(option) && meta.input.peek(syn::token::Paren) {
+                meta.parse_nested_meta(|side| {
+                    if side.path.is_ident("serialize") {
+                        value = Some(side.value()?.parse::<LitStr>()?.value());
+                    } else {
+                        side.value()?.parse::<LitStr>()?;
+                    }
+                    Ok(())
+                })?;
+            } else if meta.input.peek(Token![=]) {
+                meta.value()?.parse::<Expr>()?;
+            } else if meta.input.peek(syn::token::Paren) {
+                meta.parse_nested_meta(|_| Ok(()))?;
+            }
+            Ok(())
+        });
+    }
+    value
+}
+
+/// Rename a snake_case field as serde's `rename_all = rule` does, or return
+/// `None` for an unknown rule.
+fn rename_field(name: &str, rule: &str) -> Option<String> {
+    let capitalize = |word: &str| {
+        let mut chars = word.chars();
+        chars.next().map_or_else(String::new, |first| first.to_uppercase().chain(chars).collect())
+    };
+    Some(match rule {
+        "lowercase" | "snake_case" => name.to_string(),
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => name.to_ascii_uppercase(),
+        "PascalCase" => name.split('_').map(capitalize).collect(),
+        "camelCase" => {
+            let pascal: String = name.split('_').map(capitalize).collect();
+            let mut chars = pascal.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first.to_lowercase().chain(chars).collect()
+            })
+        }
+        "kebab-case" => name.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => name.to_ascii_uppercase().replace('_', "-"),
+        _ => return None,
+    })
+}
+
+/// The `IndexModel` expression for an index, failing on a field the struct
+/// does not have.
+fn index_model(
+    index: &Index,
+    fields: &syn::punctuated::Punctuated<syn::Field, Token![,]>,
+    rename_all: Option<&str>,
+) -> syn::Result<TokenStream2> {
+    let names = index
+        .fields
+        .iter()
+        .map(|(name, _)| {
+            let field = fields
+                .iter()
+                .find(|field| field.ident.as_ref().is_some_and(|ident| *ident == *name))
+                .ok_or_else(|| {
+                    syn::Error::new_spanned(name, format!("no field `{}` to index", name))
+                })?;
+            serialized_name(field, rename_all)
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+    let directions = index.fields.iter().map(|(_, direction)| direction);
+    let mut options = quote! { ::mongo_do::IndexOptions::builder() };
+    if index.unique {
+        options = quote! { #options.unique(true) };
+    }
+    if index.sparse {
+        options = quote! { #options.sparse(true) };
+    }
+    if let Some(name) = &index.name {
+        options = quote! { #options.name(#name) };
+    }
+    if let Some(secs) = index.expire_after_secs {
+        options = quote! { #options.expire_after(::std::time::Duration::from_secs(#secs)) };
+    }
+    Ok(quote! {
+        ::mongo_do::IndexModel::new(
+            {
+                let mut keys = ::mongo_do::bson::Document::new();
+                #(keys.insert(#names, #directions);)*
+                keys
+            },
+            #options.build(),
+        )
+    })
+}
@@ -0,0 +1,298 @@
+//! Moving old documents out of a hot collection.
+//!
+//! [`Collection::archive`] moves the documents matching a filter to an
+//! [`ArchiveTarget`] in batches of ascending `_id`. Each batch is copied,
+//! the copy is verified, and only then are the originals deleted, so a
+//! failure never loses documents. Because archived documents leave the
+//! source, running the same archive again after a failure continues where
+//! it stopped.
+//!
+//! Copies to a collection skip documents already there, so a batch
+//! interrupted between copy and delete is not copied twice. A file target
+//! is appended to, and such a batch is written to it again.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::archive::{ArchiveOptions, ArchiveTarget};
+//!
+//! let cutoff = DateTime::from_millis(now_ms - 90 * 86_400_000);
+//! let target = ArchiveTarget::collection(&archive_db.collection::<Document>("orders"));
+//! let result = orders
+//!     .archive_with_progress(
+//!         doc! { "createdAt": { "$lt": cutoff } },
+//!         target,
+//!         ArchiveOptions::builder().batch_size(500).build(),
+//!         |progress| println!("{} archived", progress.documents),
+//!     )
+//!     .await?;
+//! ```
+
+use crate::collection::{and_filter, Collection, FindOptions};
+use crate::error::{MongoError, Result};
+use crate::progress::{Progress, ProgressTracker};
+use bson::{doc, Bson, Document};
+use serde_json::Value as JsonValue;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+
+/// Default number of documents moved per batch.
+const DEFAULT_BATCH_SIZE: u32 = 1000;
+
+/// Where [`Collection::archive`] moves documents.
+pub enum ArchiveTarget {
+    /// Another collection, possibly in another database.
+    Collection(Box<Collection<JsonValue>>),
+    /// A file of newline-delimited extended JSON, appended to.
+    File(PathBuf),
+}
+
+impl ArchiveTarget {
+    /// Archive into `collection`.
+    pub fn collection<U>(collection: &Collection<U>) -> Self {
+        ArchiveTarget::Collection(Box::new(collection.clone_with_type()))
+    }
+
+    /// Archive into the file at `path`, creating it if needed.
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        ArchiveTarget::File(path.into())
+    }
+
+    /// Copy `batch`, whose `_id`s are `ids`, and check that the copy is complete.
+    async fn copy(&self, batch: &[JsonValue], ids: &[Bson]) -> Result<()> {
+        match self {
+            ArchiveTarget::Collection(target) => {
+                let in_batch = doc! { "_id": { "$in": ids } };
+                let options = FindOptions::builder().projection(doc! { "_id": 1 }).build();
+                let existing: HashSet<Bson> = target
+                    .find_with_options(in_batch.clone(), options)
+                    .await?
+                    .collect()
+                    .await?
+                    .iter()
+                    .filter_map(|doc| doc.get("_id").map(|id| target.rpc_client.decode(id)))
+                    .collect();
+                let missing = batch
+                    .iter()
+                    .zip(ids)
+                    .filter(|(_, id)| !existing.contains(*id))
+                    .map(|(doc, _)| doc);
+                if existing.len() < ids.len() {
+                    target.insert_many(missing).await?;
+                }
+                let copied = target.count_documents(in_batch).await?;
+                if copied != ids.len() as u64 {
+                    return Err(MongoError::Internal(format!(
+                        "archive found {} of {} copied documents in {}",
+                        copied,
+                        ids.len(),
+                        target.namespace()
+                    )));
+                }
+            }
+            ArchiveTarget::File(path) => {
+                let mut file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|e| file_error(path, e))?;
+                let before = file.metadata().map_err(|e| file_error(path, e))?.len();
+                let mut lines = Vec::new();
+                for doc in batch {
+                    serde_json::to_writer(&mut lines, doc)?;
+                    lines.push(b'\n');
+                }
+                file.write_all(&lines).map_err(|e| file_error(path, e))?;
+                file.sync_all().map_err(|e| file_error(path, e))?;
+                let after = file.metadata().map_err(|e| file_error(path, e))?.len();
+                if after < before + lines.len() as u64 {
+                    return Err(MongoError::Internal(format!(
+                        "archive wrote {} of {} bytes to {}",
+                        after.saturating_sub(before),
+                        lines.len(),
+                        path.display()
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for ArchiveTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveTarget::Collection(target) => f
+                .debug_tuple("Collection")
+                .field(&target.namespace())
+                .finish(),
+            ArchiveTarget::File(path) => f.debug_tuple("File").field(path).finish(),
+        }
+    }
+}
+
+/// Options for [`Collection::archive_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveOptions {
+    /// Documents moved per batch. Defaults to 1000.
+    pub batch_size: Option<u32>,
+    /// Only count the documents that would be archived, changing nothing.
+    pub dry_run: bool,
+}
+
+impl ArchiveOptions {
+    /// Create a builder.
+    pub fn builder() -> ArchiveOptionsBuilder {
+        ArchiveOptionsBuilder::default()
+    }
+}
+
+/// Builder for ArchiveOptions.
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveOptionsBuilder {
+    options: ArchiveOptions,
+}
+
+impl ArchiveOptionsBuilder {
+    /// Set the batch size.
+    pub fn batch_size(mut self, batch_size: u32) -> Self {
+        self.options.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Only count the documents that would be archived.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.options.dry_run = dry_run;
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> ArchiveOptions {
+        self.options
+    }
+}
+
+/// Result of [`Collection::archive`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveResult {
+    /// Documents moved, or that would be moved in a dry run.
+    pub archived: u64,
+    /// Batches moved.
+    pub batches: u64,
+    /// Whether this was a dry run.
+    pub dry_run: bool,
+}
+
+/// Move the documents of `source` matching `filter` to `target`.
+pub(crate) async fn archive(
+    source: Collection<JsonValue>,
+    filter: Document,
+    target: ArchiveTarget,
+    options: ArchiveOptions,
+    mut progress: impl FnMut(&Progress) + Send,
+) -> Result<ArchiveResult> {
+    if options.dry_run {
+        return Ok(ArchiveResult {
+            archived: source.count_documents(filter).await?,
+            batches: 0,
+            dry_run: true,
+        });
+    }
+    let batch_size = options.batch_size.unwrap_or(DEFAULT_BATCH_SIZE);
+    if batch_size == 0 {
+        return Err(MongoError::invalid_argument("batch size must be positive"));
+    }
+    let find = FindOptions::builder()
+        .sort(doc! { "_id": 1 })
+        .limit(i64::from(batch_size))
+        .batch_size(batch_size)
+        .build();
+
+    let mut result = ArchiveResult::default();
+    let mut tracker = ProgressTracker::start();
+    loop {
+        let batch = source
+            .find_with_options(filter.clone(), find.clone())
+            .await?
+            .collect()
+            .await?;
+        if batch.is_empty() {
+            break;
+        }
+        let ids = batch
+            .iter()
+            .map(|doc| {
+                doc.get("_id")
+                    .map(|id| source.rpc_client.decode(id))
+                    .ok_or_else(|| {
+                        MongoError::Deserialization("archived document has no _id".into())
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        target.copy(&batch, &ids).await?;
+        // Documents changed to no longer match since they were read stay.
+        let deleted = source
+            .delete_many(and_filter(&filter, doc! { "_id": { "$in": ids } }))
+            .await?;
+        result.archived += deleted.deleted_count;
+        result.batches += 1;
+        progress(&tracker.record(deleted.deleted_count, &batch));
+
+        if batch.len() < batch_size as usize {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+/// An error reading or writing an archive file.
+fn file_error(path: &std::path::Path, e: std::io::Error) -> MongoError {
+    MongoError::Internal(format!("archive file {}: {}", path.display(), e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_file_target_appends_lines() {
+        let path =
+            std::env::temp_dir().join(format!("mongo-do-archive-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let target = ArchiveTarget::file(&path);
+        let batch = vec![
+            serde_json::json!({ "_id": 1, "status": "old" }),
+            serde_json::json!({ "_id": { "$oid": "65a1b2c3d4e5f60718293a4b" } }),
+        ];
+        let ids = vec![Bson::Int32(1), Bson::Int32(2)];
+        target.copy(&batch, &ids).await.unwrap();
+        target.copy(&batch[..1], &ids[..1]).await.unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<JsonValue> = written
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![batch[0].clone(), batch[1].clone(), batch[0].clone()]
+        );
+        assert!(format!("{:?}", target).starts_with("File("));
+    }
+
+    #[test]
+    fn test_archive_options() {
+        let options = ArchiveOptions::builder()
+            .batch_size(200)
+            .dry_run(true)
+            .build();
+        assert_eq!(options.batch_size, Some(200));
+        assert!(options.dry_run);
+        assert!(!ArchiveOptions::default().dry_run);
+    }
+}
@@ -0,0 +1,331 @@
+//! Query results as Apache Arrow record batches.
+//!
+//! [`Cursor::to_arrow`] reads a cursor into [`RecordBatch`]es with a given
+//! schema, one per batch fetched from the server, without deserializing each
+//! document into a struct.
+//!
+//! Columns are read from the field of the same name; a dotted name such as
+//! `address.city` reads a nested field. Values are coerced to the column
+//! type:
+//!
+//! | Column type              | Accepted values                                   |
+//! |--------------------------|---------------------------------------------------|
+//! | `Boolean`                | booleans                                          |
+//! | `Int32`, `Int64`         | integers that fit, and doubles without a fraction |
+//! | `Float64`                | doubles and integers                              |
+//! | `Utf8`                   | strings as is, object IDs as hex, others as JSON  |
+//! | `Timestamp(Millisecond)` | dates, and integers as milliseconds               |
+//!
+//! Missing fields and nulls become nulls in nullable columns and fail in
+//! other columns, as do values that cannot be coerced.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use arrow_schema::{DataType, Field, Schema};
+//!
+//! let schema = Arc::new(Schema::new(vec![
+//!     Field::new("region", DataType::Utf8, false),
+//!     Field::new("amount", DataType::Float64, true),
+//! ]));
+//! let batches = orders.find(doc! {}).await?.to_arrow(schema).await?;
+//! ```
+
+use crate::convert;
+use crate::cursor::Cursor;
+use crate::error::{MongoError, Result};
+use crate::util;
+use arrow_array::builder::{
+    BooleanBuilder, Float64Builder, Int32Builder, Int64Builder, StringBuilder,
+    TimestampMillisecondBuilder,
+};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, SchemaRef, TimeUnit};
+use bson::{Bson, Document};
+use serde::de::DeserializeOwned;
+use std::sync::Arc;
+
+impl<T: DeserializeOwned + Send + Unpin + 'static> Cursor<T> {
+    /// Read the remaining documents into record batches with `schema`.
+    ///
+    /// Each batch fetched from the server becomes one record batch. See the
+    /// [module documentation](crate::arrow) for how values are coerced.
+    pub async fn to_arrow(mut self, schema: SchemaRef) -> Result<Vec<RecordBatch>> {
+        check_schema(&schema)?;
+        let mut batches = Vec::new();
+        while let Some(documents) = self.next_documents().await? {
+            batches.push(documents_to_record_batch(&documents, schema.clone())?);
+        }
+        Ok(batches)
+    }
+}
+
+/// Convert documents to a record batch with `schema`.
+pub fn documents_to_record_batch(documents: &[Document], schema: SchemaRef) -> Result<RecordBatch> {
+    check_schema(&schema)?;
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| build_column(field, documents))
+        .collect::<Result<Vec<_>>>()?;
+    RecordBatch::try_new(schema, columns).map_err(|e| MongoError::Internal(e.to_string()))
+}
+
+/// Fail on column types that cannot be read.
+fn check_schema(schema: &SchemaRef) -> Result<()> {
+    for field in schema.fields() {
+        match field.data_type() {
+            DataType::Boolean
+            | DataType::Int32
+            | DataType::Int64
+            | DataType::Float64
+            | DataType::Utf8
+            | DataType::Timestamp(TimeUnit::Millisecond, _) => {}
+            other => {
+                return Err(MongoError::invalid_argument(format!(
+                    "arrow column '{}' has unsupported type {}",
+                    field.name(),
+                    other
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Build the column for `field` from every document.
+fn build_column(field: &Field, documents: &[Document]) -> Result<ArrayRef> {
+    let values = documents.iter().map(|doc| {
+        let value = util::get_path(doc, field.name()).filter(|value| !matches!(value, Bson::Null));
+        if value.is_none() && !field.is_nullable() {
+            return Err(MongoError::Deserialization(format!(
+                "arrow column '{}' is not nullable but a document has no value",
+                field.name()
+            )));
+        }
+        Ok(value)
+    });
+    let mismatch = |value: &Bson| {
+        MongoError::Deserialization(format!(
+            "cannot convert {:?} to {} for arrow column '{}'",
+            value.element_type(),
+            field.data_type(),
+            field.name()
+        ))
+    };
+
+    Ok(match field.data_type() {
+        DataType::Boolean => {
+            let mut builder = BooleanBuilder::with_capacity(documents.len());
+            for value in values {
+                builder.append_option(
+                    value?
+                        .map(|v| v.as_bool().ok_or_else(|| mismatch(v)))
+                        .transpose()?,
+                );
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Int32 => {
+            let mut builder = Int32Builder::with_capacity(documents.len());
+            for value in values {
+                let value = value?
+                    .map(|v| {
+                        as_i64(v)
+                            .and_then(|n| i32::try_from(n).ok())
+                            .ok_or_else(|| mismatch(v))
+                    })
+                    .transpose()?;
+                builder.append_option(value);
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Int64 => {
+            let mut builder = Int64Builder::with_capacity(documents.len());
+            for value in values {
+                builder.append_option(
+                    value?
+                        .map(|v| as_i64(v).ok_or_else(|| mismatch(v)))
+                        .transpose()?,
+                );
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Float64 => {
+            let mut builder = Float64Builder::with_capacity(documents.len());
+            for value in values {
+                builder.append_option(
+                    value?
+                        .map(|v| as_f64(v).ok_or_else(|| mismatch(v)))
+                        .transpose()?,
+                );
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Utf8 => {
+            let mut builder = StringBuilder::with_capacity(documents.len(), documents.len() * 16);
+            for value in values {
+                builder.append_option(value?.map(as_string));
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Timestamp(TimeUnit::Millisecond, timezone) => {
+            let mut builder = TimestampMillisecondBuilder::with_capacity(documents.len());
+            for value in values {
+                let millis = value?
+                    .map(|v| {
+                        match v {
+                            Bson::DateTime(date) => Some(date.timestamp_millis()),
+                            other => as_i64(other),
+                        }
+                        .ok_or_else(|| mismatch(v))
+                    })
+                    .transpose()?;
+                builder.append_option(millis);
+            }
+            Arc::new(builder.finish().with_timezone_opt(timezone.clone()))
+        }
+        other => {
+            return Err(MongoError::invalid_argument(format!(
+                "arrow column '{}' has unsupported type {}",
+                field.name(),
+                other
+            )))
+        }
+    })
+}
+
+fn as_i64(value: &Bson) -> Option<i64> {
+    match value {
+        Bson::Int32(n) => Some(i64::from(*n)),
+        Bson::Int64(n) => Some(*n),
+        Bson::Double(n) if n.fract() == 0.0 && n.abs() < i64::MAX as f64 => Some(*n as i64),
+        _ => None,
+    }
+}
+
+fn as_f64(value: &Bson) -> Option<f64> {
+    match value {
+        Bson::Int32(n) => Some(f64::from(*n)),
+        Bson::Int64(n) => Some(*n as f64),
+        Bson::Double(n) => Some(*n),
+        _ => None,
+    }
+}
+
+fn as_string(value: &Bson) -> String {
+    match value {
+        Bson::String(s) => s.clone(),
+        Bson::ObjectId(id) => id.to_hex(),
+        other => convert::bson_to_json(other)
+            .map(|json| json.to_string())
+            .unwrap_or_else(|_| other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, Float64Array, Int64Array, StringArray, TimestampMillisecondArray};
+    use arrow_schema::Schema;
+    use bson::doc;
+
+    fn schema(fields: Vec<Field>) -> SchemaRef {
+        Arc::new(Schema::new(fields))
+    }
+
+    #[test]
+    fn test_documents_to_record_batch() {
+        let id = bson::oid::ObjectId::new();
+        let documents = vec![
+            doc! {
+                "_id": id,
+                "amount": 10,
+                "count": 2.0,
+                "at": bson::DateTime::from_millis(1_000),
+                "address": { "city": "Oslo" },
+            },
+            doc! { "_id": "plain", "amount": 2.5, "count": 3_i64, "tags": ["a"] },
+        ];
+        let schema = schema(vec![
+            Field::new("_id", DataType::Utf8, false),
+            Field::new("amount", DataType::Float64, false),
+            Field::new("count", DataType::Int64, false),
+            Field::new("at", DataType::Timestamp(TimeUnit::Millisecond, None), true),
+            Field::new("address.city", DataType::Utf8, true),
+            Field::new("tags", DataType::Utf8, true),
+        ]);
+        let batch = documents_to_record_batch(&documents, schema).unwrap();
+        assert_eq!(batch.num_rows(), 2);
+
+        let ids = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(ids.value(0), id.to_hex());
+        assert_eq!(ids.value(1), "plain");
+        let amounts = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(amounts.values(), &[10.0, 2.5]);
+        let counts = batch
+            .column(2)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(counts.values(), &[2, 3]);
+        let at = batch
+            .column(3)
+            .as_any()
+            .downcast_ref::<TimestampMillisecondArray>()
+            .unwrap();
+        assert_eq!(at.value(0), 1_000);
+        assert!(at.is_null(1));
+        let cities = batch
+            .column(4)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(cities.value(0), "Oslo");
+        assert!(cities.is_null(1));
+        let tags = batch
+            .column(5)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(tags.value(1), r#"["a"]"#);
+    }
+
+    #[test]
+    fn test_record_batch_errors() {
+        let documents = vec![doc! { "count": 1.5 }];
+        let int = schema(vec![Field::new("count", DataType::Int64, true)]);
+        assert!(documents_to_record_batch(&documents, int).is_err());
+
+        let required = schema(vec![Field::new("missing", DataType::Utf8, false)]);
+        assert!(documents_to_record_batch(&documents, required).is_err());
+
+        let unsupported = schema(vec![Field::new("count", DataType::Float16, true)]);
+        let err = documents_to_record_batch(&documents, unsupported).unwrap_err();
+        assert!(matches!(err, MongoError::InvalidArgument(_)));
+    }
+
+    #[tokio::test]
+    async fn test_cursor_to_arrow() {
+        let cursor: Cursor<Document> = Cursor::new(
+            "db.orders".to_string(),
+            vec![
+                serde_json::json!({ "amount": 1 }),
+                serde_json::json!({ "amount": 2 }),
+            ],
+            None,
+        );
+        let schema = schema(vec![Field::new("amount", DataType::Int32, false)]);
+        let batches = cursor.to_arrow(schema).await.unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].num_rows(), 2);
+    }
+}
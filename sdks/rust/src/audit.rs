@@ -0,0 +1,139 @@
+//! Write auditing.
+//!
+//! When [`ClientOptions::audit`](crate::ClientOptions::audit) is set, every
+//! insert, update and delete issued through the client is recorded in the
+//! audit collection. The entry is sent in the same pipelined batch as the
+//! write, so it records the attempt even if the write then fails. An entry
+//! that cannot be recorded never fails its write; it is counted in
+//! [`ClientStats::audit_failures`](crate::ClientStats::audit_failures).
+//! Filters are recorded by shape only: values are replaced by their type, as
+//! by [`util::redact`](crate::util::redact), so audit entries do not copy data.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::audit::AuditOptions;
+//!
+//! let options = ClientOptions::builder()
+//!     .audit(AuditOptions::new("audit", "writes").actor("billing-service"))
+//!     .build();
+//! let client = MongoClient::with_options("mongodb://localhost", options).await?;
+//! ```
+
+use crate::convert::ValueCodec;
+use crate::rpc::Method;
+use crate::util::redact_json;
+use serde_json::Value as JsonValue;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where and how to record audited writes.
+#[derive(Debug, Clone)]
+pub struct AuditOptions {
+    /// Database holding the audit collection.
+    pub database: String,
+    /// Audit collection name.
+    pub collection: String,
+    /// Who is writing. Defaults to the client's operation tag.
+    pub actor: Option<String>,
+}
+
+impl AuditOptions {
+    /// Record writes in the given database and collection.
+    pub fn new(database: impl Into<String>, collection: impl Into<String>) -> Self {
+        Self {
+            database: database.into(),
+            collection: collection.into(),
+            actor: None,
+        }
+    }
+
+    /// Set the actor recorded with each write.
+    pub fn actor(mut self, actor: impl Into<String>) -> Self {
+        self.actor = Some(actor.into());
+        self
+    }
+
+    /// Build the audit entry for a call, or `None` if the call is not an audited write.
+    ///
+    /// The filter is redacted with [`redact`](crate::util::redact) after
+    /// decoding it with `codec`.
+    pub(crate) fn entry(
+        &self,
+        method: Method,
+        args: &[JsonValue],
+        operation_tag: Option<&str>,
+        codec: Option<&dyn ValueCodec>,
+    ) -> Option<JsonValue> {
+        if !method.is_write() {
+            return None;
+        }
+        let db = args.first()?.as_str()?;
+        let coll = args.get(1)?.as_str()?;
+        if db == self.database && coll == self.collection {
+            return None;
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as i64)
+            .unwrap_or_default();
+        let mut entry = serde_json::json!({
+            "ns": format!("{}.{}", db, coll),
+            "op": method.operation(),
+            "timestamp": { "$date": timestamp },
+        });
+        // Inserts and bulk writes carry documents, not a filter.
+        let has_filter = !matches!(
+            method,
+            Method::InsertOne | Method::InsertMany | Method::BulkWrite
+        );
+        if has_filter {
+            if let Some(filter) = args.get(2) {
+                entry["filter"] = redact_json(filter, codec);
+            }
+        }
+        if let Some(actor) = self.actor.as_deref().or(operation_tag) {
+            entry["actor"] = serde_json::json!(actor);
+        }
+        Some(entry)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_for_writes() {
+        let options = AuditOptions::new("audit", "writes");
+        let args = vec![
+            serde_json::json!("shop"),
+            serde_json::json!("orders"),
+            serde_json::json!({ "_id": 7 }),
+            serde_json::json!({ "$set": { "status": "paid" } }),
+        ];
+        let entry = options.entry(Method::UpdateOne, &args, Some("checkout"), None).unwrap();
+        assert_eq!(entry["ns"], "shop.orders");
+        assert_eq!(entry["op"], "updateOne");
+        assert_eq!(entry["filter"], serde_json::json!({ "_id": "long" }));
+        assert_eq!(entry["actor"], "checkout");
+        assert!(entry["timestamp"]["$date"].as_i64().unwrap() > 0);
+
+        let options = options.actor("billing");
+        let entry = options
+            .entry(Method::InsertOne, &args[..3], Some("checkout"), None)
+            .unwrap();
+        assert_eq!(entry["actor"], "billing");
+        assert!(entry.get("filter").is_none());
+    }
+
+    #[test]
+    fn test_entry_skips_reads_and_audit_collection() {
+        let options = AuditOptions::new("audit", "writes");
+        let args = vec![serde_json::json!("shop"), serde_json::json!("orders")];
+        assert!(options.entry(Method::Find, &args, None, None).is_none());
+
+        let args = vec![serde_json::json!("audit"), serde_json::json!("writes")];
+        assert!(options.entry(Method::InsertOne, &args, None, None).is_none());
+    }
+}
@@ -0,0 +1,886 @@
+//! Change streams for watching collection changes.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut stream = orders.watch_where(doc! { "status": "shipped" }).await?;
+//! while let Some(event) = stream.try_next().await? {
+//!     println!("{:?}: {:?}", event.operation_type, event.full_document);
+//! }
+//! ```
+//!
+//! Streams can checkpoint their resume token to a [`ResumeTokenStore`] so a
+//! consumer restarts where it left off:
+//!
+//! ```ignore
+//! let store = Arc::new(CollectionTokenStore::new(db.collection_with_doc("checkpoints")));
+//! let checkpoint = Checkpoint::new(store, "order-shipper")
+//!     .every(CheckpointInterval::Period(Duration::from_secs(5)));
+//! let mut stream = orders.watch_resumable(Vec::new(), None, checkpoint).await?;
+//! ```
+//!
+//! Tokens can also be kept in files with [`FileTokenStore`], or in memory
+//! with [`MemoryTokenStore`].
+
+use crate::collection::{Collection, UpdateOptions};
+use crate::convert::{self, bson_doc_to_json, json_to_bson_doc, ValueCodec};
+#[cfg(feature = "encryption")]
+use crate::encryption::AutoEncrypter;
+use crate::error::{MongoError, Result};
+use crate::rpc::Method;
+use crate::transport::Transport;
+use async_trait::async_trait;
+use bson::{doc, Bson, Document};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::HashMap;
+use std::fmt;
+use std::marker::PhantomData;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Whether change events carry the full document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FullDocument {
+    /// Only insert and replace events carry the full document.
+    Default,
+    /// Update events carry the current version of the document.
+    UpdateLookup,
+    /// Update events carry the post-image when one is available.
+    WhenAvailable,
+    /// Update events must carry the post-image.
+    Required,
+}
+
+impl FullDocument {
+    /// The option value sent to the server.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FullDocument::Default => "default",
+            FullDocument::UpdateLookup => "updateLookup",
+            FullDocument::WhenAvailable => "whenAvailable",
+            FullDocument::Required => "required",
+        }
+    }
+}
+
+/// Options for opening a change stream.
+#[derive(Debug, Clone, Default)]
+pub struct ChangeStreamOptions {
+    /// Whether events carry the full document.
+    pub full_document: Option<FullDocument>,
+    /// Resume after the event with this resume token.
+    pub resume_after: Option<Document>,
+    /// Start after the event with this resume token, even past an invalidate.
+    pub start_after: Option<Document>,
+    /// Batch size for fetching events.
+    pub batch_size: Option<u32>,
+    /// Comment attached to the operation in server logs.
+    pub comment: Option<String>,
+}
+
+impl ChangeStreamOptions {
+    /// Create a new builder.
+    pub fn builder() -> ChangeStreamOptionsBuilder {
+        ChangeStreamOptionsBuilder::default()
+    }
+
+    /// Convert to the options map sent to the server.
+    pub(crate) fn to_document(&self) -> Document {
+        let mut options = Document::new();
+        if let Some(full_document) = self.full_document {
+            options.insert("fullDocument", full_document.as_str());
+        }
+        if let Some(ref resume_after) = self.resume_after {
+            options.insert("resumeAfter", resume_after.clone());
+        }
+        if let Some(ref start_after) = self.start_after {
+            options.insert("startAfter", start_after.clone());
+        }
+        if let Some(batch_size) = self.batch_size {
+            options.insert("batchSize", batch_size as i64);
+        }
+        if let Some(ref comment) = self.comment {
+            options.insert("comment", comment.as_str());
+        }
+        options
+    }
+}
+
+/// Builder for ChangeStreamOptions.
+#[derive(Debug, Default)]
+pub struct ChangeStreamOptionsBuilder {
+    options: ChangeStreamOptions,
+}
+
+impl ChangeStreamOptionsBuilder {
+    /// Set whether events carry the full document.
+    pub fn full_document(mut self, full_document: FullDocument) -> Self {
+        self.options.full_document = Some(full_document);
+        self
+    }
+
+    /// Resume after the given resume token.
+    pub fn resume_after(mut self, token: Document) -> Self {
+        self.options.resume_after = Some(token);
+        self
+    }
+
+    /// Start after the given resume token.
+    pub fn start_after(mut self, token: Document) -> Self {
+        self.options.start_after = Some(token);
+        self
+    }
+
+    /// Set the batch size.
+    pub fn batch_size(mut self, batch_size: u32) -> Self {
+        self.options.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Set the comment.
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.options.comment = Some(comment.into());
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> ChangeStreamOptions {
+        self.options
+    }
+}
+
+/// The kind of change a change event describes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OperationType {
+    /// A document was inserted.
+    Insert,
+    /// A document was updated.
+    Update,
+    /// A document was replaced.
+    Replace,
+    /// A document was deleted.
+    Delete,
+    /// The collection was dropped.
+    Drop,
+    /// The collection was renamed.
+    Rename,
+    /// The database was dropped.
+    DropDatabase,
+    /// The change stream was invalidated.
+    Invalidate,
+    /// Any other operation.
+    #[serde(other)]
+    Other,
+}
+
+/// The namespace a change event applies to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChangeNamespace {
+    /// Database name.
+    pub db: String,
+    /// Collection name.
+    #[serde(default)]
+    pub coll: String,
+}
+
+/// The fields changed by an update.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateDescription {
+    /// Fields that were set, with their new values.
+    #[serde(default)]
+    pub updated_fields: Document,
+    /// Fields that were removed.
+    #[serde(default)]
+    pub removed_fields: Vec<String>,
+}
+
+/// A change event.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeEvent<T> {
+    /// The resume token of this event.
+    #[serde(rename = "_id")]
+    pub id: Document,
+    /// The kind of change.
+    pub operation_type: OperationType,
+    /// The namespace of the change.
+    pub ns: Option<ChangeNamespace>,
+    /// The `_id` of the changed document.
+    pub document_key: Option<Document>,
+    /// The changed document, depending on [`FullDocument`].
+    pub full_document: Option<T>,
+    /// The fields changed by an update.
+    pub update_description: Option<UpdateDescription>,
+}
+
+/// Persistent storage for change stream resume tokens.
+#[async_trait]
+pub trait ResumeTokenStore: Send + Sync {
+    /// Load the last saved resume token of the named stream.
+    async fn load(&self, name: &str) -> Result<Option<Document>>;
+
+    /// Save the resume token of the named stream.
+    async fn save(&self, name: &str, token: &Document) -> Result<()>;
+}
+
+/// A [`ResumeTokenStore`] keeping one document per stream in a collection.
+///
+/// Documents are keyed by stream name: `{ _id: name, token, updatedAt }`.
+#[derive(Clone)]
+pub struct CollectionTokenStore {
+    /// Collection holding the tokens.
+    collection: Collection<Document>,
+}
+
+impl CollectionTokenStore {
+    /// Create a store backed by the given collection.
+    pub fn new(collection: Collection<Document>) -> Self {
+        Self { collection }
+    }
+}
+
+#[async_trait]
+impl ResumeTokenStore for CollectionTokenStore {
+    async fn load(&self, name: &str) -> Result<Option<Document>> {
+        let saved = self.collection.find_one(doc! { "_id": name }).await?;
+        Ok(saved.and_then(|d| d.get_document("token").ok().cloned()))
+    }
+
+    async fn save(&self, name: &str, token: &Document) -> Result<()> {
+        let options = UpdateOptions::builder().upsert(true).build();
+        self.collection
+            .update_one_with_options(
+                doc! { "_id": name },
+                doc! {
+                    "$set": { "token": token.clone() },
+                    "$currentDate": { "updatedAt": true },
+                },
+                options,
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// A [`ResumeTokenStore`] keeping tokens in memory, for tests and for
+/// streams that only need to survive reconnects, not restarts.
+#[derive(Debug, Default)]
+pub struct MemoryTokenStore {
+    /// Tokens by stream name.
+    tokens: std::sync::Mutex<HashMap<String, Document>>,
+}
+
+impl MemoryTokenStore {
+    /// Create an empty store.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lock the tokens. Each write leaves them consistent, so a panic while
+    /// they were locked is ignored.
+    fn tokens(&self) -> std::sync::MutexGuard<'_, HashMap<String, Document>> {
+        self.tokens.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+#[async_trait]
+impl ResumeTokenStore for MemoryTokenStore {
+    async fn load(&self, name: &str) -> Result<Option<Document>> {
+        Ok(self.tokens().get(name).cloned())
+    }
+
+    async fn save(&self, name: &str, token: &Document) -> Result<()> {
+        self.tokens().insert(name.to_string(), token.clone());
+        Ok(())
+    }
+}
+
+/// A [`ResumeTokenStore`] keeping one file per stream in a directory.
+///
+/// Each token is written as extended JSON to `<name>.json`, with characters
+/// other than ASCII letters, digits, `-` and `_` in the name escaped. Writes
+/// go to a temporary file first, so a crash leaves the previous token intact.
+///
+/// # Example
+///
+/// ```ignore
+/// let store = Arc::new(FileTokenStore::new("/var/lib/shipper/checkpoints"));
+/// let checkpoint = Checkpoint::new(store, "order-shipper");
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileTokenStore {
+    /// Directory holding the token files.
+    dir: PathBuf,
+}
+
+impl FileTokenStore {
+    /// Create a store writing to `dir`, which is created on the first save.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Get the path of the file holding the token of the named stream.
+    pub fn path(&self, name: &str) -> PathBuf {
+        let mut file = String::with_capacity(name.len() + 5);
+        for byte in name.bytes() {
+            if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+                file.push(byte as char);
+            } else {
+                file.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        file.push_str(".json");
+        self.dir.join(file)
+    }
+}
+
+#[async_trait]
+impl ResumeTokenStore for FileTokenStore {
+    async fn load(&self, name: &str) -> Result<Option<Document>> {
+        let path = self.path(name);
+        let contents = tokio::task::spawn_blocking(move || match std::fs::read(&path) {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(file_error("read", &path, e)),
+        })
+        .await
+        .map_err(|e| MongoError::Internal(e.to_string()))??;
+        match contents {
+            Some(contents) => {
+                let json: JsonValue = serde_json::from_slice(&contents)
+                    .map_err(|e| MongoError::Deserialization(e.to_string()))?;
+                json_to_bson_doc(&json).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn save(&self, name: &str, token: &Document) -> Result<()> {
+        let contents = serde_json::to_vec(&bson_doc_to_json(token)?)?;
+        let dir = self.dir.clone();
+        let path = self.path(name);
+        tokio::task::spawn_blocking(move || {
+            std::fs::create_dir_all(&dir).map_err(|e| file_error("create", &dir, e))?;
+            let tmp = path.with_extension("json.tmp");
+            std::fs::write(&tmp, contents).map_err(|e| file_error("write", &tmp, e))?;
+            std::fs::rename(&tmp, &path).map_err(|e| file_error("write", &path, e))
+        })
+        .await
+        .map_err(|e| MongoError::Internal(e.to_string()))?
+    }
+}
+
+fn file_error(action: &str, path: &Path, e: std::io::Error) -> MongoError {
+    MongoError::Internal(format!("failed to {} {}: {}", action, path.display(), e))
+}
+
+/// How often a change stream saves its resume token.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointInterval {
+    /// Save after every `n` events.
+    Events(u32),
+    /// Save on the first event after each period.
+    Period(Duration),
+}
+
+impl Default for CheckpointInterval {
+    fn default() -> Self {
+        CheckpointInterval::Events(1)
+    }
+}
+
+/// Resume token checkpointing for a change stream.
+#[derive(Clone)]
+pub struct Checkpoint {
+    /// Token store.
+    store: Arc<dyn ResumeTokenStore>,
+    /// Name of the stream in the store.
+    name: String,
+    /// Save interval.
+    interval: CheckpointInterval,
+    /// Events since the last save.
+    pending: u32,
+    /// Time of the last save.
+    last_saved: Instant,
+}
+
+impl Checkpoint {
+    /// Checkpoint the stream `name` to `store` after every event.
+    pub fn new(store: Arc<dyn ResumeTokenStore>, name: impl Into<String>) -> Self {
+        Self {
+            store,
+            name: name.into(),
+            interval: CheckpointInterval::default(),
+            pending: 0,
+            last_saved: Instant::now(),
+        }
+    }
+
+    /// Set the save interval.
+    pub fn every(mut self, interval: CheckpointInterval) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Get the name of the stream in the store.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Load the last saved resume token.
+    pub(crate) async fn load(&self) -> Result<Option<Document>> {
+        self.store.load(&self.name).await
+    }
+
+    /// Record an event, saving its token when the interval has elapsed.
+    async fn record(&mut self, token: &Document) -> Result<()> {
+        self.pending += 1;
+        let due = match self.interval {
+            CheckpointInterval::Events(n) => self.pending >= n.max(1),
+            CheckpointInterval::Period(period) => self.last_saved.elapsed() >= period,
+        };
+        if due {
+            self.save(token).await?;
+        }
+        Ok(())
+    }
+
+    /// Save a token, if any events are pending.
+    async fn flush(&mut self, token: &Document) -> Result<()> {
+        if self.pending > 0 {
+            self.save(token).await?;
+        }
+        Ok(())
+    }
+
+    async fn save(&mut self, token: &Document) -> Result<()> {
+        self.store.save(&self.name, token).await?;
+        self.pending = 0;
+        self.last_saved = Instant::now();
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Checkpoint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Checkpoint")
+            .field("name", &self.name)
+            .field("interval", &self.interval)
+            .field("pending", &self.pending)
+            .finish()
+    }
+}
+
+/// A change stream over a collection.
+///
+/// The stream tracks the resume token of the last event returned, so it can be
+/// reopened with [`ChangeStreamOptionsBuilder::resume_after`].
+pub struct ChangeStream<T> {
+    /// RPC client.
+    rpc_client: Transport,
+    /// Server-side stream ID.
+    stream_id: String,
+    /// Resume token of the last event returned.
+    resume_token: Option<Document>,
+    /// Whether the stream is closed.
+    closed: bool,
+    /// Resume token checkpointing.
+    checkpoint: Option<Checkpoint>,
+    /// Failure to save the last event's resume token, reported by the next call.
+    checkpoint_error: Option<MongoError>,
+    /// Automatic encryption used to decrypt events.
+    #[cfg(feature = "encryption")]
+    pub(crate) auto_encrypter: Option<Arc<AutoEncrypter>>,
+    /// Type marker.
+    _marker: PhantomData<T>,
+}
+
+impl<T> ChangeStream<T> {
+    /// Create a change stream handle for an opened server-side stream.
+    pub(crate) fn new(rpc_client: Transport, stream_id: String) -> Self {
+        Self {
+            rpc_client,
+            stream_id,
+            resume_token: None,
+            closed: false,
+            checkpoint: None,
+            checkpoint_error: None,
+            #[cfg(feature = "encryption")]
+            auto_encrypter: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get the server-side stream ID.
+    pub fn stream_id(&self) -> &str {
+        &self.stream_id
+    }
+
+    /// Get the resume token of the last event returned.
+    pub fn resume_token(&self) -> Option<&Document> {
+        self.resume_token.as_ref()
+    }
+
+    /// Check if the stream is closed.
+    pub fn is_closed(&self) -> bool {
+        self.closed
+    }
+
+    /// Checkpoint resume tokens as events are returned.
+    pub fn with_checkpoint(mut self, checkpoint: Checkpoint) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Save the resume token of the last event now, regardless of the interval.
+    ///
+    /// This also retries a save that failed in [`ChangeStream::try_next`].
+    pub async fn checkpoint_now(&mut self) -> Result<()> {
+        self.checkpoint_error = None;
+        if let (Some(checkpoint), Some(token)) =
+            (self.checkpoint.as_mut(), self.resume_token.as_ref())
+        {
+            checkpoint.flush(token).await?;
+        }
+        Ok(())
+    }
+
+    /// Close the change stream, saving any pending checkpoint first.
+    pub async fn close(&mut self) -> Result<()> {
+        if self.closed {
+            return Ok(());
+        }
+        self.checkpoint_now().await?;
+        self.closed = true;
+        self.rpc_client
+            .call_raw(
+                Method::ChangeStreamClose,
+                vec![serde_json::json!(self.stream_id)],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+impl<T: DeserializeOwned> ChangeStream<T> {
+    /// Get the next change event.
+    ///
+    /// Returns `None` once the server reports no further events.
+    ///
+    /// An event is returned even if saving its resume token fails; the save
+    /// error is returned by the next call instead, and the token is saved
+    /// again with the next event.
+    pub async fn try_next(&mut self) -> Result<Option<ChangeEvent<T>>> {
+        if self.closed {
+            return Err(MongoError::CursorExhausted);
+        }
+        if let Some(error) = self.checkpoint_error.take() {
+            return Err(error);
+        }
+
+        #[allow(unused_mut)]
+        let mut result = self
+            .rpc_client
+            .call_raw(
+                Method::ChangeStreamNext,
+                vec![serde_json::json!(self.stream_id)],
+            )
+            .await?;
+        if result.is_null() {
+            return Ok(None);
+        }
+        #[cfg(feature = "encryption")]
+        if let Some(ref encrypter) = self.auto_encrypter {
+            encrypter.decrypt(&mut result).await?;
+        }
+
+        let event = parse_event::<T>(&result, self.rpc_client.codec.as_deref())?;
+        self.resume_token = Some(event.id.clone());
+        if let Some(ref mut checkpoint) = self.checkpoint {
+            self.checkpoint_error = checkpoint.record(&event.id).await.err();
+        }
+        Ok(Some(event))
+    }
+}
+
+/// Deserialize a change event, decoding its values with `codec`.
+fn parse_event<T: DeserializeOwned>(
+    value: &JsonValue,
+    codec: Option<&dyn ValueCodec>,
+) -> Result<ChangeEvent<T>> {
+    convert::decode_typed(value, codec)
+}
+
+/// Rewrite a filter on document fields into a filter on change events'
+/// `fullDocument`.
+///
+/// Field names are prefixed with `fullDocument.`; the operands of `$and`,
+/// `$or` and `$nor` are rewritten recursively. Other top-level operators are
+/// kept as they are.
+pub(crate) fn full_document_filter(filter: Document) -> Document {
+    filter
+        .into_iter()
+        .map(|(key, value)| match key.as_str() {
+            "$and" | "$or" | "$nor" => {
+                let value = match value {
+                    Bson::Array(clauses) => Bson::Array(
+                        clauses
+                            .into_iter()
+                            .map(|clause| match clause {
+                                Bson::Document(clause) => {
+                                    Bson::Document(full_document_filter(clause))
+                                }
+                                other => other,
+                            })
+                            .collect(),
+                    ),
+                    other => other,
+                };
+                (key, value)
+            }
+            _ if key.starts_with('$') => (key, value),
+            _ => (format!("fullDocument.{}", key), value),
+        })
+        .collect()
+}
+
+/// Build the `$match` stage of [`Collection::watch_where`](crate::Collection::watch_where).
+pub(crate) fn watch_where_pipeline(filter: Document) -> Vec<Document> {
+    vec![doc! { "$match": full_document_filter(filter) }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Order {
+        status: String,
+    }
+
+    #[test]
+    fn test_full_document_filter() {
+        let filter = doc! {
+            "status": "shipped",
+            "$or": [{ "total": { "$gt": 100 } }, { "priority": true }],
+            "$expr": { "$gt": ["$a", "$b"] },
+        };
+        assert_eq!(
+            full_document_filter(filter),
+            doc! {
+                "fullDocument.status": "shipped",
+                "$or": [
+                    { "fullDocument.total": { "$gt": 100 } },
+                    { "fullDocument.priority": true },
+                ],
+                "$expr": { "$gt": ["$a", "$b"] },
+            }
+        );
+    }
+
+    #[test]
+    fn test_watch_where_pipeline() {
+        let pipeline = watch_where_pipeline(doc! { "status": "shipped" });
+        assert_eq!(
+            pipeline,
+            vec![doc! { "$match": { "fullDocument.status": "shipped" } }]
+        );
+    }
+
+    #[test]
+    fn test_change_stream_options() {
+        let options = ChangeStreamOptions::builder()
+            .full_document(FullDocument::UpdateLookup)
+            .resume_after(doc! { "_data": "abc" })
+            .build();
+        assert_eq!(
+            options.to_document(),
+            doc! { "fullDocument": "updateLookup", "resumeAfter": { "_data": "abc" } }
+        );
+    }
+
+    #[derive(Default)]
+    struct MemoryStore {
+        saved: std::sync::Mutex<Vec<Document>>,
+    }
+
+    #[async_trait]
+    impl ResumeTokenStore for MemoryStore {
+        async fn load(&self, _name: &str) -> Result<Option<Document>> {
+            Ok(self.saved.lock().unwrap().last().cloned())
+        }
+
+        async fn save(&self, _name: &str, token: &Document) -> Result<()> {
+            self.saved.lock().unwrap().push(token.clone());
+            Ok(())
+        }
+    }
+
+    /// A store whose saves fail until `fail` is cleared.
+    #[derive(Default)]
+    struct FlakyStore {
+        fail: std::sync::atomic::AtomicBool,
+        saved: MemoryStore,
+    }
+
+    #[async_trait]
+    impl ResumeTokenStore for FlakyStore {
+        async fn load(&self, name: &str) -> Result<Option<Document>> {
+            self.saved.load(name).await
+        }
+
+        async fn save(&self, name: &str, token: &Document) -> Result<()> {
+            if self.fail.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(MongoError::connection("store down"));
+            }
+            self.saved.save(name, token).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_failure_keeps_event() {
+        let server = crate::mock::MockServer::new(|_, _| {
+            Ok(serde_json::json!({
+                "_id": { "_data": "t1" },
+                "operationType": "insert",
+                "fullDocument": { "status": "paid" },
+            }))
+        });
+        let store = Arc::new(FlakyStore::default());
+        store.fail.store(true, std::sync::atomic::Ordering::SeqCst);
+        let mut stream = ChangeStream::<Order>::new(server.transport(), "s1".to_string())
+            .with_checkpoint(Checkpoint::new(store.clone(), "orders"));
+
+        // The event is delivered although its token could not be saved.
+        let event = stream.try_next().await.unwrap().unwrap();
+        assert_eq!(event.full_document.unwrap().status, "paid");
+        assert_eq!(stream.resume_token(), Some(&doc! { "_data": "t1" }));
+
+        // The next call reports the failure without taking another event.
+        assert!(stream.try_next().await.unwrap_err().is_connection_error());
+        assert_eq!(server.calls_of(Method::ChangeStreamNext).len(), 1);
+
+        // Once the store recovers, the pending token is saved.
+        store.fail.store(false, std::sync::atomic::Ordering::SeqCst);
+        stream.try_next().await.unwrap().unwrap();
+        assert_eq!(*store.saved.saved.lock().unwrap(), vec![doc! { "_data": "t1" }]);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_every_events() {
+        let store = Arc::new(MemoryStore::default());
+        let mut checkpoint =
+            Checkpoint::new(store.clone(), "s").every(CheckpointInterval::Events(2));
+        for i in 0..5 {
+            checkpoint.record(&doc! { "_data": i }).await.unwrap();
+        }
+        assert_eq!(
+            *store.saved.lock().unwrap(),
+            vec![doc! { "_data": 1 }, doc! { "_data": 3 }]
+        );
+
+        checkpoint.flush(&doc! { "_data": 4 }).await.unwrap();
+        checkpoint.flush(&doc! { "_data": 4 }).await.unwrap();
+        assert_eq!(store.saved.lock().unwrap().len(), 3);
+        assert_eq!(checkpoint.load().await.unwrap(), Some(doc! { "_data": 4 }));
+    }
+
+    #[tokio::test]
+    async fn test_memory_token_store() {
+        let store = MemoryTokenStore::new();
+        assert_eq!(store.load("a").await.unwrap(), None);
+        store.save("a", &doc! { "_data": "1" }).await.unwrap();
+        store.save("b", &doc! { "_data": "2" }).await.unwrap();
+        store.save("a", &doc! { "_data": "3" }).await.unwrap();
+        assert_eq!(store.load("a").await.unwrap(), Some(doc! { "_data": "3" }));
+        assert_eq!(store.load("b").await.unwrap(), Some(doc! { "_data": "2" }));
+
+        // A panic while the tokens are locked does not break the store.
+        let store = Arc::new(store);
+        let poisoner = store.clone();
+        let _ = std::thread::spawn(move || {
+            let _tokens = poisoner.tokens.lock();
+            panic!("consumer panicked");
+        })
+        .join();
+        assert!(store.tokens.is_poisoned());
+        store.save("c", &doc! { "_data": "4" }).await.unwrap();
+        assert_eq!(store.load("a").await.unwrap(), Some(doc! { "_data": "3" }));
+    }
+
+    #[tokio::test]
+    async fn test_file_token_store() {
+        let dir = std::env::temp_dir()
+            .join(format!("mongo-do-tokens-{}", bson::oid::ObjectId::new()));
+        let store = FileTokenStore::new(&dir);
+        assert_eq!(store.load("orders/shipper").await.unwrap(), None);
+
+        let token = doc! { "_data": "8263a1", "ts": bson::Timestamp { time: 5, increment: 1 } };
+        store.save("orders/shipper", &token).await.unwrap();
+        store.save("orders/shipper", &token).await.unwrap();
+        assert_eq!(store.path("orders/shipper"), dir.join("orders%2Fshipper.json"));
+
+        // A new store over the same directory sees the saved token.
+        let reopened = FileTokenStore::new(&dir);
+        assert_eq!(reopened.load("orders/shipper").await.unwrap(), Some(token));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint_every_period() {
+        let store = Arc::new(MemoryStore::default());
+        let mut checkpoint = Checkpoint::new(store.clone(), "s")
+            .every(CheckpointInterval::Period(Duration::from_secs(3600)));
+        checkpoint.record(&doc! { "_data": 0 }).await.unwrap();
+        assert!(store.saved.lock().unwrap().is_empty());
+
+        let mut checkpoint = checkpoint.every(CheckpointInterval::Period(Duration::ZERO));
+        checkpoint.record(&doc! { "_data": 1 }).await.unwrap();
+        assert_eq!(store.saved.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_event() {
+        let json = serde_json::json!({
+            "_id": { "_data": "token" },
+            "operationType": "update",
+            "ns": { "db": "shop", "coll": "orders" },
+            "documentKey": { "_id": 1 },
+            "fullDocument": { "status": "shipped" },
+            "updateDescription": { "updatedFields": { "status": "shipped" }, "removedFields": [] },
+        });
+        let event: ChangeEvent<Order> = parse_event(&json, None).unwrap();
+        assert_eq!(event.operation_type, OperationType::Update);
+        assert_eq!(event.id, doc! { "_data": "token" });
+        assert_eq!(event.full_document.unwrap().status, "shipped");
+        assert_eq!(event.ns.unwrap().coll, "orders");
+        assert_eq!(
+            event.update_description.unwrap().updated_fields,
+            doc! { "status": "shipped" }
+        );
+    }
+
+    #[test]
+    fn test_parse_delete_event() {
+        let json = serde_json::json!({
+            "_id": { "_data": "token" },
+            "operationType": "delete",
+            "documentKey": { "_id": 1 },
+        });
+        let event: ChangeEvent<Order> = parse_event(&json, None).unwrap();
+        assert_eq!(event.operation_type, OperationType::Delete);
+        assert!(event.full_document.is_none());
+    }
+
+    #[test]
+    fn test_parse_unknown_operation() {
+        let json = serde_json::json!({ "_id": {}, "operationType": "createIndexes" });
+        let event: ChangeEvent<Order> = parse_event(&json, None).unwrap();
+        assert_eq!(event.operation_type, OperationType::Other);
+    }
+}
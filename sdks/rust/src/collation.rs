@@ -0,0 +1,298 @@
+//! Locale-aware string comparison.
+//!
+//! A [`Collation`] makes queries, sorts and indexes compare strings by the
+//! rules of a language instead of by byte value, e.g. ignoring case or
+//! sorting `"10"` after `"9"`. Operations that take one:
+//! [`FindOptions`](crate::FindOptions), [`UpdateOptions`](crate::UpdateOptions),
+//! [`DeleteOptions`](crate::DeleteOptions),
+//! [`AggregateOptions`](crate::AggregateOptions) and
+//! [`IndexOptions`](crate::IndexOptions). A query only uses an index whose
+//! collation matches its own.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::collation::{Collation, CollationStrength};
+//!
+//! // Case-insensitive match on a French name.
+//! let collation = Collation::new("fr").strength(CollationStrength::Secondary);
+//! let options = FindOptions::builder().collation(collation).build();
+//! let users = users.find_with_options(doc! { "name": "élodie" }, options).await?;
+//! ```
+
+use bson::Document;
+use serde::{Deserialize, Serialize};
+
+/// Rules for comparing strings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Collation {
+    /// ICU locale, e.g. `en` or `fr_CA`, or `simple` for byte comparison.
+    pub locale: String,
+    /// Whether to compare case at the primary and secondary strengths.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub case_level: Option<bool>,
+    /// Whether upper or lower case sorts first.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub case_first: Option<CollationCaseFirst>,
+    /// Which differences between characters count.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strength: Option<CollationStrength>,
+    /// Whether digit sequences compare as numbers.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub numeric_ordering: Option<bool>,
+    /// Whether whitespace and punctuation count as base characters.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alternate: Option<CollationAlternate>,
+    /// Which characters are ignorable when `alternate` is shifted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_variable: Option<CollationMaxVariable>,
+    /// Whether to normalize text before comparing it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub normalization: Option<bool>,
+    /// Whether diacritics compare from the end of the string, as in
+    /// Canadian French.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub backwards: Option<bool>,
+}
+
+impl Collation {
+    /// Compare strings by the rules of `locale`.
+    pub fn new(locale: impl Into<String>) -> Self {
+        Self {
+            locale: locale.into(),
+            case_level: None,
+            case_first: None,
+            strength: None,
+            numeric_ordering: None,
+            alternate: None,
+            max_variable: None,
+            normalization: None,
+            backwards: None,
+        }
+    }
+
+    /// Set whether to compare case at lower strengths.
+    pub fn case_level(mut self, case_level: bool) -> Self {
+        self.case_level = Some(case_level);
+        self
+    }
+
+    /// Set which case sorts first.
+    pub fn case_first(mut self, case_first: CollationCaseFirst) -> Self {
+        self.case_first = Some(case_first);
+        self
+    }
+
+    /// Set the comparison strength.
+    pub fn strength(mut self, strength: CollationStrength) -> Self {
+        self.strength = Some(strength);
+        self
+    }
+
+    /// Set whether digit sequences compare as numbers.
+    pub fn numeric_ordering(mut self, numeric_ordering: bool) -> Self {
+        self.numeric_ordering = Some(numeric_ordering);
+        self
+    }
+
+    /// Set whether whitespace and punctuation count.
+    pub fn alternate(mut self, alternate: CollationAlternate) -> Self {
+        self.alternate = Some(alternate);
+        self
+    }
+
+    /// Set which characters are ignorable.
+    pub fn max_variable(mut self, max_variable: CollationMaxVariable) -> Self {
+        self.max_variable = Some(max_variable);
+        self
+    }
+
+    /// Set whether to normalize text.
+    pub fn normalization(mut self, normalization: bool) -> Self {
+        self.normalization = Some(normalization);
+        self
+    }
+
+    /// Set whether diacritics compare from the end.
+    pub fn backwards(mut self, backwards: bool) -> Self {
+        self.backwards = Some(backwards);
+        self
+    }
+
+    /// Convert to the collation document sent to the server.
+    pub fn to_document(&self) -> Document {
+        let mut collation = Document::new();
+        collation.insert("locale", self.locale.as_str());
+        if let Some(case_level) = self.case_level {
+            collation.insert("caseLevel", case_level);
+        }
+        if let Some(case_first) = self.case_first {
+            collation.insert("caseFirst", case_first.as_str());
+        }
+        if let Some(strength) = self.strength {
+            collation.insert("strength", u32::from(strength) as i32);
+        }
+        if let Some(numeric_ordering) = self.numeric_ordering {
+            collation.insert("numericOrdering", numeric_ordering);
+        }
+        if let Some(alternate) = self.alternate {
+            collation.insert("alternate", alternate.as_str());
+        }
+        if let Some(max_variable) = self.max_variable {
+            collation.insert("maxVariable", max_variable.as_str());
+        }
+        if let Some(normalization) = self.normalization {
+            collation.insert("normalization", normalization);
+        }
+        if let Some(backwards) = self.backwards {
+            collation.insert("backwards", backwards);
+        }
+        collation
+    }
+}
+
+/// Which differences between characters a [`Collation`] compares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(into = "u32", try_from = "u32")]
+pub enum CollationStrength {
+    /// Base characters only: `a` equals `A` and `á`.
+    Primary,
+    /// Base characters and diacritics: `a` equals `A`.
+    Secondary,
+    /// Base characters, diacritics and case. The default.
+    Tertiary,
+    /// Also punctuation, when `alternate` is shifted.
+    Quaternary,
+    /// Every code point.
+    Identical,
+}
+
+impl From<CollationStrength> for u32 {
+    fn from(strength: CollationStrength) -> Self {
+        match strength {
+            CollationStrength::Primary => 1,
+            CollationStrength::Secondary => 2,
+            CollationStrength::Tertiary => 3,
+            CollationStrength::Quaternary => 4,
+            CollationStrength::Identical => 5,
+        }
+    }
+}
+
+impl TryFrom<u32> for CollationStrength {
+    type Error = String;
+
+    fn try_from(level: u32) -> Result<Self, Self::Error> {
+        match level {
+            1 => Ok(CollationStrength::Primary),
+            2 => Ok(CollationStrength::Secondary),
+            3 => Ok(CollationStrength::Tertiary),
+            4 => Ok(CollationStrength::Quaternary),
+            5 => Ok(CollationStrength::Identical),
+            _ => Err(format!("invalid collation strength {}", level)),
+        }
+    }
+}
+
+/// Which case sorts first in a [`Collation`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CollationCaseFirst {
+    /// Upper case first.
+    Upper,
+    /// Lower case first.
+    Lower,
+    /// The locale's default order.
+    Off,
+}
+
+impl CollationCaseFirst {
+    /// The server's name for this option.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CollationCaseFirst::Upper => "upper",
+            CollationCaseFirst::Lower => "lower",
+            CollationCaseFirst::Off => "off",
+        }
+    }
+}
+
+/// Whether a [`Collation`] compares whitespace and punctuation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CollationAlternate {
+    /// Whitespace and punctuation are base characters.
+    #[serde(rename = "non-ignorable")]
+    NonIgnorable,
+    /// Whitespace and punctuation are only compared at the quaternary strength.
+    #[serde(rename = "shifted")]
+    Shifted,
+}
+
+impl CollationAlternate {
+    /// The server's name for this option.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CollationAlternate::NonIgnorable => "non-ignorable",
+            CollationAlternate::Shifted => "shifted",
+        }
+    }
+}
+
+/// Which characters a shifted [`Collation`] ignores.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CollationMaxVariable {
+    /// Whitespace and punctuation.
+    Punct,
+    /// Whitespace only.
+    Space,
+}
+
+impl CollationMaxVariable {
+    /// The server's name for this option.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            CollationMaxVariable::Punct => "punct",
+            CollationMaxVariable::Space => "space",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::doc;
+
+    #[test]
+    fn test_collation_document() {
+        let collation = Collation::new("fr_CA")
+            .strength(CollationStrength::Secondary)
+            .case_first(CollationCaseFirst::Upper)
+            .numeric_ordering(true)
+            .alternate(CollationAlternate::Shifted)
+            .max_variable(CollationMaxVariable::Space)
+            .backwards(true);
+        let expected = doc! {
+            "locale": "fr_CA",
+            "caseFirst": "upper",
+            "strength": 2,
+            "numericOrdering": true,
+            "alternate": "shifted",
+            "maxVariable": "space",
+            "backwards": true,
+        };
+        assert_eq!(collation.to_document(), expected);
+        assert_eq!(Collation::new("en").to_document(), doc! { "locale": "en" });
+
+        let json = serde_json::to_value(&collation).unwrap();
+        assert_eq!(json["strength"], 2);
+        assert_eq!(json["alternate"], "shifted");
+        assert!(json.get("caseLevel").is_none());
+        assert_eq!(serde_json::from_value::<Collation>(json).unwrap(), collation);
+        assert!(serde_json::from_value::<Collation>(
+            serde_json::json!({ "locale": "en", "strength": 9 })
+        )
+        .is_err());
+    }
+}
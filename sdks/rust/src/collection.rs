@@ -1,18 +1,86 @@
 //! Collection struct with CRUD operations.
 
-use crate::cursor::Cursor;
-use crate::error::{MongoError, Result};
-use bson::{doc, oid::ObjectId, Document};
-use serde::{de::DeserializeOwned, Serialize};
+use crate::archive::{self, ArchiveOptions, ArchiveResult, ArchiveTarget};
+use crate::change_stream::{
+    watch_where_pipeline, ChangeStream, ChangeStreamOptions, Checkpoint, FullDocument,
+};
+use crate::collation::Collation;
+use crate::convert::{encode_document, json_to_bson_doc, ValueCodec};
+use crate::cursor::{Cursor, RawCursor};
+use crate::db::{CollModOptions, ReadConcern, WriteConcern};
+#[cfg(feature = "encryption")]
+use crate::encryption::AutoEncrypter;
+use crate::error::{InsertManyError, MongoError, Result, WriteError};
+use crate::handshake::capability;
+use crate::index::{default_index_name, EnsureIndexesResult, IndexModel, ID_INDEX_NAME};
+use crate::model::Model;
+use crate::pipeline::{
+    validate_pipeline, OutputPipeline, OutputStage, OutputSummary, PipelineBuilder,
+};
+use crate::progress::{Progress, ProgressTracker};
+use crate::rpc::Method;
+use crate::scan::Scan;
+use crate::tail::{self, TailOptions};
+use crate::text::{text_score, TextIndexOptions};
+use crate::transport::Transport;
+use crate::util::redact;
+use bson::{doc, Document};
+use futures::Stream;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value as JsonValue;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
 use std::marker::PhantomData;
+use std::time::Duration;
+#[cfg(feature = "encryption")]
 use std::sync::Arc;
 
 /// Result of an insert_one operation.
+///
+/// Marked `#[non_exhaustive]` so fields can be added without breaking
+/// callers; build one with `Default` and assign its fields.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct InsertOneResult {
     /// The ID of the inserted document.
     pub inserted_id: bson::Bson,
+    /// Whether the server acknowledged the write.
+    pub acknowledged: bool,
+    /// The server's response, for fields this type does not expose.
+    pub raw_response: Document,
+}
+
+impl Default for InsertOneResult {
+    fn default() -> Self {
+        Self {
+            inserted_id: bson::Bson::Null,
+            acknowledged: true,
+            raw_response: Document::new(),
+        }
+    }
+}
+
+impl InsertOneResult {
+    /// Get the inserted ID as `I`, such as an `ObjectId`, a `bson::Uuid` or
+    /// a `String`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let id: ObjectId = users.insert_one(user).await?.inserted_id_as()?;
+    /// ```
+    pub fn inserted_id_as<I: DeserializeOwned>(&self) -> Result<I> {
+        bson::from_bson(self.inserted_id.clone()).map_err(|e| {
+            MongoError::Deserialization(format!(
+                "inserted _id {} cannot be read as {}: {}",
+                self.inserted_id,
+                std::any::type_name::<I>(),
+                e
+            ))
+        })
+    }
 }
 
 /// Result of an insert_many operation.
@@ -23,7 +91,10 @@ pub struct InsertManyResult {
 }
 
 /// Result of an update operation.
+///
+/// Marked `#[non_exhaustive]` like [`InsertOneResult`].
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct UpdateResult {
     /// Number of documents matched.
     pub matched_count: u64,
@@ -31,13 +102,317 @@ pub struct UpdateResult {
     pub modified_count: u64,
     /// The ID of the upserted document, if any.
     pub upserted_id: Option<bson::Bson>,
+    /// Whether the server acknowledged the write.
+    pub acknowledged: bool,
+    /// The server's response, for fields this type does not expose.
+    pub raw_response: Document,
+}
+
+impl Default for UpdateResult {
+    fn default() -> Self {
+        Self {
+            matched_count: 0,
+            modified_count: 0,
+            upserted_id: None,
+            acknowledged: true,
+            raw_response: Document::new(),
+        }
+    }
+}
+
+/// Result of [`Collection::update_in_batches`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BatchUpdateResult {
+    /// Number of `update_many` calls made.
+    pub batches: u64,
+    /// Number of documents matched, over all batches.
+    pub matched_count: u64,
+    /// Number of documents modified, over all batches.
+    pub modified_count: u64,
 }
 
 /// Result of a delete operation.
+///
+/// Marked `#[non_exhaustive]` like [`InsertOneResult`].
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub struct DeleteResult {
     /// Number of documents deleted.
     pub deleted_count: u64,
+    /// Whether the server acknowledged the write.
+    pub acknowledged: bool,
+    /// The server's response, for fields this type does not expose.
+    pub raw_response: Document,
+}
+
+impl Default for DeleteResult {
+    fn default() -> Self {
+        Self {
+            deleted_count: 0,
+            acknowledged: true,
+            raw_response: Document::new(),
+        }
+    }
+}
+
+/// Write conflict error code, reported when [`Collection::modify`] runs out of retries.
+const WRITE_CONFLICT_CODE: i32 = 112;
+
+/// Options for [`Collection::modify_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct ModifyOptions {
+    /// Guard the write with this numeric version field, incrementing it on
+    /// each write, instead of with all of the document's original values.
+    pub version_field: Option<String>,
+    /// How many times to retry after a conflicting write. Defaults to 3.
+    /// These retries do not spend the client's retry budget.
+    pub max_retries: Option<u32>,
+}
+
+impl ModifyOptions {
+    /// Create a builder.
+    pub fn builder() -> ModifyOptionsBuilder {
+        ModifyOptionsBuilder::default()
+    }
+}
+
+/// Builder for ModifyOptions.
+#[derive(Debug, Clone, Default)]
+pub struct ModifyOptionsBuilder {
+    options: ModifyOptions,
+}
+
+impl ModifyOptionsBuilder {
+    /// Guard writes with a version field.
+    pub fn version_field(mut self, field: impl Into<String>) -> Self {
+        self.options.version_field = Some(field.into());
+        self
+    }
+
+    /// Set the number of retries after a conflicting write.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.options.max_retries = Some(max_retries);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> ModifyOptions {
+        self.options
+    }
+}
+
+/// Result of a save operation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveResult {
+    /// A new document was inserted with this `_id`.
+    Inserted(bson::Bson),
+    /// The existing document with this `_id` was replaced.
+    Replaced(bson::Bson),
+}
+
+impl SaveResult {
+    /// The `_id` of the saved document.
+    pub fn id(&self) -> &bson::Bson {
+        match self {
+            SaveResult::Inserted(id) | SaveResult::Replaced(id) => id,
+        }
+    }
+}
+
+/// Result of a validate command.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidateResult {
+    /// Namespace of the validated collection.
+    #[serde(default)]
+    pub ns: String,
+    /// Whether the collection is valid.
+    pub valid: bool,
+    /// Validation errors.
+    #[serde(default)]
+    pub errors: Vec<String>,
+    /// Validation warnings.
+    #[serde(default)]
+    pub warnings: Vec<String>,
+    /// Number of records in the collection.
+    #[serde(default)]
+    pub nrecords: i64,
+    /// Number of keys in each index.
+    #[serde(default)]
+    pub keys_per_index: HashMap<String, i64>,
+}
+
+/// Result of a compact command.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompactResult {
+    /// Bytes of storage released to the operating system.
+    #[serde(default)]
+    pub bytes_freed: i64,
+}
+
+/// Direction of a sort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SortOrder {
+    /// Smallest values first.
+    Ascending,
+    /// Largest values first.
+    Descending,
+}
+
+impl From<SortOrder> for bson::Bson {
+    fn from(order: SortOrder) -> Self {
+        match order {
+            SortOrder::Ascending => bson::Bson::Int32(1),
+            SortOrder::Descending => bson::Bson::Int32(-1),
+        }
+    }
+}
+
+/// Whether a find cursor stays open at the end of a capped collection.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CursorType {
+    /// The cursor closes after the last document.
+    #[default]
+    NonTailable,
+    /// The cursor stays open and returns documents inserted later.
+    Tailable,
+    /// Like [`CursorType::Tailable`], but the server waits for new documents
+    /// before returning an empty batch.
+    TailableAwait,
+}
+
+/// The index an operation must use, overriding the query planner.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Hint {
+    /// An index name, e.g. `email_1`.
+    Name(String),
+    /// An index key pattern, e.g. `{ "email": 1 }`.
+    Keys(Document),
+}
+
+impl Hint {
+    /// Convert to the value sent to the server.
+    pub(crate) fn to_json(&self, codec: Option<&dyn ValueCodec>) -> Result<JsonValue> {
+        match self {
+            Hint::Name(name) => Ok(serde_json::json!(name)),
+            Hint::Keys(keys) => encode_document(keys, codec),
+        }
+    }
+}
+
+impl From<&str> for Hint {
+    fn from(name: &str) -> Self {
+        Hint::Name(name.to_string())
+    }
+}
+
+impl From<String> for Hint {
+    fn from(name: String) -> Self {
+        Hint::Name(name)
+    }
+}
+
+impl From<Document> for Hint {
+    fn from(keys: Document) -> Self {
+        Hint::Keys(keys)
+    }
+}
+
+/// Options for [`Collection::insert_one_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct InsertOneOptions {
+    /// Whether to skip the collection's schema validation.
+    pub bypass_document_validation: Option<bool>,
+}
+
+impl InsertOneOptions {
+    /// Create a builder.
+    pub fn builder() -> InsertOneOptionsBuilder {
+        InsertOneOptionsBuilder::default()
+    }
+
+    /// Convert to the options map sent to the server.
+    pub(crate) fn to_json(&self) -> JsonValue {
+        let mut opts_json = serde_json::Map::new();
+        if let Some(bypass) = self.bypass_document_validation {
+            opts_json.insert("bypassDocumentValidation".to_string(), serde_json::json!(bypass));
+        }
+        JsonValue::Object(opts_json)
+    }
+}
+
+/// Builder for InsertOneOptions.
+#[derive(Debug, Clone, Default)]
+pub struct InsertOneOptionsBuilder {
+    options: InsertOneOptions,
+}
+
+impl InsertOneOptionsBuilder {
+    /// Set whether to skip schema validation.
+    pub fn bypass_document_validation(mut self, bypass: bool) -> Self {
+        self.options.bypass_document_validation = Some(bypass);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> InsertOneOptions {
+        self.options
+    }
+}
+
+/// Options for [`Collection::insert_many_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct InsertManyOptions {
+    /// Whether to stop at the first failed document. Defaults to true; when
+    /// false, the remaining documents are still inserted.
+    pub ordered: Option<bool>,
+    /// Whether to skip the collection's schema validation.
+    pub bypass_document_validation: Option<bool>,
+}
+
+impl InsertManyOptions {
+    /// Create a builder.
+    pub fn builder() -> InsertManyOptionsBuilder {
+        InsertManyOptionsBuilder::default()
+    }
+
+    /// Convert to the options map sent to the server.
+    pub(crate) fn to_json(&self) -> JsonValue {
+        let mut opts_json = serde_json::Map::new();
+        if let Some(ordered) = self.ordered {
+            opts_json.insert("ordered".to_string(), serde_json::json!(ordered));
+        }
+        if let Some(bypass) = self.bypass_document_validation {
+            opts_json.insert("bypassDocumentValidation".to_string(), serde_json::json!(bypass));
+        }
+        JsonValue::Object(opts_json)
+    }
+}
+
+/// Builder for InsertManyOptions.
+#[derive(Debug, Clone, Default)]
+pub struct InsertManyOptionsBuilder {
+    options: InsertManyOptions,
+}
+
+impl InsertManyOptionsBuilder {
+    /// Set whether to stop at the first failed document.
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.options.ordered = Some(ordered);
+        self
+    }
+
+    /// Set whether to skip schema validation.
+    pub fn bypass_document_validation(mut self, bypass: bool) -> Self {
+        self.options.bypass_document_validation = Some(bypass);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> InsertManyOptions {
+        self.options
+    }
 }
 
 /// Options for find operations.
@@ -53,6 +428,17 @@ pub struct FindOptions {
     pub projection: Option<Document>,
     /// Batch size for cursor.
     pub batch_size: Option<u32>,
+    /// Comment attached to the operation in server logs.
+    pub comment: Option<String>,
+    /// Whether the cursor is tailable, for capped collections.
+    pub cursor_type: Option<CursorType>,
+    /// How long the server waits for new documents on a
+    /// [`CursorType::TailableAwait`] cursor.
+    pub max_await_time: Option<Duration>,
+    /// How strings are compared in the filter and sort.
+    pub collation: Option<Collation>,
+    /// Index to use.
+    pub hint: Option<Hint>,
 }
 
 impl FindOptions {
@@ -60,6 +446,12 @@ impl FindOptions {
     pub fn builder() -> FindOptionsBuilder {
         FindOptionsBuilder::default()
     }
+
+    /// Create options that project the text search score into `field` and
+    /// sort by it, best match first.
+    pub fn text_score_projection(field: &str) -> Self {
+        Self::builder().text_score_projection(field).build()
+    }
 }
 
 /// Builder for FindOptions.
@@ -99,12 +491,81 @@ impl FindOptionsBuilder {
         self
     }
 
+    /// Set the comment.
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.options.comment = Some(comment.into());
+        self
+    }
+
+    /// Set the cursor type.
+    pub fn cursor_type(mut self, cursor_type: CursorType) -> Self {
+        self.options.cursor_type = Some(cursor_type);
+        self
+    }
+
+    /// Set how long the server waits for new documents on a tailable cursor.
+    pub fn max_await_time(mut self, max_await_time: Duration) -> Self {
+        self.options.max_await_time = Some(max_await_time);
+        self
+    }
+
+    /// Set the collation.
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.options.collation = Some(collation);
+        self
+    }
+
+    /// Set the index to use, by name or key pattern.
+    pub fn hint(mut self, hint: impl Into<Hint>) -> Self {
+        self.options.hint = Some(hint.into());
+        self
+    }
+
+    /// Project the text search score into `field` and sort by it.
+    ///
+    /// Adds to any projection and sort already set.
+    pub fn text_score_projection(mut self, field: &str) -> Self {
+        self.options
+            .projection
+            .get_or_insert_with(Document::new)
+            .insert(field, text_score());
+        self.options
+            .sort
+            .get_or_insert_with(Document::new)
+            .insert(field, text_score());
+        self
+    }
+
     /// Build the options.
     pub fn build(self) -> FindOptions {
         self.options
     }
 }
 
+/// The changes an update applies: an operator document or an aggregation pipeline.
+///
+/// Pipelines can compute new values from existing fields, e.g.
+/// `vec![doc! { "$set": { "total": { "$add": ["$price", "$tax"] } } }]`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum UpdateModifications {
+    /// An update operator document such as `{ "$set": { ... } }`.
+    Document(Document),
+    /// An aggregation pipeline of `$set`, `$unset`, `$replaceWith` and similar stages.
+    Pipeline(Vec<Document>),
+}
+
+impl From<Document> for UpdateModifications {
+    fn from(update: Document) -> Self {
+        UpdateModifications::Document(update)
+    }
+}
+
+impl From<Vec<Document>> for UpdateModifications {
+    fn from(pipeline: Vec<Document>) -> Self {
+        UpdateModifications::Pipeline(pipeline)
+    }
+}
+
 /// Options for update operations.
 #[derive(Debug, Clone, Default)]
 pub struct UpdateOptions {
@@ -112,6 +573,16 @@ pub struct UpdateOptions {
     pub upsert: Option<bool>,
     /// Array filters for updating nested arrays.
     pub array_filters: Option<Vec<Document>>,
+    /// Variables usable as `$$name` in the filter and update expressions.
+    pub let_vars: Option<Document>,
+    /// Comment attached to the operation in server logs.
+    pub comment: Option<String>,
+    /// How strings are compared in the filter.
+    pub collation: Option<Collation>,
+    /// Index to use.
+    pub hint: Option<Hint>,
+    /// Whether to skip the collection's schema validation.
+    pub bypass_document_validation: Option<bool>,
 }
 
 impl UpdateOptions {
@@ -119,6 +590,38 @@ impl UpdateOptions {
     pub fn builder() -> UpdateOptionsBuilder {
         UpdateOptionsBuilder::default()
     }
+
+    /// Convert to the options map sent to the server.
+    pub(crate) fn to_json(&self, codec: Option<&dyn ValueCodec>) -> Result<JsonValue> {
+        let mut opts_json = serde_json::Map::new();
+        if let Some(upsert) = self.upsert {
+            opts_json.insert("upsert".to_string(), serde_json::json!(upsert));
+        }
+        if let Some(ref array_filters) = self.array_filters {
+            let filters: Vec<JsonValue> = array_filters
+                .iter()
+                .map(|f| encode_document(f, codec))
+                .collect::<Result<_>>()?;
+            opts_json.insert("arrayFilters".to_string(), serde_json::json!(filters));
+        }
+        if let Some(ref let_vars) = self.let_vars {
+            opts_json.insert("let".to_string(), encode_document(let_vars, codec)?);
+        }
+        if let Some(ref comment) = self.comment {
+            opts_json.insert("comment".to_string(), serde_json::json!(comment));
+        }
+        if let Some(ref collation) = self.collation {
+            let collation = encode_document(&collation.to_document(), codec)?;
+            opts_json.insert("collation".to_string(), collation);
+        }
+        if let Some(ref hint) = self.hint {
+            opts_json.insert("hint".to_string(), hint.to_json(codec)?);
+        }
+        if let Some(bypass) = self.bypass_document_validation {
+            opts_json.insert("bypassDocumentValidation".to_string(), serde_json::json!(bypass));
+        }
+        Ok(JsonValue::Object(opts_json))
+    }
 }
 
 /// Builder for UpdateOptions.
@@ -140,66 +643,653 @@ impl UpdateOptionsBuilder {
         self
     }
 
+    /// Set variables usable as `$$name` in the filter and update.
+    pub fn let_vars(mut self, let_vars: Document) -> Self {
+        self.options.let_vars = Some(let_vars);
+        self
+    }
+
+    /// Set the comment.
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.options.comment = Some(comment.into());
+        self
+    }
+
+    /// Set the collation.
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.options.collation = Some(collation);
+        self
+    }
+
+    /// Set the index to use, by name or key pattern.
+    pub fn hint(mut self, hint: impl Into<Hint>) -> Self {
+        self.options.hint = Some(hint.into());
+        self
+    }
+
+    /// Set whether to skip schema validation.
+    pub fn bypass_document_validation(mut self, bypass: bool) -> Self {
+        self.options.bypass_document_validation = Some(bypass);
+        self
+    }
+
     /// Build the options.
     pub fn build(self) -> UpdateOptions {
         self.options
     }
 }
 
-/// A handle to a MongoDB collection.
-///
-/// # Type Parameters
-///
-/// * `T` - The type of documents in this collection.
-///
-/// # Example
-///
-/// ```ignore
-/// use mongo_do::{Client, bson::doc};
-/// use serde::{Serialize, Deserialize};
-///
-/// #[derive(Debug, Serialize, Deserialize)]
-/// struct User {
-///     name: String,
-///     email: String,
-/// }
-///
-/// let client = Client::new("mongodb://localhost").await?;
-/// let db = client.database("mydb");
-/// let users = db.collection::<User>("users");
-///
-/// users.insert_one(User { name: "John".to_string(), email: "john@example.com".to_string() }).await?;
-/// ```
-pub struct Collection<T> {
-    /// Database name.
-    pub(crate) db_name: String,
-    /// Collection name.
-    pub(crate) name: String,
-    /// RPC client.
-    pub(crate) rpc_client: Arc<rpc_do::RpcClient>,
-    /// Type marker.
-    _marker: PhantomData<T>,
+/// Options for [`Collection::replace_one_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct ReplaceOptions {
+    /// Whether to insert if no documents match.
+    pub upsert: Option<bool>,
+    /// Variables usable as `$$name` in the filter.
+    pub let_vars: Option<Document>,
+    /// Comment attached to the operation in server logs.
+    pub comment: Option<String>,
+    /// How strings are compared in the filter.
+    pub collation: Option<Collation>,
+    /// Index to use.
+    pub hint: Option<Hint>,
+    /// Whether to skip the collection's schema validation.
+    pub bypass_document_validation: Option<bool>,
 }
 
-impl<T> Collection<T> {
-    /// Create a new collection handle.
-    pub(crate) fn new(db_name: String, name: String, rpc_client: Arc<rpc_do::RpcClient>) -> Self {
-        Self {
-            db_name,
-            name,
-            rpc_client,
-            _marker: PhantomData,
-        }
+impl ReplaceOptions {
+    /// Create a builder.
+    pub fn builder() -> ReplaceOptionsBuilder {
+        ReplaceOptionsBuilder::default()
     }
 
-    /// Get the collection name.
-    pub fn name(&self) -> &str {
-        &self.name
+    /// Convert to the options map sent to the server.
+    pub(crate) fn to_json(&self, codec: Option<&dyn ValueCodec>) -> Result<JsonValue> {
+        let update = UpdateOptions {
+            upsert: self.upsert,
+            array_filters: None,
+            let_vars: self.let_vars.clone(),
+            comment: self.comment.clone(),
+            collation: self.collation.clone(),
+            hint: self.hint.clone(),
+            bypass_document_validation: self.bypass_document_validation,
+        };
+        update.to_json(codec)
     }
+}
 
-    /// Get the database name.
-    pub fn database_name(&self) -> &str {
-        &self.db_name
+/// Builder for ReplaceOptions.
+#[derive(Debug, Clone, Default)]
+pub struct ReplaceOptionsBuilder {
+    options: ReplaceOptions,
+}
+
+impl ReplaceOptionsBuilder {
+    /// Set upsert option.
+    pub fn upsert(mut self, upsert: bool) -> Self {
+        self.options.upsert = Some(upsert);
+        self
+    }
+
+    /// Set variables usable as `$$name` in the filter.
+    pub fn let_vars(mut self, let_vars: Document) -> Self {
+        self.options.let_vars = Some(let_vars);
+        self
+    }
+
+    /// Set the comment.
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.options.comment = Some(comment.into());
+        self
+    }
+
+    /// Set the collation.
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.options.collation = Some(collation);
+        self
+    }
+
+    /// Set the index to use, by name or key pattern.
+    pub fn hint(mut self, hint: impl Into<Hint>) -> Self {
+        self.options.hint = Some(hint.into());
+        self
+    }
+
+    /// Set whether to skip schema validation.
+    pub fn bypass_document_validation(mut self, bypass: bool) -> Self {
+        self.options.bypass_document_validation = Some(bypass);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> ReplaceOptions {
+        self.options
+    }
+}
+
+/// Which version of the document [`Collection::find_one_and_update`] returns.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ReturnDocument {
+    /// The document as it was before the update, or none if it was upserted.
+    #[default]
+    Before,
+    /// The document as it is after the update or upsert.
+    After,
+}
+
+/// Options for [`Collection::find_one_and_update_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct FindOneAndUpdateOptions {
+    /// Which version of the document to return; before the update by default.
+    pub return_document: Option<ReturnDocument>,
+    /// Whether to insert if no documents match.
+    pub upsert: Option<bool>,
+    /// Order deciding which document is updated when several match.
+    pub sort: Option<Document>,
+    /// Projection applied to the returned document.
+    pub projection: Option<Document>,
+    /// Array filters for updating nested arrays.
+    pub array_filters: Option<Vec<Document>>,
+    /// Variables usable as `$$name` in the filter and update expressions.
+    pub let_vars: Option<Document>,
+    /// Comment attached to the operation in server logs.
+    pub comment: Option<String>,
+    /// How strings are compared in the filter and sort.
+    pub collation: Option<Collation>,
+    /// Index to use.
+    pub hint: Option<Hint>,
+    /// Whether to skip the collection's schema validation.
+    pub bypass_document_validation: Option<bool>,
+}
+
+impl FindOneAndUpdateOptions {
+    /// Create a builder.
+    pub fn builder() -> FindOneAndUpdateOptionsBuilder {
+        FindOneAndUpdateOptionsBuilder::default()
+    }
+
+    /// Convert to the options map sent to the server.
+    pub(crate) fn to_json(&self, codec: Option<&dyn ValueCodec>) -> Result<JsonValue> {
+        let update = UpdateOptions {
+            upsert: self.upsert,
+            array_filters: self.array_filters.clone(),
+            let_vars: self.let_vars.clone(),
+            comment: self.comment.clone(),
+            collation: self.collation.clone(),
+            hint: self.hint.clone(),
+            bypass_document_validation: self.bypass_document_validation,
+        };
+        let mut opts_json = update.to_json(codec)?;
+        let JsonValue::Object(ref mut opts) = opts_json else {
+            return Ok(opts_json);
+        };
+        if let Some(return_document) = self.return_document {
+            let value = match return_document {
+                ReturnDocument::Before => "before",
+                ReturnDocument::After => "after",
+            };
+            opts.insert("returnDocument".to_string(), serde_json::json!(value));
+        }
+        if let Some(ref sort) = self.sort {
+            opts.insert("sort".to_string(), encode_document(sort, codec)?);
+        }
+        if let Some(ref projection) = self.projection {
+            opts.insert("projection".to_string(), encode_document(projection, codec)?);
+        }
+        Ok(opts_json)
+    }
+}
+
+/// Builder for FindOneAndUpdateOptions.
+#[derive(Debug, Clone, Default)]
+pub struct FindOneAndUpdateOptionsBuilder {
+    options: FindOneAndUpdateOptions,
+}
+
+impl FindOneAndUpdateOptionsBuilder {
+    /// Set which version of the document to return.
+    pub fn return_document(mut self, return_document: ReturnDocument) -> Self {
+        self.options.return_document = Some(return_document);
+        self
+    }
+
+    /// Set upsert option.
+    pub fn upsert(mut self, upsert: bool) -> Self {
+        self.options.upsert = Some(upsert);
+        self
+    }
+
+    /// Set the sort order.
+    pub fn sort(mut self, sort: Document) -> Self {
+        self.options.sort = Some(sort);
+        self
+    }
+
+    /// Set the projection.
+    pub fn projection(mut self, projection: Document) -> Self {
+        self.options.projection = Some(projection);
+        self
+    }
+
+    /// Set array filters.
+    pub fn array_filters(mut self, filters: Vec<Document>) -> Self {
+        self.options.array_filters = Some(filters);
+        self
+    }
+
+    /// Set variables usable as `$$name` in the filter and update.
+    pub fn let_vars(mut self, let_vars: Document) -> Self {
+        self.options.let_vars = Some(let_vars);
+        self
+    }
+
+    /// Set the comment.
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.options.comment = Some(comment.into());
+        self
+    }
+
+    /// Set the collation.
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.options.collation = Some(collation);
+        self
+    }
+
+    /// Set the index to use, by name or key pattern.
+    pub fn hint(mut self, hint: impl Into<Hint>) -> Self {
+        self.options.hint = Some(hint.into());
+        self
+    }
+
+    /// Set whether to skip schema validation.
+    pub fn bypass_document_validation(mut self, bypass: bool) -> Self {
+        self.options.bypass_document_validation = Some(bypass);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> FindOneAndUpdateOptions {
+        self.options
+    }
+}
+
+/// Options for delete operations.
+#[derive(Debug, Clone, Default)]
+pub struct DeleteOptions {
+    /// Variables usable as `$$name` in the filter.
+    pub let_vars: Option<Document>,
+    /// Comment attached to the operation in server logs.
+    pub comment: Option<String>,
+    /// How strings are compared in the filter.
+    pub collation: Option<Collation>,
+    /// Index to use.
+    pub hint: Option<Hint>,
+}
+
+impl DeleteOptions {
+    /// Create a builder.
+    pub fn builder() -> DeleteOptionsBuilder {
+        DeleteOptionsBuilder::default()
+    }
+
+    /// Convert to the options map sent to the server.
+    pub(crate) fn to_json(&self, codec: Option<&dyn ValueCodec>) -> Result<JsonValue> {
+        let mut opts_json = serde_json::Map::new();
+        if let Some(ref let_vars) = self.let_vars {
+            opts_json.insert("let".to_string(), encode_document(let_vars, codec)?);
+        }
+        if let Some(ref comment) = self.comment {
+            opts_json.insert("comment".to_string(), serde_json::json!(comment));
+        }
+        if let Some(ref collation) = self.collation {
+            let collation = encode_document(&collation.to_document(), codec)?;
+            opts_json.insert("collation".to_string(), collation);
+        }
+        if let Some(ref hint) = self.hint {
+            opts_json.insert("hint".to_string(), hint.to_json(codec)?);
+        }
+        Ok(JsonValue::Object(opts_json))
+    }
+}
+
+/// Builder for DeleteOptions.
+#[derive(Debug, Clone, Default)]
+pub struct DeleteOptionsBuilder {
+    options: DeleteOptions,
+}
+
+impl DeleteOptionsBuilder {
+    /// Set variables usable as `$$name` in the filter.
+    pub fn let_vars(mut self, let_vars: Document) -> Self {
+        self.options.let_vars = Some(let_vars);
+        self
+    }
+
+    /// Set the comment.
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.options.comment = Some(comment.into());
+        self
+    }
+
+    /// Set the collation.
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.options.collation = Some(collation);
+        self
+    }
+
+    /// Set the index to use, by name or key pattern.
+    pub fn hint(mut self, hint: impl Into<Hint>) -> Self {
+        self.options.hint = Some(hint.into());
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> DeleteOptions {
+        self.options
+    }
+}
+
+/// Options for [`Collection::count_documents_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct CountOptions {
+    /// Most documents to count.
+    pub limit: Option<u64>,
+    /// Matching documents to skip before counting.
+    pub skip: Option<u64>,
+    /// Index to use.
+    pub hint: Option<Hint>,
+    /// Server-side time limit for the count, in milliseconds.
+    pub max_time_ms: Option<u64>,
+    /// How strings are compared in the filter.
+    pub collation: Option<Collation>,
+}
+
+impl CountOptions {
+    /// Create a builder.
+    pub fn builder() -> CountOptionsBuilder {
+        CountOptionsBuilder::default()
+    }
+
+    /// Convert to the options map sent to the server.
+    pub(crate) fn to_json(&self, codec: Option<&dyn ValueCodec>) -> Result<JsonValue> {
+        let mut opts_json = serde_json::Map::new();
+        if let Some(limit) = self.limit {
+            opts_json.insert("limit".to_string(), serde_json::json!(limit));
+        }
+        if let Some(skip) = self.skip {
+            opts_json.insert("skip".to_string(), serde_json::json!(skip));
+        }
+        if let Some(ref hint) = self.hint {
+            opts_json.insert("hint".to_string(), hint.to_json(codec)?);
+        }
+        if let Some(max_time_ms) = self.max_time_ms {
+            opts_json.insert("maxTimeMS".to_string(), serde_json::json!(max_time_ms));
+        }
+        if let Some(ref collation) = self.collation {
+            let collation = encode_document(&collation.to_document(), codec)?;
+            opts_json.insert("collation".to_string(), collation);
+        }
+        Ok(JsonValue::Object(opts_json))
+    }
+}
+
+/// Builder for CountOptions.
+#[derive(Debug, Clone, Default)]
+pub struct CountOptionsBuilder {
+    options: CountOptions,
+}
+
+impl CountOptionsBuilder {
+    /// Set the most documents to count.
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.options.limit = Some(limit);
+        self
+    }
+
+    /// Set the number of documents to skip.
+    pub fn skip(mut self, skip: u64) -> Self {
+        self.options.skip = Some(skip);
+        self
+    }
+
+    /// Set the index to use, by name or key pattern.
+    pub fn hint(mut self, hint: impl Into<Hint>) -> Self {
+        self.options.hint = Some(hint.into());
+        self
+    }
+
+    /// Set the server-side time limit in milliseconds.
+    pub fn max_time_ms(mut self, max_time_ms: u64) -> Self {
+        self.options.max_time_ms = Some(max_time_ms);
+        self
+    }
+
+    /// Set the collation.
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.options.collation = Some(collation);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> CountOptions {
+        self.options
+    }
+}
+
+/// Options for [`Collection::estimated_document_count_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct EstimatedDocumentCountOptions {
+    /// Server-side time limit for the count, in milliseconds.
+    pub max_time_ms: Option<u64>,
+}
+
+impl EstimatedDocumentCountOptions {
+    /// Create a builder.
+    pub fn builder() -> EstimatedDocumentCountOptionsBuilder {
+        EstimatedDocumentCountOptionsBuilder::default()
+    }
+
+    /// Convert to the options map sent to the server.
+    pub(crate) fn to_json(&self) -> JsonValue {
+        let mut opts_json = serde_json::Map::new();
+        if let Some(max_time_ms) = self.max_time_ms {
+            opts_json.insert("maxTimeMS".to_string(), serde_json::json!(max_time_ms));
+        }
+        JsonValue::Object(opts_json)
+    }
+}
+
+/// Builder for EstimatedDocumentCountOptions.
+#[derive(Debug, Clone, Default)]
+pub struct EstimatedDocumentCountOptionsBuilder {
+    options: EstimatedDocumentCountOptions,
+}
+
+impl EstimatedDocumentCountOptionsBuilder {
+    /// Set the server-side time limit in milliseconds.
+    pub fn max_time_ms(mut self, max_time_ms: u64) -> Self {
+        self.options.max_time_ms = Some(max_time_ms);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> EstimatedDocumentCountOptions {
+        self.options
+    }
+}
+
+/// Options for [`Collection::aggregate_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct AggregateOptions {
+    /// Whether stages may write temporary files when they exceed the memory limit.
+    pub allow_disk_use: Option<bool>,
+    /// Batch size for the cursor.
+    pub batch_size: Option<u32>,
+    /// Variables usable as `$$name` in the pipeline.
+    pub let_vars: Option<Document>,
+    /// Comment attached to the operation in server logs.
+    pub comment: Option<String>,
+    /// How strings are compared in the pipeline.
+    pub collation: Option<Collation>,
+    /// Index to use for the first stages.
+    pub hint: Option<Hint>,
+}
+
+impl AggregateOptions {
+    /// Create a builder.
+    pub fn builder() -> AggregateOptionsBuilder {
+        AggregateOptionsBuilder::default()
+    }
+
+    /// Convert to the options map sent to the server.
+    pub(crate) fn to_json(&self, codec: Option<&dyn ValueCodec>) -> Result<JsonValue> {
+        let mut opts_json = serde_json::Map::new();
+        if let Some(allow_disk_use) = self.allow_disk_use {
+            opts_json.insert("allowDiskUse".to_string(), serde_json::json!(allow_disk_use));
+        }
+        if let Some(batch_size) = self.batch_size {
+            opts_json.insert("batchSize".to_string(), serde_json::json!(batch_size));
+        }
+        if let Some(ref let_vars) = self.let_vars {
+            opts_json.insert("let".to_string(), encode_document(let_vars, codec)?);
+        }
+        if let Some(ref comment) = self.comment {
+            opts_json.insert("comment".to_string(), serde_json::json!(comment));
+        }
+        if let Some(ref collation) = self.collation {
+            let collation = encode_document(&collation.to_document(), codec)?;
+            opts_json.insert("collation".to_string(), collation);
+        }
+        if let Some(ref hint) = self.hint {
+            opts_json.insert("hint".to_string(), hint.to_json(codec)?);
+        }
+        Ok(JsonValue::Object(opts_json))
+    }
+}
+
+/// Builder for AggregateOptions.
+#[derive(Debug, Clone, Default)]
+pub struct AggregateOptionsBuilder {
+    options: AggregateOptions,
+}
+
+impl AggregateOptionsBuilder {
+    /// Set whether stages may use temporary files.
+    pub fn allow_disk_use(mut self, allow_disk_use: bool) -> Self {
+        self.options.allow_disk_use = Some(allow_disk_use);
+        self
+    }
+
+    /// Set the batch size.
+    pub fn batch_size(mut self, batch_size: u32) -> Self {
+        self.options.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Set variables usable as `$$name` in the pipeline.
+    pub fn let_vars(mut self, let_vars: Document) -> Self {
+        self.options.let_vars = Some(let_vars);
+        self
+    }
+
+    /// Set the comment.
+    pub fn comment(mut self, comment: impl Into<String>) -> Self {
+        self.options.comment = Some(comment.into());
+        self
+    }
+
+    /// Set the collation.
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.options.collation = Some(collation);
+        self
+    }
+
+    /// Set the index to use, by name or key pattern.
+    pub fn hint(mut self, hint: impl Into<Hint>) -> Self {
+        self.options.hint = Some(hint.into());
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> AggregateOptions {
+        self.options
+    }
+}
+
+/// A handle to a MongoDB collection.
+///
+/// # Type Parameters
+///
+/// * `T` - The type of documents in this collection.
+///
+/// # Example
+///
+/// ```ignore
+/// use mongo_do::{Client, bson::doc};
+/// use serde::{Serialize, Deserialize};
+///
+/// #[derive(Debug, Serialize, Deserialize)]
+/// struct User {
+///     name: String,
+///     email: String,
+/// }
+///
+/// let client = Client::new("mongodb://localhost").await?;
+/// let db = client.database("mydb");
+/// let users = db.collection::<User>("users");
+///
+/// users.insert_one(User { name: "John".to_string(), email: "john@example.com".to_string() }).await?;
+/// ```
+pub struct Collection<T> {
+    /// Database name.
+    pub(crate) db_name: String,
+    /// Collection name.
+    pub(crate) name: String,
+    /// RPC client.
+    pub(crate) rpc_client: Transport,
+    /// Automatic encryption, when configured on the client.
+    #[cfg(feature = "encryption")]
+    pub(crate) auto_encrypter: Option<Arc<AutoEncrypter>>,
+    /// Read concern inherited from the database handle.
+    pub(crate) read_concern: Option<ReadConcern>,
+    /// Write concern inherited from the database handle.
+    pub(crate) write_concern: Option<WriteConcern>,
+    /// Type marker.
+    _marker: PhantomData<T>,
+}
+
+impl<T> Collection<T> {
+    /// Create a new collection handle.
+    pub(crate) fn new(db_name: String, name: String, rpc_client: Transport) -> Self {
+        Self {
+            db_name,
+            name,
+            rpc_client,
+            #[cfg(feature = "encryption")]
+            auto_encrypter: None,
+            read_concern: None,
+            write_concern: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get the collection name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Get the database name.
+    pub fn database_name(&self) -> &str {
+        &self.db_name
+    }
+
+    /// Get the read concern inherited from the database, if any.
+    pub fn read_concern(&self) -> Option<&ReadConcern> {
+        self.read_concern.as_ref()
+    }
+
+    /// Get the write concern inherited from the database, if any.
+    pub fn write_concern(&self) -> Option<&WriteConcern> {
+        self.write_concern.as_ref()
     }
 
     /// Get the full namespace (db.collection).
@@ -207,201 +1297,1189 @@ impl<T> Collection<T> {
         format!("{}.{}", self.db_name, self.name)
     }
 
-    /// Clone this collection with a new type parameter.
-    pub fn clone_with_type<U>(&self) -> Collection<U> {
-        Collection {
-            db_name: self.db_name.clone(),
-            name: self.name.clone(),
-            rpc_client: self.rpc_client.clone(),
-            _marker: PhantomData,
+    /// Clone this collection with a new type parameter.
+    pub fn clone_with_type<U>(&self) -> Collection<U> {
+        Collection {
+            db_name: self.db_name.clone(),
+            name: self.name.clone(),
+            rpc_client: self.rpc_client.clone(),
+            #[cfg(feature = "encryption")]
+            auto_encrypter: self.auto_encrypter.clone(),
+            read_concern: self.read_concern.clone(),
+            write_concern: self.write_concern.clone(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Create a cursor over a fetched batch, sharing this collection's client state.
+    fn cursor<U>(&self, documents: Vec<JsonValue>, cursor_id: Option<String>) -> Cursor<U> {
+        #[allow(unused_mut)]
+        let mut cursor = Cursor::new(self.namespace(), documents, cursor_id)
+            .with_transport(self.rpc_client.clone());
+        #[cfg(feature = "encryption")]
+        {
+            cursor.auto_encrypter = self.auto_encrypter.clone();
+        }
+        cursor
+    }
+
+    /// Encrypt the designated fields of an outgoing document.
+    #[cfg(feature = "encryption")]
+    async fn encrypt_document(&self, doc: &mut JsonValue) -> Result<()> {
+        match self.auto_encrypter {
+            Some(ref encrypter) => encrypter.encrypt_document(&self.namespace(), doc).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Encrypt the designated fields of an outgoing document.
+    #[cfg(not(feature = "encryption"))]
+    async fn encrypt_document(&self, _doc: &mut JsonValue) -> Result<()> {
+        Ok(())
+    }
+
+    /// Encrypt the designated fields assigned by an outgoing update.
+    #[cfg(feature = "encryption")]
+    async fn encrypt_update(&self, update: &mut JsonValue) -> Result<()> {
+        match self.auto_encrypter {
+            Some(ref encrypter) => encrypter.encrypt_update(&self.namespace(), update).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Encrypt the designated fields assigned by an outgoing update.
+    #[cfg(not(feature = "encryption"))]
+    async fn encrypt_update(&self, _update: &mut JsonValue) -> Result<()> {
+        Ok(())
+    }
+
+    /// Decrypt any encrypted values in an incoming document.
+    #[cfg(feature = "encryption")]
+    async fn decrypt(&self, value: &mut JsonValue) -> Result<()> {
+        match self.auto_encrypter {
+            Some(ref encrypter) => encrypter.decrypt(value).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Decrypt any encrypted values in an incoming document.
+    #[cfg(not(feature = "encryption"))]
+    async fn decrypt(&self, _value: &mut JsonValue) -> Result<()> {
+        Ok(())
+    }
+
+    /// Convert an update to JSON, encrypting the designated fields it assigns.
+    async fn update_json(&self, update: UpdateModifications) -> Result<JsonValue> {
+        match update {
+            UpdateModifications::Document(update) => {
+                let mut update_json = self.rpc_client.encode(&update)?;
+                self.encrypt_update(&mut update_json).await?;
+                Ok(update_json)
+            }
+            UpdateModifications::Pipeline(stages) => {
+                let mut stages_json = Vec::with_capacity(stages.len());
+                for stage in &stages {
+                    let mut stage_json = self.rpc_client.encode(stage)?;
+                    self.encrypt_update(&mut stage_json).await?;
+                    stages_json.push(stage_json);
+                }
+                Ok(JsonValue::Array(stages_json))
+            }
+        }
+    }
+}
+
+impl<T> fmt::Debug for Collection<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Collection")
+            .field("namespace", &self.namespace())
+            .field("read_concern", &self.read_concern)
+            .field("write_concern", &self.write_concern)
+            .finish()
+    }
+}
+
+impl<T> Clone for Collection<T> {
+    fn clone(&self) -> Self {
+        Self {
+            db_name: self.db_name.clone(),
+            name: self.name.clone(),
+            rpc_client: self.rpc_client.clone(),
+            #[cfg(feature = "encryption")]
+            auto_encrypter: self.auto_encrypter.clone(),
+            read_concern: self.read_concern.clone(),
+            write_concern: self.write_concern.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// Writes, which only serialize `T`, so insert-only types need not implement
+// `Deserialize`.
+impl<T: Serialize + Send + Sync + Unpin + 'static> Collection<T> {
+    /// Insert a single document.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let result = collection.insert_one(doc! { "name": "John" }).await?;
+    /// println!("Inserted ID: {:?}", result.inserted_id);
+    /// ```
+    pub async fn insert_one(&self, doc: impl Into<T>) -> Result<InsertOneResult> {
+        self.insert_one_with_options(doc, None).await
+    }
+
+    /// Insert a single document with options.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = InsertOneOptions::builder().bypass_document_validation(true).build();
+    /// collection.insert_one_with_options(legacy_doc, options).await?;
+    /// ```
+    pub async fn insert_one_with_options(
+        &self,
+        doc: impl Into<T>,
+        options: impl Into<Option<InsertOneOptions>>,
+    ) -> Result<InsertOneResult> {
+        let document = doc.into();
+        let options = options.into().unwrap_or_default();
+        self.insert_json(self.rpc_client.encode_typed(&document)?, &options).await
+    }
+
+    /// Insert a single document by reference, without cloning or moving it.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let result = collection.insert_one_ref(&report).await?;
+    /// println!("Inserted {:?}, still have {}", result.inserted_id, report.title);
+    /// ```
+    pub async fn insert_one_ref(&self, doc: &T) -> Result<InsertOneResult> {
+        self.insert_json(self.rpc_client.encode_typed(doc)?, &InsertOneOptions::default()).await
+    }
+
+    /// Insert a [`Model`], storing the `_id` the server generates back into
+    /// it when it has none.
+    ///
+    /// An unset `_id` is left out of the inserted document, so a model whose
+    /// `_id` is an `Option` need not skip serializing `None`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut user = User { id: None, email: "ada@example.com".into() };
+    /// users.insert_one_mut(&mut user).await?;
+    /// assert!(user.id.is_some());
+    /// ```
+    pub async fn insert_one_mut(&self, doc: &mut T) -> Result<InsertOneResult>
+    where
+        T: Model,
+    {
+        let mut json_doc = self.rpc_client.encode_typed(&*doc)?;
+        let generated = match json_doc.as_object_mut() {
+            Some(fields) => match fields.get("_id") {
+                None => true,
+                Some(JsonValue::Null) => fields.remove("_id").is_some(),
+                Some(_) => false,
+            },
+            None => false,
+        };
+        let result = self.insert_json(json_doc, &InsertOneOptions::default()).await?;
+        if generated && !matches!(result.inserted_id, bson::Bson::Null) {
+            doc.set_id(result.inserted_id.clone());
+        }
+        Ok(result)
+    }
+
+    /// Insert multiple documents.
+    ///
+    /// Accepts owned documents or references, so existing values can be
+    /// inserted without cloning them.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let docs = vec![
+    ///     doc! { "name": "John" },
+    ///     doc! { "name": "Jane" },
+    /// ];
+    /// let result = collection.insert_many(&docs).await?;
+    /// let result = collection.insert_many(docs).await?;
+    /// ```
+    pub async fn insert_many<I>(&self, docs: I) -> Result<InsertManyResult>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<T>,
+    {
+        self.insert_many_with_options(docs, None).await
+    }
+
+    /// Insert multiple documents with options.
+    ///
+    /// If some documents fail, returns [`MongoError::InsertMany`] with the
+    /// failures and the ids of the documents that were inserted.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = InsertManyOptions::builder().ordered(false).build();
+    /// let result = collection.insert_many_with_options(&docs, options).await?;
+    /// ```
+    pub async fn insert_many_with_options<I>(
+        &self,
+        docs: I,
+        options: impl Into<Option<InsertManyOptions>>,
+    ) -> Result<InsertManyResult>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<T>,
+    {
+        let options = options.into().unwrap_or_default();
+        let mut json_docs: Vec<JsonValue> = docs
+            .into_iter()
+            .map(|d| self.rpc_client.encode_typed(d.borrow()))
+            .collect::<Result<_>>()?;
+        for json_doc in &mut json_docs {
+            self.encrypt_document(json_doc).await?;
+        }
+
+        let mut args = vec![
+            serde_json::json!(self.db_name),
+            serde_json::json!(self.name),
+            serde_json::json!(json_docs),
+        ];
+        let opts_json = options.to_json();
+        if opts_json.as_object().is_some_and(|opts| !opts.is_empty()) {
+            args.push(opts_json);
+        }
+        let result = self.rpc_client.call_raw(Method::InsertMany, args).await?;
+
+        let mut inserted_ids = std::collections::HashMap::new();
+        if let Some(ids) = result.get("insertedIds").and_then(|v| v.as_object()) {
+            for (k, v) in ids {
+                if let Ok(idx) = k.parse::<usize>() {
+                    inserted_ids.insert(idx, self.rpc_client.decode(v));
+                }
+            }
+        }
+        let write_errors = write_errors(&result);
+        if !write_errors.is_empty() {
+            return Err(MongoError::InsertMany(InsertManyError {
+                write_errors,
+                inserted_ids,
+            }));
+        }
+        if inserted_ids.len() != json_docs.len() && self.rpc_client.checks(&result) {
+            return Err(MongoError::protocol(
+                format!("expected {} ids in `insertedIds`", json_docs.len()),
+                &result,
+            ));
+        }
+
+        Ok(InsertManyResult { inserted_ids })
+    }
+
+    /// Replace a single document.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let result = users.replace_one(doc! { "_id": id }, user).await?;
+    /// ```
+    pub async fn replace_one(
+        &self,
+        filter: Document,
+        replacement: impl Into<T>,
+    ) -> Result<UpdateResult> {
+        self.replace_one_with_options(filter, replacement, None).await
+    }
+
+    /// Replace a single document with options.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = ReplaceOptions::builder().upsert(true).build();
+    /// users.replace_one_with_options(doc! { "_id": id }, user, options).await?;
+    /// ```
+    pub async fn replace_one_with_options(
+        &self,
+        filter: Document,
+        replacement: impl Into<T>,
+        options: impl Into<Option<ReplaceOptions>>,
+    ) -> Result<UpdateResult> {
+        let options = options.into().unwrap_or_default();
+
+        let filter_json = self.rpc_client.encode(&filter)?;
+        let mut replacement_json = self.rpc_client.encode_typed::<T>(&replacement.into())?;
+        self.encrypt_document(&mut replacement_json).await?;
+
+        let args = vec![
+            serde_json::json!(self.db_name),
+            serde_json::json!(self.name),
+            filter_json,
+            replacement_json,
+            options.to_json(self.rpc_client.codec.as_deref())?,
+        ];
+        let result = self.rpc_client.call_raw(Method::ReplaceOne, args).await?;
+
+        Ok(UpdateResult {
+            matched_count: self.rpc_client.response_count(&result, "matchedCount")?,
+            modified_count: self.rpc_client.response_count(&result, "modifiedCount")?,
+            upserted_id: result.get("upsertedId").map(|v| self.rpc_client.decode(v)),
+            acknowledged: write_acknowledged(&result),
+            raw_response: write_response(&result),
+        })
+    }
+
+    /// Insert a document without an `_id`, or replace the document with its `_id`.
+    ///
+    /// A document whose `_id` is missing or null is inserted and gets a
+    /// server-generated `_id`. A document with an `_id` replaces the stored
+    /// document, or is inserted if there is none.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// match users.save(&user).await? {
+    ///     SaveResult::Inserted(id) => println!("Created {}", id),
+    ///     SaveResult::Replaced(id) => println!("Updated {}", id),
+    /// }
+    /// ```
+    pub async fn save(&self, doc: &T) -> Result<SaveResult> {
+        let mut json_doc = self.rpc_client.encode_typed(doc)?;
+        let fields = json_doc
+            .as_object_mut()
+            .ok_or_else(|| MongoError::invalid_argument("save requires a document"))?;
+        let id = match fields.get("_id") {
+            Some(JsonValue::Null) => {
+                fields.remove("_id");
+                None
+            }
+            id => id.cloned(),
+        };
+        self.encrypt_document(&mut json_doc).await?;
+
+        let id = match id {
+            Some(id) => id,
+            None => {
+                let result = self
+                    .rpc_client
+                    .call_raw(
+                        Method::InsertOne,
+                        vec![
+                            serde_json::json!(self.db_name),
+                            serde_json::json!(self.name),
+                            json_doc,
+                        ],
+                    )
+                    .await?;
+                let inserted_id = self.rpc_client.response_id(&result, "insertedId")?;
+                return Ok(SaveResult::Inserted(inserted_id));
+            }
+        };
+
+        let result = self
+            .rpc_client
+            .call_raw(
+                Method::ReplaceOne,
+                vec![
+                    serde_json::json!(self.db_name),
+                    serde_json::json!(self.name),
+                    serde_json::json!({ "_id": id }),
+                    json_doc,
+                    serde_json::json!({ "upsert": true }),
+                ],
+            )
+            .await?;
+
+        if result.get("upsertedId").is_some_and(|upserted| !upserted.is_null()) {
+            Ok(SaveResult::Inserted(self.rpc_client.decode(&id)))
+        } else {
+            Ok(SaveResult::Replaced(self.rpc_client.decode(&id)))
+        }
+    }
+}
+
+// Reads, which only deserialize `T`, so view-only types need not implement
+// `Serialize`.
+impl<T: DeserializeOwned + Send + Sync + Unpin + 'static> Collection<T> {
+    /// Find documents matching a filter.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let cursor = collection.find(doc! { "status": "active" }).await?;
+    /// let docs: Vec<User> = cursor.collect().await?;
+    /// ```
+    pub async fn find(&self, filter: impl Into<Option<Document>>) -> Result<Cursor<T>> {
+        self.find_with_options(filter, None).await
+    }
+
+    /// Find documents with options.
+    pub async fn find_with_options(
+        &self,
+        filter: impl Into<Option<Document>>,
+        options: impl Into<Option<FindOptions>>,
+    ) -> Result<Cursor<T>> {
+        let filter_doc = filter.into().unwrap_or_default();
+        let options = options.into().unwrap_or_default();
+
+        let filter_json = self.rpc_client.encode(&filter_doc)?;
+        let mut args = vec![
+            serde_json::json!(self.db_name),
+            serde_json::json!(self.name),
+            filter_json,
+        ];
+
+        // Add options
+        let mut opts_json = serde_json::Map::new();
+        if let Some(limit) = options.limit {
+            opts_json.insert("limit".to_string(), serde_json::json!(limit));
+        }
+        if let Some(skip) = options.skip {
+            opts_json.insert("skip".to_string(), serde_json::json!(skip));
+        }
+        if let Some(ref sort) = options.sort {
+            opts_json.insert("sort".to_string(), self.rpc_client.encode(sort)?);
+        }
+        if let Some(ref projection) = options.projection {
+            opts_json.insert("projection".to_string(), self.rpc_client.encode(projection)?);
+        }
+        if let Some(batch_size) = options.batch_size {
+            opts_json.insert("batchSize".to_string(), serde_json::json!(batch_size));
+        }
+        if let Some(ref comment) = options.comment {
+            opts_json.insert("comment".to_string(), serde_json::json!(comment));
+        }
+        let cursor_type = options.cursor_type.unwrap_or_default();
+        if cursor_type != CursorType::NonTailable {
+            self.rpc_client.hello().await?.require(capability::TAILABLE_CURSORS)?;
+            opts_json.insert("tailable".to_string(), serde_json::json!(true));
+        }
+        if cursor_type == CursorType::TailableAwait {
+            opts_json.insert("awaitData".to_string(), serde_json::json!(true));
+        }
+        if let Some(max_await_time) = options.max_await_time {
+            opts_json.insert(
+                "maxAwaitTimeMS".to_string(),
+                serde_json::json!(max_await_time.as_millis() as u64),
+            );
+        }
+        if let Some(ref collation) = options.collation {
+            opts_json.insert(
+                "collation".to_string(),
+                self.rpc_client.encode(&collation.to_document())?,
+            );
+        }
+        if let Some(ref hint) = options.hint {
+            opts_json.insert(
+                "hint".to_string(),
+                hint.to_json(self.rpc_client.codec.as_deref())?,
+            );
+        }
+        args.push(JsonValue::Object(opts_json));
+
+        let result = self.rpc_client.call_raw(Method::Find, args).await?;
+
+        let mut documents = result
+            .get("documents")
+            .and_then(|v| v.as_array())
+            .cloned()
+            .unwrap_or_default();
+        for document in &mut documents {
+            self.decrypt(document).await?;
+        }
+
+        let cursor_id = result
+            .get("cursorId")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+
+        let cursor = self.cursor(documents, cursor_id);
+        if cursor_type != CursorType::NonTailable {
+            cursor.state.lock().await.tailable = true;
+        }
+        Ok(cursor)
+    }
+
+    /// Find the first `n` documents matching `filter`, ordered by `sort_field`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let leaders = players
+    ///     .top_n(doc! { "season": 2024 }, "score", SortOrder::Descending, 10)
+    ///     .await?;
+    /// ```
+    pub async fn top_n(
+        &self,
+        filter: impl Into<Option<Document>>,
+        sort_field: &str,
+        order: SortOrder,
+        n: u32,
+    ) -> Result<Vec<T>> {
+        // A limit of 0 means no limit to the server.
+        if n == 0 {
+            return Ok(Vec::new());
+        }
+        let options = FindOptions::builder()
+            .sort(doc! { sort_field: order })
+            .limit(i64::from(n))
+            .build();
+        self.find_with_options(filter, options).await?.collect().await
+    }
+
+    /// Find a single document.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let user = collection.find_one(doc! { "email": "john@example.com" }).await?;
+    /// ```
+    pub async fn find_one(&self, filter: impl Into<Option<Document>>) -> Result<Option<T>> {
+        let filter_doc = filter.into().unwrap_or_default();
+        let filter_json = self.rpc_client.encode(&filter_doc)?;
+
+        let mut result = self
+            .rpc_client
+            .call_raw(
+                Method::FindOne,
+                vec![
+                    serde_json::json!(self.db_name),
+                    serde_json::json!(self.name),
+                    filter_json,
+                ],
+            )
+            .await?;
+
+        if result.is_null() {
+            return Ok(None);
+        }
+        self.decrypt(&mut result).await?;
+
+        self.rpc_client.decode_typed(&result).map(Some)
+    }
+
+    /// Scan the whole collection in `_id` order, `batch_size` documents at a time.
+    ///
+    /// See [`Scan`] for checkpointing, so long jobs such as backfills and
+    /// re-indexing can resume where they stopped.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut scan = users.scan(1000);
+    /// while let Some(batch) = scan.next_batch().await? {
+    ///     backfill(batch).await?;
+    /// }
+    /// ```
+    pub fn scan(&self, batch_size: u32) -> Scan<T> {
+        Scan::new(self.clone_with_type(), batch_size)
+    }
+
+    /// Follow a capped collection, returning the documents matching `filter`
+    /// and then each new one as it is inserted.
+    ///
+    /// The stream never ends; it reconnects when the cursor dies. See the
+    /// [`tail`](crate::tail) module for details.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut log = Box::pin(events.tail(None));
+    /// while let Some(event) = log.next().await {
+    ///     println!("{:?}", event?);
+    /// }
+    /// ```
+    pub fn tail(
+        &self,
+        filter: impl Into<Option<Document>>,
+    ) -> impl Stream<Item = Result<T>> + Send + 'static {
+        self.tail_with_options(filter, TailOptions::default())
+    }
+
+    /// Follow a capped collection with options.
+    pub fn tail_with_options(
+        &self,
+        filter: impl Into<Option<Document>>,
+        options: TailOptions,
+    ) -> impl Stream<Item = Result<T>> + Send + 'static {
+        tail::tail(self.clone_with_type(), filter.into().unwrap_or_default(), options)
+    }
+
+    /// Split the documents matching `filter` into up to `n` cursors over
+    /// disjoint, ascending ranges of `key`.
+    ///
+    /// Range boundaries come from a `$bucketAuto` on `key`, so the cursors
+    /// hold roughly equal numbers of documents and can be consumed by
+    /// separate tasks. Each cursor is sorted by `key`. Every matching
+    /// document should have `key`, with values of a single type; `_id` is
+    /// the usual choice.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let workers: Vec<_> = events
+    ///     .find_split(doc! { "processed": false }, "_id", 4)
+    ///     .await?
+    ///     .into_iter()
+    ///     .map(|mut cursor| tokio::spawn(async move {
+    ///         while let Some(event) = cursor.try_next().await? {
+    ///             process(event).await;
+    ///         }
+    ///         Ok::<_, MongoError>(())
+    ///     }))
+    ///     .collect();
+    /// ```
+    pub async fn find_split(
+        &self,
+        filter: impl Into<Option<Document>>,
+        key: &str,
+        n: usize,
+    ) -> Result<Vec<Cursor<T>>> {
+        if n == 0 {
+            return Err(MongoError::invalid_argument("cannot split into 0 cursors"));
+        }
+        let filter = filter.into().unwrap_or_default();
+        let buckets = self
+            .run_aggregate::<Document>(vec![
+                doc! { "$match": filter.clone() },
+                doc! { "$bucketAuto": { "groupBy": format!("${}", key), "buckets": n as i64 } },
+            ])
+            .await?
+            .collect()
+            .await?;
+
+        let options = FindOptions::builder().sort(doc! { key: 1 }).build();
+        let mut cursors = Vec::with_capacity(buckets.len());
+        for range in range_filters(&filter, key, &buckets)? {
+            cursors.push(self.find_with_options(range, options.clone()).await?);
         }
+        Ok(cursors)
     }
-}
 
-impl<T> Clone for Collection<T> {
-    fn clone(&self) -> Self {
-        Self {
-            db_name: self.db_name.clone(),
-            name: self.name.clone(),
-            rpc_client: self.rpc_client.clone(),
-            _marker: PhantomData,
+    /// Read all documents matching `filter` using `n` concurrent tasks.
+    ///
+    /// The result set is split with [`Collection::find_split`] and the
+    /// results are returned in ascending `key` order.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let events = events.parallel_collect(doc! { "day": "2024-06-01" }, "_id", 8).await?;
+    /// ```
+    pub async fn parallel_collect(
+        &self,
+        filter: impl Into<Option<Document>>,
+        key: &str,
+        n: usize,
+    ) -> Result<Vec<T>> {
+        let workers = self
+            .find_split(filter, key, n)
+            .await?
+            .into_iter()
+            .map(|cursor| tokio::spawn(cursor.collect()));
+        let mut results = Vec::new();
+        for batch in futures::future::try_join_all(workers)
+            .await
+            .map_err(|e| MongoError::Internal(e.to_string()))?
+        {
+            results.extend(batch?);
         }
+        Ok(results)
     }
-}
 
-impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection<T> {
-    /// Insert a single document.
+    /// Stream all documents matching `filter` from `n` cursors read
+    /// concurrently.
+    ///
+    /// The result set is split with [`Collection::find_split`], and each
+    /// cursor's next batch is fetched while the others are read, so large
+    /// exports are not limited by the latency of one cursor. Documents of
+    /// different cursors are interleaved in no particular order. For the
+    /// cursors themselves, use [`Collection::find_split`].
     ///
     /// # Example
     ///
     /// ```ignore
-    /// let result = collection.insert_one(doc! { "name": "John" }).await?;
-    /// println!("Inserted ID: {:?}", result.inserted_id);
+    /// use futures::StreamExt;
+    ///
+    /// let mut events = events.parallel_scan(None, "_id", 8).await?;
+    /// while let Some(event) = events.next().await {
+    ///     sink.write(&event?).await?;
+    /// }
     /// ```
-    pub async fn insert_one(&self, doc: impl Into<T>) -> Result<InsertOneResult> {
-        let document = doc.into();
-        let json_doc = serde_json::to_value(&document)?;
+    pub async fn parallel_scan(
+        &self,
+        filter: impl Into<Option<Document>>,
+        key: &str,
+        n: usize,
+    ) -> Result<impl Stream<Item = Result<T>> + Send + 'static> {
+        let cursors = self.find_split(filter, key, n).await?;
+        Ok(futures::stream::select_all(cursors))
+    }
 
-        let result = self
+    /// Find one document and update it, returning it as it was before the
+    /// update.
+    pub async fn find_one_and_update(
+        &self,
+        filter: Document,
+        update: impl Into<UpdateModifications>,
+    ) -> Result<Option<T>> {
+        self.find_one_and_update_with_options(filter, update, None).await
+    }
+
+    /// Find one document and update it, with options.
+    ///
+    /// Returns `None` if no document matched, or if one was upserted and
+    /// [`ReturnDocument::Before`] was asked for.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = FindOneAndUpdateOptions::builder()
+    ///     .return_document(ReturnDocument::After)
+    ///     .sort(doc! { "priority": -1 })
+    ///     .build();
+    /// let job = jobs
+    ///     .find_one_and_update_with_options(
+    ///         doc! { "state": "queued" },
+    ///         doc! { "$set": { "state": "running" } },
+    ///         options,
+    ///     )
+    ///     .await?;
+    /// ```
+    pub async fn find_one_and_update_with_options(
+        &self,
+        filter: Document,
+        update: impl Into<UpdateModifications>,
+        options: impl Into<Option<FindOneAndUpdateOptions>>,
+    ) -> Result<Option<T>> {
+        let options = options.into().unwrap_or_default();
+        let filter_json = self.rpc_client.encode(&filter)?;
+        let update_json = self.update_json(update.into()).await?;
+
+        let mut result = self
             .rpc_client
             .call_raw(
-                "mongo.insertOne",
+                Method::FindOneAndUpdate,
                 vec![
                     serde_json::json!(self.db_name),
                     serde_json::json!(self.name),
-                    json_doc,
+                    filter_json,
+                    update_json,
+                    options.to_json(self.rpc_client.codec.as_deref())?,
                 ],
             )
             .await?;
 
-        let inserted_id = if let Some(id) = result.get("insertedId") {
-            json_to_bson(id)
-        } else {
-            bson::Bson::Null
-        };
+        if result.is_null() {
+            return Ok(None);
+        }
+        self.decrypt(&mut result).await?;
 
-        Ok(InsertOneResult { inserted_id })
+        self.rpc_client.decode_typed(&result).map(Some)
     }
 
-    /// Insert multiple documents.
+    /// Update the document matching `filter`, inserting it if none matches,
+    /// and return it as it is after the update.
     ///
     /// # Example
     ///
     /// ```ignore
-    /// let docs = vec![
-    ///     doc! { "name": "John" },
-    ///     doc! { "name": "Jane" },
-    /// ];
-    /// let result = collection.insert_many(docs).await?;
+    /// let counter = counters
+    ///     .find_one_and_upsert(doc! { "_id": "orders" }, doc! { "$inc": { "seq": 1 } })
+    ///     .await?;
     /// ```
-    pub async fn insert_many(&self, docs: impl IntoIterator<Item = T>) -> Result<InsertManyResult> {
-        let json_docs: Vec<JsonValue> = docs
-            .into_iter()
-            .map(|d| serde_json::to_value(&d))
-            .collect::<std::result::Result<_, _>>()?;
+    pub async fn find_one_and_upsert(
+        &self,
+        filter: Document,
+        update: impl Into<UpdateModifications>,
+    ) -> Result<T> {
+        let options = FindOneAndUpdateOptions::builder()
+            .upsert(true)
+            .return_document(ReturnDocument::After)
+            .build();
+        self.find_one_and_update_with_options(filter, update, options)
+            .await?
+            .ok_or_else(|| MongoError::Internal("upsert returned no document".to_string()))
+    }
 
-        let result = self
+    /// Find one document and delete it.
+    pub async fn find_one_and_delete(&self, filter: Document) -> Result<Option<T>> {
+        self.find_one_and_delete_with_options(filter, None).await
+    }
+
+    /// Find one document and delete it, with options.
+    pub async fn find_one_and_delete_with_options(
+        &self,
+        filter: Document,
+        options: impl Into<Option<DeleteOptions>>,
+    ) -> Result<Option<T>> {
+        let options = options.into().unwrap_or_default();
+        let filter_json = self.rpc_client.encode(&filter)?;
+
+        let mut result = self
             .rpc_client
             .call_raw(
-                "mongo.insertMany",
+                Method::FindOneAndDelete,
                 vec![
                     serde_json::json!(self.db_name),
                     serde_json::json!(self.name),
-                    serde_json::json!(json_docs),
+                    filter_json,
+                    options.to_json(self.rpc_client.codec.as_deref())?,
                 ],
             )
             .await?;
 
-        let mut inserted_ids = std::collections::HashMap::new();
-        if let Some(ids) = result.get("insertedIds").and_then(|v| v.as_object()) {
-            for (k, v) in ids {
-                if let Ok(idx) = k.parse::<usize>() {
-                    inserted_ids.insert(idx, json_to_bson(v));
-                }
-            }
+        if result.is_null() {
+            return Ok(None);
         }
+        self.decrypt(&mut result).await?;
 
-        Ok(InsertManyResult { inserted_ids })
+        self.rpc_client.decode_typed(&result).map(Some)
     }
 
-    /// Find documents matching a filter.
+    /// Find the document with the given `_id`.
     ///
     /// # Example
     ///
     /// ```ignore
-    /// let cursor = collection.find(doc! { "status": "active" }).await?;
-    /// let docs: Vec<User> = cursor.collect().await?;
+    /// let user = users.find_by_id(ObjectId::parse_str("65a1f0c2e4b0a1b2c3d4e5f6")?).await?;
     /// ```
-    pub async fn find(&self, filter: impl Into<Option<Document>>) -> Result<Cursor<T>> {
-        self.find_with_options(filter, None).await
+    pub async fn find_by_id(&self, id: impl Into<bson::Bson>) -> Result<Option<T>> {
+        self.find_one(id_filter(id)).await
     }
 
-    /// Find documents with options.
-    pub async fn find_with_options(
+    /// Open a change stream on the collection.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let pipeline = vec![doc! { "$match": { "operationType": "insert" } }];
+    /// let mut stream = collection.watch(pipeline, None).await?;
+    /// while let Some(event) = stream.try_next().await? {
+    ///     println!("{:?}", event.full_document);
+    /// }
+    /// ```
+    pub async fn watch(
         &self,
-        filter: impl Into<Option<Document>>,
-        options: impl Into<Option<FindOptions>>,
-    ) -> Result<Cursor<T>> {
-        let filter_doc = filter.into().unwrap_or_default();
+        pipeline: impl IntoIterator<Item = Document>,
+        options: impl Into<Option<ChangeStreamOptions>>,
+    ) -> Result<ChangeStream<T>> {
+        let pipeline_json: Vec<JsonValue> = pipeline
+            .into_iter()
+            .map(|d| self.rpc_client.encode(&d))
+            .collect::<Result<_>>()?;
         let options = options.into().unwrap_or_default();
 
-        let filter_json = bson_doc_to_json(&filter_doc)?;
-        let mut args = vec![
-            serde_json::json!(self.db_name),
-            serde_json::json!(self.name),
-            filter_json,
-        ];
+        let result = self
+            .rpc_client
+            .call_raw(
+                Method::Watch,
+                vec![
+                    serde_json::json!(self.db_name),
+                    serde_json::json!(self.name),
+                    serde_json::json!(pipeline_json),
+                    self.rpc_client.encode(&options.to_document())?,
+                ],
+            )
+            .await?;
 
-        // Add options
-        let mut opts_json = serde_json::Map::new();
-        if let Some(limit) = options.limit {
-            opts_json.insert("limit".to_string(), serde_json::json!(limit));
-        }
-        if let Some(skip) = options.skip {
-            opts_json.insert("skip".to_string(), serde_json::json!(skip));
-        }
-        if let Some(ref sort) = options.sort {
-            opts_json.insert("sort".to_string(), bson_doc_to_json(sort)?);
+        let stream_id = result
+            .as_str()
+            .ok_or_else(|| MongoError::Deserialization("Expected change stream ID".to_string()))?;
+
+        #[allow(unused_mut)]
+        let mut stream = ChangeStream::new(self.rpc_client.clone(), stream_id.to_string());
+        #[cfg(feature = "encryption")]
+        {
+            stream.auto_encrypter = self.auto_encrypter.clone();
         }
-        if let Some(ref projection) = options.projection {
-            opts_json.insert("projection".to_string(), bson_doc_to_json(projection)?);
+        Ok(stream)
+    }
+
+    /// Open a change stream that checkpoints its resume token.
+    ///
+    /// If the checkpoint's store holds a token for the stream, the stream
+    /// resumes after it, unless `options` already sets a resume point.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let store = Arc::new(CollectionTokenStore::new(db.collection_with_doc("checkpoints")));
+    /// let checkpoint = Checkpoint::new(store, "order-shipper");
+    /// let mut stream = orders.watch_resumable(Vec::new(), None, checkpoint).await?;
+    /// ```
+    pub async fn watch_resumable(
+        &self,
+        pipeline: impl IntoIterator<Item = Document>,
+        options: impl Into<Option<ChangeStreamOptions>>,
+        checkpoint: Checkpoint,
+    ) -> Result<ChangeStream<T>> {
+        let mut options = options.into().unwrap_or_default();
+        if options.resume_after.is_none() && options.start_after.is_none() {
+            options.resume_after = checkpoint.load().await?;
         }
-        if let Some(batch_size) = options.batch_size {
-            opts_json.insert("batchSize".to_string(), serde_json::json!(batch_size));
+        Ok(self.watch(pipeline, options).await?.with_checkpoint(checkpoint))
+    }
+
+    /// Watch for changes to documents matching `filter`.
+    ///
+    /// The filter is written against the collection's documents and applied to
+    /// each event's full document, which is looked up for updates. Deletes
+    /// carry no full document and are therefore not reported.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut stream = orders.watch_where(doc! { "status": "shipped" }).await?;
+    /// while let Some(event) = stream.try_next().await? {
+    ///     ship(event.full_document.unwrap()).await?;
+    /// }
+    /// ```
+    pub async fn watch_where(&self, filter: Document) -> Result<ChangeStream<T>> {
+        let options = ChangeStreamOptions::builder()
+            .full_document(FullDocument::UpdateLookup)
+            .build();
+        self.watch(watch_where_pipeline(filter), options).await
+    }
+}
+
+// Operations that both read and write `T`.
+impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection<T> {
+    /// Find one document and replace it.
+    pub async fn find_one_and_replace(
+        &self,
+        filter: Document,
+        replacement: T,
+    ) -> Result<Option<T>> {
+        let filter_json = self.rpc_client.encode(&filter)?;
+        let mut replacement_json = self.rpc_client.encode_typed(&replacement)?;
+        self.encrypt_document(&mut replacement_json).await?;
+
+        let mut result = self
+            .rpc_client
+            .call_raw(
+                Method::FindOneAndReplace,
+                vec![
+                    serde_json::json!(self.db_name),
+                    serde_json::json!(self.name),
+                    filter_json,
+                    replacement_json,
+                ],
+            )
+            .await?;
+
+        if result.is_null() {
+            return Ok(None);
         }
-        args.push(JsonValue::Object(opts_json));
+        self.decrypt(&mut result).await?;
 
-        let result = self.rpc_client.call_raw("mongo.find", args).await?;
+        self.rpc_client.decode_typed(&result).map(Some)
+    }
 
-        let documents = result
-            .get("documents")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.clone())
-            .unwrap_or_default();
+    /// Read a document, apply `f` to it and write it back if it has not changed since.
+    ///
+    /// The write only succeeds if the stored document still has its original
+    /// values; otherwise the document is read again and `f` reapplied. Returns
+    /// the written document, or `None` if nothing matches `filter`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let account = accounts
+    ///     .modify(doc! { "_id": id }, |account: &mut Account| account.balance -= 10)
+    ///     .await?;
+    /// ```
+    pub async fn modify<F>(&self, filter: Document, f: F) -> Result<Option<T>>
+    where
+        F: FnMut(&mut T),
+    {
+        self.modify_with_options(filter, f, None).await
+    }
 
-        let cursor_id = result
-            .get("cursorId")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
+    /// Read-modify-write a document, with options.
+    pub async fn modify_with_options<F>(
+        &self,
+        filter: Document,
+        mut f: F,
+        options: impl Into<Option<ModifyOptions>>,
+    ) -> Result<Option<T>>
+    where
+        F: FnMut(&mut T),
+    {
+        let options = options.into().unwrap_or_default();
+        let filter_json = self.rpc_client.encode(&filter)?;
+
+        for attempt in 0..=options.max_retries.unwrap_or(3) {
+            if attempt > 0 {
+                self.rpc_client.stats.record_retry();
+            }
+            let stored = self
+                .rpc_client
+                .call_raw(
+                    Method::FindOne,
+                    vec![
+                        serde_json::json!(self.db_name),
+                        serde_json::json!(self.name),
+                        filter_json.clone(),
+                    ],
+                )
+                .await?;
+            if stored.is_null() {
+                return Ok(None);
+            }
 
-        Ok(Cursor::new(self.namespace(), documents, cursor_id)
-            .with_rpc_client(self.rpc_client.clone()))
+            let mut current = stored.clone();
+            self.decrypt(&mut current).await?;
+            let mut value: T = self.rpc_client.decode_typed(&current)?;
+            f(&mut value);
+
+            let mut replacement = self.rpc_client.encode_typed(&value)?;
+            let guard = modify_guard(&stored, &mut replacement, options.version_field.as_deref())?;
+            let modified: T = self.rpc_client.decode_typed(&replacement)?;
+            self.encrypt_document(&mut replacement).await?;
+
+            let result = self
+                .rpc_client
+                .call_raw(
+                    Method::ReplaceOne,
+                    vec![
+                        serde_json::json!(self.db_name),
+                        serde_json::json!(self.name),
+                        guard,
+                        replacement,
+                    ],
+                )
+                .await?;
+            if self.rpc_client.response_count(&result, "matchedCount")? > 0 {
+                return Ok(Some(modified));
+            }
+        }
+
+        Err(MongoError::write(
+            Some(WRITE_CONFLICT_CODE),
+            format!(
+                "document matching {} kept changing while being modified",
+                redact(&filter)
+            ),
+        ))
     }
 
-    /// Find a single document.
+    /// Replace the document matching `filter`, inserting it if none matches,
+    /// and return the stored document.
     ///
     /// # Example
     ///
     /// ```ignore
-    /// let user = collection.find_one(doc! { "email": "john@example.com" }).await?;
+    /// let stored = settings.upsert_one(
+    ///     doc! { "userId": user_id },
+    ///     Settings { user_id, theme: "dark".to_string() },
+    /// ).await?;
     /// ```
-    pub async fn find_one(&self, filter: impl Into<Option<Document>>) -> Result<Option<T>> {
-        let filter_doc = filter.into().unwrap_or_default();
-        let filter_json = bson_doc_to_json(&filter_doc)?;
+    pub async fn upsert_one(&self, filter: Document, doc: impl Into<T>) -> Result<T> {
+        let filter_json = self.rpc_client.encode(&filter)?;
+        let mut replacement_json = self.rpc_client.encode_typed::<T>(&doc.into())?;
+        self.encrypt_document(&mut replacement_json).await?;
 
-        let result = self
+        let mut result = self
             .rpc_client
             .call_raw(
-                "mongo.findOne",
+                Method::FindOneAndReplace,
                 vec![
                     serde_json::json!(self.db_name),
                     serde_json::json!(self.name),
                     filter_json,
+                    replacement_json,
+                    serde_json::json!({ "upsert": true, "returnDocument": "after" }),
                 ],
             )
             .await?;
 
         if result.is_null() {
-            return Ok(None);
+            return Err(MongoError::Internal("No document returned by upsert".to_string()));
+        }
+        self.decrypt(&mut result).await?;
+
+        self.rpc_client.decode_typed(&result)
+    }
+}
+
+// Operations that never convert `T`.
+impl<T: Send + Sync + Unpin + 'static> Collection<T> {
+    /// Insert an untyped document, e.g. one with fields `T` does not model.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut raw = to_document(&user)?;
+    /// raw.insert("importedAt", bson::DateTime::now());
+    /// users.insert_one_doc(raw).await?;
+    /// ```
+    pub async fn insert_one_doc(&self, doc: Document) -> Result<InsertOneResult> {
+        self.insert_json(self.rpc_client.encode(&doc)?, &InsertOneOptions::default()).await
+    }
+
+    /// Insert a document already converted to JSON.
+    async fn insert_json(
+        &self,
+        mut json_doc: JsonValue,
+        options: &InsertOneOptions,
+    ) -> Result<InsertOneResult> {
+        self.encrypt_document(&mut json_doc).await?;
+
+        let mut args = vec![
+            serde_json::json!(self.db_name),
+            serde_json::json!(self.name),
+            json_doc,
+        ];
+        let opts_json = options.to_json();
+        if opts_json.as_object().is_some_and(|opts| !opts.is_empty()) {
+            args.push(opts_json);
         }
+        let result = self.rpc_client.call_raw(Method::InsertOne, args).await?;
+
+        let inserted_id = self.rpc_client.response_id(&result, "insertedId")?;
+
+        Ok(InsertOneResult {
+            inserted_id,
+            acknowledged: write_acknowledged(&result),
+            raw_response: write_response(&result),
+        })
+    }
+
+    /// Find documents matching a filter, as raw BSON instead of `T`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut cursor = events.find_raw(doc! { "day": today }).await?;
+    /// while let Some(event) = cursor.try_next().await? {
+    ///     socket.write_all(event.as_bytes()).await?;
+    /// }
+    /// ```
+    pub async fn find_raw(&self, filter: impl Into<Option<Document>>) -> Result<RawCursor> {
+        self.find_raw_with_options(filter, None).await
+    }
 
-        serde_json::from_value(result)
-            .map(Some)
-            .map_err(|e| MongoError::Deserialization(e.to_string()))
+    /// Find documents as raw BSON, with options.
+    pub async fn find_raw_with_options(
+        &self,
+        filter: impl Into<Option<Document>>,
+        options: impl Into<Option<FindOptions>>,
+    ) -> Result<RawCursor> {
+        self.clone_with_type::<JsonValue>()
+            .find_with_options(filter, options)
+            .await
+            .map(RawCursor::new)
     }
 
     /// Update a single document.
     ///
+    /// The update is either an operator document or an aggregation pipeline.
+    ///
     /// # Example
     ///
     /// ```ignore
@@ -409,11 +2487,17 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
     ///     doc! { "_id": id },
     ///     doc! { "$set": { "name": "Jane" } },
     /// ).await?;
+    ///
+    /// // Compute the new value from existing fields.
+    /// collection.update_one(
+    ///     doc! { "_id": id },
+    ///     vec![doc! { "$set": { "total": { "$add": ["$price", "$tax"] } } }],
+    /// ).await?;
     /// ```
     pub async fn update_one(
         &self,
         filter: Document,
-        update: Document,
+        update: impl Into<UpdateModifications>,
     ) -> Result<UpdateResult> {
         self.update_one_with_options(filter, update, None).await
     }
@@ -422,13 +2506,13 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
     pub async fn update_one_with_options(
         &self,
         filter: Document,
-        update: Document,
+        update: impl Into<UpdateModifications>,
         options: impl Into<Option<UpdateOptions>>,
     ) -> Result<UpdateResult> {
         let options = options.into().unwrap_or_default();
 
-        let filter_json = bson_doc_to_json(&filter)?;
-        let update_json = bson_doc_to_json(&update)?;
+        let filter_json = self.rpc_client.encode(&filter)?;
+        let update_json = self.update_json(update.into()).await?;
 
         let mut args = vec![
             serde_json::json!(self.db_name),
@@ -437,31 +2521,16 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
             update_json,
         ];
 
-        let mut opts_json = serde_json::Map::new();
-        if let Some(upsert) = options.upsert {
-            opts_json.insert("upsert".to_string(), serde_json::json!(upsert));
-        }
-        if let Some(ref array_filters) = options.array_filters {
-            let filters: Vec<JsonValue> = array_filters
-                .iter()
-                .map(|f| bson_doc_to_json(f))
-                .collect::<Result<_>>()?;
-            opts_json.insert("arrayFilters".to_string(), serde_json::json!(filters));
-        }
-        args.push(JsonValue::Object(opts_json));
+        args.push(options.to_json(self.rpc_client.codec.as_deref())?);
 
-        let result = self.rpc_client.call_raw("mongo.updateOne", args).await?;
+        let result = self.rpc_client.call_raw(Method::UpdateOne, args).await?;
 
         Ok(UpdateResult {
-            matched_count: result
-                .get("matchedCount")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0),
-            modified_count: result
-                .get("modifiedCount")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0),
-            upserted_id: result.get("upsertedId").map(json_to_bson),
+            matched_count: self.rpc_client.response_count(&result, "matchedCount")?,
+            modified_count: self.rpc_client.response_count(&result, "modifiedCount")?,
+            upserted_id: result.get("upsertedId").map(|v| self.rpc_client.decode(v)),
+            acknowledged: write_acknowledged(&result),
+            raw_response: write_response(&result),
         })
     }
 
@@ -478,7 +2547,7 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
     pub async fn update_many(
         &self,
         filter: Document,
-        update: Document,
+        update: impl Into<UpdateModifications>,
     ) -> Result<UpdateResult> {
         self.update_many_with_options(filter, update, None).await
     }
@@ -487,13 +2556,13 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
     pub async fn update_many_with_options(
         &self,
         filter: Document,
-        update: Document,
+        update: impl Into<UpdateModifications>,
         options: impl Into<Option<UpdateOptions>>,
     ) -> Result<UpdateResult> {
         let options = options.into().unwrap_or_default();
 
-        let filter_json = bson_doc_to_json(&filter)?;
-        let update_json = bson_doc_to_json(&update)?;
+        let filter_json = self.rpc_client.encode(&filter)?;
+        let update_json = self.update_json(update.into()).await?;
 
         let mut args = vec![
             serde_json::json!(self.db_name),
@@ -502,34 +2571,156 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
             update_json,
         ];
 
-        let mut opts_json = serde_json::Map::new();
-        if let Some(upsert) = options.upsert {
-            opts_json.insert("upsert".to_string(), serde_json::json!(upsert));
-        }
-        if let Some(ref array_filters) = options.array_filters {
-            let filters: Vec<JsonValue> = array_filters
-                .iter()
-                .map(|f| bson_doc_to_json(f))
-                .collect::<Result<_>>()?;
-            opts_json.insert("arrayFilters".to_string(), serde_json::json!(filters));
-        }
-        args.push(JsonValue::Object(opts_json));
+        args.push(options.to_json(self.rpc_client.codec.as_deref())?);
 
-        let result = self.rpc_client.call_raw("mongo.updateMany", args).await?;
+        let result = self.rpc_client.call_raw(Method::UpdateMany, args).await?;
 
         Ok(UpdateResult {
-            matched_count: result
-                .get("matchedCount")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0),
-            modified_count: result
-                .get("modifiedCount")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0),
-            upserted_id: result.get("upsertedId").map(json_to_bson),
+            matched_count: self.rpc_client.response_count(&result, "matchedCount")?,
+            modified_count: self.rpc_client.response_count(&result, "modifiedCount")?,
+            upserted_id: result.get("upsertedId").map(|v| self.rpc_client.decode(v)),
+            acknowledged: write_acknowledged(&result),
+            raw_response: write_response(&result),
         })
     }
 
+    /// Apply `update` to the documents matching `filter`, `batch_size` at a time.
+    ///
+    /// Each batch is an `update_many` restricted to the next range of `_id`s,
+    /// followed by a `pause`, so a large backfill does not hold the database
+    /// busy in one long write. Documents inserted behind the current position
+    /// while the job runs are not updated.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let result = users
+    ///     .update_in_batches(
+    ///         doc! { "plan": { "$exists": false } },
+    ///         doc! { "$set": { "plan": "free" } },
+    ///         500,
+    ///         Duration::from_millis(100),
+    ///     )
+    ///     .await?;
+    /// println!("updated {} users in {} batches", result.modified_count, result.batches);
+    /// ```
+    pub async fn update_in_batches(
+        &self,
+        filter: Document,
+        update: impl Into<UpdateModifications>,
+        batch_size: u32,
+        pause: Duration,
+    ) -> Result<BatchUpdateResult> {
+        self.update_in_batches_with_progress(filter, update, batch_size, pause, |_| {})
+            .await
+    }
+
+    /// [`Collection::update_in_batches`], calling `progress` after each batch
+    /// with the number of documents matched so far.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// users
+    ///     .update_in_batches_with_progress(filter, update, 500, pause, |progress| {
+    ///         println!("{} users, {:.0}/s", progress.documents, progress.rate());
+    ///     })
+    ///     .await?;
+    /// ```
+    pub async fn update_in_batches_with_progress(
+        &self,
+        filter: Document,
+        update: impl Into<UpdateModifications>,
+        batch_size: u32,
+        pause: Duration,
+        mut progress: impl FnMut(&Progress) + Send,
+    ) -> Result<BatchUpdateResult> {
+        if batch_size == 0 {
+            return Err(MongoError::invalid_argument("batch size must be positive"));
+        }
+        let update = update.into();
+        let ids = self.clone_with_type::<JsonValue>();
+        let options = FindOptions::builder()
+            .sort(doc! { "_id": 1 })
+            .projection(doc! { "_id": 1 })
+            .limit(i64::from(batch_size))
+            .batch_size(batch_size)
+            .build();
+
+        let mut result = BatchUpdateResult::default();
+        let mut tracker = ProgressTracker::start();
+        let mut after = filter.clone();
+        loop {
+            let batch = ids.find_with_options(after, options.clone()).await?.collect().await?;
+            let id = |doc: Option<&JsonValue>| {
+                doc.and_then(|d| d.get("_id")).map(|id| self.rpc_client.decode(id))
+            };
+            let (Some(first), Some(last)) = (id(batch.first()), id(batch.last())) else {
+                break;
+            };
+
+            let range = doc! { "_id": { "$gte": first, "$lte": last.clone() } };
+            let range = and_filter(&filter, range);
+            let updated = self.update_many(range, update.clone()).await?;
+            result.batches += 1;
+            result.matched_count += updated.matched_count;
+            result.modified_count += updated.modified_count;
+            progress(&tracker.record(updated.matched_count, &batch));
+
+            if batch.len() < batch_size as usize {
+                break;
+            }
+            after = and_filter(&filter, doc! { "_id": { "$gt": last } });
+            tokio::time::sleep(pause).await;
+        }
+        Ok(result)
+    }
+
+    /// Move the documents matching `filter` to `target`, in batches that are
+    /// copied, verified and only then deleted. Safe to run again after a
+    /// failure; see [`archive`](crate::archive).
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let target = ArchiveTarget::file("orders-2024.jsonl");
+    /// let result = orders.archive(doc! { "year": 2024 }, target).await?;
+    /// println!("archived {} orders", result.archived);
+    /// ```
+    pub async fn archive(&self, filter: Document, target: ArchiveTarget) -> Result<ArchiveResult> {
+        self.archive_with_options(filter, target, ArchiveOptions::default()).await
+    }
+
+    /// Archive documents with options, e.g. a dry run counting what would move.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = ArchiveOptions::builder().dry_run(true).build();
+    /// let result = orders.archive_with_options(filter, target, options).await?;
+    /// println!("would archive {} orders", result.archived);
+    /// ```
+    pub async fn archive_with_options(
+        &self,
+        filter: Document,
+        target: ArchiveTarget,
+        options: ArchiveOptions,
+    ) -> Result<ArchiveResult> {
+        self.archive_with_progress(filter, target, options, |_| {}).await
+    }
+
+    /// [`Collection::archive_with_options`], calling `progress` after each
+    /// batch with the number of documents archived so far.
+    pub async fn archive_with_progress(
+        &self,
+        filter: Document,
+        target: ArchiveTarget,
+        options: ArchiveOptions,
+        progress: impl FnMut(&Progress) + Send,
+    ) -> Result<ArchiveResult> {
+        archive::archive(self.clone_with_type(), filter, target, options, progress).await
+    }
+
     /// Delete a single document.
     ///
     /// # Example
@@ -538,25 +2729,35 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
     /// let result = collection.delete_one(doc! { "_id": id }).await?;
     /// ```
     pub async fn delete_one(&self, filter: Document) -> Result<DeleteResult> {
-        let filter_json = bson_doc_to_json(&filter)?;
+        self.delete_one_with_options(filter, None).await
+    }
+
+    /// Delete a single document with options.
+    pub async fn delete_one_with_options(
+        &self,
+        filter: Document,
+        options: impl Into<Option<DeleteOptions>>,
+    ) -> Result<DeleteResult> {
+        let options = options.into().unwrap_or_default();
+        let filter_json = self.rpc_client.encode(&filter)?;
 
         let result = self
             .rpc_client
             .call_raw(
-                "mongo.deleteOne",
+                Method::DeleteOne,
                 vec![
                     serde_json::json!(self.db_name),
                     serde_json::json!(self.name),
                     filter_json,
+                    options.to_json(self.rpc_client.codec.as_deref())?,
                 ],
             )
             .await?;
 
         Ok(DeleteResult {
-            deleted_count: result
-                .get("deletedCount")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0),
+            deleted_count: self.rpc_client.response_count(&result, "deletedCount")?,
+            acknowledged: write_acknowledged(&result),
+            raw_response: write_response(&result),
         })
     }
 
@@ -568,25 +2769,35 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
     /// let result = collection.delete_many(doc! { "status": "deleted" }).await?;
     /// ```
     pub async fn delete_many(&self, filter: Document) -> Result<DeleteResult> {
-        let filter_json = bson_doc_to_json(&filter)?;
+        self.delete_many_with_options(filter, None).await
+    }
+
+    /// Delete multiple documents with options.
+    pub async fn delete_many_with_options(
+        &self,
+        filter: Document,
+        options: impl Into<Option<DeleteOptions>>,
+    ) -> Result<DeleteResult> {
+        let options = options.into().unwrap_or_default();
+        let filter_json = self.rpc_client.encode(&filter)?;
 
         let result = self
             .rpc_client
             .call_raw(
-                "mongo.deleteMany",
+                Method::DeleteMany,
                 vec![
                     serde_json::json!(self.db_name),
                     serde_json::json!(self.name),
                     filter_json,
+                    options.to_json(self.rpc_client.codec.as_deref())?,
                 ],
             )
             .await?;
 
         Ok(DeleteResult {
-            deleted_count: result
-                .get("deletedCount")
-                .and_then(|v| v.as_u64())
-                .unwrap_or(0),
+            deleted_count: self.rpc_client.response_count(&result, "deletedCount")?,
+            acknowledged: write_acknowledged(&result),
+            raw_response: write_response(&result),
         })
     }
 
@@ -598,20 +2809,37 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
     /// let count = collection.count_documents(doc! { "status": "active" }).await?;
     /// ```
     pub async fn count_documents(&self, filter: impl Into<Option<Document>>) -> Result<u64> {
+        self.count_documents_with_options(filter, None).await
+    }
+
+    /// Count documents matching a filter with options.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// // Only need to know whether there are more than 100.
+    /// let options = CountOptions::builder().limit(101).max_time_ms(500).build();
+    /// let count = collection.count_documents_with_options(filter, options).await?;
+    /// ```
+    pub async fn count_documents_with_options(
+        &self,
+        filter: impl Into<Option<Document>>,
+        options: impl Into<Option<CountOptions>>,
+    ) -> Result<u64> {
+        let options = options.into().unwrap_or_default();
         let filter_doc = filter.into().unwrap_or_default();
-        let filter_json = bson_doc_to_json(&filter_doc)?;
+        let filter_json = self.rpc_client.encode(&filter_doc)?;
 
-        let result = self
-            .rpc_client
-            .call_raw(
-                "mongo.countDocuments",
-                vec![
-                    serde_json::json!(self.db_name),
-                    serde_json::json!(self.name),
-                    filter_json,
-                ],
-            )
-            .await?;
+        let mut args = vec![
+            serde_json::json!(self.db_name),
+            serde_json::json!(self.name),
+            filter_json,
+        ];
+        let opts_json = options.to_json(self.rpc_client.codec.as_deref())?;
+        if opts_json.as_object().is_some_and(|opts| !opts.is_empty()) {
+            args.push(opts_json);
+        }
+        let result = self.rpc_client.call_raw(Method::CountDocuments, args).await?;
 
         result
             .as_u64()
@@ -620,16 +2848,28 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
 
     /// Estimated document count (fast).
     pub async fn estimated_document_count(&self) -> Result<u64> {
-        let result = self
-            .rpc_client
-            .call_raw(
-                "mongo.estimatedDocumentCount",
-                vec![
-                    serde_json::json!(self.db_name),
-                    serde_json::json!(self.name),
-                ],
-            )
-            .await?;
+        self.estimated_document_count_with_options(None).await
+    }
+
+    /// Estimated document count with options.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = EstimatedDocumentCountOptions::builder().max_time_ms(200).build();
+    /// let count = collection.estimated_document_count_with_options(options).await?;
+    /// ```
+    pub async fn estimated_document_count_with_options(
+        &self,
+        options: impl Into<Option<EstimatedDocumentCountOptions>>,
+    ) -> Result<u64> {
+        let options = options.into().unwrap_or_default();
+        let mut args = vec![serde_json::json!(self.db_name), serde_json::json!(self.name)];
+        let opts_json = options.to_json();
+        if opts_json.as_object().is_some_and(|opts| !opts.is_empty()) {
+            args.push(opts_json);
+        }
+        let result = self.rpc_client.call_raw(Method::EstimatedDocumentCount, args).await?;
 
         result
             .as_u64()
@@ -648,47 +2888,258 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
     /// let cursor = collection.aggregate(pipeline).await?;
     /// ```
     pub async fn aggregate(&self, pipeline: impl IntoIterator<Item = Document>) -> Result<Cursor<Document>> {
-        let pipeline_json: Vec<JsonValue> = pipeline
-            .into_iter()
-            .map(|d| bson_doc_to_json(&d))
-            .collect::<Result<_>>()?;
+        self.run_aggregate(pipeline).await
+    }
 
-        let result = self
-            .rpc_client
-            .call_raw(
-                "mongo.aggregate",
-                vec![
-                    serde_json::json!(self.db_name),
-                    serde_json::json!(self.name),
-                    serde_json::json!(pipeline_json),
-                ],
-            )
+    /// Run an aggregation pipeline with options.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = AggregateOptions::builder()
+    ///     .collation(Collation::new("en").numeric_ordering(true))
+    ///     .build();
+    /// let cursor = collection
+    ///     .aggregate_with_options([doc! { "$sort": { "sku": 1 } }], options)
+    ///     .await?;
+    /// ```
+    pub async fn aggregate_with_options(
+        &self,
+        pipeline: impl IntoIterator<Item = Document>,
+        options: impl Into<Option<AggregateOptions>>,
+    ) -> Result<Cursor<Document>> {
+        self.run_aggregate_with_options(pipeline, &options.into().unwrap_or_default())
+            .await
+    }
+
+    /// Run an aggregation pipeline, returning the results as raw BSON.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let paid = doc! { "$match": { "status": "paid" } };
+    /// let mut cursor = orders.aggregate_raw([paid], None).await?;
+    /// while let Some(batch) = cursor.next_batch().await? {
+    ///     forward(batch).await?;
+    /// }
+    /// ```
+    pub async fn aggregate_raw(
+        &self,
+        pipeline: impl IntoIterator<Item = Document>,
+        options: impl Into<Option<AggregateOptions>>,
+    ) -> Result<RawCursor> {
+        self.run_aggregate_with_options(pipeline, &options.into().unwrap_or_default())
+            .await
+            .map(RawCursor::new)
+    }
+
+    /// Run a typed aggregation pipeline.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let pipeline = users
+    ///     .pipeline()
+    ///     .filter(doc! { "active": true })
+    ///     .lookup_typed(&orders, "_id", "userId", "orders");
+    ///
+    /// let mut cursor = users.aggregate_pipeline(pipeline).await?;
+    /// while let Some(joined) = cursor.try_next().await? {
+    ///     println!("{} has {} orders", joined.doc.name, joined.joined.len());
+    /// }
+    /// ```
+    pub async fn aggregate_pipeline<R>(&self, pipeline: PipelineBuilder<R>) -> Result<Cursor<R>> {
+        self.run_aggregate(pipeline.build()).await
+    }
+
+    /// Run a pipeline ending in `$out` or `$merge`, returning a summary of the write.
+    ///
+    /// Refuses to run an `$out` into this collection, which would replace the
+    /// source documents with the pipeline's results.
+    pub async fn aggregate_output(&self, pipeline: OutputPipeline) -> Result<OutputSummary> {
+        let target_db = pipeline
+            .target_db
+            .clone()
+            .unwrap_or_else(|| self.db_name.clone());
+        let target_coll = pipeline.target_coll.clone();
+        if pipeline.kind == OutputStage::Out && target_db == self.db_name && target_coll == self.name {
+            return Err(MongoError::invalid_argument(format!(
+                "refusing to $out into the source collection {}",
+                self.namespace()
+            )));
+        }
+
+        let args = self.aggregate_args(pipeline.build(), &AggregateOptions::default())?;
+        let reply = self.rpc_client.call_raw(Method::Aggregate, args).await?;
+        let documents_written = ["insertedCount", "modifiedCount", "upsertedCount"]
+            .iter()
+            .filter_map(|field| reply.get(field)?.as_u64())
+            .reduce(|total, count| total + count);
+        Ok(OutputSummary { documents_written })
+    }
+
+    /// Group documents by `key_expr` and compute `accumulators` for each group.
+    ///
+    /// Builds a `$group` stage and returns its results keyed by group: each
+    /// group's `_id` deserializes into `K` and its accumulated fields into `V`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// struct Totals {
+    ///     count: i32,
+    ///     revenue: f64,
+    /// }
+    ///
+    /// let by_region: HashMap<String, Totals> = orders
+    ///     .group_by(
+    ///         "$region",
+    ///         doc! { "count": { "$sum": 1 }, "revenue": { "$sum": "$amount" } },
+    ///     )
+    ///     .await?;
+    /// ```
+    pub async fn group_by<K, V>(
+        &self,
+        key_expr: impl Into<bson::Bson>,
+        accumulators: Document,
+    ) -> Result<HashMap<K, V>>
+    where
+        K: DeserializeOwned + Eq + Hash,
+        V: DeserializeOwned,
+    {
+        let mut group = doc! { "_id": key_expr.into() };
+        group.extend(accumulators);
+        let groups = self
+            .run_aggregate::<Document>(vec![doc! { "$group": group }])
+            .await?
+            .collect()
+            .await?;
+        group_results(groups)
+    }
+
+    /// Count documents per distinct value of `field`.
+    ///
+    /// Documents without the field are counted under [`Bson::Null`](bson::Bson::Null).
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let by_status = orders.count_by("status").await?;
+    /// println!("{} paid", by_status.get(&Bson::from("paid")).unwrap_or(&0));
+    /// ```
+    pub async fn count_by(&self, field: &str) -> Result<HashMap<bson::Bson, u64>> {
+        #[derive(Deserialize)]
+        struct Count {
+            count: u64,
+        }
+
+        let counts: HashMap<bson::Bson, Count> = self
+            .group_by(format!("${}", field), doc! { "count": { "$sum": 1 } })
             .await?;
+        Ok(counts.into_iter().map(|(value, c)| (value, c.count)).collect())
+    }
+
+    /// Explain how the server would run an aggregation pipeline, without running it.
+    ///
+    /// The pipeline is checked with [`validate_pipeline`] first, so obvious
+    /// mistakes fail without a round trip.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let plan = orders
+    ///     .aggregate_explain([doc! { "$match": { "status": "paid" } }])
+    ///     .await?;
+    /// println!("{:?}", plan.get("queryPlanner"));
+    /// ```
+    pub async fn aggregate_explain(
+        &self,
+        pipeline: impl IntoIterator<Item = Document>,
+    ) -> Result<Document> {
+        let pipeline: Vec<Document> = pipeline.into_iter().collect();
+        validate_pipeline(&pipeline)?;
+        self.run_db_command(doc! {
+            "explain": {
+                "aggregate": self.name.as_str(),
+                "pipeline": pipeline,
+                "cursor": {},
+            },
+            "verbosity": "queryPlanner",
+        })
+        .await
+    }
+
+    /// Start a typed aggregation pipeline over this collection's documents.
+    pub fn pipeline(&self) -> PipelineBuilder<T> {
+        PipelineBuilder::for_database(&self.db_name)
+    }
+
+    /// Run an aggregation pipeline, returning a cursor of `R`.
+    async fn run_aggregate<R>(&self, pipeline: impl IntoIterator<Item = Document>) -> Result<Cursor<R>> {
+        self.run_aggregate_with_options(pipeline, &AggregateOptions::default())
+            .await
+    }
+
+    /// Run an aggregation pipeline with options, returning a cursor of `R`.
+    async fn run_aggregate_with_options<R>(
+        &self,
+        pipeline: impl IntoIterator<Item = Document>,
+        options: &AggregateOptions,
+    ) -> Result<Cursor<R>> {
+        let args = self.aggregate_args(pipeline, options)?;
+        let result = self.rpc_client.call_raw(Method::Aggregate, args).await?;
 
-        let documents = result
+        let mut documents = result
             .get("documents")
             .and_then(|v| v.as_array())
-            .map(|arr| arr.clone())
+            .cloned()
             .unwrap_or_default();
+        for document in &mut documents {
+            self.decrypt(document).await?;
+        }
 
         let cursor_id = result
             .get("cursorId")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string());
 
-        Ok(Cursor::new(self.namespace(), documents, cursor_id)
-            .with_rpc_client(self.rpc_client.clone()))
+        Ok(self.cursor(documents, cursor_id))
+    }
+
+    /// Build the arguments of an aggregate call.
+    fn aggregate_args(
+        &self,
+        pipeline: impl IntoIterator<Item = Document>,
+        options: &AggregateOptions,
+    ) -> Result<Vec<JsonValue>> {
+        let pipeline_json: Vec<JsonValue> = pipeline
+            .into_iter()
+            .map(|d| self.rpc_client.encode(&d))
+            .collect::<Result<_>>()?;
+
+        let mut args = vec![
+            serde_json::json!(self.db_name),
+            serde_json::json!(self.name),
+            serde_json::json!(pipeline_json),
+        ];
+        // Without options the call keeps the shape older servers expect.
+        let opts_json = options.to_json(self.rpc_client.codec.as_deref())?;
+        if opts_json.as_object().is_some_and(|opts| !opts.is_empty()) {
+            args.push(opts_json);
+        }
+        Ok(args)
     }
 
     /// Get distinct values for a field.
     pub async fn distinct(&self, field_name: &str, filter: impl Into<Option<Document>>) -> Result<Vec<bson::Bson>> {
         let filter_doc = filter.into().unwrap_or_default();
-        let filter_json = bson_doc_to_json(&filter_doc)?;
+        let filter_json = self.rpc_client.encode(&filter_doc)?;
 
         let result = self
             .rpc_client
             .call_raw(
-                "mongo.distinct",
+                Method::Distinct,
                 vec![
                     serde_json::json!(self.db_name),
                     serde_json::json!(self.name),
@@ -699,104 +3150,128 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
             .await?;
 
         if let Some(arr) = result.as_array() {
-            Ok(arr.iter().map(json_to_bson).collect())
+            Ok(arr.iter().map(|v| self.rpc_client.decode(v)).collect())
         } else {
             Ok(vec![])
         }
     }
 
-    /// Find one document and update it.
-    pub async fn find_one_and_update(
+    /// Update the document with the given `_id`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// users.update_by_id("user-42", doc! { "$set": { "active": false } }).await?;
+    /// ```
+    pub async fn update_by_id(
         &self,
-        filter: Document,
-        update: Document,
-    ) -> Result<Option<T>> {
-        let filter_json = bson_doc_to_json(&filter)?;
-        let update_json = bson_doc_to_json(&update)?;
+        id: impl Into<bson::Bson>,
+        update: impl Into<UpdateModifications>,
+    ) -> Result<UpdateResult> {
+        self.update_one(id_filter(id), update).await
+    }
+
+    /// Delete the document with the given `_id`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// users.delete_by_id(42_i64).await?;
+    /// ```
+    pub async fn delete_by_id(&self, id: impl Into<bson::Bson>) -> Result<DeleteResult> {
+        self.delete_one(id_filter(id)).await
+    }
 
+    /// Validate the collection's data and indexes.
+    ///
+    /// A `full` validation is more thorough but slower, and blocks writes
+    /// while it runs.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let result = collection.validate(false).await?;
+    /// if !result.valid {
+    ///     eprintln!("{} is invalid: {:?}", result.ns, result.errors);
+    /// }
+    /// ```
+    pub async fn validate(&self, full: bool) -> Result<ValidateResult> {
         let result = self
-            .rpc_client
-            .call_raw(
-                "mongo.findOneAndUpdate",
-                vec![
-                    serde_json::json!(self.db_name),
-                    serde_json::json!(self.name),
-                    filter_json,
-                    update_json,
-                ],
-            )
+            .run_command(doc! { "validate": self.name.as_str(), "full": full })
             .await?;
+        Ok(bson::from_document(result)?)
+    }
 
-        if result.is_null() {
-            return Ok(None);
-        }
-
-        serde_json::from_value(result)
-            .map(Some)
-            .map_err(|e| MongoError::Deserialization(e.to_string()))
+    /// Compact the collection's data and indexes, releasing unused storage.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let result = collection.compact().await?;
+    /// println!("freed {} bytes", result.bytes_freed);
+    /// ```
+    pub async fn compact(&self) -> Result<CompactResult> {
+        let result = self.run_command(doc! { "compact": self.name.as_str() }).await?;
+        Ok(bson::from_document(result)?)
     }
 
-    /// Find one document and delete it.
-    pub async fn find_one_and_delete(&self, filter: Document) -> Result<Option<T>> {
-        let filter_json = bson_doc_to_json(&filter)?;
-
-        let result = self
-            .rpc_client
-            .call_raw(
-                "mongo.findOneAndDelete",
-                vec![
-                    serde_json::json!(self.db_name),
-                    serde_json::json!(self.name),
-                    filter_json,
-                ],
-            )
-            .await?;
+    /// Rebuild all indexes of the collection.
+    pub async fn reindex(&self) -> Result<()> {
+        self.run_command(doc! { "reIndex": self.name.as_str() }).await?;
+        Ok(())
+    }
 
-        if result.is_null() {
-            return Ok(None);
-        }
+    /// Modify the collection's options with `collMod`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = CollModOptions::builder()
+    ///     .validator(doc! { "email": { "$type": "string" } })
+    ///     .expire_after_seconds("createdAt_1", 86400)
+    ///     .build();
+    /// collection.coll_mod(options).await?;
+    /// ```
+    pub async fn coll_mod(&self, options: CollModOptions) -> Result<()> {
+        self.run_command(options.to_command(&self.name)).await?;
+        Ok(())
+    }
 
-        serde_json::from_value(result)
-            .map(Some)
-            .map_err(|e| MongoError::Deserialization(e.to_string()))
+    /// Run a command against this collection, returning the raw reply.
+    ///
+    /// The collection name is filled in as the value of the command's first
+    /// field (or of `collection`, for `getMore`), so commands the SDK does not
+    /// model can be written without repeating the name.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let stats = collection.run_command(doc! { "collStats": 1, "scale": 1024 }).await?;
+    /// ```
+    pub async fn run_command(&self, command: Document) -> Result<Document> {
+        self.run_db_command(with_collection_name(command, &self.name)?).await
     }
 
-    /// Find one document and replace it.
-    pub async fn find_one_and_replace(
-        &self,
-        filter: Document,
-        replacement: T,
-    ) -> Result<Option<T>> {
-        let filter_json = bson_doc_to_json(&filter)?;
-        let replacement_json = serde_json::to_value(&replacement)?;
+    /// Run a command against this collection's database as given.
+    async fn run_db_command(&self, command: Document) -> Result<Document> {
+        let command_json = self.rpc_client.encode(&command)?;
 
         let result = self
             .rpc_client
             .call_raw(
-                "mongo.findOneAndReplace",
-                vec![
-                    serde_json::json!(self.db_name),
-                    serde_json::json!(self.name),
-                    filter_json,
-                    replacement_json,
-                ],
+                Method::RunCommand,
+                vec![serde_json::json!(self.db_name), command_json],
             )
             .await?;
 
-        if result.is_null() {
-            return Ok(None);
-        }
-
-        serde_json::from_value(result)
-            .map(Some)
-            .map_err(|e| MongoError::Deserialization(e.to_string()))
+        self.rpc_client.decode_document(&result)
     }
 
     /// Drop the collection.
     pub async fn drop(&self) -> Result<()> {
         self.rpc_client
             .call_raw(
-                "mongo.dropCollection",
+                Method::DropCollection,
                 vec![
                     serde_json::json!(self.db_name),
                     serde_json::json!(self.name),
@@ -808,16 +3283,16 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
 
     /// Create an index.
     pub async fn create_index(&self, keys: Document, options: impl Into<Option<Document>>) -> Result<String> {
-        let keys_json = bson_doc_to_json(&keys)?;
+        let keys_json = self.rpc_client.encode(&keys)?;
         let options_json = match options.into() {
-            Some(doc) => bson_doc_to_json(&doc)?,
+            Some(doc) => self.rpc_client.encode(&doc)?,
             None => serde_json::json!({}),
         };
 
         let result = self
             .rpc_client
             .call_raw(
-                "mongo.createIndex",
+                Method::CreateIndex,
                 vec![
                     serde_json::json!(self.db_name),
                     serde_json::json!(self.name),
@@ -837,7 +3312,7 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
     pub async fn drop_index(&self, index_name: &str) -> Result<()> {
         self.rpc_client
             .call_raw(
-                "mongo.dropIndex",
+                Method::DropIndex,
                 vec![
                     serde_json::json!(self.db_name),
                     serde_json::json!(self.name),
@@ -848,12 +3323,45 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
         Ok(())
     }
 
+    /// Create a text index over fields with the given weights.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// articles.create_text_index([("title", 10), ("body", 1)], None).await?;
+    /// ```
+    pub async fn create_text_index<'a>(
+        &self,
+        weights: impl IntoIterator<Item = (&'a str, i32)>,
+        options: impl Into<Option<TextIndexOptions>>,
+    ) -> Result<String> {
+        let mut keys = Document::new();
+        let mut field_weights = Document::new();
+        for (field, weight) in weights {
+            keys.insert(field, "text");
+            field_weights.insert(field, weight);
+        }
+        let options = options.into().unwrap_or_default().to_document(field_weights);
+        self.create_index(keys, options).await
+    }
+
+    /// Create a `2dsphere` index on a field holding GeoJSON geometries.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// places.create_2dsphere_index("location").await?;
+    /// ```
+    pub async fn create_2dsphere_index(&self, field: &str) -> Result<String> {
+        self.create_index(doc! { field: "2dsphere" }, None).await
+    }
+
     /// List all indexes.
     pub async fn list_indexes(&self) -> Result<Vec<Document>> {
         let result = self
             .rpc_client
             .call_raw(
-                "mongo.listIndexes",
+                Method::ListIndexes,
                 vec![
                     serde_json::json!(self.db_name),
                     serde_json::json!(self.name),
@@ -863,146 +3371,310 @@ impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> Collection
 
         if let Some(arr) = result.as_array() {
             arr.iter()
-                .map(|v| json_to_bson_doc(v))
+                .map(|v| self.rpc_client.decode_document(v))
                 .collect()
         } else {
             Ok(vec![])
         }
     }
-}
-
-/// Convert a BSON document to JSON.
-fn bson_doc_to_json(doc: &Document) -> Result<JsonValue> {
-    // Convert BSON to JSON-compatible format
-    let bson_value = bson::Bson::Document(doc.clone());
-    bson_to_json(&bson_value)
-}
 
-/// Convert a BSON value to JSON.
-fn bson_to_json(bson: &bson::Bson) -> Result<JsonValue> {
-    match bson {
-        bson::Bson::Double(v) => Ok(serde_json::json!(*v)),
-        bson::Bson::String(v) => Ok(serde_json::json!(v)),
-        bson::Bson::Array(arr) => {
-            let json_arr: Vec<JsonValue> = arr
-                .iter()
-                .map(bson_to_json)
-                .collect::<Result<_>>()?;
-            Ok(serde_json::json!(json_arr))
-        }
-        bson::Bson::Document(doc) => {
-            let mut map = serde_json::Map::new();
-            for (k, v) in doc {
-                map.insert(k.clone(), bson_to_json(v)?);
+    /// Create the declared indexes that do not exist yet.
+    ///
+    /// Indexes are matched by name. When `drop_extras` is set, existing
+    /// indexes that are not declared are dropped; the `_id` index is always
+    /// kept. Fails without changing anything if a declared index name exists
+    /// with different keys.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let indexes = [
+    ///     IndexModel::new(doc! { "email": 1 }, IndexOptions::builder().unique(true).build()),
+    ///     IndexModel::new(doc! { "createdAt": -1 }, None),
+    /// ];
+    /// let result = users.ensure_indexes(&indexes, false).await?;
+    /// println!("Created: {:?}", result.created);
+    /// ```
+    pub async fn ensure_indexes(&self, models: &[IndexModel], drop_extras: bool) -> Result<EnsureIndexesResult> {
+        let existing: HashMap<String, Document> = self
+            .list_indexes()
+            .await?
+            .into_iter()
+            .filter_map(|index| {
+                let name = index.get_str("name").ok()?.to_string();
+                let keys = index.get_document("key").cloned().unwrap_or_default();
+                Some((name, keys))
+            })
+            .collect();
+
+        let mut missing = Vec::new();
+        for model in models {
+            let name = model.name();
+            match existing.get(&name) {
+                // Compare by derived name, since numeric key types differ across the wire.
+                Some(keys) if default_index_name(keys) != default_index_name(&model.keys) => {
+                    return Err(MongoError::invalid_argument(format!(
+                        "index '{}' already exists with keys {}",
+                        name, keys
+                    )));
+                }
+                Some(_) => {}
+                None if name == ID_INDEX_NAME => {}
+                None => missing.push((name, model)),
             }
-            Ok(JsonValue::Object(map))
         }
-        bson::Bson::Boolean(v) => Ok(serde_json::json!(*v)),
-        bson::Bson::Null => Ok(JsonValue::Null),
-        bson::Bson::Int32(v) => Ok(serde_json::json!(*v)),
-        bson::Bson::Int64(v) => Ok(serde_json::json!(*v)),
-        bson::Bson::ObjectId(oid) => Ok(serde_json::json!({ "$oid": oid.to_hex() })),
-        bson::Bson::DateTime(dt) => Ok(serde_json::json!({ "$date": dt.timestamp_millis() })),
-        bson::Bson::Binary(bin) => {
-            let base64 = base64_encode(&bin.bytes);
-            Ok(serde_json::json!({ "$binary": { "base64": base64, "subType": format!("{:02x}", bin.subtype as u8) } }))
-        }
-        bson::Bson::RegularExpression(regex) => {
-            Ok(serde_json::json!({ "$regex": regex.pattern.clone(), "$options": regex.options.clone() }))
+
+        let mut result = EnsureIndexesResult::default();
+        for (name, model) in missing {
+            let mut options = model.options.clone().unwrap_or_default();
+            options.name = Some(name);
+            result.created.push(self.create_index(model.keys.clone(), options).await?);
         }
-        bson::Bson::Timestamp(ts) => {
-            Ok(serde_json::json!({ "$timestamp": { "t": ts.time, "i": ts.increment } }))
+
+        if drop_extras {
+            let declared: Vec<String> = models.iter().map(|model| model.name()).collect();
+            let mut extras: Vec<&String> = existing
+                .keys()
+                .filter(|name| *name != ID_INDEX_NAME && !declared.contains(name))
+                .collect();
+            extras.sort();
+            for name in extras {
+                self.drop_index(name).await?;
+                result.dropped.push(name.clone());
+            }
         }
-        _ => Ok(serde_json::json!(bson.to_string())),
+
+        Ok(result)
     }
 }
 
-/// Simple base64 encoding.
-fn base64_encode(data: &[u8]) -> String {
-    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
-    let mut result = String::new();
-    let mut i = 0;
-    while i < data.len() {
-        let b0 = data[i] as usize;
-        let b1 = if i + 1 < data.len() { data[i + 1] as usize } else { 0 };
-        let b2 = if i + 2 < data.len() { data[i + 2] as usize } else { 0 };
+/// Build the filter guarding a read-modify-write of `stored`, and carry the
+/// `_id` and next version into `replacement`.
+fn modify_guard(
+    stored: &JsonValue,
+    replacement: &mut JsonValue,
+    version_field: Option<&str>,
+) -> Result<JsonValue> {
+    let id = stored
+        .get("_id")
+        .cloned()
+        .ok_or_else(|| MongoError::invalid_argument("modify requires documents with an _id"))?;
+    let fields = replacement
+        .as_object_mut()
+        .ok_or_else(|| MongoError::invalid_argument("modify requires a document"))?;
+    fields.insert("_id".to_string(), id.clone());
+
+    let Some(version_field) = version_field else {
+        return Ok(stored.clone());
+    };
+    let (version, next) = match stored.get(version_field) {
+        Some(version) => {
+            let current = version.as_i64().ok_or_else(|| {
+                MongoError::invalid_argument(format!(
+                    "version field '{}' is not an integer",
+                    version_field
+                ))
+            })?;
+            (version.clone(), current + 1)
+        }
+        None => (serde_json::json!({ "$exists": false }), 1),
+    };
+    fields.insert(version_field.to_string(), serde_json::json!(next));
+    Ok(serde_json::json!({ "_id": id, version_field: version }))
+}
 
-        result.push(ALPHABET[b0 >> 2] as char);
-        result.push(ALPHABET[((b0 & 0x03) << 4) | (b1 >> 4)] as char);
+/// The `{ "_id": id }` filter.
+fn id_filter(id: impl Into<bson::Bson>) -> Document {
+    doc! { "_id": id.into() }
+}
 
-        if i + 1 < data.len() {
-            result.push(ALPHABET[((b1 & 0x0f) << 2) | (b2 >> 6)] as char);
-        } else {
-            result.push('=');
-        }
+/// Split `$group` results into a map from group key to accumulated fields.
+fn group_results<K, V>(groups: Vec<Document>) -> Result<HashMap<K, V>>
+where
+    K: DeserializeOwned + Eq + Hash,
+    V: DeserializeOwned,
+{
+    groups
+        .into_iter()
+        .map(|mut group| {
+            let key = group.remove("_id").unwrap_or(bson::Bson::Null);
+            Ok((bson::from_bson(key)?, bson::from_document(group)?))
+        })
+        .collect()
+}
 
-        if i + 2 < data.len() {
-            result.push(ALPHABET[b2 & 0x3f] as char);
-        } else {
-            result.push('=');
-        }
+/// Build one filter per `$bucketAuto` bucket, restricting `filter` to the
+/// bucket's range of `key`. Bucket maxima are exclusive except for the last.
+fn range_filters(filter: &Document, key: &str, buckets: &[Document]) -> Result<Vec<Document>> {
+    buckets
+        .iter()
+        .enumerate()
+        .map(|(i, bucket)| {
+            let bounds = bucket.get_document("_id").map_err(|_| {
+                MongoError::Deserialization("$bucketAuto result without bounds".to_string())
+            })?;
+            let min = bounds.get("min").cloned().unwrap_or(bson::Bson::Null);
+            let max = bounds.get("max").cloned().unwrap_or(bson::Bson::Null);
+            let upper = if i + 1 == buckets.len() { "$lte" } else { "$lt" };
+            Ok(and_filter(filter, doc! { key: { "$gte": min, upper: max } }))
+        })
+        .collect()
+}
 
-        i += 3;
+/// Combine `filter` with another condition.
+pub(crate) fn and_filter(filter: &Document, condition: Document) -> Document {
+    if filter.is_empty() {
+        condition
+    } else {
+        doc! { "$and": [filter.clone(), condition] }
     }
-    result
 }
 
-/// Convert JSON to BSON.
-fn json_to_bson(json: &JsonValue) -> bson::Bson {
-    match json {
-        JsonValue::Null => bson::Bson::Null,
-        JsonValue::Bool(v) => bson::Bson::Boolean(*v),
-        JsonValue::Number(n) => {
-            if let Some(i) = n.as_i64() {
-                bson::Bson::Int64(i)
-            } else if let Some(f) = n.as_f64() {
-                bson::Bson::Double(f)
-            } else {
-                bson::Bson::Null
-            }
-        }
-        JsonValue::String(s) => bson::Bson::String(s.clone()),
-        JsonValue::Array(arr) => {
-            bson::Bson::Array(arr.iter().map(json_to_bson).collect())
-        }
-        JsonValue::Object(obj) => {
-            // Check for extended JSON types
-            if let Some(oid) = obj.get("$oid").and_then(|v| v.as_str()) {
-                if let Ok(oid) = ObjectId::parse_str(oid) {
-                    return bson::Bson::ObjectId(oid);
-                }
-            }
-            if let Some(date) = obj.get("$date").and_then(|v| v.as_i64()) {
-                return bson::Bson::DateTime(bson::DateTime::from_millis(date));
-            }
+/// Put the collection name in the field of `command` that names the collection.
+fn with_collection_name(mut command: Document, collection: &str) -> Result<Document> {
+    let field = match command.keys().next() {
+        Some(name) if name == "getMore" => "collection".to_string(),
+        Some(name) => name.clone(),
+        None => return Err(MongoError::invalid_argument("command must not be empty")),
+    };
+    command.insert(field, collection);
+    Ok(command)
+}
 
-            let mut doc = Document::new();
-            for (k, v) in obj {
-                doc.insert(k.clone(), json_to_bson(v));
-            }
-            bson::Bson::Document(doc)
-        }
-    }
+/// Whether the server acknowledged a write. Responses without the field are acknowledged.
+fn write_acknowledged(result: &JsonValue) -> bool {
+    result
+        .get("acknowledged")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(true)
 }
 
-/// Convert JSON to BSON document.
-fn json_to_bson_doc(json: &JsonValue) -> Result<Document> {
-    match json_to_bson(json) {
-        bson::Bson::Document(doc) => Ok(doc),
-        _ => Err(MongoError::Deserialization("Expected document".to_string())),
-    }
+/// The per-document failures listed in a write response's `writeErrors`.
+fn write_errors(result: &JsonValue) -> Vec<WriteError> {
+    let Some(errors) = result.get("writeErrors").and_then(|v| v.as_array()) else {
+        return Vec::new();
+    };
+    errors
+        .iter()
+        .map(|error| WriteError {
+            index: error.get("index").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+            code: error.get("code").and_then(|v| v.as_i64()).unwrap_or(0) as i32,
+            message: error
+                .get("errmsg")
+                .or_else(|| error.get("message"))
+                .and_then(|v| v.as_str())
+                .unwrap_or_default()
+                .to_string(),
+        })
+        .collect()
+}
+
+/// The server's response to a write, or an empty document if it was not a document.
+fn write_response(result: &JsonValue) -> Document {
+    json_to_bson_doc(result).unwrap_or_default()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::collation::CollationStrength;
+    use crate::convert::{bson_doc_to_json, bson_to_json, json_to_bson};
+    use bson::oid::ObjectId;
+
+    #[tokio::test]
+    async fn test_one_way_document_types() {
+        // Neither type is both `Serialize` and `Deserialize`.
+        #[derive(Serialize)]
+        struct Click {
+            path: &'static str,
+        }
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Summary {
+            _id: String,
+            clicks: u64,
+        }
+
+        let server = crate::mock::MockServer::new(|method, _| {
+            Ok(match method {
+                Method::InsertOne => serde_json::json!({ "acknowledged": true, "insertedId": 7 }),
+                Method::FindOne => serde_json::json!({ "_id": "/", "clicks": 12 }),
+                Method::DeleteMany => serde_json::json!({ "deletedCount": 1 }),
+                _ => serde_json::json!(2),
+            })
+        });
+        let db = crate::db::Database::new("app".to_string(), server.transport());
+
+        let clicks = db.collection::<Click>("clicks");
+        let inserted = clicks.insert_one(Click { path: "/" }).await.unwrap();
+        assert_eq!(inserted.inserted_id, bson::Bson::Int64(7));
+        assert_eq!(clicks.count_documents(None).await.unwrap(), 2);
+        let sent = &server.calls_of(Method::InsertOne)[0];
+        assert_eq!(sent[1], "clicks");
+        assert_eq!(sent[2], serde_json::json!({ "path": "/" }));
+
+        let summaries = db.collection::<Summary>("summaries");
+        let summary = summaries.find_one(doc! { "_id": "/" }).await.unwrap();
+        assert_eq!(summary, Some(Summary { _id: "/".into(), clicks: 12 }));
+        assert_eq!(summaries.delete_many(doc! {}).await.unwrap().deleted_count, 1);
+    }
 
     #[test]
     fn test_insert_one_result() {
         let result = InsertOneResult {
             inserted_id: bson::Bson::ObjectId(ObjectId::new()),
+            acknowledged: true,
+            raw_response: Document::new(),
+        };
+        assert!(result.inserted_id.as_object_id().is_some());
+    }
+
+    #[test]
+    fn test_inserted_id_as() {
+        let id = ObjectId::new();
+        let result = |inserted_id| InsertOneResult {
+            inserted_id,
+            acknowledged: true,
+            raw_response: Document::new(),
         };
-        assert!(!result.inserted_id.as_object_id().is_none());
+        assert_eq!(
+            result(bson::Bson::ObjectId(id)).inserted_id_as::<ObjectId>().unwrap(),
+            id
+        );
+        assert_eq!(
+            result(bson::Bson::String("u1".into())).inserted_id_as::<String>().unwrap(),
+            "u1"
+        );
+        let uuid = bson::Uuid::new();
+        assert_eq!(
+            result(bson::Bson::Binary(uuid.into())).inserted_id_as::<bson::Uuid>().unwrap(),
+            uuid
+        );
+        let err = result(bson::Bson::Int32(1)).inserted_id_as::<ObjectId>().unwrap_err();
+        assert!(matches!(err, MongoError::Deserialization(_)));
+    }
+
+    #[test]
+    fn test_find_one_and_update_options_to_json() {
+        let options = FindOneAndUpdateOptions::builder()
+            .return_document(ReturnDocument::After)
+            .upsert(true)
+            .sort(doc! { "priority": -1 })
+            .projection(doc! { "state": 1 })
+            .build();
+        assert_eq!(
+            options.to_json(None).unwrap(),
+            serde_json::json!({
+                "upsert": true,
+                "returnDocument": "after",
+                "sort": { "priority": -1 },
+                "projection": { "state": 1 },
+            })
+        );
+        assert_eq!(
+            FindOneAndUpdateOptions::default().to_json(None).unwrap(),
+            serde_json::json!({})
+        );
+        assert_eq!(ReturnDocument::default(), ReturnDocument::Before);
     }
 
     #[test]
@@ -1014,12 +3686,92 @@ mod tests {
         assert_eq!(result.inserted_ids.len(), 2);
     }
 
+    #[test]
+    fn test_bypass_document_validation() {
+        let bypass = serde_json::json!({ "bypassDocumentValidation": true });
+        let insert_one = InsertOneOptions::builder().bypass_document_validation(true).build();
+        assert_eq!(insert_one.to_json(), bypass);
+        assert_eq!(InsertOneOptions::default().to_json(), serde_json::json!({}));
+
+        let update = UpdateOptions::builder().bypass_document_validation(true).build();
+        assert_eq!(update.to_json(None).unwrap(), bypass);
+        let replace = ReplaceOptions::builder()
+            .upsert(true)
+            .bypass_document_validation(true)
+            .build();
+        assert_eq!(
+            replace.to_json(None).unwrap(),
+            serde_json::json!({ "upsert": true, "bypassDocumentValidation": true })
+        );
+        let find_one_and_update = FindOneAndUpdateOptions::builder()
+            .bypass_document_validation(false)
+            .build();
+        assert_eq!(
+            find_one_and_update.to_json(None).unwrap(),
+            serde_json::json!({ "bypassDocumentValidation": false })
+        );
+    }
+
+    #[test]
+    fn test_count_options() {
+        let options = CountOptions::builder()
+            .limit(101)
+            .skip(5)
+            .hint("status_1")
+            .max_time_ms(500)
+            .collation(Collation::new("en"))
+            .build();
+        assert_eq!(
+            options.to_json(None).unwrap(),
+            serde_json::json!({
+                "limit": 101,
+                "skip": 5,
+                "hint": "status_1",
+                "maxTimeMS": 500,
+                "collation": { "locale": "en" },
+            })
+        );
+        assert_eq!(CountOptions::default().to_json(None).unwrap(), serde_json::json!({}));
+
+        let estimated = EstimatedDocumentCountOptions::builder().max_time_ms(200).build();
+        assert_eq!(estimated.to_json(), serde_json::json!({ "maxTimeMS": 200 }));
+    }
+
+    #[test]
+    fn test_insert_many_options() {
+        let options = InsertManyOptions::builder()
+            .ordered(false)
+            .bypass_document_validation(true)
+            .build();
+        assert_eq!(
+            options.to_json(),
+            serde_json::json!({ "ordered": false, "bypassDocumentValidation": true })
+        );
+        assert_eq!(InsertManyOptions::default().to_json(), serde_json::json!({}));
+    }
+
+    #[test]
+    fn test_write_errors() {
+        let result = serde_json::json!({
+            "insertedIds": { "0": 1, "2": 3 },
+            "writeErrors": [{ "index": 1, "code": 11000, "errmsg": "E11000 duplicate key" }],
+        });
+        let errors = write_errors(&result);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].index, 1);
+        assert_eq!(errors[0].code, 11000);
+        assert_eq!(errors[0].message, "E11000 duplicate key");
+        assert!(write_errors(&serde_json::json!({ "insertedIds": {} })).is_empty());
+    }
+
     #[test]
     fn test_update_result() {
         let result = UpdateResult {
             matched_count: 5,
             modified_count: 3,
             upserted_id: None,
+            acknowledged: true,
+            raw_response: Document::new(),
         };
         assert_eq!(result.matched_count, 5);
         assert_eq!(result.modified_count, 3);
@@ -1028,10 +3780,52 @@ mod tests {
 
     #[test]
     fn test_delete_result() {
-        let result = DeleteResult { deleted_count: 10 };
+        let result = DeleteResult {
+            deleted_count: 10,
+            acknowledged: true,
+            raw_response: Document::new(),
+        };
         assert_eq!(result.deleted_count, 10);
     }
 
+    #[test]
+    fn test_with_collection_name() {
+        let command = with_collection_name(doc! { "collStats": 1, "scale": 1024 }, "users");
+        assert_eq!(command.unwrap(), doc! { "collStats": "users", "scale": 1024 });
+
+        let command = with_collection_name(doc! { "getMore": 42_i64 }, "users").unwrap();
+        assert_eq!(command, doc! { "getMore": 42_i64, "collection": "users" });
+
+        assert!(with_collection_name(Document::new(), "users").is_err());
+    }
+
+    #[test]
+    fn test_write_acknowledgment() {
+        let result = serde_json::json!({ "deletedCount": 2, "ok": 1 });
+        assert!(write_acknowledged(&result));
+        assert_eq!(write_response(&result).get_i64("deletedCount").unwrap(), 2);
+
+        let result = serde_json::json!({ "acknowledged": false });
+        assert!(!write_acknowledged(&result));
+        assert!(write_response(&serde_json::json!("ok")).is_empty());
+    }
+
+    #[test]
+    fn test_find_options_text_score_projection() {
+        let options = FindOptions::builder()
+            .projection(doc! { "title": 1 })
+            .text_score_projection("score")
+            .build();
+        assert_eq!(
+            options.projection.unwrap(),
+            doc! { "title": 1, "score": { "$meta": "textScore" } }
+        );
+        assert_eq!(options.sort.unwrap(), doc! { "score": { "$meta": "textScore" } });
+
+        let options = FindOptions::text_score_projection("score");
+        assert!(options.projection.is_some());
+    }
+
     #[test]
     fn test_find_options_builder() {
         let options = FindOptions::builder()
@@ -1040,6 +3834,7 @@ mod tests {
             .sort(doc! { "created": -1 })
             .projection(doc! { "name": 1, "email": 1 })
             .batch_size(100)
+            .comment("search-page")
             .build();
 
         assert_eq!(options.limit, Some(10));
@@ -1047,6 +3842,15 @@ mod tests {
         assert!(options.sort.is_some());
         assert!(options.projection.is_some());
         assert_eq!(options.batch_size, Some(100));
+        assert_eq!(options.comment.as_deref(), Some("search-page"));
+        assert_eq!(options.cursor_type, None);
+
+        let options = FindOptions::builder()
+            .cursor_type(CursorType::TailableAwait)
+            .max_await_time(Duration::from_secs(2))
+            .build();
+        assert_eq!(options.cursor_type, Some(CursorType::TailableAwait));
+        assert_eq!(options.max_await_time, Some(Duration::from_secs(2)));
     }
 
     #[test]
@@ -1054,10 +3858,378 @@ mod tests {
         let options = UpdateOptions::builder()
             .upsert(true)
             .array_filters(vec![doc! { "elem.status": "active" }])
+            .comment("nightly-cleanup")
             .build();
 
         assert_eq!(options.upsert, Some(true));
         assert!(options.array_filters.is_some());
+        assert_eq!(options.comment.as_deref(), Some("nightly-cleanup"));
+    }
+
+    #[test]
+    fn test_modify_guard_original_values() {
+        let stored = serde_json::json!({ "_id": 1, "balance": 100 });
+        let mut replacement = serde_json::json!({ "balance": 90 });
+        let guard = modify_guard(&stored, &mut replacement, None).unwrap();
+        assert_eq!(guard, stored);
+        assert_eq!(replacement, serde_json::json!({ "_id": 1, "balance": 90 }));
+    }
+
+    #[test]
+    fn test_modify_guard_version_field() {
+        let stored = serde_json::json!({ "_id": 1, "balance": 100, "v": 4 });
+        let mut replacement = serde_json::json!({ "_id": 1, "balance": 90, "v": 4 });
+        let guard = modify_guard(&stored, &mut replacement, Some("v")).unwrap();
+        assert_eq!(guard, serde_json::json!({ "_id": 1, "v": 4 }));
+        assert_eq!(replacement["v"], 5);
+
+        let stored = serde_json::json!({ "_id": 1 });
+        let mut replacement = serde_json::json!({ "_id": 1 });
+        let guard = modify_guard(&stored, &mut replacement, Some("v")).unwrap();
+        assert_eq!(guard, serde_json::json!({ "_id": 1, "v": { "$exists": false } }));
+        assert_eq!(replacement["v"], 1);
+
+        let stored = serde_json::json!({ "balance": 100 });
+        assert!(modify_guard(&stored, &mut serde_json::json!({}), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parallel_scan() {
+        use futures::StreamExt;
+
+        #[derive(Debug, Deserialize)]
+        struct Event {
+            _id: i64,
+        }
+        let batch = |ids: &[i64], cursor_id: Option<&str>| {
+            let documents: Vec<_> = ids.iter().map(|id| serde_json::json!({ "_id": id })).collect();
+            serde_json::json!({ "documents": documents, "cursorId": cursor_id })
+        };
+        let server = crate::mock::MockServer::new(move |method, args| match method {
+            Method::Aggregate => Ok(serde_json::json!({ "documents": [
+                { "_id": { "min": 0, "max": 10 } },
+                { "_id": { "min": 10, "max": 20 } },
+                { "_id": { "min": 20, "max": 30 } },
+            ] })),
+            // The first range has two batches, the second ends after its
+            // first, and the third fails fetching its second.
+            Method::Find => Ok(match args[2]["_id"]["$gte"].as_i64() {
+                Some(0) => batch(&[1, 2], Some("a")),
+                Some(10) => batch(&[10], None),
+                _ => batch(&[20], Some("c")),
+            }),
+            Method::GetMore if args[0] == "a" => Ok(batch(&[3], None)),
+            _ => Err(MongoError::connection("cursor lost")),
+        });
+        let events = Collection::<Event>::new("app".into(), "events".into(), server.transport());
+
+        let scan = events.parallel_scan(None, "_id", 3).await.unwrap();
+        let items: Vec<Result<Event>> = scan.collect().await;
+        let ids: Vec<i64> = items.iter().flatten().map(|event| event._id).collect();
+        let errors: Vec<_> = items.iter().filter_map(|item| item.as_ref().err()).collect();
+
+        let mut sorted = ids.clone();
+        sorted.sort_unstable();
+        assert_eq!(sorted, vec![1, 2, 3, 10, 20]);
+        // The other cursors are read while the first fetches its next batch.
+        let position = |id| ids.iter().position(|&i| i == id).unwrap();
+        assert!(position(10) < position(3) && position(20) < position(3));
+        // The failing cursor's error is in the stream, which still ends.
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].is_connection_error());
+        assert_eq!(server.calls_of(Method::GetMore).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_insert_one_ref() {
+        #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+        struct Report {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            _id: Option<ObjectId>,
+            title: String,
+            pages: Vec<u32>,
+        }
+
+        let id = ObjectId::new();
+        let server = crate::mock::MockServer::new(move |_, _| {
+            Ok(serde_json::json!({ "acknowledged": true, "insertedId": { "$oid": id.to_hex() } }))
+        });
+        let reports = Collection::<Report>::new("app".into(), "reports".into(), server.transport());
+        let report = Report { _id: None, title: "Q3".into(), pages: vec![1, 2] };
+        let original = report.clone();
+
+        let result = reports.insert_one_ref(&report).await.unwrap();
+        assert_eq!(result.inserted_id, bson::Bson::ObjectId(id));
+        // The generated `_id` is only returned; the document is untouched and still ours.
+        assert_eq!(report, original);
+        reports.insert_one_ref(&report).await.unwrap();
+
+        let calls = server.calls_of(Method::InsertOne);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0][2], serde_json::json!({ "title": "Q3", "pages": [1, 2] }));
+    }
+
+    #[tokio::test]
+    async fn test_upsert_one() {
+        use std::sync::{Arc, Mutex};
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Settings {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            _id: Option<i64>,
+            user: i64,
+            theme: String,
+        }
+        let settings = |user, theme: &str| Settings { _id: None, user, theme: theme.into() };
+
+        // Stores documents keyed by `user`, as the server would with an upsert.
+        let stored = Arc::new(Mutex::new(Vec::<JsonValue>::new()));
+        let store = stored.clone();
+        let server = crate::mock::MockServer::new(move |_, args| {
+            let mut docs = store.lock().unwrap();
+            let (filter, mut replacement) = (&args[2], args[3].clone());
+            match docs.iter_mut().find(|doc| doc["user"] == filter["user"]) {
+                Some(doc) => {
+                    replacement["_id"] = doc["_id"].clone();
+                    *doc = replacement;
+                    Ok(doc.clone())
+                }
+                None => {
+                    replacement["_id"] = serde_json::json!(docs.len() + 1);
+                    docs.push(replacement.clone());
+                    Ok(replacement)
+                }
+            }
+        });
+        let coll = Collection::<Settings>::new("app".into(), "settings".into(), server.transport());
+
+        // Nothing matches: the document is inserted and comes back with its `_id`.
+        let inserted = coll.upsert_one(doc! { "user": 7 }, settings(7, "dark")).await.unwrap();
+        assert_eq!(inserted, Settings { _id: Some(1), ..settings(7, "dark") });
+
+        // A match is replaced, keeping its `_id`.
+        let replaced = coll.upsert_one(doc! { "user": 7 }, settings(7, "light")).await.unwrap();
+        assert_eq!(replaced, Settings { _id: Some(1), ..settings(7, "light") });
+        assert_eq!(stored.lock().unwrap().len(), 1);
+
+        let calls = server.calls_of(Method::FindOneAndReplace);
+        assert_eq!(calls[0][4], serde_json::json!({ "upsert": true, "returnDocument": "after" }));
+
+        // A server returning no document is an error, not a default value.
+        let server = crate::mock::MockServer::new(|_, _| Ok(JsonValue::Null));
+        let coll = Collection::<Settings>::new("app".into(), "settings".into(), server.transport());
+        let error = coll.upsert_one(doc! { "user": 8 }, settings(8, "dark")).await.unwrap_err();
+        assert!(matches!(error, MongoError::Internal(_)));
+    }
+
+    #[tokio::test]
+    async fn test_modify_retries_outside_retry_budget() {
+        let server = crate::mock::MockServer::new(|method, _| match method {
+            Method::FindOne => Ok(serde_json::json!({ "_id": 1, "balance": 100 })),
+            _ => Ok(serde_json::json!({ "matchedCount": 0, "modifiedCount": 0 })),
+        });
+        // A budget allowing no retries at all.
+        let budget = crate::RetryBudget::builder().ratio(0.0).min_retries(0).build();
+        let transport = server.transport().with_retry_budget(Some(budget));
+        let accounts = Collection::<Document>::new("bank".into(), "accounts".into(), transport);
+
+        let options = ModifyOptions::builder().max_retries(2).build();
+        let debit = |account: &mut Document| {
+            account.insert("balance", 90);
+        };
+        let error = accounts
+            .modify_with_options(doc! { "_id": 1 }, debit, options)
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Some(WRITE_CONFLICT_CODE));
+        assert_eq!(server.calls_of(Method::ReplaceOne).len(), 3);
+        let stats = accounts.rpc_client.stats.snapshot();
+        assert_eq!((stats.retries, stats.retries_denied), (2, 0));
+    }
+
+    #[test]
+    fn test_id_filter() {
+        let oid = ObjectId::new();
+        assert_eq!(id_filter(oid), doc! { "_id": oid });
+        assert_eq!(id_filter("user-42"), doc! { "_id": "user-42" });
+        assert_eq!(id_filter(42_i64), doc! { "_id": 42_i64 });
+    }
+
+    #[test]
+    fn test_save_result_id() {
+        let id = bson::Bson::ObjectId(ObjectId::new());
+        assert_eq!(SaveResult::Inserted(id.clone()).id(), &id);
+        assert_eq!(SaveResult::Replaced(id.clone()).id(), &id);
+    }
+
+    #[test]
+    fn test_update_modifications_from() {
+        let update: UpdateModifications = doc! { "$set": { "a": 1 } }.into();
+        assert_eq!(update, UpdateModifications::Document(doc! { "$set": { "a": 1 } }));
+
+        let pipeline = vec![doc! { "$set": { "total": { "$add": ["$price", "$tax"] } } }];
+        let update: UpdateModifications = pipeline.clone().into();
+        assert_eq!(update, UpdateModifications::Pipeline(pipeline));
+    }
+
+    #[test]
+    fn test_let_vars_options() {
+        let options = UpdateOptions::builder()
+            .let_vars(doc! { "cutoff": 30 })
+            .build();
+        assert_eq!(
+            options.to_json(None).unwrap(),
+            serde_json::json!({ "let": { "cutoff": 30 } })
+        );
+
+        let options = DeleteOptions::builder()
+            .let_vars(doc! { "status": "expired" })
+            .comment("ttl-sweep")
+            .build();
+        assert_eq!(
+            options.to_json(None).unwrap(),
+            serde_json::json!({ "let": { "status": "expired" }, "comment": "ttl-sweep" })
+        );
+    }
+
+    #[test]
+    fn test_collation_options() {
+        let collation = Collation::new("en").strength(CollationStrength::Secondary);
+        let expected = serde_json::json!({ "collation": { "locale": "en", "strength": 2 } });
+        let update = UpdateOptions::builder().collation(collation.clone()).build();
+        assert_eq!(update.to_json(None).unwrap(), expected);
+        let delete = DeleteOptions::builder().collation(collation.clone()).build();
+        assert_eq!(delete.to_json(None).unwrap(), expected);
+
+        let aggregate = AggregateOptions::builder()
+            .allow_disk_use(true)
+            .batch_size(100)
+            .collation(collation)
+            .build();
+        assert_eq!(
+            aggregate.to_json(None).unwrap(),
+            serde_json::json!({
+                "allowDiskUse": true,
+                "batchSize": 100,
+                "collation": { "locale": "en", "strength": 2 },
+            })
+        );
+        assert_eq!(AggregateOptions::default().to_json(None).unwrap(), serde_json::json!({}));
+    }
+
+    #[test]
+    fn test_hint_options() {
+        let update = UpdateOptions::builder().hint("email_1").build();
+        assert_eq!(update.hint, Some(Hint::Name("email_1".to_string())));
+        assert_eq!(update.to_json(None).unwrap(), serde_json::json!({ "hint": "email_1" }));
+
+        let delete = DeleteOptions::builder().hint(doc! { "status": 1, "age": -1 }).build();
+        assert_eq!(
+            delete.to_json(None).unwrap(),
+            serde_json::json!({ "hint": { "status": 1, "age": -1 } })
+        );
+
+        let aggregate = AggregateOptions::builder().hint(String::from("_id_")).build();
+        assert_eq!(aggregate.to_json(None).unwrap(), serde_json::json!({ "hint": "_id_" }));
+
+        let find = FindOptions::builder().hint(doc! { "email": 1 }).build();
+        assert_eq!(find.hint, Some(Hint::Keys(doc! { "email": 1 })));
+    }
+
+    #[test]
+    fn test_validate_result_deserialization() {
+        let result = doc! {
+            "ns": "test.users",
+            "nrecords": 42_i64,
+            "keysPerIndex": { "_id_": 42_i64, "email_1": 40_i64 },
+            "valid": false,
+            "errors": ["index email_1 is missing keys"],
+            "ok": 1.0,
+        };
+        let result: ValidateResult = bson::from_document(result).unwrap();
+        assert!(!result.valid);
+        assert_eq!(result.nrecords, 42);
+        assert_eq!(result.keys_per_index["email_1"], 40);
+        assert_eq!(result.errors.len(), 1);
+        assert!(result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_group_results() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Totals {
+            count: i32,
+        }
+
+        let groups = vec![
+            doc! { "_id": "east", "count": 3 },
+            doc! { "_id": "west", "count": 5 },
+        ];
+        let totals: HashMap<String, Totals> = group_results(groups).unwrap();
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals["west"], Totals { count: 5 });
+
+        let bad = vec![doc! { "_id": 1, "count": 3 }];
+        assert!(group_results::<String, Totals>(bad).is_err());
+    }
+
+    #[test]
+    fn test_group_results_bson_keys() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Count {
+            count: u64,
+        }
+
+        let groups = vec![
+            doc! { "_id": "paid", "count": 3_i64 },
+            doc! { "_id": null, "count": 1 },
+        ];
+        let counts: HashMap<bson::Bson, Count> = group_results(groups).unwrap();
+        assert_eq!(counts[&bson::Bson::from("paid")], Count { count: 3 });
+        assert_eq!(counts[&bson::Bson::Null], Count { count: 1 });
+    }
+
+    #[test]
+    fn test_sort_order() {
+        assert_eq!(
+            doc! { "score": SortOrder::Descending, "name": SortOrder::Ascending },
+            doc! { "score": -1, "name": 1 }
+        );
+    }
+
+    #[test]
+    fn test_and_filter() {
+        let condition = doc! { "_id": { "$gt": 5 } };
+        assert_eq!(and_filter(&Document::new(), condition.clone()), condition);
+        assert_eq!(
+            and_filter(&doc! { "plan": "free" }, condition),
+            doc! { "$and": [{ "plan": "free" }, { "_id": { "$gt": 5 } }] }
+        );
+    }
+
+    #[test]
+    fn test_range_filters() {
+        let buckets = vec![
+            doc! { "_id": { "min": 1, "max": 50 }, "count": 49 },
+            doc! { "_id": { "min": 50, "max": 99 }, "count": 50 },
+        ];
+        let ranges = range_filters(&Document::new(), "_id", &buckets).unwrap();
+        assert_eq!(
+            ranges,
+            vec![
+                doc! { "_id": { "$gte": 1, "$lt": 50 } },
+                doc! { "_id": { "$gte": 50, "$lte": 99 } },
+            ]
+        );
+
+        let filter = doc! { "status": "open" };
+        let ranges = range_filters(&filter, "_id", &buckets[..1]).unwrap();
+        assert_eq!(
+            ranges,
+            vec![doc! { "$and": [{ "status": "open" }, { "_id": { "$gte": 1, "$lte": 50 } }] }]
+        );
+
+        assert!(range_filters(&filter, "_id", &[doc! { "count": 1 }]).is_err());
     }
 
     #[test]
@@ -1071,7 +4243,7 @@ mod tests {
         let json = bson_doc_to_json(&doc).unwrap();
         assert_eq!(json.get("name").unwrap().as_str().unwrap(), "John");
         assert_eq!(json.get("age").unwrap().as_i64().unwrap(), 30);
-        assert_eq!(json.get("active").unwrap().as_bool().unwrap(), true);
+        assert!(json.get("active").unwrap().as_bool().unwrap());
     }
 
     #[test]
@@ -1114,21 +4286,12 @@ mod tests {
         assert!(matches!(result, Err(MongoError::Deserialization(_))));
     }
 
-    #[test]
-    fn test_base64_encode() {
-        assert_eq!(base64_encode(b"hello"), "aGVsbG8=");
-        assert_eq!(base64_encode(b""), "");
-        assert_eq!(base64_encode(b"a"), "YQ==");
-        assert_eq!(base64_encode(b"ab"), "YWI=");
-        assert_eq!(base64_encode(b"abc"), "YWJj");
-    }
-
     #[test]
     fn test_bson_to_json_all_types() {
         // Double
-        let bson = bson::Bson::Double(3.14);
+        let bson = bson::Bson::Double(2.5);
         let json = bson_to_json(&bson).unwrap();
-        assert_eq!(json.as_f64().unwrap(), 3.14);
+        assert_eq!(json.as_f64().unwrap(), 2.5);
 
         // String
         let bson = bson::Bson::String("test".to_string());
@@ -1138,7 +4301,7 @@ mod tests {
         // Boolean
         let bson = bson::Bson::Boolean(true);
         let json = bson_to_json(&bson).unwrap();
-        assert_eq!(json.as_bool().unwrap(), true);
+        assert!(json.as_bool().unwrap());
 
         // Null
         let bson = bson::Bson::Null;
@@ -1192,7 +4355,7 @@ mod tests {
         assert!(matches!(bson, bson::Bson::Int64(42)));
 
         // Float
-        let json = serde_json::json!(3.14);
+        let json = serde_json::json!(2.5);
         let bson = json_to_bson(&json);
         assert!(matches!(bson, bson::Bson::Double(_)));
 
@@ -1223,4 +4386,38 @@ mod tests {
         assert!(options.upsert.is_none());
         assert!(options.array_filters.is_none());
     }
+
+    #[tokio::test]
+    async fn test_aggregate_output_summary() {
+        use crate::pipeline::{WhenMatched, WhenNotMatched};
+
+        let server = crate::mock::MockServer::new(|_, args| {
+            let stages = args[2].as_array().unwrap();
+            Ok(match stages.last().unwrap().get("$merge") {
+                Some(_) => serde_json::json!({ "insertedCount": 2, "modifiedCount": 1 }),
+                None => serde_json::json!({ "ok": 1 }),
+            })
+        });
+        let orders =
+            Collection::<Document>::new("shop".into(), "orders".into(), server.transport());
+
+        let merge = orders.pipeline().merge_into(
+            "reports",
+            "totals",
+            &[],
+            WhenMatched::Replace,
+            WhenNotMatched::Insert,
+        );
+        let summary = orders.aggregate_output(merge).await.unwrap();
+        assert_eq!(summary.documents_written, Some(3));
+
+        let out = orders.pipeline().out_to("archive");
+        let summary = orders.aggregate_output(out).await.unwrap();
+        assert_eq!(summary.documents_written, None);
+
+        // Each pipeline ran once, with nothing counted on the side.
+        let calls = server.calls();
+        assert_eq!(calls.len(), 2);
+        assert!(calls.iter().all(|(method, _)| *method == Method::Aggregate));
+    }
 }
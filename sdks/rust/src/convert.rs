@@ -0,0 +1,684 @@
+//! Conversions between BSON values and their JSON wire representation.
+//!
+//! Every document sent to or received from the server passes through these
+//! functions, so they are useful for pre- and post-processing payloads the
+//! same way the client does. Types without a plain JSON equivalent use
+//! extended JSON: `{ "$oid": ... }`, `{ "$date": <millis> }`,
+//! `{ "$binary": { "base64": ..., "subType": "xx" } }`,
+//! `{ "$regex": ..., "$options": ... }` and `{ "$timestamp": { "t", "i" } }`.
+//! A [`ValueCodec`] can override the representation of individual values.
+//!
+//! UUIDs are binary values of subtype `04`. A `bson::Uuid` in a [`Document`]
+//! is sent as such, and with the `uuid` feature a `uuid::Uuid` field of a
+//! typed document is too when marked with [`uuid_as_binary`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::convert::{bson_doc_to_json, json_to_bson_doc};
+//!
+//! let json = bson_doc_to_json(&doc! { "_id": ObjectId::new(), "n": 1 })?;
+//! assert!(json["_id"]["$oid"].is_string());
+//! let doc = json_to_bson_doc(&json)?;
+//! ```
+
+use crate::error::{MongoError, Result};
+use bson::oid::ObjectId;
+use bson::spec::BinarySubtype;
+use bson::{Binary, Bson, Document, RawArrayBuf, RawBson, RawDocumentBuf, Regex, Timestamp};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+
+const BASE64_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// Controls how individual BSON values map to their wire representation.
+///
+/// Register a codec with [`ClientOptions::codec`](crate::ClientOptions::codec)
+/// to change the encoding of specific types, e.g. to talk to a backend that
+/// expects something other than extended JSON. Both methods are consulted for
+/// every value, including nested ones; returning `None` falls back to the
+/// default conversion.
+///
+/// The codec applies to everything sent and received: filters, updates,
+/// pipelines and commands, and typed documents too, which are serialized to
+/// BSON with serde first and deserialized from BSON after decoding.
+///
+/// # Example
+///
+/// ```ignore
+/// use mongo_do::convert::ValueCodec;
+///
+/// /// Sends dates as RFC 3339 strings.
+/// #[derive(Debug)]
+/// struct Rfc3339Dates;
+///
+/// impl ValueCodec for Rfc3339Dates {
+///     fn encode(&self, value: &Bson) -> Result<Option<JsonValue>> {
+///         match value {
+///             Bson::DateTime(date) => Ok(date.try_to_rfc3339_string().ok().map(Into::into)),
+///             _ => Ok(None),
+///         }
+///     }
+/// }
+///
+/// let options = ClientOptions::builder().codec(Rfc3339Dates).build();
+/// ```
+pub trait ValueCodec: std::fmt::Debug + Send + Sync {
+    /// Encode a value sent to the server, or return `None` to use the default.
+    fn encode(&self, value: &Bson) -> Result<Option<JsonValue>> {
+        let _ = value;
+        Ok(None)
+    }
+
+    /// Decode a value received from the server, or return `None` to use the default.
+    fn decode(&self, value: &JsonValue) -> Option<Bson> {
+        let _ = value;
+        None
+    }
+}
+
+/// Convert a BSON document to JSON.
+pub fn bson_doc_to_json(doc: &Document) -> Result<JsonValue> {
+    encode_document(doc, None)
+}
+
+/// Convert a BSON document to JSON, letting `codec` encode values first.
+pub fn bson_doc_to_json_with(doc: &Document, codec: &dyn ValueCodec) -> Result<JsonValue> {
+    encode_document(doc, Some(codec))
+}
+
+/// Convert a BSON value to JSON.
+///
+/// Types without a JSON representation listed in the [module docs](self)
+/// are sent as their string form.
+pub fn bson_to_json(bson: &Bson) -> Result<JsonValue> {
+    encode(bson, None)
+}
+
+/// Convert a BSON value to JSON, letting `codec` encode values first.
+pub fn bson_to_json_with(bson: &Bson, codec: &dyn ValueCodec) -> Result<JsonValue> {
+    encode(bson, Some(codec))
+}
+
+/// Convert JSON to BSON, recognizing the extended JSON forms listed in the
+/// [module docs](self).
+///
+/// Integers become `Int64`, since JSON does not distinguish integer widths.
+pub fn json_to_bson(json: &JsonValue) -> Bson {
+    decode(json, None)
+}
+
+/// Convert JSON to BSON, letting `codec` decode values first.
+pub fn json_to_bson_with(json: &JsonValue, codec: &dyn ValueCodec) -> Bson {
+    decode(json, Some(codec))
+}
+
+/// Convert JSON to a BSON document, failing if it is not an object.
+pub fn json_to_bson_doc(json: &JsonValue) -> Result<Document> {
+    decode_document(json, None)
+}
+
+/// Convert JSON to a BSON document, letting `codec` decode values first.
+pub fn json_to_bson_doc_with(json: &JsonValue, codec: &dyn ValueCodec) -> Result<Document> {
+    decode_document(json, Some(codec))
+}
+
+/// Serialize a typed value to BSON and convert it to JSON, consulting
+/// `codec` for every value.
+pub(crate) fn encode_typed<T: Serialize + ?Sized>(
+    value: &T,
+    codec: Option<&dyn ValueCodec>,
+) -> Result<JsonValue> {
+    encode(&bson::to_bson(value)?, codec)
+}
+
+/// Convert JSON to BSON, consulting `codec` for every value, and
+/// deserialize it as `T`.
+pub(crate) fn decode_typed<T: DeserializeOwned>(
+    json: &JsonValue,
+    codec: Option<&dyn ValueCodec>,
+) -> Result<T> {
+    bson::from_bson(decode(json, codec)).map_err(|e| MongoError::Deserialization(e.to_string()))
+}
+
+/// Convert a document to JSON, consulting `codec` for every value.
+pub(crate) fn encode_document(
+    doc: &Document,
+    codec: Option<&dyn ValueCodec>,
+) -> Result<JsonValue> {
+    let mut map = serde_json::Map::new();
+    for (k, v) in doc {
+        map.insert(k.clone(), encode(v, codec)?);
+    }
+    Ok(JsonValue::Object(map))
+}
+
+/// Convert a value to JSON, consulting `codec` before the default encoding.
+pub(crate) fn encode(bson: &Bson, codec: Option<&dyn ValueCodec>) -> Result<JsonValue> {
+    if let Some(json) = codec.map(|codec| codec.encode(bson)).transpose()?.flatten() {
+        return Ok(json);
+    }
+    match bson {
+        Bson::Double(v) => Ok(serde_json::json!(*v)),
+        Bson::String(v) => Ok(serde_json::json!(v)),
+        Bson::Array(arr) => {
+            let json_arr = arr.iter().map(|v| encode(v, codec)).collect::<Result<_>>()?;
+            Ok(JsonValue::Array(json_arr))
+        }
+        Bson::Document(doc) => encode_document(doc, codec),
+        Bson::Boolean(v) => Ok(serde_json::json!(*v)),
+        Bson::Null => Ok(JsonValue::Null),
+        Bson::Int32(v) => Ok(serde_json::json!(*v)),
+        Bson::Int64(v) => Ok(serde_json::json!(*v)),
+        Bson::ObjectId(oid) => Ok(serde_json::json!({ "$oid": oid.to_hex() })),
+        Bson::DateTime(dt) => Ok(serde_json::json!({ "$date": dt.timestamp_millis() })),
+        Bson::Binary(bin) => Ok(binary_to_json(bin)),
+        Bson::RegularExpression(regex) => Ok(serde_json::json!({
+            "$regex": regex.pattern.clone(),
+            "$options": regex.options.clone(),
+        })),
+        Bson::Timestamp(ts) => {
+            Ok(serde_json::json!({ "$timestamp": { "t": ts.time, "i": ts.increment } }))
+        }
+        _ => Ok(serde_json::json!(bson.to_string())),
+    }
+}
+
+/// Convert JSON to BSON, consulting `codec` before the default decoding.
+pub(crate) fn decode(json: &JsonValue, codec: Option<&dyn ValueCodec>) -> Bson {
+    if let Some(bson) = codec.and_then(|codec| codec.decode(json)) {
+        return bson;
+    }
+    match json {
+        JsonValue::Null => Bson::Null,
+        JsonValue::Bool(v) => Bson::Boolean(*v),
+        JsonValue::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                Bson::Int64(i)
+            } else if let Some(f) = n.as_f64() {
+                Bson::Double(f)
+            } else {
+                Bson::Null
+            }
+        }
+        JsonValue::String(s) => Bson::String(s.clone()),
+        JsonValue::Array(arr) => Bson::Array(arr.iter().map(|v| decode(v, codec)).collect()),
+        JsonValue::Object(obj) => {
+            if let Some(bson) = decode_extended(json, obj) {
+                return bson;
+            }
+            let mut doc = Document::new();
+            for (k, v) in obj {
+                doc.insert(k.clone(), decode(v, codec));
+            }
+            Bson::Document(doc)
+        }
+    }
+}
+
+/// Decode an extended JSON object such as `{ "$oid": "..." }`, or return
+/// `None` for an ordinary object.
+fn decode_extended(json: &JsonValue, obj: &serde_json::Map<String, JsonValue>) -> Option<Bson> {
+    if let Some(oid) = obj.get("$oid").and_then(|v| v.as_str()) {
+        if let Ok(oid) = ObjectId::parse_str(oid) {
+            return Some(Bson::ObjectId(oid));
+        }
+    }
+    // Canonical extended JSON, as typed documents read into JSON hold
+    // dates, wraps the milliseconds in `$numberLong`.
+    if let Some(date) = obj.get("$date") {
+        let millis = date.as_i64().or_else(|| date.get("$numberLong")?.as_str()?.parse().ok());
+        if let Some(millis) = millis {
+            return Some(Bson::DateTime(bson::DateTime::from_millis(millis)));
+        }
+    }
+    if let Some(binary) = binary_from_json(json) {
+        return Some(Bson::Binary(binary));
+    }
+    if let Some(ts) = obj.get("$timestamp") {
+        let part = |key: &str| ts.get(key).and_then(|v| v.as_u64()).map(|v| v as u32);
+        if let (Some(time), Some(increment)) = (part("t"), part("i")) {
+            return Some(Bson::Timestamp(Timestamp { time, increment }));
+        }
+    }
+    if obj.len() == 2 {
+        let part = |key: &str| obj.get(key).and_then(|v| v.as_str());
+        if let (Some(pattern), Some(options)) = (part("$regex"), part("$options")) {
+            return Some(Bson::RegularExpression(Regex {
+                pattern: pattern.to_string(),
+                options: options.to_string(),
+            }));
+        }
+    }
+    None
+}
+
+/// Convert a JSON document straight to raw BSON, decoding values as
+/// [`decode_document`] does but without building a [`Document`] first.
+pub(crate) fn decode_raw_document(
+    json: &JsonValue,
+    codec: Option<&dyn ValueCodec>,
+) -> Result<RawDocumentBuf> {
+    match decode_raw(json, codec)? {
+        RawBson::Document(doc) => Ok(doc),
+        _ => Err(MongoError::Deserialization("Expected document".to_string())),
+    }
+}
+
+/// Convert JSON to a raw BSON value, consulting `codec` before the default decoding.
+fn decode_raw(json: &JsonValue, codec: Option<&dyn ValueCodec>) -> Result<RawBson> {
+    if let Some(bson) = codec.and_then(|codec| codec.decode(json)) {
+        return raw_bson(bson);
+    }
+    match json {
+        JsonValue::Array(arr) => {
+            let mut raw = RawArrayBuf::new();
+            for v in arr {
+                raw.push(decode_raw(v, codec)?);
+            }
+            Ok(RawBson::Array(raw))
+        }
+        JsonValue::Object(obj) => {
+            if let Some(bson) = decode_extended(json, obj) {
+                return raw_bson(bson);
+            }
+            let mut raw = RawDocumentBuf::new();
+            for (k, v) in obj {
+                // Raw documents cannot hold such keys, and appending one panics.
+                if k.contains('\0') {
+                    return Err(MongoError::Bson(format!("key {:?} contains a null byte", k)));
+                }
+                raw.append(k, decode_raw(v, codec)?);
+            }
+            Ok(RawBson::Document(raw))
+        }
+        scalar => raw_bson(decode(scalar, None)),
+    }
+}
+
+/// Convert a decoded value to raw BSON.
+fn raw_bson(bson: Bson) -> Result<RawBson> {
+    RawBson::try_from(bson).map_err(|e| MongoError::Bson(e.to_string()))
+}
+
+/// Convert JSON to a document, consulting `codec` for every value.
+pub(crate) fn decode_document(
+    json: &JsonValue,
+    codec: Option<&dyn ValueCodec>,
+) -> Result<Document> {
+    match decode(json, codec) {
+        Bson::Document(doc) => Ok(doc),
+        _ => Err(MongoError::Deserialization("Expected document".to_string())),
+    }
+}
+
+/// Encode bytes as padded standard base64.
+pub fn base64_encode(data: &[u8]) -> String {
+    let mut result = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let sextets = [
+            b[0] >> 2,
+            ((b[0] & 0x03) << 4) | (b[1] >> 4),
+            ((b[1] & 0x0f) << 2) | (b[2] >> 6),
+            b[2] & 0x3f,
+        ];
+        for (i, sextet) in sextets.iter().enumerate() {
+            if i <= chunk.len() {
+                result.push(BASE64_ALPHABET[*sextet as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// Decode standard base64, with or without padding.
+pub fn base64_decode(text: &str) -> Result<Vec<u8>> {
+    let text = text.trim_end_matches('=');
+    let mut result = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let value = BASE64_ALPHABET.iter().position(|&a| a == c).ok_or_else(|| {
+            MongoError::Deserialization(format!("invalid base64 character {:?}", c as char))
+        })?;
+        buffer = (buffer << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            result.push((buffer >> bits) as u8);
+        }
+    }
+    if bits >= 6 {
+        return Err(MongoError::Deserialization("truncated base64".to_string()));
+    }
+    Ok(result)
+}
+
+/// Convert a binary value to canonical extended JSON.
+pub fn binary_to_json(binary: &Binary) -> JsonValue {
+    serde_json::json!({
+        "$binary": {
+            "base64": base64_encode(&binary.bytes),
+            "subType": format!("{:02x}", u8::from(binary.subtype)),
+        }
+    })
+}
+
+/// Parse a binary value from extended JSON.
+///
+/// Accepts the canonical `{ "$binary": { "base64", "subType" } }` form and
+/// the legacy `{ "$binary": "...", "$type": "xx" }` form. Returns `None` if
+/// `value` is not a binary value.
+pub fn binary_from_json(value: &JsonValue) -> Option<Binary> {
+    let object = value.as_object()?;
+    let (base64, subtype) = match object.get("$binary")? {
+        JsonValue::Object(binary) => (binary.get("base64")?, binary.get("subType")?),
+        base64 @ JsonValue::String(_) => (base64, object.get("$type")?),
+        _ => return None,
+    };
+    let subtype = u8::from_str_radix(subtype.as_str()?, 16).ok()?;
+    Some(Binary {
+        subtype: BinarySubtype::from(subtype),
+        bytes: base64_decode(base64.as_str()?).ok()?,
+    })
+}
+
+/// Serde helpers storing a `uuid::Uuid` field as binary subtype `04` rather
+/// than as a string, so it matches UUIDs written by other drivers.
+///
+/// Reading accepts both forms.
+///
+/// # Example
+///
+/// ```ignore
+/// #[derive(Serialize, Deserialize)]
+/// struct Device {
+///     #[serde(with = "mongo_do::convert::uuid_as_binary")]
+///     serial: uuid::Uuid,
+/// }
+/// ```
+#[cfg(feature = "uuid")]
+pub mod uuid_as_binary {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    /// Serialize `uuid` as binary subtype `04`.
+    pub fn serialize<S: Serializer>(uuid: &uuid::Uuid, serializer: S) -> Result<S::Ok, S::Error> {
+        bson::Binary::from(bson::Uuid::from(*uuid)).serialize(serializer)
+    }
+
+    /// Deserialize a UUID from binary subtype `04` or a string.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<uuid::Uuid, D::Error> {
+        bson::Uuid::deserialize(deserializer).map(bson::Uuid::to_uuid_1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic pseudo-random bytes for round-trip tests.
+    fn xorshift_bytes(seed: u64, len: usize) -> Vec<u8> {
+        let mut state = seed | 1;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_bson_json_round_trip() {
+        let doc = bson::doc! {
+            "_id": ObjectId::new(),
+            "name": "Ada",
+            "age": 36_i64,
+            "score": 1.5,
+            "active": true,
+            "deleted": null,
+            "created": bson::DateTime::from_millis(1_700_000_000_000),
+            "avatar": Binary { subtype: BinarySubtype::Generic, bytes: vec![1, 2, 3] },
+            "pattern": Regex { pattern: "^a".to_string(), options: "i".to_string() },
+            "ts": Timestamp { time: 7, increment: 2 },
+            "tags": ["a", { "nested": [1_i64] }],
+        };
+        let json = bson_doc_to_json(&doc).unwrap();
+        assert_eq!(json["created"], serde_json::json!({ "$date": 1_700_000_000_000_i64 }));
+        assert_eq!(json_to_bson_doc(&json).unwrap(), doc);
+    }
+
+    #[test]
+    fn test_json_to_bson_int_widths() {
+        let json = serde_json::json!({ "n": 1, "big": 1_i64 << 40 });
+        assert_eq!(json_to_bson_doc(&json).unwrap(), bson::doc! { "n": 1_i64, "big": 1_i64 << 40 });
+        assert_eq!(bson_to_json(&Bson::Int32(5)).unwrap(), serde_json::json!(5));
+    }
+
+    #[test]
+    fn test_json_to_bson_operators_stay_documents() {
+        // A $regex query operator with other operators is not a regex value.
+        let json = serde_json::json!({ "$regex": "^a", "$options": "i", "$ne": "ab" });
+        assert!(json_to_bson(&json).as_document().is_some());
+        assert!(json_to_bson_doc(&serde_json::json!([1])).is_err());
+    }
+
+    /// Sends dates as RFC 3339 strings and reads them back.
+    #[derive(Debug)]
+    struct Rfc3339Dates;
+
+    impl ValueCodec for Rfc3339Dates {
+        fn encode(&self, value: &Bson) -> Result<Option<JsonValue>> {
+            match value {
+                Bson::DateTime(date) => Ok(date.try_to_rfc3339_string().ok().map(Into::into)),
+                _ => Ok(None),
+            }
+        }
+
+        fn decode(&self, value: &JsonValue) -> Option<Bson> {
+            let date = bson::DateTime::parse_rfc3339_str(value.as_str()?).ok()?;
+            Some(Bson::DateTime(date))
+        }
+    }
+
+    #[test]
+    fn test_value_codec() {
+        let date = bson::DateTime::from_millis(0);
+        let doc = bson::doc! { "at": date, "log": [{ "at": date }], "n": 1 };
+
+        let json = bson_doc_to_json_with(&doc, &Rfc3339Dates).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "at": "1970-01-01T00:00:00Z",
+                "log": [{ "at": "1970-01-01T00:00:00Z" }],
+                "n": 1,
+            })
+        );
+        let expected = bson::doc! { "at": date, "log": [{ "at": date }], "n": 1_i64 };
+        assert_eq!(json_to_bson_doc_with(&json, &Rfc3339Dates).unwrap(), expected);
+        // Without the codec, the strings stay strings.
+        assert!(json_to_bson_doc(&json).unwrap().get_str("at").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_value_codec_typed_documents() {
+        use crate::rpc::Method;
+
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Shipment {
+            sku: String,
+            shipped_at: bson::DateTime,
+        }
+
+        let server = crate::mock::MockServer::new(|method, _| {
+            Ok(match method {
+                Method::FindOne => {
+                    serde_json::json!({ "sku": "a-1", "shipped_at": "2024-05-01T12:00:00Z" })
+                }
+                _ => serde_json::json!({ "acknowledged": true, "insertedId": 1 }),
+            })
+        });
+        let transport = server.transport().with_codec(Some(std::sync::Arc::new(Rfc3339Dates)));
+        let shipments =
+            crate::Collection::<Shipment>::new("app".into(), "shipments".into(), transport);
+        let shipped_at = bson::DateTime::parse_rfc3339_str("2024-05-01T12:00:00Z").unwrap();
+
+        let shipment = Shipment { sku: "a-1".into(), shipped_at };
+        shipments.insert_one(shipment).await.unwrap();
+        let sent = &server.calls_of(Method::InsertOne)[0][2];
+        assert_eq!(sent["shipped_at"], "2024-05-01T12:00:00Z");
+
+        let found = shipments.find_one(None).await.unwrap();
+        assert_eq!(found, Some(Shipment { sku: "a-1".into(), shipped_at }));
+
+        // Cursors decode their documents the same way.
+        let doc = serde_json::json!({ "sku": "b-2", "shipped_at": "2024-05-01T12:00:00Z" });
+        let mut cursor =
+            crate::Cursor::<Shipment>::new("app.shipments".to_string(), vec![doc], None);
+        cursor.rpc_client = Some(shipments.rpc_client.clone());
+        assert_eq!(cursor.try_next().await.unwrap().unwrap().shipped_at, shipped_at);
+    }
+
+    #[test]
+    fn test_value_codec_defaults() {
+        #[derive(Debug)]
+        struct Passthrough;
+        impl ValueCodec for Passthrough {}
+
+        let doc = bson::doc! { "_id": ObjectId::new(), "at": bson::DateTime::from_millis(5) };
+        let json = bson_to_json_with(&Bson::Document(doc.clone()), &Passthrough).unwrap();
+        assert_eq!(json, bson_doc_to_json(&doc).unwrap());
+        assert_eq!(json_to_bson_with(&json, &Passthrough), Bson::Document(doc));
+    }
+
+    #[test]
+    fn test_decode_raw_document() {
+        let doc = bson::doc! {
+            "_id": ObjectId::new(),
+            "name": "Ada",
+            "score": 1.5,
+            "deleted": null,
+            "created": bson::DateTime::from_millis(1_700_000_000_000),
+            "avatar": Binary { subtype: BinarySubtype::Generic, bytes: vec![1, 2, 3] },
+            "ts": Timestamp { time: 7, increment: 2 },
+            "tags": ["a", { "nested": [1_i64] }],
+        };
+        let json = bson_doc_to_json(&doc).unwrap();
+        let raw = decode_raw_document(&json, None).unwrap();
+        assert_eq!(raw, RawDocumentBuf::from_document(&doc).unwrap());
+
+        let json = serde_json::json!({ "at": "1970-01-01T00:00:00Z", "log": [{ "n": 1 }] });
+        let raw = decode_raw_document(&json, Some(&Rfc3339Dates)).unwrap();
+        assert_eq!(raw.get_datetime("at").unwrap(), bson::DateTime::from_millis(0));
+        assert_eq!(
+            raw.to_document().unwrap(),
+            json_to_bson_doc_with(&json, &Rfc3339Dates).unwrap()
+        );
+
+        assert!(decode_raw_document(&serde_json::json!({ "a\u{0}b": 1 }), None).is_err());
+        assert!(decode_raw_document(&serde_json::json!([1]), None).is_err());
+    }
+
+    #[test]
+    fn test_base64_encode() {
+        assert_eq!(base64_encode(b"hello"), "aGVsbG8=");
+        assert_eq!(base64_encode(b""), "");
+        assert_eq!(base64_encode(b"a"), "YQ==");
+        assert_eq!(base64_encode(b"ab"), "YWI=");
+        assert_eq!(base64_encode(b"abc"), "YWJj");
+    }
+
+    #[test]
+    fn test_base64_decode() {
+        assert_eq!(base64_decode("aGVsbG8=").unwrap(), b"hello");
+        assert_eq!(base64_decode("aGVsbG8").unwrap(), b"hello");
+        assert_eq!(base64_decode("").unwrap(), b"");
+        assert!(base64_decode("a").is_err());
+        assert!(base64_decode("YW!=").is_err());
+    }
+
+    #[test]
+    fn test_base64_round_trip() {
+        for len in 0..=130 {
+            for seed in 0..8 {
+                let bytes = xorshift_bytes(seed * 1000 + len as u64, len);
+                assert_eq!(base64_decode(&base64_encode(&bytes)).unwrap(), bytes);
+            }
+        }
+    }
+
+    #[test]
+    fn test_binary_round_trip_all_subtypes() {
+        for subtype in 0..=u8::MAX {
+            let binary = Binary {
+                subtype: BinarySubtype::from(subtype),
+                bytes: xorshift_bytes(subtype as u64, subtype as usize % 40),
+            };
+            let json = binary_to_json(&binary);
+            assert_eq!(json["$binary"]["subType"], format!("{:02x}", subtype));
+            assert_eq!(binary_from_json(&json).unwrap(), binary);
+        }
+    }
+
+    #[test]
+    fn test_binary_from_json_forms() {
+        let legacy = serde_json::json!({ "$binary": "AQID", "$type": "80" });
+        let binary = binary_from_json(&legacy).unwrap();
+        assert_eq!(binary.subtype, BinarySubtype::UserDefined(0x80));
+        assert_eq!(binary.bytes, vec![1, 2, 3]);
+
+        assert!(binary_from_json(&serde_json::json!({ "base64": "AQID" })).is_none());
+        assert!(binary_from_json(&serde_json::json!("AQID")).is_none());
+        let bad = serde_json::json!({ "$binary": { "base64": "A!", "subType": "00" } });
+        assert!(binary_from_json(&bad).is_none());
+    }
+
+    #[test]
+    fn test_uuid_round_trip() {
+        let uuid = bson::Uuid::new();
+        let json = bson_doc_to_json(&bson::doc! { "_id": uuid }).unwrap();
+        assert_eq!(json["_id"]["$binary"]["subType"], "04");
+        let doc = json_to_bson_doc(&json).unwrap();
+        match doc.get("_id") {
+            Some(Bson::Binary(binary)) => {
+                assert_eq!(binary.subtype, BinarySubtype::Uuid);
+                assert_eq!(binary.to_uuid().unwrap(), uuid);
+            }
+            other => panic!("expected a binary _id, got {:?}", other),
+        }
+    }
+
+    #[cfg(feature = "uuid")]
+    #[tokio::test]
+    async fn test_uuid_as_binary_insert_find() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Device {
+            #[serde(with = "uuid_as_binary")]
+            serial: uuid::Uuid,
+        }
+
+        let device = Device {
+            serial: bson::Uuid::new().to_uuid_1(),
+        };
+        // Sent as insert_one sends it, stored, and read back as find reads it.
+        let sent = encode_typed(&device, None).unwrap();
+        let stored = json_to_bson_doc(&sent).unwrap();
+        assert!(matches!(
+            stored.get("serial"),
+            Some(Bson::Binary(Binary { subtype: BinarySubtype::Uuid, .. }))
+        ));
+        let found = bson_doc_to_json(&stored).unwrap();
+        let cursor = crate::Cursor::<Device>::new("app.devices".to_string(), vec![found], None);
+        assert_eq!(cursor.collect().await.unwrap(), vec![device]);
+
+        let legacy = serde_json::json!({ "serial": "67e55044-10b1-426f-9247-bb680e5fe0c8" });
+        assert!(serde_json::from_value::<Device>(legacy).is_ok());
+    }
+}
@@ -0,0 +1,206 @@
+//! CSV export of query results.
+//!
+//! [`Cursor::write_csv`] writes a header row with the column names, then one
+//! row per document. Columns are read from the field of the same name; a
+//! dotted name such as `address.city` reads a nested field. Values are
+//! written as:
+//!
+//! | Value                     | Written as                                   |
+//! |---------------------------|----------------------------------------------|
+//! | missing, null, undefined  | an empty cell                                |
+//! | strings, symbols          | as is                                        |
+//! | booleans                  | `true` or `false`                            |
+//! | numbers                   | decimal, e.g. `42`, `2.5`, `1.10`            |
+//! | object IDs                | hex                                          |
+//! | dates                     | RFC 3339, e.g. `2024-01-02T03:04:05.006Z`    |
+//! | arrays, documents, others | relaxed extended JSON                        |
+//!
+//! Cells containing a comma, a quote or a line break are quoted, with quotes
+//! doubled, as in RFC 4180. Rows end with `\n`.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let file = std::fs::File::create("orders.csv")?;
+//! let rows = orders
+//!     .find(doc! { "status": "shipped" })
+//!     .await?
+//!     .write_csv(std::io::BufWriter::new(file), &["_id", "customer.name", "total"])
+//!     .await?;
+//! ```
+
+use crate::convert;
+use crate::cursor::Cursor;
+use crate::error::{MongoError, Result};
+use crate::progress::{Progress, ProgressTracker};
+use crate::util;
+use bson::{Bson, Document};
+use serde::de::DeserializeOwned;
+use std::borrow::Cow;
+use std::io::Write;
+
+impl<T: DeserializeOwned + Send + Unpin + 'static> Cursor<T> {
+    /// Write the remaining documents to `writer` as CSV with `columns`, and
+    /// return the number of rows written, not counting the header.
+    ///
+    /// See the [module documentation](crate::csv) for how values are written.
+    pub async fn write_csv<W: Write>(self, writer: W, columns: &[&str]) -> Result<u64> {
+        self.write_csv_with_progress(writer, columns, |_| {}).await
+    }
+
+    /// [`Cursor::write_csv`], calling `progress` after each batch is written.
+    pub async fn write_csv_with_progress<W: Write>(
+        mut self,
+        mut writer: W,
+        columns: &[&str],
+        mut progress: impl FnMut(&Progress) + Send,
+    ) -> Result<u64> {
+        if columns.is_empty() {
+            return Err(MongoError::invalid_argument("CSV export needs at least one column"));
+        }
+        write_row(&mut writer, columns.iter().map(|column| Cow::Borrowed(*column)))?;
+        let mut tracker = ProgressTracker::start();
+        let mut rows = 0;
+        while let Some(documents) = self.next_documents().await? {
+            for doc in &documents {
+                write_row(&mut writer, csv_row(doc, columns))?;
+                rows += 1;
+            }
+            progress(&tracker.record(documents.len() as u64, &documents));
+        }
+        writer.flush().map_err(write_error)?;
+        Ok(rows)
+    }
+}
+
+/// The cells of `doc` for `columns`.
+fn csv_row<'a>(
+    doc: &'a Document,
+    columns: &'a [&str],
+) -> impl Iterator<Item = Cow<'a, str>> + 'a {
+    columns
+        .iter()
+        .map(move |column| util::get_path(doc, column).map_or(Cow::Borrowed(""), csv_cell))
+}
+
+/// Format a value as a CSV cell, before escaping.
+pub fn csv_cell(value: &Bson) -> Cow<'_, str> {
+    match value {
+        Bson::Null | Bson::Undefined => Cow::Borrowed(""),
+        Bson::String(s) | Bson::Symbol(s) => Cow::Borrowed(s),
+        Bson::Boolean(b) => Cow::Borrowed(if *b { "true" } else { "false" }),
+        Bson::Int32(n) => Cow::Owned(n.to_string()),
+        Bson::Int64(n) => Cow::Owned(n.to_string()),
+        Bson::Double(n) => Cow::Owned(n.to_string()),
+        Bson::Decimal128(n) => Cow::Owned(n.to_string()),
+        Bson::ObjectId(id) => Cow::Owned(id.to_hex()),
+        Bson::DateTime(date) => Cow::Owned(
+            date.try_to_rfc3339_string()
+                .unwrap_or_else(|_| date.timestamp_millis().to_string()),
+        ),
+        other => Cow::Owned(
+            convert::bson_to_json(other)
+                .map(|json| json.to_string())
+                .unwrap_or_else(|_| other.to_string()),
+        ),
+    }
+}
+
+/// Quote a cell if it contains a comma, a quote or a line break.
+pub fn escape_csv(cell: &str) -> Cow<'_, str> {
+    if cell.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", cell.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(cell)
+    }
+}
+
+/// Write one row of cells.
+fn write_row<'a, W: Write>(
+    writer: &mut W,
+    cells: impl Iterator<Item = Cow<'a, str>>,
+) -> Result<()> {
+    for (i, cell) in cells.enumerate() {
+        if i > 0 {
+            writer.write_all(b",").map_err(write_error)?;
+        }
+        writer
+            .write_all(escape_csv(&cell).as_bytes())
+            .map_err(write_error)?;
+    }
+    writer.write_all(b"\n").map_err(write_error)
+}
+
+fn write_error(e: std::io::Error) -> MongoError {
+    MongoError::Internal(format!("failed to write CSV: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::oid::ObjectId;
+
+    #[test]
+    fn test_csv_cell() {
+        let id = ObjectId::new();
+        assert_eq!(csv_cell(&Bson::Null), "");
+        assert_eq!(csv_cell(&Bson::String("a".into())), "a");
+        assert_eq!(csv_cell(&Bson::Boolean(true)), "true");
+        assert_eq!(csv_cell(&Bson::Int64(-3)), "-3");
+        assert_eq!(csv_cell(&Bson::Double(2.5)), "2.5");
+        assert_eq!(csv_cell(&Bson::ObjectId(id)), id.to_hex());
+        assert_eq!(
+            csv_cell(&Bson::DateTime(bson::DateTime::from_millis(1_704_164_645_006))),
+            "2024-01-02T03:04:05.006Z"
+        );
+        assert_eq!(csv_cell(&bson::bson!([1, "x"])), r#"[1,"x"]"#);
+    }
+
+    #[test]
+    fn test_escape_csv() {
+        assert_eq!(escape_csv("plain"), "plain");
+        assert_eq!(escape_csv("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(escape_csv("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[tokio::test]
+    async fn test_cursor_write_csv() {
+        let cursor: Cursor<Document> = Cursor::new(
+            "shop.orders".to_string(),
+            vec![
+                serde_json::json!({ "sku": "A-1", "qty": 2, "customer": { "name": "Ada, L." } }),
+                serde_json::json!({ "sku": "B-2", "qty": null }),
+            ],
+            None,
+        );
+        let mut out = Vec::new();
+        let rows = cursor
+            .write_csv(&mut out, &["sku", "qty", "customer.name"])
+            .await
+            .unwrap();
+        assert_eq!(rows, 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "sku,qty,customer.name\nA-1,2,\"Ada, L.\"\nB-2,,\n"
+        );
+
+        let cursor: Cursor<Document> = Cursor::new(
+            "shop.orders".to_string(),
+            vec![serde_json::json!({ "sku": "A-1" }), serde_json::json!({ "sku": "B-2" })],
+            None,
+        );
+        let mut reports = Vec::new();
+        cursor
+            .write_csv_with_progress(Vec::new(), &["sku"], |progress| reports.push(*progress))
+            .await
+            .unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].documents, 2);
+        assert!(reports[0].bytes > 0);
+
+        let empty: Cursor<Document> = Cursor::empty("shop.orders".to_string());
+        let err = empty.write_csv(Vec::new(), &[]).await.unwrap_err();
+        assert!(matches!(err, MongoError::InvalidArgument(_)));
+    }
+}
@@ -1,15 +1,27 @@
 //! Cursor implementation for iterating over query results.
 
+#[cfg(feature = "encryption")]
+use crate::encryption::AutoEncrypter;
+use crate::client::ClientSession;
+use crate::convert;
 use crate::error::{MongoError, Result};
-use futures::Stream;
+use crate::rpc::Method;
+use crate::stats::OpenCursor;
+use crate::transport::Transport;
+use bson::{Document, RawDocumentBuf};
+use futures::future::BoxFuture;
+use futures::stream::{BoxStream, FusedStream};
+use futures::{Stream, StreamExt};
 use serde::de::DeserializeOwned;
+use serde::Deserialize;
 use serde_json::Value as JsonValue;
 use std::collections::VecDeque;
 use std::marker::PhantomData;
+use std::panic::AssertUnwindSafe;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
-use tokio::sync::Mutex;
+use tokio::sync::{mpsc, Mutex};
 
 /// Internal cursor state.
 #[derive(Debug)]
@@ -24,10 +36,17 @@ pub(crate) struct CursorState {
     pub namespace: String,
     /// Batch size for fetches.
     pub batch_size: usize,
+    /// Counts the cursor in the client's metrics while a server cursor is open.
+    pub open: Option<OpenCursor>,
+    /// Whether the server cursor is tailable, so an empty batch does not end it.
+    pub tailable: bool,
+    /// Batches fetched ahead in the background, when prefetching.
+    pub prefetched: Option<mpsc::Receiver<Result<JsonValue>>>,
 }
 
 impl CursorState {
     /// Create a new cursor state.
+    #[cfg(test)]
     pub fn new(namespace: String, batch_size: usize) -> Self {
         Self {
             cursor_id: None,
@@ -35,21 +54,68 @@ impl CursorState {
             buffer: VecDeque::new(),
             namespace,
             batch_size,
+            open: None,
+            tailable: false,
+            prefetched: None,
         }
     }
 
     /// Create a cursor state with initial data.
     pub fn with_data(namespace: String, data: Vec<JsonValue>, cursor_id: Option<String>) -> Self {
+        let exhausted = cursor_id.is_none();
         Self {
             cursor_id,
-            exhausted: cursor_id.is_none(),
+            exhausted,
             buffer: data.into(),
             namespace,
             batch_size: 100,
+            open: None,
+            tailable: false,
+            prefetched: None,
         }
     }
+
+    /// Buffer the documents of a `getMore` reply, releasing the server
+    /// cursor when the reply has no cursor ID.
+    fn apply_batch(&mut self, value: &JsonValue) {
+        if let Some(docs) = value.get("documents").and_then(|d| d.as_array()) {
+            self.buffer.extend(docs.iter().cloned());
+        }
+        if let Some(new_cursor_id) = value.get("cursorId").and_then(|c| c.as_str()) {
+            self.cursor_id = Some(new_cursor_id.to_string());
+        } else {
+            self.cursor_id = None;
+            self.exhausted = true;
+            self.open = None;
+            self.prefetched = None;
+        }
+    }
+
+    /// Buffer the next batch fetched in the background, or return `None`
+    /// when not prefetching.
+    async fn next_prefetched(&mut self) -> Option<Result<()>> {
+        let batch = self.prefetched.as_mut()?.recv().await;
+        Some(match batch {
+            Some(Ok(value)) => {
+                self.apply_batch(&value);
+                Ok(())
+            }
+            Some(Err(e)) => {
+                self.exhausted = true;
+                Err(e)
+            }
+            None => {
+                self.exhausted = true;
+                Ok(())
+            }
+        })
+    }
 }
 
+/// Fetches the next batch of a cursor.
+pub(crate) type FetchMore =
+    Box<dyn Fn() -> futures::future::BoxFuture<'static, Result<Vec<JsonValue>>> + Send + Sync>;
+
 /// A cursor for iterating over query results.
 ///
 /// Cursors implement `Stream` and can be used with async iteration.
@@ -68,9 +134,22 @@ pub struct Cursor<T> {
     /// Internal state.
     pub(crate) state: Arc<Mutex<CursorState>>,
     /// RPC client for fetching more data.
-    pub(crate) rpc_client: Option<Arc<rpc_do::RpcClient>>,
+    pub(crate) rpc_client: Option<Transport>,
     /// Fetch function for getting more documents.
-    pub(crate) fetch_more: Option<Box<dyn Fn() -> futures::future::BoxFuture<'static, Result<Vec<JsonValue>>> + Send + Sync>>,
+    pub(crate) fetch_more: Option<FetchMore>,
+    /// Automatic encryption used to decrypt fetched batches.
+    #[cfg(feature = "encryption")]
+    pub(crate) auto_encrypter: Option<Arc<AutoEncrypter>>,
+    /// The document returned by the last [`Cursor::next_borrowed`], kept
+    /// alive so the value deserialized from it can borrow from it.
+    borrowed: Option<JsonValue>,
+    /// The document being fetched by the stream, kept across polls so a
+    /// `getMore` in flight is not dropped and sent again. Held undecoded so it
+    /// survives [`Cursor::with_type`]. Behind a mutex only so the cursor stays
+    /// `Sync`; polling has `&mut` access and never locks.
+    pending: std::sync::Mutex<Option<BoxFuture<'static, Result<Option<JsonValue>>>>>,
+    /// Whether the stream has returned `None`.
+    terminated: bool,
     /// Type marker.
     _marker: PhantomData<T>,
 }
@@ -82,6 +161,11 @@ impl<T> Cursor<T> {
             state: Arc::new(Mutex::new(CursorState::with_data(namespace, data, cursor_id))),
             rpc_client: None,
             fetch_more: None,
+            #[cfg(feature = "encryption")]
+            auto_encrypter: None,
+            borrowed: None,
+            pending: std::sync::Mutex::new(None),
+            terminated: false,
             _marker: PhantomData,
         }
     }
@@ -95,16 +179,36 @@ impl<T> Cursor<T> {
                 buffer: VecDeque::new(),
                 namespace,
                 batch_size: 100,
+                open: None,
+                tailable: false,
+                prefetched: None,
             })),
             rpc_client: None,
             fetch_more: None,
+            #[cfg(feature = "encryption")]
+            auto_encrypter: None,
+            borrowed: None,
+            pending: std::sync::Mutex::new(None),
+            terminated: false,
             _marker: PhantomData,
         }
     }
 
     /// Set the RPC client for fetching more data.
     pub fn with_rpc_client(mut self, client: Arc<rpc_do::RpcClient>) -> Self {
-        self.rpc_client = Some(client);
+        self.rpc_client = Some(Transport::new(client));
+        self
+    }
+
+    /// Set the transport for fetching more data.
+    pub(crate) fn with_transport(mut self, transport: Transport) -> Self {
+        // The state is not shared yet, so the lock is free.
+        if let Ok(mut state) = self.state.try_lock() {
+            if state.cursor_id.is_some() {
+                state.open = Some(transport.stats.open_cursor());
+            }
+        }
+        self.rpc_client = Some(transport);
         self
     }
 
@@ -126,8 +230,78 @@ impl<T> Cursor<T> {
         state.exhausted = true;
         state.buffer.clear();
         state.cursor_id = None;
+        state.open = None;
+        state.prefetched = None;
         Ok(())
     }
+
+    /// Read the remaining documents as `U` instead of `T`.
+    ///
+    /// Documents are deserialized from the raw results, so `U` may have
+    /// fields `T` does not. A document the stream was fetching is kept, and
+    /// read as `U`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut raw = users.find(filter).await?.with_type::<Document>();
+    /// ```
+    pub fn with_type<U>(self) -> Cursor<U> {
+        let pending = self.pending.into_inner().unwrap_or_else(|e| e.into_inner());
+        Cursor {
+            state: self.state,
+            rpc_client: self.rpc_client,
+            fetch_more: self.fetch_more,
+            #[cfg(feature = "encryption")]
+            auto_encrypter: self.auto_encrypter,
+            borrowed: None,
+            pending: std::sync::Mutex::new(pending),
+            terminated: self.terminated,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Fetch up to `depth` batches ahead in the background while the
+    /// current one is read.
+    ///
+    /// Hides the latency of each `getMore` on large scans over slow links,
+    /// at the cost of holding the prefetched batches in memory. Batches are
+    /// fetched outside any session the cursor is iterated in. Has no effect
+    /// on tailable cursors, or once the server cursor is exhausted. Must be
+    /// called within a Tokio runtime.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut events = events.find(None).await?.prefetch(2);
+    /// while let Some(event) = events.try_next().await? {
+    ///     process(event).await;
+    /// }
+    /// ```
+    pub fn prefetch(self, depth: usize) -> Self {
+        let Some(ref transport) = self.rpc_client else {
+            return self;
+        };
+        // Not iterated yet, so the lock is free unless the cursor is shared.
+        if let Ok(mut state) = self.state.try_lock() {
+            if let (Some(cursor_id), false, None) =
+                (state.cursor_id.clone(), state.tailable, &state.prefetched)
+            {
+                let (sender, receiver) = mpsc::channel(depth.max(1));
+                tokio::spawn(prefetch_batches(
+                    transport.clone(),
+                    state.namespace.clone(),
+                    cursor_id,
+                    state.batch_size,
+                    #[cfg(feature = "encryption")]
+                    self.auto_encrypter.clone(),
+                    sender,
+                ));
+                state.prefetched = Some(receiver);
+            }
+        }
+        self
+    }
 }
 
 impl<T: DeserializeOwned + Send + Unpin + 'static> Cursor<T> {
@@ -145,6 +319,11 @@ impl<T: DeserializeOwned + Send + Unpin + 'static> Cursor<T> {
             return Ok(false);
         }
 
+        if let Some(result) = state.next_prefetched().await {
+            result?;
+            return Ok(!state.buffer.is_empty());
+        }
+
         // Try to fetch more if we have a cursor ID and RPC client
         if state.cursor_id.is_some() {
             if let Some(ref rpc_client) = self.rpc_client {
@@ -156,7 +335,7 @@ impl<T: DeserializeOwned + Send + Unpin + 'static> Cursor<T> {
                 // Fetch more documents
                 let result = rpc_client
                     .call_raw(
-                        "mongo.getMore",
+                        Method::GetMore,
                         vec![
                             serde_json::json!(cursor_id),
                             serde_json::json!(namespace),
@@ -164,25 +343,15 @@ impl<T: DeserializeOwned + Send + Unpin + 'static> Cursor<T> {
                         ],
                     )
                     .await;
+                #[cfg(feature = "encryption")]
+                let result = decrypt_batch(self.auto_encrypter.as_deref(), result).await;
 
                 let mut state = self.state.lock().await;
                 match result {
-                    Ok(value) => {
-                        if let Some(docs) = value.get("documents").and_then(|d| d.as_array()) {
-                            for doc in docs {
-                                state.buffer.push_back(doc.clone());
-                            }
-                        }
-                        if let Some(new_cursor_id) = value.get("cursorId").and_then(|c| c.as_str()) {
-                            state.cursor_id = Some(new_cursor_id.to_string());
-                        } else {
-                            state.cursor_id = None;
-                            state.exhausted = true;
-                        }
-                    }
+                    Ok(value) => state.apply_batch(&value),
                     Err(e) => {
                         state.exhausted = true;
-                        return Err(e.into());
+                        return Err(e);
                     }
                 }
 
@@ -197,80 +366,161 @@ impl<T: DeserializeOwned + Send + Unpin + 'static> Cursor<T> {
 
     /// Get the current document.
     pub async fn current(&self) -> Result<T> {
-        let state = self.state.lock().await;
-        if let Some(doc) = state.buffer.front() {
-            serde_json::from_value(doc.clone()).map_err(|e| MongoError::Deserialization(e.to_string()))
-        } else {
-            Err(MongoError::CursorExhausted)
+        let doc = self.state.lock().await.buffer.front().cloned();
+        match doc {
+            Some(doc) => deserialize(&doc, self.rpc_client.as_ref()),
+            None => Err(MongoError::CursorExhausted),
         }
     }
 
     /// Try to get the next document.
     pub async fn try_next(&mut self) -> Result<Option<T>> {
-        let mut state = self.state.lock().await;
+        let transport = self.rpc_client.clone();
+        self.next_via(transport.as_ref()).await
+    }
 
-        if let Some(doc) = state.buffer.pop_front() {
-            return serde_json::from_value(doc)
-                .map(Some)
-                .map_err(|e| MongoError::Deserialization(e.to_string()));
-        }
+    /// Iterate the cursor within `session`.
+    ///
+    /// Batches are fetched in the session, so reads stay causally consistent
+    /// with the session's earlier operations. The session cannot be used for
+    /// anything else while the stream is alive. A document the cursor's own
+    /// stream was fetching is finished first, outside the session, so its
+    /// batch is not lost.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use futures::StreamExt;
+    ///
+    /// let mut session = client.start_session().await?;
+    /// let mut stream = cursor.stream(&mut session);
+    /// while let Some(order) = stream.next().await {
+    ///     println!("{:?}", order?);
+    /// }
+    /// ```
+    pub fn stream<'a>(
+        &'a mut self,
+        session: &'a mut ClientSession,
+    ) -> impl Stream<Item = Result<T>> + Send + 'a {
+        let transport = self.rpc_client.as_ref().map(|t| t.with_session(session.id()));
+        let pending = self.pending.get_mut().unwrap_or_else(|e| e.into_inner()).take();
+        futures::stream::unfold(
+            (self, transport, pending),
+            |(cursor, transport, pending)| async move {
+                let item = match pending {
+                    Some(pending) => {
+                        let doc = pending.await;
+                        cursor.decode(doc)
+                    }
+                    None => cursor.next_via(transport.as_ref()).await,
+                };
+                Some((item.transpose()?, (cursor, transport, None)))
+            },
+        )
+    }
 
-        if state.exhausted {
-            return Ok(None);
-        }
+    /// Get the next document, fetching another batch through `transport`.
+    ///
+    /// A document the stream was fetching when it was last dropped mid-poll
+    /// is returned first.
+    async fn next_via(&mut self, transport: Option<&Transport>) -> Result<Option<T>> {
+        let pending = self.pending.get_mut().unwrap_or_else(|e| e.into_inner()).take();
+        let doc = match pending {
+            Some(pending) => pending.await,
+            None => {
+                next_document(
+                    self.state.clone(),
+                    transport.cloned(),
+                    #[cfg(feature = "encryption")]
+                    self.auto_encrypter.clone(),
+                )
+                .await
+            }
+        };
+        self.decode(doc)
+    }
 
-        // Check if we need to fetch more
-        if state.cursor_id.is_some() {
-            if let Some(ref rpc_client) = self.rpc_client {
-                let cursor_id = state.cursor_id.clone().unwrap();
-                let namespace = state.namespace.clone();
-                let batch_size = state.batch_size;
-                drop(state);
+    /// Deserialize a fetched document with the cursor's codec.
+    fn decode(&self, doc: Result<Option<JsonValue>>) -> Result<Option<T>> {
+        doc?.map(|doc| deserialize(&doc, self.rpc_client.as_ref())).transpose()
+    }
 
-                // Fetch more documents
-                let result = rpc_client
-                    .call_raw(
-                        "mongo.getMore",
-                        vec![
-                            serde_json::json!(cursor_id),
-                            serde_json::json!(namespace),
-                            serde_json::json!(batch_size),
-                        ],
-                    )
-                    .await;
+    /// Get the next document, deserialized as `D` borrowing from the cursor.
+    ///
+    /// Strings in `D` can be `&str` instead of `String`, avoiding an
+    /// allocation per field on hot read paths. The document stays alive until
+    /// the next call.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// #[derive(Deserialize)]
+    /// struct Name<'a> {
+    ///     name: &'a str,
+    /// }
+    ///
+    /// while let Some(doc) = cursor.next_borrowed::<Name>().await? {
+    ///     if doc.name.starts_with("test-") {
+    ///         test_accounts += 1;
+    ///     }
+    /// }
+    /// ```
+    pub async fn next_borrowed<'a, D: Deserialize<'a>>(&'a mut self) -> Result<Option<D>> {
+        self.borrowed = None;
+        if !self.advance().await? {
+            return Ok(None);
+        }
+        self.borrowed = self.state.lock().await.buffer.pop_front();
+        match self.borrowed {
+            Some(ref doc) => D::deserialize(doc)
+                .map(Some)
+                .map_err(|e| MongoError::Deserialization(e.to_string())),
+            None => Ok(None),
+        }
+    }
 
-                let mut state = self.state.lock().await;
-                match result {
-                    Ok(value) => {
-                        if let Some(docs) = value.get("documents").and_then(|d| d.as_array()) {
-                            for doc in docs {
-                                state.buffer.push_back(doc.clone());
-                            }
-                        }
-                        if let Some(new_cursor_id) = value.get("cursorId").and_then(|c| c.as_str()) {
-                            state.cursor_id = Some(new_cursor_id.to_string());
-                        } else {
-                            state.cursor_id = None;
-                            state.exhausted = true;
-                        }
+    /// Append all remaining documents to `out`, returning how many were added.
+    ///
+    /// Space for each fetched batch is reserved up front. Documents read
+    /// before an error stay in `out`; the rest stay in the cursor.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut users = Vec::with_capacity(1000);
+    /// active.try_collect_into(&mut users).await?;
+    /// inactive.try_collect_into(&mut users).await?;
+    /// ```
+    pub async fn try_collect_into(&mut self, out: &mut Vec<T>) -> Result<usize> {
+        let start = out.len();
+        while self.advance().await? {
+            // The batch is deserialized outside the lock. Documents not read
+            // because of an error or a panic go back to the cursor.
+            let mut batch = std::mem::take(&mut self.state.lock().await.buffer);
+            let transport = self.rpc_client.as_ref();
+            out.reserve(batch.len());
+            while let Some(doc) = batch.pop_front() {
+                match std::panic::catch_unwind(AssertUnwindSafe(|| deserialize(&doc, transport))) {
+                    Ok(Ok(value)) => out.push(value),
+                    Ok(Err(error)) => {
+                        self.unread(batch).await;
+                        return Err(error);
                     }
-                    Err(e) => {
-                        state.exhausted = true;
-                        return Err(e.into());
+                    Err(panic) => {
+                        self.unread(batch).await;
+                        std::panic::resume_unwind(panic);
                     }
                 }
-
-                if let Some(doc) = state.buffer.pop_front() {
-                    return serde_json::from_value(doc)
-                        .map(Some)
-                        .map_err(|e| MongoError::Deserialization(e.to_string()));
-                }
             }
         }
+        Ok(out.len() - start)
+    }
 
+    /// Put documents taken from the buffer back in front of it.
+    async fn unread(&self, mut docs: VecDeque<JsonValue>) {
         let mut state = self.state.lock().await;
-        state.exhausted = true;
-        Ok(None)
+        docs.append(&mut state.buffer);
+        state.buffer = docs;
     }
 
     /// Collect all documents into a vector.
@@ -281,6 +531,81 @@ impl<T: DeserializeOwned + Send + Unpin + 'static> Cursor<T> {
         }
         Ok(results)
     }
+
+    /// Stream the remaining documents passed through `f`, one at a time.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use futures::StreamExt;
+    ///
+    /// let mut emails = users.find(None).await?.map(|user| user.email);
+    /// while let Some(email) = emails.next().await {
+    ///     send_newsletter(&email?).await;
+    /// }
+    /// ```
+    pub fn map<U, F>(self, f: F) -> BoxStream<'static, Result<U>>
+    where
+        U: Send + 'static,
+        F: FnMut(T) -> U + Send + 'static,
+    {
+        let stream = futures::stream::unfold((self, f), |(mut cursor, mut f)| async move {
+            let item = cursor.try_next().await.transpose()?;
+            Some((item.map(&mut f), (cursor, f)))
+        });
+        Box::pin(stream)
+    }
+
+    /// Stream the remaining documents for which `predicate` returns true.
+    ///
+    /// Errors are passed through.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut flagged = orders.find(None).await?.try_filter(|order| order.total > 10_000);
+    /// ```
+    pub fn try_filter<F>(self, predicate: F) -> BoxStream<'static, Result<T>>
+    where
+        F: FnMut(&T) -> bool + Send + 'static,
+    {
+        let stream = futures::stream::unfold(
+            (self, predicate),
+            |(mut cursor, mut predicate)| async move {
+                loop {
+                    match cursor.try_next().await {
+                        Ok(Some(doc)) if !predicate(&doc) => continue,
+                        Ok(doc) => return doc.map(|doc| (Ok(doc), (cursor, predicate))),
+                        Err(e) => return Some((Err(e), (cursor, predicate))),
+                    }
+                }
+            },
+        );
+        Box::pin(stream)
+    }
+
+    /// Take the next batch as BSON documents, without deserializing to `T`.
+    pub(crate) async fn next_documents(&mut self) -> Result<Option<Vec<Document>>> {
+        let Some(batch) = self.next_json_batch().await? else {
+            return Ok(None);
+        };
+        batch
+            .iter()
+            .map(|json| match self.rpc_client {
+                Some(ref transport) => transport.decode_document(json),
+                None => crate::convert::json_to_bson_doc(json),
+            })
+            .collect::<Result<Vec<_>>>()
+            .map(Some)
+    }
+
+    /// Take the next batch as it arrived, without decoding it.
+    async fn next_json_batch(&mut self) -> Result<Option<VecDeque<JsonValue>>> {
+        if !self.advance().await? {
+            return Ok(None);
+        }
+        Ok(Some(std::mem::take(&mut self.state.lock().await.buffer)))
+    }
 }
 
 impl<T: DeserializeOwned + Send + Unpin + 'static> Stream for Cursor<T> {
@@ -288,85 +613,271 @@ impl<T: DeserializeOwned + Send + Unpin + 'static> Stream for Cursor<T> {
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         let this = self.get_mut();
+        if this.terminated {
+            return Poll::Ready(None);
+        }
 
-        // Create a future for try_next
-        let state = this.state.clone();
-        let rpc_client = this.rpc_client.clone();
+        // Taken out while polled, so a panic in it does not leave behind a
+        // future that cannot be polled again.
+        let pending = this.pending.get_mut().unwrap_or_else(|e| e.into_inner());
+        let mut fut = pending.take().unwrap_or_else(|| {
+            Box::pin(next_document(
+                this.state.clone(),
+                this.rpc_client.clone(),
+                #[cfg(feature = "encryption")]
+                this.auto_encrypter.clone(),
+            ))
+        });
+        let poll = fut.as_mut().poll(cx);
+        if poll.is_pending() {
+            *pending = Some(fut);
+        }
+        match poll.map(|doc| this.decode(doc)) {
+            Poll::Ready(Ok(None)) => {
+                this.terminated = true;
+                Poll::Ready(None)
+            }
+            Poll::Ready(result) => Poll::Ready(result.transpose()),
+            Poll::Pending => Poll::Pending,
+        }
+    }
 
-        // Use a boxed future to avoid lifetime issues
-        let fut = async move {
-            let mut state_guard = state.lock().await;
+    /// At least the buffered documents remain; with no server cursor left,
+    /// exactly those do.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.terminated {
+            return (0, Some(0));
+        }
+        match self.state.try_lock() {
+            Ok(state) if state.exhausted => (state.buffer.len(), Some(state.buffer.len())),
+            Ok(state) => (state.buffer.len(), None),
+            Err(_) => (0, None),
+        }
+    }
+}
 
-            if let Some(doc) = state_guard.buffer.pop_front() {
-                return Some(
-                    serde_json::from_value(doc)
-                        .map_err(|e| MongoError::Deserialization(e.to_string())),
-                );
-            }
+impl<T: DeserializeOwned + Send + Unpin + 'static> FusedStream for Cursor<T> {
+    fn is_terminated(&self) -> bool {
+        self.terminated
+    }
+}
 
-            if state_guard.exhausted {
-                return None;
-            }
+/// A cursor over documents as raw BSON, returned by
+/// [`Collection::find_raw`](crate::Collection::find_raw) and
+/// [`Collection::aggregate_raw`](crate::Collection::aggregate_raw).
+///
+/// Documents are not deserialized to a Rust type, so they can be forwarded
+/// untouched, e.g. written to a socket or another database.
+///
+/// # Example
+///
+/// ```ignore
+/// use futures::TryStreamExt;
+///
+/// let docs: Vec<RawDocumentBuf> = users.find_raw(None).await?.try_collect().await?;
+/// ```
+pub struct RawCursor {
+    inner: Cursor<JsonValue>,
+}
 
-            // Check if we need to fetch more
-            if state_guard.cursor_id.is_some() {
-                if let Some(ref client) = rpc_client {
-                    let cursor_id = state_guard.cursor_id.clone().unwrap();
-                    let namespace = state_guard.namespace.clone();
-                    let batch_size = state_guard.batch_size;
-                    drop(state_guard);
-
-                    // Fetch more documents
-                    let result = client
-                        .call_raw(
-                            "mongo.getMore",
-                            vec![
-                                serde_json::json!(cursor_id),
-                                serde_json::json!(namespace),
-                                serde_json::json!(batch_size),
-                            ],
-                        )
-                        .await;
-
-                    let mut state_guard = state.lock().await;
-                    match result {
-                        Ok(value) => {
-                            if let Some(docs) = value.get("documents").and_then(|d| d.as_array()) {
-                                for doc in docs {
-                                    state_guard.buffer.push_back(doc.clone());
-                                }
-                            }
-                            if let Some(new_cursor_id) = value.get("cursorId").and_then(|c| c.as_str()) {
-                                state_guard.cursor_id = Some(new_cursor_id.to_string());
-                            } else {
-                                state_guard.cursor_id = None;
-                                state_guard.exhausted = true;
-                            }
-                        }
-                        Err(e) => {
-                            state_guard.exhausted = true;
-                            return Some(Err(e.into()));
-                        }
-                    }
+impl RawCursor {
+    /// Read the documents of `inner` as raw BSON.
+    pub(crate) fn new(inner: Cursor<JsonValue>) -> Self {
+        Self { inner }
+    }
 
-                    if let Some(doc) = state_guard.buffer.pop_front() {
-                        return Some(
-                            serde_json::from_value(doc)
-                                .map_err(|e| MongoError::Deserialization(e.to_string())),
-                        );
-                    }
-                }
+    /// Get the next document.
+    pub async fn try_next(&mut self) -> Result<Option<RawDocumentBuf>> {
+        match self.inner.try_next().await? {
+            Some(json) => raw_document(self.inner.rpc_client.as_ref(), &json).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Take the rest of the current batch, fetching one if none is left.
+    pub async fn next_batch(&mut self) -> Result<Option<Vec<RawDocumentBuf>>> {
+        let Some(batch) = self.inner.next_json_batch().await? else {
+            return Ok(None);
+        };
+        let transport = self.inner.rpc_client.as_ref();
+        batch
+            .iter()
+            .map(|json| raw_document(transport, json))
+            .collect::<Result<Vec<_>>>()
+            .map(Some)
+    }
+
+    /// Collect all documents into a vector.
+    pub async fn collect(mut self) -> Result<Vec<RawDocumentBuf>> {
+        let mut results = Vec::new();
+        while let Some(batch) = self.next_batch().await? {
+            results.extend(batch);
+        }
+        Ok(results)
+    }
+
+    /// Read the documents as the JSON they arrive in instead.
+    pub fn into_json(self) -> Cursor<JsonValue> {
+        self.inner
+    }
+}
+
+impl Stream for RawCursor {
+    type Item = Result<RawDocumentBuf>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let poll = self.inner.poll_next_unpin(cx);
+        let transport = self.inner.rpc_client.as_ref();
+        poll.map(|item| item.map(|json| raw_document(transport, &json?)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl FusedStream for RawCursor {
+    fn is_terminated(&self) -> bool {
+        self.inner.is_terminated()
+    }
+}
+
+/// Convert a document received through `transport` straight to raw BSON.
+fn raw_document(transport: Option<&Transport>, json: &JsonValue) -> Result<RawDocumentBuf> {
+    crate::convert::decode_raw_document(json, transport.and_then(|t| t.codec.as_deref()))
+}
+
+/// Take the next document from `state`, fetching another batch through
+/// `transport` when the buffer is empty.
+///
+/// The state lock is released before the document is deserialized, so
+/// handles sharing the cursor are not serialized behind each other.
+async fn next_document(
+    state: Arc<Mutex<CursorState>>,
+    transport: Option<Transport>,
+    #[cfg(feature = "encryption")] auto_encrypter: Option<Arc<AutoEncrypter>>,
+) -> Result<Option<JsonValue>> {
+    let mut guard = state.lock().await;
+
+    if let Some(doc) = guard.buffer.pop_front() {
+        return Ok(Some(doc));
+    }
+
+    if guard.exhausted {
+        return Ok(None);
+    }
+
+    if let Some(result) = guard.next_prefetched().await {
+        result?;
+        let doc = guard.buffer.pop_front();
+        if doc.is_none() && !guard.tailable {
+            guard.exhausted = true;
+        }
+        return Ok(doc);
+    }
+
+    // Check if we need to fetch more
+    if let (Some(cursor_id), Some(rpc_client)) = (guard.cursor_id.clone(), &transport) {
+        let namespace = guard.namespace.clone();
+        let batch_size = guard.batch_size;
+        drop(guard);
+
+        // Fetch more documents
+        let result = rpc_client
+            .call_raw(
+                Method::GetMore,
+                vec![
+                    serde_json::json!(cursor_id),
+                    serde_json::json!(namespace),
+                    serde_json::json!(batch_size),
+                ],
+            )
+            .await;
+        #[cfg(feature = "encryption")]
+        let result = decrypt_batch(auto_encrypter.as_deref(), result).await;
+
+        let mut guard = state.lock().await;
+        match result {
+            Ok(value) => guard.apply_batch(&value),
+            Err(e) => {
+                guard.exhausted = true;
+                return Err(e);
             }
+        }
+
+        let doc = guard.buffer.pop_front();
+        if doc.is_none() && !guard.tailable {
+            guard.exhausted = true;
+        }
+        return Ok(doc);
+    }
+
+    guard.exhausted = true;
+    Ok(None)
+}
 
-            let mut state_guard = state.lock().await;
-            state_guard.exhausted = true;
-            None
+/// Fetch the batches of the server cursor `cursor_id` in order, sending each
+/// to `sender` until the cursor is exhausted or the receiver dropped.
+async fn prefetch_batches(
+    transport: Transport,
+    namespace: String,
+    mut cursor_id: String,
+    batch_size: usize,
+    #[cfg(feature = "encryption")] auto_encrypter: Option<Arc<AutoEncrypter>>,
+    sender: mpsc::Sender<Result<JsonValue>>,
+) {
+    loop {
+        let result = transport
+            .call_raw(
+                Method::GetMore,
+                vec![
+                    serde_json::json!(cursor_id),
+                    serde_json::json!(namespace),
+                    serde_json::json!(batch_size),
+                ],
+            )
+            .await;
+        #[cfg(feature = "encryption")]
+        let result = decrypt_batch(auto_encrypter.as_deref(), result).await;
+
+        let next = match result {
+            Ok(ref value) => value.get("cursorId").and_then(|c| c.as_str()).map(String::from),
+            Err(_) => None,
         };
+        if sender.send(result).await.is_err() {
+            return;
+        }
+        match next {
+            Some(next) => cursor_id = next,
+            None => return,
+        }
+    }
+}
 
-        // Poll the future
-        let mut boxed = Box::pin(fut);
-        boxed.as_mut().poll(cx)
+/// Deserialize a document taken from the cursor buffer, decoding its values
+/// with the codec of `transport`, if any.
+pub(crate) fn deserialize<T: DeserializeOwned>(
+    doc: &JsonValue,
+    transport: Option<&Transport>,
+) -> Result<T> {
+    convert::decode_typed(doc, transport.and_then(|t| t.codec.as_deref()))
+}
+
+/// Decrypt the documents of a fetched batch.
+#[cfg(feature = "encryption")]
+async fn decrypt_batch(
+    encrypter: Option<&AutoEncrypter>,
+    result: Result<JsonValue>,
+) -> Result<JsonValue> {
+    let mut value = result?;
+    if let Some(encrypter) = encrypter {
+        if let Some(docs) = value.get_mut("documents").and_then(|d| d.as_array_mut()) {
+            for doc in docs {
+                encrypter.decrypt(doc).await?;
+            }
+        }
     }
+    Ok(value)
 }
 
 #[cfg(test)]
@@ -434,6 +945,277 @@ mod tests {
         assert_eq!(docs[2].name, "doc3");
     }
 
+    #[tokio::test]
+    async fn test_cursor_try_collect_into() {
+        let data = vec![
+            serde_json::json!({"name": "doc1", "value": 1}),
+            serde_json::json!({"name": "doc2", "value": 2}),
+        ];
+        let mut cursor: Cursor<TestDoc> = Cursor::new("test.docs".to_string(), data, None);
+
+        let mut docs = vec![TestDoc { name: "doc0".to_string(), value: 0 }];
+        assert_eq!(cursor.try_collect_into(&mut docs).await.unwrap(), 2);
+        assert_eq!(docs.len(), 3);
+        assert_eq!(docs[2].name, "doc2");
+        assert_eq!(cursor.try_collect_into(&mut docs).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_try_collect_into_keeps_unread() {
+        use futures::FutureExt;
+
+        #[derive(Debug, Deserialize)]
+        struct Touchy {
+            #[serde(deserialize_with = "panic_on_two")]
+            value: i32,
+        }
+        fn panic_on_two<'de, D>(d: D) -> std::result::Result<i32, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            match i32::deserialize(d)? {
+                2 => panic!("cannot handle 2"),
+                value => Ok(value),
+            }
+        }
+
+        let data = [1, 2, 3].iter().map(|value| serde_json::json!({ "value": value })).collect();
+        let mut cursor: Cursor<Touchy> = Cursor::new("test.docs".to_string(), data, None);
+        let mut docs = Vec::new();
+        let panicked = AssertUnwindSafe(cursor.try_collect_into(&mut docs)).catch_unwind().await;
+        assert!(panicked.is_err());
+        assert_eq!(docs.len(), 1);
+        assert_eq!(cursor.try_collect_into(&mut docs).await.unwrap(), 1);
+        assert_eq!(docs[1].value, 3);
+
+        let data = vec![
+            serde_json::json!({ "value": 1 }),
+            serde_json::json!({ "value": "one" }),
+            serde_json::json!({ "value": 3 }),
+        ];
+        let mut cursor: Cursor<Touchy> = Cursor::new("test.docs".to_string(), data, None);
+        let mut docs = Vec::new();
+        assert!(cursor.try_collect_into(&mut docs).await.is_err());
+        assert_eq!(docs.len(), 1);
+        assert_eq!(cursor.try_collect_into(&mut docs).await.unwrap(), 1);
+        assert_eq!(docs[1].value, 3);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_stream_keeps_pending_fetch() {
+        use futures::StreamExt;
+
+        fn assert_sync<S: Sync>(_: &S) {}
+        let data = vec![
+            serde_json::json!({"name": "doc1", "value": 1}),
+            serde_json::json!({"name": "doc2", "value": 2}),
+        ];
+        let mut cursor: Cursor<TestDoc> = Cursor::new("test.docs".to_string(), data, None);
+        assert_sync(&cursor);
+
+        // A poll that cannot finish keeps its future for the next call.
+        let guard = cursor.state.clone().lock_owned().await;
+        assert!(futures::poll!(cursor.next()).is_pending());
+        assert!(cursor.pending.get_mut().unwrap().is_some());
+        drop(guard);
+        assert_eq!(cursor.try_next().await.unwrap().unwrap().name, "doc1");
+        assert!(cursor.pending.get_mut().unwrap().is_none());
+        assert_eq!(cursor.next().await.unwrap().unwrap().name, "doc2");
+        assert!(cursor.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cursor_pending_fetch_is_kept() {
+        use crate::client::ClientOptions;
+        use futures::StreamExt;
+
+        let data = vec![
+            serde_json::json!({"name": "doc1", "value": 1}),
+            serde_json::json!({"name": "doc2", "value": 2}),
+        ];
+
+        // Re-typing keeps the document in flight.
+        let mut cursor: Cursor<TestDoc> = Cursor::new("test.docs".to_string(), data.clone(), None);
+        let guard = cursor.state.clone().lock_owned().await;
+        assert!(futures::poll!(cursor.next()).is_pending());
+        let mut raw = cursor.with_type::<Document>();
+        drop(guard);
+        assert_eq!(raw.try_next().await.unwrap().unwrap().get_str("name"), Ok("doc1"));
+        assert_eq!(raw.try_next().await.unwrap().unwrap().get_str("name"), Ok("doc2"));
+
+        // So does switching to a session.
+        let transport = Transport::lazy("mongodb://localhost".into(), ClientOptions::default());
+        let mut session = ClientSession::new("session1".to_string(), transport);
+        let mut cursor: Cursor<TestDoc> = Cursor::new("test.docs".to_string(), data, None);
+        let guard = cursor.state.clone().lock_owned().await;
+        assert!(futures::poll!(cursor.next()).is_pending());
+        drop(guard);
+        let names: Vec<String> = cursor
+            .stream(&mut session)
+            .map(|doc| doc.unwrap().name)
+            .collect()
+            .await;
+        assert_eq!(names, ["doc1", "doc2"]);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_prefetched_batches() {
+        let cursor: Cursor<TestDoc> = Cursor::new(
+            "test.docs".to_string(),
+            vec![serde_json::json!({"name": "doc1", "value": 1})],
+            Some("cursor1".to_string()),
+        );
+        // Without a transport there is nothing to prefetch from.
+        let cursor = cursor.prefetch(2);
+        assert!(cursor.state.lock().await.prefetched.is_none());
+
+        let (sender, receiver) = mpsc::channel(2);
+        cursor.state.lock().await.prefetched = Some(receiver);
+        let batch = serde_json::json!({
+            "documents": [{"name": "doc2", "value": 2}],
+            "cursorId": "cursor1",
+        });
+        sender.send(Ok(batch)).await.unwrap();
+        sender
+            .send(Ok(serde_json::json!({"documents": [{"name": "doc3", "value": 3}]})))
+            .await
+            .unwrap();
+
+        let docs = cursor.collect().await.unwrap();
+        let names: Vec<_> = docs.iter().map(|doc| doc.name.as_str()).collect();
+        assert_eq!(names, ["doc1", "doc2", "doc3"]);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_survives_consumer_panic() {
+        use futures::StreamExt;
+
+        #[derive(Debug, Deserialize)]
+        struct Touchy {
+            #[serde(deserialize_with = "panic_on_two")]
+            value: i32,
+        }
+        fn panic_on_two<'de, D>(d: D) -> std::result::Result<i32, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            match i32::deserialize(d)? {
+                2 => panic!("cannot handle 2"),
+                value => Ok(value),
+            }
+        }
+
+        let data = (1..=4).map(|value| serde_json::json!({ "value": value })).collect();
+        let cursor: Cursor<Touchy> = Cursor::new("test.docs".to_string(), data, None);
+        let shared = Arc::new(Mutex::new(cursor));
+        assert_eq!(shared.lock().await.next().await.unwrap().unwrap().value, 1);
+
+        let consumer = shared.clone();
+        let panicked = tokio::spawn(async move { consumer.lock().await.next().await.map(|_| ()) });
+        assert!(panicked.await.unwrap_err().is_panic());
+
+        // Other holders carry on after the document that caused the panic.
+        let mut cursor = shared.lock().await;
+        assert_eq!(cursor.next().await.unwrap().unwrap().value, 3);
+
+        let mut rest = Vec::new();
+        assert_eq!(cursor.try_collect_into(&mut rest).await.unwrap(), 1);
+        assert_eq!(rest[0].value, 4);
+    }
+
+    #[tokio::test]
+    async fn test_raw_cursor() {
+        use futures::StreamExt;
+
+        let data = vec![
+            serde_json::json!({"name": "doc1", "value": 1}),
+            serde_json::json!({"name": "doc2", "value": 2}),
+            serde_json::json!({"name": "doc3", "value": 3}),
+        ];
+        let mut cursor = RawCursor::new(Cursor::new("test.docs".to_string(), data, None));
+        let first = cursor.try_next().await.unwrap().unwrap();
+        assert_eq!(first.get_str("name").unwrap(), "doc1");
+        let second = cursor.next().await.unwrap().unwrap();
+        assert_eq!(second.to_document().unwrap(), bson::doc! { "name": "doc2", "value": 2_i64 });
+        let rest = cursor.collect().await.unwrap();
+        assert_eq!(rest.len(), 1);
+        assert_eq!(rest[0].get_i64("value").unwrap(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_size_hint_and_fused() {
+        use futures::StreamExt;
+
+        let data = vec![
+            serde_json::json!({"name": "doc1", "value": 1}),
+            serde_json::json!({"name": "doc2", "value": 2}),
+        ];
+        let mut cursor: Cursor<TestDoc> = Cursor::new("test.docs".to_string(), data.clone(), None);
+        assert_eq!(cursor.size_hint(), (2, Some(2)));
+        cursor.next().await.unwrap().unwrap();
+        assert_eq!(cursor.size_hint(), (1, Some(1)));
+        cursor.next().await.unwrap().unwrap();
+        assert!(!cursor.is_terminated());
+        assert!(cursor.next().await.is_none());
+        assert!(cursor.is_terminated());
+        assert_eq!(cursor.size_hint(), (0, Some(0)));
+
+        let open: Cursor<TestDoc> =
+            Cursor::new("test.docs".to_string(), data, Some("cursor1".to_string()));
+        assert_eq!(open.size_hint(), (2, None));
+    }
+
+    #[tokio::test]
+    async fn test_cursor_stream_in_session() {
+        use crate::client::ClientOptions;
+        use futures::StreamExt;
+
+        let transport = Transport::lazy("mongodb://localhost".into(), ClientOptions::default());
+        let mut session = ClientSession::new("session1".to_string(), transport);
+        let data = vec![
+            serde_json::json!({"name": "doc1", "value": 1}),
+            serde_json::json!({"name": "doc2", "value": 2}),
+        ];
+        let mut cursor: Cursor<TestDoc> = Cursor::new("test.docs".to_string(), data, None);
+
+        let names: Vec<String> = cursor
+            .stream(&mut session)
+            .map(|doc| doc.unwrap().name)
+            .collect()
+            .await;
+        assert_eq!(names, ["doc1", "doc2"]);
+        assert_eq!(session.id(), "session1");
+    }
+
+    #[tokio::test]
+    async fn test_cursor_adapters() {
+        use futures::StreamExt;
+
+        let data = vec![
+            serde_json::json!({"name": "doc1", "value": 1}),
+            serde_json::json!({"name": "doc2", "value": 2}),
+            serde_json::json!({"name": "doc3", "value": 3}),
+        ];
+        let cursor: Cursor<TestDoc> = Cursor::new("test.docs".to_string(), data.clone(), None);
+        let values: Vec<i32> = cursor.map(|doc| doc.value * 10).map(Result::unwrap).collect().await;
+        assert_eq!(values, [10, 20, 30]);
+
+        let cursor: Cursor<TestDoc> = Cursor::new("test.docs".to_string(), data.clone(), None);
+        let odd = cursor.try_filter(|doc| doc.value % 2 == 1);
+        let names: Vec<String> = odd.map(|doc| doc.unwrap().name).collect().await;
+        assert_eq!(names, ["doc1", "doc3"]);
+
+        let mut cursor: Cursor<TestDoc> = Cursor::new("test.docs".to_string(), data, None);
+        cursor.try_next().await.unwrap();
+        let raw = cursor.with_type::<Document>().collect().await.unwrap();
+        // Documents decode as the wire format does, with JSON integers as Int64.
+        let expected = [
+            bson::doc! { "name": "doc2", "value": 2_i64 },
+            bson::doc! { "name": "doc3", "value": 3_i64 },
+        ];
+        assert_eq!(raw, expected);
+    }
+
     #[tokio::test]
     async fn test_cursor_advance_and_current() {
         let data = vec![
@@ -448,6 +1230,56 @@ mod tests {
         assert_eq!(doc.name, "doc1");
     }
 
+    #[tokio::test]
+    async fn test_cursor_next_borrowed() {
+        #[derive(Debug, PartialEq, Deserialize)]
+        struct Borrowed<'a> {
+            name: &'a str,
+            value: i32,
+        }
+
+        let data = vec![
+            serde_json::json!({"name": "doc1", "value": 1}),
+            serde_json::json!({"name": "doc2", "value": 2}),
+        ];
+        let mut cursor: Cursor<TestDoc> = Cursor::new("test.docs".to_string(), data, None);
+
+        let mut names = Vec::new();
+        while let Some(doc) = cursor.next_borrowed::<Borrowed>().await.unwrap() {
+            names.push(doc.name.to_string());
+        }
+        assert_eq!(names, ["doc1", "doc2"]);
+        assert!(cursor.is_exhausted().await);
+    }
+
+    #[tokio::test]
+    async fn test_cursor_counts_open_cursors() {
+        let transport = Transport::lazy("mongodb://localhost".to_string(), Default::default());
+        let open = || transport.stats.metrics().open_cursors;
+
+        let cursor: Cursor<TestDoc> =
+            Cursor::new("db.coll".to_string(), vec![], Some("c1".to_string()))
+                .with_transport(transport.clone());
+        let finished: Cursor<TestDoc> =
+            Cursor::new("db.coll".to_string(), vec![], None).with_transport(transport.clone());
+        assert_eq!(open(), 1);
+
+        let mut state = cursor.state.lock().await;
+        state.apply_batch(&serde_json::json!({ "documents": [], "cursorId": "c2" }));
+        assert_eq!(open(), 1);
+        state.apply_batch(&serde_json::json!({ "documents": [] }));
+        assert_eq!(open(), 0);
+        drop(state);
+        drop(finished);
+
+        let cursor: Cursor<TestDoc> =
+            Cursor::new("db.coll".to_string(), vec![], Some("c3".to_string()))
+                .with_transport(transport.clone());
+        assert_eq!(open(), 1);
+        drop(cursor);
+        assert_eq!(open(), 0);
+    }
+
     #[tokio::test]
     async fn test_cursor_close() {
         let data = vec![
@@ -490,6 +1322,75 @@ mod tests {
         assert!(matches!(result, Err(MongoError::Deserialization(_))));
     }
 
+    #[tokio::test]
+    async fn test_cursor_open_without_transport() {
+        let data = vec![serde_json::json!({"name": "doc1", "value": 1})];
+        let mut cursor: Cursor<TestDoc> =
+            Cursor::new("test.docs".to_string(), data, Some("cursor1".to_string()));
+
+        assert!(cursor.try_next().await.unwrap().is_some());
+        assert!(cursor.try_next().await.unwrap().is_none());
+        assert!(cursor.is_exhausted().await);
+    }
+
+    /// Compares consumers sharing one cursor state when documents are
+    /// deserialized under the state lock versus after releasing it.
+    ///
+    /// Run with `cargo test --release -- --ignored --nocapture bench_`.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    #[ignore]
+    async fn bench_shared_cursor_deserialization() {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Heavy {
+            tags: Vec<String>,
+        }
+
+        const DOCS: usize = 20_000;
+        const CONSUMERS: usize = 4;
+        let tags: Vec<String> = (0..200).map(|i| format!("tag-{i}")).collect();
+        let doc = serde_json::json!({ "tags": tags });
+        let shared = || {
+            let data = vec![doc.clone(); DOCS];
+            Arc::new(Mutex::new(CursorState::with_data("test.docs".to_string(), data, None)))
+        };
+
+        let state = shared();
+        let started = std::time::Instant::now();
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|_| {
+                let state = state.clone();
+                tokio::spawn(async move {
+                    loop {
+                        let mut guard = state.lock().await;
+                        let Some(doc) = guard.buffer.pop_front() else { break };
+                        let _: Heavy = deserialize(&doc, None).unwrap();
+                    }
+                })
+            })
+            .collect();
+        futures::future::try_join_all(consumers).await.unwrap();
+        let under_lock = started.elapsed();
+
+        let state = shared();
+        let started = std::time::Instant::now();
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|_| {
+                let mut cursor: Cursor<Heavy> = Cursor::empty("test.docs".to_string());
+                cursor.state = state.clone();
+                tokio::spawn(async move { while cursor.try_next().await.unwrap().is_some() {} })
+            })
+            .collect();
+        futures::future::try_join_all(consumers).await.unwrap();
+        let off_lock = started.elapsed();
+
+        println!(
+            "{DOCS} docs, {CONSUMERS} consumers: under lock {under_lock:?}, off lock {off_lock:?} \
+             ({:.1}x)",
+            under_lock.as_secs_f64() / off_lock.as_secs_f64()
+        );
+    }
+
     #[tokio::test]
     async fn test_cursor_state_new() {
         let state = CursorState::new("test.collection".to_string(), 50);
@@ -1,10 +1,22 @@
 //! Database struct for managing collections.
 
+use crate::collation::Collation;
 use crate::collection::Collection;
+use crate::cursor::Cursor;
+#[cfg(feature = "encryption")]
+use crate::encryption::AutoEncrypter;
 use crate::error::{MongoError, Result};
-use bson::Document;
+use crate::index::EnsureIndexesResult;
+use crate::model::Model;
+use crate::pipeline::validate_pipeline;
+use crate::rolling::{RollingCollection, RollingOptions};
+use crate::rpc::Method;
+use crate::transport::Transport;
+use bson::{doc, Document};
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+#[cfg(feature = "encryption")]
 use std::sync::Arc;
 
 /// A handle to a MongoDB database.
@@ -24,13 +36,24 @@ pub struct Database {
     /// Database name.
     pub(crate) name: String,
     /// RPC client.
-    pub(crate) rpc_client: Arc<rpc_do::RpcClient>,
+    pub(crate) rpc_client: Transport,
+    /// Automatic encryption, when configured on the client.
+    #[cfg(feature = "encryption")]
+    pub(crate) auto_encrypter: Option<Arc<AutoEncrypter>>,
+    /// Read and write concerns.
+    pub(crate) options: DatabaseOptions,
 }
 
 impl Database {
     /// Create a new database handle.
-    pub(crate) fn new(name: String, rpc_client: Arc<rpc_do::RpcClient>) -> Self {
-        Self { name, rpc_client }
+    pub(crate) fn new(name: String, rpc_client: Transport) -> Self {
+        Self {
+            name,
+            rpc_client,
+            #[cfg(feature = "encryption")]
+            auto_encrypter: None,
+            options: DatabaseOptions::default(),
+        }
     }
 
     /// Get the database name.
@@ -38,8 +61,22 @@ impl Database {
         &self.name
     }
 
+    /// Get the read concern, if one was set.
+    pub fn read_concern(&self) -> Option<&ReadConcern> {
+        self.options.read_concern.as_ref()
+    }
+
+    /// Get the write concern, if one was set.
+    pub fn write_concern(&self) -> Option<&WriteConcern> {
+        self.options.write_concern.as_ref()
+    }
+
     /// Get a handle to a collection with a specific type.
     ///
+    /// If `name` is not a valid collection name, every operation on the
+    /// returned handle fails with [`MongoError::InvalidArgument`] without
+    /// reaching the server.
+    ///
     /// # Example
     ///
     /// ```ignore
@@ -54,10 +91,61 @@ impl Database {
     /// let users = db.collection::<User>("users");
     /// ```
     pub fn collection<T>(&self, name: &str) -> Collection<T>
+    where
+        T: Send + Sync + Unpin + 'static,
+    {
+        self.collection_handle(name)
+    }
+
+    /// Get a handle to documents stored in one collection per time period,
+    /// named `{prefix}_{period}`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let events = db.rolling_collection::<Event>("events", RollingOptions::default());
+    /// events.insert_one(event).await?;
+    /// ```
+    pub fn rolling_collection<T>(
+        &self,
+        prefix: &str,
+        options: RollingOptions,
+    ) -> RollingCollection<T>
     where
         T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static,
     {
-        Collection::new(self.name.clone(), name.to_string(), self.rpc_client.clone())
+        RollingCollection::new(self.clone(), prefix, options)
+    }
+
+    /// Get a handle to the collection of a [`Model`].
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let users = db.model_collection::<User>();
+    /// ```
+    pub fn model_collection<M>(&self) -> Collection<M>
+    where
+        M: Model + Serialize + DeserializeOwned + Send + Sync + Unpin + 'static,
+    {
+        self.collection(M::COLLECTION)
+    }
+
+    /// Create the indexes a [`Model`] declares that do not exist yet.
+    ///
+    /// Indexes that are not declared are kept. See
+    /// [`Collection::ensure_indexes`].
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let result = db.sync_indexes::<User>().await?;
+    /// println!("Created: {:?}", result.created);
+    /// ```
+    pub async fn sync_indexes<M: Model>(&self) -> Result<EnsureIndexesResult> {
+        self.collection_with_doc(M::COLLECTION)
+            .ensure_indexes(&M::indexes(), false)
+            .await
     }
 
     /// Get a handle to a collection with Document type.
@@ -68,7 +156,26 @@ impl Database {
     /// let users = db.collection_with_doc("users");
     /// ```
     pub fn collection_with_doc(&self, name: &str) -> Collection<Document> {
-        Collection::new(self.name.clone(), name.to_string(), self.rpc_client.clone())
+        self.collection_handle(name)
+    }
+
+    /// Create a collection handle sharing this database's client state.
+    ///
+    /// A handle with an invalid name is still returned, but every operation on
+    /// it fails with an invalid argument error without reaching the server.
+    fn collection_handle<T>(&self, name: &str) -> Collection<T> {
+        let rpc_client = match validate_collection_name(&self.name, name) {
+            Ok(()) => self.rpc_client.clone(),
+            Err(err) => self.rpc_client.rejecting(err.to_string()),
+        };
+        let mut collection = Collection::new(self.name.clone(), name.to_string(), rpc_client);
+        #[cfg(feature = "encryption")]
+        {
+            collection.auto_encrypter = self.auto_encrypter.clone();
+        }
+        collection.read_concern = self.options.read_concern.clone();
+        collection.write_concern = self.options.write_concern.clone();
+        collection
     }
 
     /// List all collection names in this database.
@@ -84,7 +191,7 @@ impl Database {
     pub async fn list_collection_names(&self) -> Result<Vec<String>> {
         let result = self
             .rpc_client
-            .call_raw("mongo.listCollections", vec![serde_json::json!(self.name)])
+            .call_raw(Method::ListCollections, vec![serde_json::json!(self.name)])
             .await?;
 
         if let Some(arr) = result.as_array() {
@@ -97,6 +204,26 @@ impl Database {
         }
     }
 
+    /// List the collections and views in this database with their options,
+    /// optionally only those matching `filter`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut capped = db.list_collections(doc! { "options.capped": true }).await?;
+    /// while let Some(spec) = capped.try_next().await? {
+    ///     println!("{} holds at most {:?} bytes", spec.name, spec.options.size);
+    /// }
+    /// ```
+    pub async fn list_collections(
+        &self,
+        filter: impl Into<Option<Document>>,
+    ) -> Result<Cursor<CollectionSpecification>> {
+        let filter = filter.into().unwrap_or_default();
+        self.cursor_command(doc! { "listCollections": 1, "filter": filter, "cursor": {} })
+            .await
+    }
+
     /// Create a new collection.
     ///
     /// # Example
@@ -105,9 +232,10 @@ impl Database {
     /// db.create_collection("new_collection").await?;
     /// ```
     pub async fn create_collection(&self, name: &str) -> Result<()> {
+        validate_collection_name(&self.name, name)?;
         self.rpc_client
             .call_raw(
-                "mongo.createCollection",
+                Method::CreateCollection,
                 vec![serde_json::json!(self.name), serde_json::json!(name)],
             )
             .await?;
@@ -120,6 +248,10 @@ impl Database {
         name: &str,
         options: CreateCollectionOptions,
     ) -> Result<()> {
+        validate_collection_name(&self.name, name)?;
+        if options.pipeline.is_some() && options.view_on.is_none() {
+            return Err(MongoError::invalid_argument("a pipeline requires view_on"));
+        }
         let mut opts = serde_json::Map::new();
         if let Some(capped) = options.capped {
             opts.insert("capped".to_string(), serde_json::json!(capped));
@@ -133,13 +265,29 @@ impl Database {
         if let Some(ref validator) = options.validator {
             opts.insert(
                 "validator".to_string(),
-                bson_doc_to_json(validator)?,
+                self.rpc_client.encode(validator)?,
+            );
+        }
+        if let Some(ref view_on) = options.view_on {
+            validate_collection_name(&self.name, view_on)?;
+            opts.insert("viewOn".to_string(), serde_json::json!(view_on));
+            let pipeline = options.pipeline.as_deref().unwrap_or_default();
+            let stages = pipeline
+                .iter()
+                .map(|stage| self.rpc_client.encode(stage))
+                .collect::<Result<Vec<_>>>()?;
+            opts.insert("pipeline".to_string(), serde_json::Value::Array(stages));
+        }
+        if let Some(ref collation) = options.collation {
+            opts.insert(
+                "collation".to_string(),
+                self.rpc_client.encode(&collation.to_document())?,
             );
         }
 
         self.rpc_client
             .call_raw(
-                "mongo.createCollection",
+                Method::CreateCollection,
                 vec![
                     serde_json::json!(self.name),
                     serde_json::json!(name),
@@ -150,6 +298,33 @@ impl Database {
         Ok(())
     }
 
+    /// Create a read-only view named `name` that runs `pipeline` over the
+    /// collection or view `view_on`.
+    ///
+    /// The view is read through an ordinary collection handle; writes to it
+    /// are rejected by the server.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// db.create_view("paid_orders", "orders", vec![doc! { "$match": { "status": "paid" } }])
+    ///     .await?;
+    /// let paid = db.collection::<Order>("paid_orders").find(doc! {}).await?;
+    /// ```
+    pub async fn create_view(
+        &self,
+        name: &str,
+        view_on: &str,
+        pipeline: Vec<Document>,
+    ) -> Result<()> {
+        validate_pipeline(&pipeline)?;
+        let options = CreateCollectionOptions::builder()
+            .view_on(view_on)
+            .pipeline(pipeline)
+            .build();
+        self.create_collection_with_options(name, options).await
+    }
+
     /// Drop the database.
     ///
     /// # Warning
@@ -163,7 +338,7 @@ impl Database {
     /// ```
     pub async fn drop(&self) -> Result<()> {
         self.rpc_client
-            .call_raw("mongo.dropDatabase", vec![serde_json::json!(self.name)])
+            .call_raw(Method::DropDatabase, vec![serde_json::json!(self.name)])
             .await?;
         Ok(())
     }
@@ -176,51 +351,138 @@ impl Database {
     /// let result = db.run_command(doc! { "ping": 1 }).await?;
     /// ```
     pub async fn run_command(&self, command: Document) -> Result<Document> {
-        let command_json = bson_doc_to_json(&command)?;
+        let command_json = self.rpc_client.encode(&command)?;
 
         let result = self
             .rpc_client
             .call_raw(
-                "mongo.runCommand",
+                Method::RunCommand,
                 vec![serde_json::json!(self.name), command_json],
             )
             .await?;
 
-        json_to_bson_doc(&result)
+        self.rpc_client.decode_document(&result)
+    }
+
+    /// Run a command that returns a cursor, such as `listCollections`,
+    /// `listIndexes` or `aggregate`, and iterate its results.
+    ///
+    /// Further batches are fetched with `getMore` as the cursor is iterated.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let mut indexes = db
+    ///     .run_cursor_command(doc! { "listIndexes": "users", "cursor": { "batchSize": 10 } })
+    ///     .await?;
+    /// while let Some(index) = indexes.try_next().await? {
+    ///     println!("{}", index.get_str("name")?);
+    /// }
+    /// ```
+    pub async fn run_cursor_command(&self, command: Document) -> Result<Cursor<Document>> {
+        self.cursor_command(command).await
+    }
+
+    /// Run a command that returns a cursor over `U`.
+    async fn cursor_command<U>(&self, command: Document) -> Result<Cursor<U>> {
+        let command_json = self.rpc_client.encode(&command)?;
+
+        let result = self
+            .rpc_client
+            .call_raw(
+                Method::RunCommand,
+                vec![serde_json::json!(self.name), command_json],
+            )
+            .await?;
+
+        let (documents, cursor_id, namespace) = cursor_response(&result)?;
+        let namespace = namespace.unwrap_or_else(|| format!("{}.$cmd", self.name));
+        Ok(Cursor::new(namespace, documents, cursor_id).with_transport(self.rpc_client.clone()))
     }
 
     /// Run an aggregation pipeline on the database.
     ///
-    /// This is useful for $currentOp, $listLocalSessions, etc.
-    pub async fn aggregate(&self, pipeline: impl IntoIterator<Item = Document>) -> Result<Vec<Document>> {
+    /// This is useful for $currentOp, $listLocalSessions, etc. Results are
+    /// fetched in batches as the cursor is iterated.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// use futures::TryStreamExt;
+    ///
+    /// let admin = client.database("admin");
+    /// let mut ops = admin.aggregate([doc! { "$currentOp": {} }]).await?;
+    /// while let Some(op) = ops.try_next().await? {
+    ///     println!("{:?}", op);
+    /// }
+    /// ```
+    pub async fn aggregate(&self, pipeline: impl IntoIterator<Item = Document>) -> Result<Cursor<Document>> {
         let pipeline_json: Vec<serde_json::Value> = pipeline
             .into_iter()
-            .map(|d| bson_doc_to_json(&d))
+            .map(|d| self.rpc_client.encode(&d))
             .collect::<Result<_>>()?;
 
         let result = self
             .rpc_client
             .call_raw(
-                "mongo.aggregateDb",
+                Method::AggregateDb,
                 vec![serde_json::json!(self.name), serde_json::json!(pipeline_json)],
             )
             .await?;
 
-        if let Some(arr) = result.as_array() {
-            arr.iter().map(json_to_bson_doc).collect()
-        } else {
-            Ok(vec![])
-        }
+        let (documents, cursor_id) = match result {
+            serde_json::Value::Array(documents) => (documents, None),
+            result => (
+                result
+                    .get("documents")
+                    .and_then(|v| v.as_array())
+                    .cloned()
+                    .unwrap_or_default(),
+                result
+                    .get("cursorId")
+                    .and_then(|v| v.as_str())
+                    .map(|s| s.to_string()),
+            ),
+        };
+
+        Ok(Cursor::new(format!("{}.$cmd.aggregate", self.name), documents, cursor_id)
+            .with_transport(self.rpc_client.clone()))
+    }
+
+    /// Modify a collection's options with `collMod`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = CollModOptions::builder()
+    ///     .expire_after_seconds("createdAt_1", 3600)
+    ///     .build();
+    /// db.modify_collection("sessions", options).await?;
+    /// ```
+    pub async fn modify_collection(&self, name: &str, options: CollModOptions) -> Result<()> {
+        self.run_command(options.to_command(name)).await?;
+        Ok(())
     }
 
     /// Get database statistics.
-    pub async fn stats(&self) -> Result<Document> {
-        self.run_command(bson::doc! { "dbStats": 1 }).await
+    pub async fn stats(&self) -> Result<DbStats> {
+        let result = self.run_command(bson::doc! { "dbStats": 1 }).await?;
+        Ok(bson::from_document(result)?)
     }
 
     /// Get server status.
-    pub async fn server_status(&self) -> Result<Document> {
-        self.run_command(bson::doc! { "serverStatus": 1 }).await
+    pub async fn server_status(&self) -> Result<ServerStatus> {
+        let result = self.run_command(bson::doc! { "serverStatus": 1 }).await?;
+        Ok(bson::from_document(result)?)
+    }
+}
+
+impl fmt::Debug for Database {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Database")
+            .field("name", &self.name)
+            .field("options", &self.options)
+            .finish()
     }
 }
 
@@ -229,21 +491,40 @@ impl Clone for Database {
         Self {
             name: self.name.clone(),
             rpc_client: self.rpc_client.clone(),
+            #[cfg(feature = "encryption")]
+            auto_encrypter: self.auto_encrypter.clone(),
+            options: self.options.clone(),
         }
     }
 }
 
 /// Options for creating a collection.
-#[derive(Debug, Clone, Default)]
+///
+/// Also the options of an existing collection in a [`CollectionSpecification`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CreateCollectionOptions {
     /// Whether the collection is capped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub capped: Option<bool>,
     /// Maximum size in bytes for a capped collection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub size: Option<u64>,
     /// Maximum number of documents in a capped collection.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub max: Option<u64>,
     /// Document validation rules.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub validator: Option<Document>,
+    /// Source collection or view, making this a read-only view.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub view_on: Option<String>,
+    /// Aggregation pipeline a view applies to its source.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pipeline: Option<Vec<Document>>,
+    /// Default collation of the collection or view.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collation: Option<Collation>,
 }
 
 impl CreateCollectionOptions {
@@ -284,96 +565,821 @@ impl CreateCollectionOptionsBuilder {
         self
     }
 
+    /// Create a view on this source collection or view.
+    pub fn view_on(mut self, view_on: impl Into<String>) -> Self {
+        self.options.view_on = Some(view_on.into());
+        self
+    }
+
+    /// Set the pipeline of a view.
+    pub fn pipeline(mut self, pipeline: Vec<Document>) -> Self {
+        self.options.pipeline = Some(pipeline);
+        self
+    }
+
+    /// Set the default collation.
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.options.collation = Some(collation);
+        self
+    }
+
     /// Build the options.
     pub fn build(self) -> CreateCollectionOptions {
         self.options
     }
 }
 
-/// Convert a BSON document to JSON.
-fn bson_doc_to_json(doc: &Document) -> Result<serde_json::Value> {
-    let bson_value = bson::Bson::Document(doc.clone());
-    bson_to_json(&bson_value)
+/// What kind of namespace a [`CollectionSpecification`] describes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CollectionType {
+    /// A regular collection.
+    #[default]
+    Collection,
+    /// A read-only view defined by a pipeline.
+    View,
+    /// A time series collection.
+    Timeseries,
+    /// A kind this SDK does not know.
+    #[serde(other)]
+    Unknown,
 }
 
-/// Convert a BSON value to JSON.
-fn bson_to_json(bson: &bson::Bson) -> Result<serde_json::Value> {
-    match bson {
-        bson::Bson::Double(v) => Ok(serde_json::json!(*v)),
-        bson::Bson::String(v) => Ok(serde_json::json!(v)),
-        bson::Bson::Array(arr) => {
-            let json_arr: Vec<serde_json::Value> = arr
-                .iter()
-                .map(bson_to_json)
-                .collect::<Result<_>>()?;
-            Ok(serde_json::json!(json_arr))
+/// A collection or view, as returned by [`Database::list_collections`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionSpecification {
+    /// Collection name.
+    pub name: String,
+    /// Whether this is a collection or a view.
+    #[serde(rename = "type", default)]
+    pub collection_type: CollectionType,
+    /// Options the collection was created with.
+    #[serde(default)]
+    pub options: CreateCollectionOptions,
+    /// Read-only information about the collection.
+    #[serde(default)]
+    pub info: CollectionSpecificationInfo,
+    /// The `_id` index, for collections.
+    #[serde(default)]
+    pub id_index: Option<Document>,
+}
+
+/// The `info` of a [`CollectionSpecification`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollectionSpecificationInfo {
+    /// Whether the collection is read-only, as views are.
+    #[serde(default)]
+    pub read_only: bool,
+    /// The collection's UUID.
+    #[serde(default)]
+    pub uuid: Option<bson::Binary>,
+}
+
+/// How much data a read must have replicated before it is returned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReadConcernLevel {
+    /// The instance's most recent data.
+    Local,
+    /// The instance's most recent data, without waiting on sharding metadata.
+    Available,
+    /// Data acknowledged by a majority of replica set members.
+    Majority,
+    /// Data reflecting all majority-acknowledged writes before the read.
+    Linearizable,
+    /// Data from a majority-committed snapshot.
+    Snapshot,
+}
+
+impl ReadConcernLevel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ReadConcernLevel::Local => "local",
+            ReadConcernLevel::Available => "available",
+            ReadConcernLevel::Majority => "majority",
+            ReadConcernLevel::Linearizable => "linearizable",
+            ReadConcernLevel::Snapshot => "snapshot",
         }
-        bson::Bson::Document(doc) => {
-            let mut map = serde_json::Map::new();
-            for (k, v) in doc {
-                map.insert(k.clone(), bson_to_json(v)?);
-            }
-            Ok(serde_json::Value::Object(map))
+    }
+}
+
+/// A read concern.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReadConcern {
+    /// The read concern level.
+    pub level: ReadConcernLevel,
+}
+
+impl ReadConcern {
+    /// Create a read concern with the given level.
+    pub fn new(level: ReadConcernLevel) -> Self {
+        Self { level }
+    }
+
+    /// The `local` read concern.
+    pub fn local() -> Self {
+        Self::new(ReadConcernLevel::Local)
+    }
+
+    /// The `majority` read concern.
+    pub fn majority() -> Self {
+        Self::new(ReadConcernLevel::Majority)
+    }
+
+    /// Convert to a `readConcern` document.
+    pub fn to_document(&self) -> Document {
+        bson::doc! { "level": self.level.as_str() }
+    }
+}
+
+/// How many members must acknowledge a write.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Acknowledgment {
+    /// A number of members.
+    Nodes(u32),
+    /// A majority of members.
+    Majority,
+    /// A custom tag set.
+    Custom(String),
+}
+
+/// A write concern.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WriteConcern {
+    /// Required acknowledgment.
+    pub w: Option<Acknowledgment>,
+    /// Whether the write must reach the on-disk journal.
+    pub journal: Option<bool>,
+    /// How long to wait for acknowledgment, in milliseconds.
+    pub w_timeout_ms: Option<u64>,
+}
+
+impl WriteConcern {
+    /// Create a new builder.
+    pub fn builder() -> WriteConcernBuilder {
+        WriteConcernBuilder::default()
+    }
+
+    /// The `majority` write concern.
+    pub fn majority() -> Self {
+        Self {
+            w: Some(Acknowledgment::Majority),
+            ..Default::default()
         }
-        bson::Bson::Boolean(v) => Ok(serde_json::json!(*v)),
-        bson::Bson::Null => Ok(serde_json::Value::Null),
-        bson::Bson::Int32(v) => Ok(serde_json::json!(*v)),
-        bson::Bson::Int64(v) => Ok(serde_json::json!(*v)),
-        bson::Bson::ObjectId(oid) => Ok(serde_json::json!({ "$oid": oid.to_hex() })),
-        bson::Bson::DateTime(dt) => Ok(serde_json::json!({ "$date": dt.timestamp_millis() })),
-        _ => Ok(serde_json::json!(bson.to_string())),
-    }
-}
-
-/// Convert JSON to BSON.
-fn json_to_bson(json: &serde_json::Value) -> bson::Bson {
-    match json {
-        serde_json::Value::Null => bson::Bson::Null,
-        serde_json::Value::Bool(v) => bson::Bson::Boolean(*v),
-        serde_json::Value::Number(n) => {
-            if let Some(i) = n.as_i64() {
-                bson::Bson::Int64(i)
-            } else if let Some(f) = n.as_f64() {
-                bson::Bson::Double(f)
-            } else {
-                bson::Bson::Null
+    }
+
+    /// Convert to a `writeConcern` document.
+    pub fn to_document(&self) -> Document {
+        let mut doc = Document::new();
+        match self.w {
+            Some(Acknowledgment::Nodes(n)) => {
+                doc.insert("w", n as i32);
             }
-        }
-        serde_json::Value::String(s) => bson::Bson::String(s.clone()),
-        serde_json::Value::Array(arr) => {
-            bson::Bson::Array(arr.iter().map(json_to_bson).collect())
-        }
-        serde_json::Value::Object(obj) => {
-            // Check for extended JSON types
-            if let Some(oid) = obj.get("$oid").and_then(|v| v.as_str()) {
-                if let Ok(oid) = bson::oid::ObjectId::parse_str(oid) {
-                    return bson::Bson::ObjectId(oid);
-                }
+            Some(Acknowledgment::Majority) => {
+                doc.insert("w", "majority");
             }
-            if let Some(date) = obj.get("$date").and_then(|v| v.as_i64()) {
-                return bson::Bson::DateTime(bson::DateTime::from_millis(date));
+            Some(Acknowledgment::Custom(ref tag)) => {
+                doc.insert("w", tag.as_str());
             }
+            None => {}
+        }
+        if let Some(journal) = self.journal {
+            doc.insert("j", journal);
+        }
+        if let Some(w_timeout_ms) = self.w_timeout_ms {
+            doc.insert("wtimeout", w_timeout_ms as i64);
+        }
+        doc
+    }
+}
 
-            let mut doc = Document::new();
-            for (k, v) in obj {
-                doc.insert(k.clone(), json_to_bson(v));
-            }
-            bson::Bson::Document(doc)
+/// Builder for WriteConcern.
+#[derive(Debug, Default)]
+pub struct WriteConcernBuilder {
+    options: WriteConcern,
+}
+
+impl WriteConcernBuilder {
+    /// Set the required acknowledgment.
+    pub fn w(mut self, w: Acknowledgment) -> Self {
+        self.options.w = Some(w);
+        self
+    }
+
+    /// Set whether the write must reach the journal.
+    pub fn journal(mut self, journal: bool) -> Self {
+        self.options.journal = Some(journal);
+        self
+    }
+
+    /// Set the acknowledgment timeout in milliseconds.
+    pub fn w_timeout_ms(mut self, timeout: u64) -> Self {
+        self.options.w_timeout_ms = Some(timeout);
+        self
+    }
+
+    /// Build the write concern.
+    pub fn build(self) -> WriteConcern {
+        self.options
+    }
+}
+
+/// Options for a database handle.
+#[derive(Debug, Clone, Default)]
+pub struct DatabaseOptions {
+    /// Read concern for operations through the handle.
+    pub read_concern: Option<ReadConcern>,
+    /// Write concern for operations through the handle.
+    pub write_concern: Option<WriteConcern>,
+}
+
+impl DatabaseOptions {
+    /// Create a new builder.
+    pub fn builder() -> DatabaseOptionsBuilder {
+        DatabaseOptionsBuilder::default()
+    }
+}
+
+/// Builder for DatabaseOptions.
+#[derive(Debug, Default)]
+pub struct DatabaseOptionsBuilder {
+    options: DatabaseOptions,
+}
+
+impl DatabaseOptionsBuilder {
+    /// Set the read concern.
+    pub fn read_concern(mut self, read_concern: ReadConcern) -> Self {
+        self.options.read_concern = Some(read_concern);
+        self
+    }
+
+    /// Set the write concern.
+    pub fn write_concern(mut self, write_concern: WriteConcern) -> Self {
+        self.options.write_concern = Some(write_concern);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> DatabaseOptions {
+        self.options
+    }
+}
+
+/// Check that a name is usable as a database name.
+pub(crate) fn validate_database_name(name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(MongoError::invalid_argument("database name cannot be empty"));
+    }
+    if name.len() >= 64 {
+        return Err(MongoError::invalid_argument(format!(
+            "database name '{}' must be shorter than 64 bytes",
+            name
+        )));
+    }
+    if let Some(c) = name.chars().find(|c| matches!(c, '/' | '\\' | '.' | ' ' | '"' | '$' | '\0')) {
+        return Err(MongoError::invalid_argument(format!(
+            "database name '{}' contains invalid character {:?}",
+            name, c
+        )));
+    }
+    Ok(())
+}
+
+/// Check that a name is usable as a collection name in database `db`.
+///
+/// Names must be non-empty, must not contain `$` or null bytes, must not use
+/// the reserved `system.` prefix, and the full `db.collection` namespace must
+/// be at most 255 bytes.
+pub(crate) fn validate_collection_name(db: &str, name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(MongoError::invalid_argument("collection name cannot be empty"));
+    }
+    if let Some(c) = name.chars().find(|c| matches!(c, '$' | '\0')) {
+        return Err(MongoError::invalid_argument(format!(
+            "collection name '{}' contains invalid character {:?}",
+            name, c
+        )));
+    }
+    if name.starts_with("system.") {
+        return Err(MongoError::invalid_argument(format!(
+            "collection name '{}' uses the reserved 'system.' prefix",
+            name
+        )));
+    }
+    if db.len() + 1 + name.len() > 255 {
+        return Err(MongoError::invalid_argument(format!(
+            "namespace '{}.{}' must be at most 255 bytes",
+            db, name
+        )));
+    }
+    Ok(())
+}
+
+/// Database statistics, as returned by `dbStats`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DbStats {
+    /// Database name.
+    #[serde(default)]
+    pub db: String,
+    /// Number of collections.
+    #[serde(default)]
+    pub collections: i64,
+    /// Number of views.
+    #[serde(default)]
+    pub views: i64,
+    /// Number of documents.
+    #[serde(default)]
+    pub objects: i64,
+    /// Average document size in bytes.
+    #[serde(default)]
+    pub avg_obj_size: f64,
+    /// Uncompressed size of the data in bytes.
+    #[serde(default)]
+    pub data_size: f64,
+    /// Storage allocated for the data in bytes.
+    #[serde(default)]
+    pub storage_size: f64,
+    /// Number of indexes.
+    #[serde(default)]
+    pub indexes: i64,
+    /// Storage allocated for indexes in bytes.
+    #[serde(default)]
+    pub index_size: f64,
+    /// Storage allocated for data and indexes in bytes.
+    #[serde(default)]
+    pub total_size: f64,
+    /// Any other fields.
+    #[serde(flatten)]
+    pub extra: Document,
+}
+
+/// Connection counts reported by `serverStatus`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConnectionStats {
+    /// Open connections.
+    #[serde(default)]
+    pub current: i64,
+    /// Connections still available.
+    #[serde(default)]
+    pub available: i64,
+    /// Connections created since startup.
+    #[serde(default)]
+    pub total_created: i64,
+}
+
+/// Operation counts since startup, reported by `serverStatus`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OpCounters {
+    /// Inserts.
+    #[serde(default)]
+    pub insert: i64,
+    /// Queries.
+    #[serde(default)]
+    pub query: i64,
+    /// Updates.
+    #[serde(default)]
+    pub update: i64,
+    /// Deletes.
+    #[serde(default)]
+    pub delete: i64,
+    /// getMore operations.
+    #[serde(default)]
+    pub getmore: i64,
+    /// Other commands.
+    #[serde(default)]
+    pub command: i64,
+}
+
+/// Server status, as returned by `serverStatus`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerStatus {
+    /// Host name and port.
+    #[serde(default)]
+    pub host: String,
+    /// Server version.
+    #[serde(default)]
+    pub version: String,
+    /// Server process name.
+    #[serde(default)]
+    pub process: String,
+    /// Process ID.
+    #[serde(default)]
+    pub pid: i64,
+    /// Uptime in seconds.
+    #[serde(default)]
+    pub uptime: f64,
+    /// Uptime in milliseconds.
+    #[serde(default)]
+    pub uptime_millis: i64,
+    /// Server time.
+    #[serde(default)]
+    pub local_time: Option<bson::DateTime>,
+    /// Connection counts.
+    #[serde(default)]
+    pub connections: Option<ConnectionStats>,
+    /// Operation counts.
+    #[serde(default)]
+    pub opcounters: Option<OpCounters>,
+    /// Any other fields.
+    #[serde(flatten)]
+    pub extra: Document,
+}
+
+/// How strictly document validation applies to existing documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationLevel {
+    /// No validation.
+    Off,
+    /// Validate all inserts and updates.
+    Strict,
+    /// Validate inserts and updates of documents that are already valid.
+    Moderate,
+}
+
+impl ValidationLevel {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ValidationLevel::Off => "off",
+            ValidationLevel::Strict => "strict",
+            ValidationLevel::Moderate => "moderate",
+        }
+    }
+}
+
+/// What happens to writes that fail document validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationAction {
+    /// Reject the write.
+    Error,
+    /// Allow the write and log a warning.
+    Warn,
+}
+
+impl ValidationAction {
+    fn as_str(&self) -> &'static str {
+        match self {
+            ValidationAction::Error => "error",
+            ValidationAction::Warn => "warn",
+        }
+    }
+}
+
+/// Options for modifying a collection with `collMod`.
+#[derive(Debug, Clone, Default)]
+pub struct CollModOptions {
+    /// New document validation rules.
+    pub validator: Option<Document>,
+    /// New validation level.
+    pub validation_level: Option<ValidationLevel>,
+    /// New validation action.
+    pub validation_action: Option<ValidationAction>,
+    /// Index modification: the index `name` plus the options to change.
+    pub index: Option<Document>,
+}
+
+impl CollModOptions {
+    /// Create a new builder.
+    pub fn builder() -> CollModOptionsBuilder {
+        CollModOptionsBuilder::default()
+    }
+
+    /// Build the `collMod` command for a collection.
+    pub(crate) fn to_command(&self, collection: &str) -> Document {
+        let mut command = bson::doc! { "collMod": collection };
+        if let Some(ref validator) = self.validator {
+            command.insert("validator", validator.clone());
+        }
+        if let Some(level) = self.validation_level {
+            command.insert("validationLevel", level.as_str());
+        }
+        if let Some(action) = self.validation_action {
+            command.insert("validationAction", action.as_str());
+        }
+        if let Some(ref index) = self.index {
+            command.insert("index", index.clone());
         }
+        command
+    }
+}
+
+/// Builder for CollModOptions.
+#[derive(Debug, Clone, Default)]
+pub struct CollModOptionsBuilder {
+    options: CollModOptions,
+}
+
+impl CollModOptionsBuilder {
+    /// Set document validation rules.
+    pub fn validator(mut self, validator: Document) -> Self {
+        self.options.validator = Some(validator);
+        self
+    }
+
+    /// Set the validation level.
+    pub fn validation_level(mut self, level: ValidationLevel) -> Self {
+        self.options.validation_level = Some(level);
+        self
+    }
+
+    /// Set the validation action.
+    pub fn validation_action(mut self, action: ValidationAction) -> Self {
+        self.options.validation_action = Some(action);
+        self
+    }
+
+    /// Change the TTL of the named index.
+    pub fn expire_after_seconds(self, index_name: &str, seconds: i64) -> Self {
+        self.modify_index(index_name, "expireAfterSeconds", seconds.into())
+    }
+
+    /// Hide or unhide the named index from the query planner.
+    pub fn hidden(self, index_name: &str, hidden: bool) -> Self {
+        self.modify_index(index_name, "hidden", hidden.into())
+    }
+
+    /// Set an option of the index being modified; only one index can be
+    /// modified per command.
+    fn modify_index(mut self, index_name: &str, key: &str, value: bson::Bson) -> Self {
+        let index = self
+            .options
+            .index
+            .get_or_insert_with(|| bson::doc! { "name": index_name });
+        index.insert("name", index_name);
+        index.insert(key, value);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> CollModOptions {
+        self.options
     }
 }
 
-/// Convert JSON to BSON document.
-fn json_to_bson_doc(json: &serde_json::Value) -> Result<Document> {
-    match json_to_bson(json) {
-        bson::Bson::Document(doc) => Ok(doc),
-        _ => Err(MongoError::Deserialization("Expected document".to_string())),
+/// Split a command's cursor response into the first batch, the cursor ID
+/// (`None` once exhausted) and the namespace of later batches.
+fn cursor_response(
+    result: &serde_json::Value,
+) -> Result<(Vec<serde_json::Value>, Option<String>, Option<String>)> {
+    let Some(cursor) = result.get("cursor") else {
+        return Err(MongoError::protocol("expected a `cursor` document", result));
+    };
+    let documents = cursor
+        .get("firstBatch")
+        .and_then(|v| v.as_array())
+        .cloned()
+        .ok_or_else(|| MongoError::protocol("expected a `cursor.firstBatch` array", result))?;
+    // Cursor IDs are 64-bit, so they may arrive as extended JSON.
+    let cursor_id = match cursor.get("id") {
+        Some(serde_json::Value::Number(id)) => Some(id.to_string()),
+        Some(serde_json::Value::String(id)) => Some(id.clone()),
+        Some(id) => id.get("$numberLong").and_then(|v| v.as_str()).map(str::to_string),
+        None => None,
     }
+    .filter(|id| id != "0");
+    let namespace = cursor.get("ns").and_then(|v| v.as_str()).map(str::to_string);
+    Ok((documents, cursor_id, namespace))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::convert::{bson_doc_to_json, bson_to_json, json_to_bson, json_to_bson_doc};
+
+    #[test]
+    fn test_write_concern_document() {
+        let concern = WriteConcern::builder()
+            .w(Acknowledgment::Nodes(2))
+            .journal(true)
+            .w_timeout_ms(500)
+            .build();
+        assert_eq!(concern.to_document(), bson::doc! { "w": 2, "j": true, "wtimeout": 500_i64 });
+        assert_eq!(WriteConcern::majority().to_document(), bson::doc! { "w": "majority" });
+        assert_eq!(ReadConcern::majority().to_document(), bson::doc! { "level": "majority" });
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Event {
+        #[serde(rename = "_id")]
+        id: i64,
+    }
+
+    impl Model for Event {
+        type Id = i64;
+        const COLLECTION: &'static str = "events";
+
+        fn id(&self) -> &i64 {
+            &self.id
+        }
+    }
+
+    #[test]
+    fn test_cursor_response() {
+        let result = serde_json::json!({
+            "cursor": {
+                "id": { "$numberLong": "8675309" },
+                "ns": "app.$cmd.listCollections",
+                "firstBatch": [{ "name": "users" }],
+            },
+            "ok": 1,
+        });
+        let (documents, cursor_id, namespace) = cursor_response(&result).unwrap();
+        assert_eq!(documents, vec![serde_json::json!({ "name": "users" })]);
+        assert_eq!(cursor_id.as_deref(), Some("8675309"));
+        assert_eq!(namespace.as_deref(), Some("app.$cmd.listCollections"));
+
+        let exhausted = serde_json::json!({ "cursor": { "id": 0, "firstBatch": [] }, "ok": 1 });
+        let (documents, cursor_id, namespace) = cursor_response(&exhausted).unwrap();
+        assert!(documents.is_empty());
+        assert_eq!(cursor_id, None);
+        assert_eq!(namespace, None);
+
+        let err = cursor_response(&serde_json::json!({ "ok": 1 })).unwrap_err();
+        assert!(matches!(err, MongoError::Protocol { .. }));
+    }
+
+    #[test]
+    fn test_collection_specification_deserialization() {
+        let json = serde_json::json!({
+            "name": "events",
+            "type": "collection",
+            "options": {
+                "capped": true,
+                "size": 1048576,
+                "validator": { "level": { "$exists": true } },
+            },
+            "info": {
+                "readOnly": false,
+                "uuid": { "$binary": { "base64": "AAAAAAAAQACAAAAAAAAAAA==", "subType": "04" } },
+            },
+            "idIndex": { "v": 2, "key": { "_id": 1 }, "name": "_id_" },
+        });
+        let spec: CollectionSpecification = serde_json::from_value(json).unwrap();
+        assert_eq!(spec.name, "events");
+        assert_eq!(spec.collection_type, CollectionType::Collection);
+        assert_eq!(spec.options.capped, Some(true));
+        assert_eq!(spec.options.size, Some(1_048_576));
+        assert!(spec.options.validator.is_some());
+        assert_eq!(spec.info.uuid.unwrap().subtype, bson::spec::BinarySubtype::Uuid);
+        assert_eq!(spec.id_index.unwrap().get_str("name").unwrap(), "_id_");
+
+        let view = serde_json::json!({
+            "name": "recent",
+            "type": "view",
+            "options": { "viewOn": "events", "pipeline": [] },
+            "info": { "readOnly": true },
+        });
+        let view: CollectionSpecification = serde_json::from_value(view).unwrap();
+        assert_eq!(view.collection_type, CollectionType::View);
+        assert!(view.info.read_only);
+        assert_eq!(view.options.view_on.as_deref(), Some("events"));
+        assert_eq!(view.options.pipeline, Some(vec![]));
+
+        let other: CollectionSpecification =
+            serde_json::from_value(serde_json::json!({ "name": "x", "type": "future" })).unwrap();
+        assert_eq!(other.collection_type, CollectionType::Unknown);
+    }
+
+    #[test]
+    fn test_model_collection() {
+        let transport =
+            Transport::lazy("mongodb://localhost".to_string(), crate::ClientOptions::default());
+        let db = Database::new("app".to_string(), transport);
+        let events = db.model_collection::<Event>();
+        assert_eq!(events.namespace(), "app.events");
+        assert_eq!(*Event { id: 7 }.id(), 7);
+        assert!(Event::indexes().is_empty());
+    }
+
+    #[test]
+    fn test_validate_database_name() {
+        assert!(validate_database_name("my_db-1").is_ok());
+        assert!(validate_database_name("").is_err());
+        assert!(validate_database_name("my.db").is_err());
+        assert!(validate_database_name("my db").is_err());
+        assert!(validate_database_name("a$b").is_err());
+        assert!(validate_database_name(&"x".repeat(64)).is_err());
+        assert!(matches!(
+            validate_database_name("a/b"),
+            Err(MongoError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_collection_name() {
+        assert!(validate_collection_name("app", "users").is_ok());
+        assert!(validate_collection_name("app", "users.archive").is_ok());
+        assert!(validate_collection_name("app", "").is_err());
+        assert!(validate_collection_name("app", "a$b").is_err());
+        assert!(validate_collection_name("app", "a\0b").is_err());
+        assert!(validate_collection_name("app", "system.users").is_err());
+        assert!(validate_collection_name("app", &"x".repeat(251)).is_ok());
+        assert!(validate_collection_name("app", &"x".repeat(252)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_create_view() {
+        let server = crate::mock::MockServer::new(|_, _| Ok(serde_json::json!({ "ok": 1 })));
+        let db = Database::new("shop".to_string(), server.transport());
+        let paid = vec![doc! { "$match": { "status": "paid" } }];
+
+        db.create_view("paid_orders", "orders", paid.clone()).await.unwrap();
+        let options = CreateCollectionOptions::builder()
+            .view_on("orders")
+            .pipeline(paid)
+            .collation(Collation::new("fr"))
+            .build();
+        db.create_collection_with_options("paid_orders_fr", options).await.unwrap();
+
+        let calls = server.calls_of(Method::CreateCollection);
+        assert_eq!(calls[0][..2], [serde_json::json!("shop"), serde_json::json!("paid_orders")]);
+        assert_eq!(
+            calls[0][2],
+            serde_json::json!({
+                "viewOn": "orders",
+                "pipeline": [{ "$match": { "status": "paid" } }],
+            })
+        );
+        assert_eq!(calls[1][2]["viewOn"], "orders");
+        assert_eq!(calls[1][2]["collation"], serde_json::json!({ "locale": "fr" }));
+
+        // Views are checked before anything is sent.
+        assert!(db.create_view("v", "orders", vec![doc! { "$nope": 1 }]).await.is_err());
+        assert!(db.create_view("v", "a$b", vec![]).await.is_err());
+        assert_eq!(server.calls().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_collection_handle_rejects_operations() {
+        let db = crate::MongoClient::new_lazy("mongodb://localhost").database("app");
+        let err = db.collection_with_doc("a$b").count_documents(None).await.unwrap_err();
+        assert!(matches!(err, MongoError::InvalidArgument(_)));
+        assert!(db.create_collection("system.users").await.is_err());
+    }
+
+    #[test]
+    fn test_db_stats_deserialization() {
+        let json = serde_json::json!({
+            "db": "mydb",
+            "collections": 3,
+            "objects": 120,
+            "avgObjSize": 81.5,
+            "dataSize": 9780,
+            "fsUsedSize": 1000,
+            "ok": 1,
+        });
+        let stats: DbStats = bson::from_document(json_to_bson_doc(&json).unwrap()).unwrap();
+        assert_eq!(stats.db, "mydb");
+        assert_eq!(stats.collections, 3);
+        assert_eq!(stats.avg_obj_size, 81.5);
+        assert_eq!(stats.data_size, 9780.0);
+        assert_eq!(stats.extra.get_i64("fsUsedSize").unwrap(), 1000);
+        assert!(stats.extra.contains_key("ok"));
+    }
+
+    #[test]
+    fn test_server_status_deserialization() {
+        let json = serde_json::json!({
+            "host": "db1:27017",
+            "version": "7.0.0",
+            "uptime": 3600,
+            "localTime": { "$date": 1700000000000_i64 },
+            "connections": { "current": 5, "available": 995, "totalCreated": 42 },
+            "opcounters": { "insert": 10, "query": 20 },
+            "mem": { "resident": 512 },
+        });
+        let status: ServerStatus = bson::from_document(json_to_bson_doc(&json).unwrap()).unwrap();
+        assert_eq!(status.version, "7.0.0");
+        assert_eq!(status.uptime, 3600.0);
+        assert_eq!(status.local_time.unwrap().timestamp_millis(), 1700000000000);
+        assert_eq!(status.connections.unwrap().total_created, 42);
+        assert_eq!(status.opcounters.unwrap().delete, 0);
+        assert!(status.extra.contains_key("mem"));
+    }
+
+    #[test]
+    fn test_coll_mod_command() {
+        let options = CollModOptions::builder()
+            .validator(bson::doc! { "age": { "$gte": 0 } })
+            .validation_level(ValidationLevel::Moderate)
+            .validation_action(ValidationAction::Warn)
+            .expire_after_seconds("createdAt_1", 3600)
+            .hidden("createdAt_1", false)
+            .build();
+        assert_eq!(
+            options.to_command("sessions"),
+            bson::doc! {
+                "collMod": "sessions",
+                "validator": { "age": { "$gte": 0 } },
+                "validationLevel": "moderate",
+                "validationAction": "warn",
+                "index": { "name": "createdAt_1", "expireAfterSeconds": 3600_i64, "hidden": false },
+            }
+        );
+    }
 
     #[test]
     fn test_create_collection_options_builder() {
@@ -388,6 +1394,18 @@ mod tests {
         assert_eq!(options.size, Some(1024 * 1024));
         assert_eq!(options.max, Some(1000));
         assert!(options.validator.is_some());
+
+        let view = CreateCollectionOptions::builder()
+            .view_on("orders")
+            .pipeline(vec![bson::doc! { "$match": { "status": "paid" } }])
+            .build();
+        assert_eq!(
+            serde_json::to_value(&view).unwrap(),
+            serde_json::json!({
+                "viewOn": "orders",
+                "pipeline": [{ "$match": { "status": "paid" } }],
+            })
+        );
     }
 
     #[test]
@@ -409,7 +1427,7 @@ mod tests {
         let json = bson_doc_to_json(&doc).unwrap();
         assert_eq!(json.get("name").unwrap().as_str().unwrap(), "test");
         assert_eq!(json.get("value").unwrap().as_i64().unwrap(), 42);
-        assert_eq!(json.get("active").unwrap().as_bool().unwrap(), true);
+        assert!(json.get("active").unwrap().as_bool().unwrap());
     }
 
     #[test]
@@ -433,14 +1451,14 @@ mod tests {
     #[test]
     fn test_bson_to_json_types() {
         // Test various BSON types
-        let double = bson_to_json(&bson::Bson::Double(3.14)).unwrap();
-        assert_eq!(double.as_f64().unwrap(), 3.14);
+        let double = bson_to_json(&bson::Bson::Double(2.5)).unwrap();
+        assert_eq!(double.as_f64().unwrap(), 2.5);
 
         let string = bson_to_json(&bson::Bson::String("test".to_string())).unwrap();
         assert_eq!(string.as_str().unwrap(), "test");
 
         let boolean = bson_to_json(&bson::Bson::Boolean(true)).unwrap();
-        assert_eq!(boolean.as_bool().unwrap(), true);
+        assert!(boolean.as_bool().unwrap());
 
         let null = bson_to_json(&bson::Bson::Null).unwrap();
         assert!(null.is_null());
@@ -467,7 +1485,7 @@ mod tests {
         assert!(matches!(number, bson::Bson::Int64(42)));
 
         // Float
-        let float = json_to_bson(&serde_json::json!(3.14));
+        let float = json_to_bson(&serde_json::json!(2.5));
         assert!(matches!(float, bson::Bson::Double(_)));
 
         // String
@@ -0,0 +1,1681 @@
+//! Client-side field level encryption.
+//!
+//! [`ClientEncryption`] encrypts and decrypts individual field values with data
+//! keys stored in a key vault collection, so sensitive values can be encrypted
+//! before they ever leave the process. Data keys are themselves encrypted
+//! ("wrapped") with a master key held by a KMS provider; only the `local`
+//! provider is supported for now.
+//!
+//! Values are encrypted with `AEAD_AES_256_CBC_HMAC_SHA_512` and stored as BSON
+//! binary subtype 6, following the layout used by the MongoDB drivers.
+//!
+//! Encryption can also be applied automatically by configuring
+//! [`AutoEncryptionOptions`] on the client: fields designated by a JSON schema
+//! are encrypted on insert and update, and encrypted values are decrypted on
+//! every read.
+//!
+//! Fields that must stay queryable are encrypted with
+//! [`ClientEncryption::encrypt_indexed`], which stores keyed query tags next to
+//! the ciphertext in [`SAFE_CONTENT_FIELD`]. Filters built with
+//! [`ClientEncryption::equality_filter`] and [`ClientEncryption::range_filter`]
+//! match on those tags, so the server never sees the plaintext.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::encryption::{Algorithm, ClientEncryption, EncryptKey, KmsProviders};
+//!
+//! let kms_providers = KmsProviders::local(master_key)?;
+//! let encryption = ClientEncryption::new(&client, "encryption.__keyVault", kms_providers)?;
+//!
+//! let ssn = encryption
+//!     .encrypt(
+//!         "123-45-6789",
+//!         EncryptKey::AltName("pii".to_string()),
+//!         Algorithm::AeadAes256CbcHmacSha512Deterministic,
+//!     )
+//!     .await?;
+//! users.insert_one(doc! { "name": "John", "ssn": ssn }).await?;
+//! ```
+
+use crate::client::MongoClient;
+use crate::collection::{Collection, DeleteResult};
+use crate::convert::binary_from_json;
+use crate::cursor::Cursor;
+use crate::error::{MongoError, Result};
+use aes::cipher::{block_padding::Pkcs7, BlockDecryptMut, BlockEncryptMut, KeyIvInit};
+use bson::spec::{BinarySubtype, ElementType};
+use bson::{doc, Binary, Bson, Document};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde_json::Value as JsonValue;
+use sha2::{Sha256, Sha512};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+type Aes256CbcEnc = cbc::Encryptor<aes::Aes256>;
+type Aes256CbcDec = cbc::Decryptor<aes::Aes256>;
+type HmacSha256 = Hmac<Sha256>;
+type HmacSha512 = Hmac<Sha512>;
+
+/// Length in bytes of data keys and local master keys.
+pub const KEY_LEN: usize = 96;
+
+/// Name of the array field holding the query tags of queryable encrypted fields.
+pub const SAFE_CONTENT_FIELD: &str = "__safeContent__";
+
+const IV_LEN: usize = 16;
+const TAG_LEN: usize = 32;
+const UUID_LEN: usize = 16;
+/// Length of the ciphertext header: blob subtype, key UUID and original BSON type.
+const HEADER_LEN: usize = 1 + UUID_LEN + 1;
+
+/// A 96-byte master key for the `local` KMS provider.
+#[derive(Clone)]
+pub struct LocalMasterKey {
+    key: Vec<u8>,
+}
+
+impl LocalMasterKey {
+    /// Create a master key from raw key material.
+    ///
+    /// Returns an error if the key is not exactly [`KEY_LEN`] bytes long.
+    pub fn new(key: impl Into<Vec<u8>>) -> Result<Self> {
+        let key = key.into();
+        if key.len() != KEY_LEN {
+            return Err(MongoError::invalid_argument(format!(
+                "local master key must be {} bytes, got {}",
+                KEY_LEN,
+                key.len()
+            )));
+        }
+        Ok(Self { key })
+    }
+}
+
+impl fmt::Debug for LocalMasterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LocalMasterKey(<redacted>)")
+    }
+}
+
+/// Master key providers used to wrap and unwrap data keys.
+#[derive(Debug, Clone, Default)]
+pub struct KmsProviders {
+    /// Master key for the `local` provider.
+    pub local: Option<LocalMasterKey>,
+}
+
+impl KmsProviders {
+    /// Create providers with only a `local` master key.
+    pub fn local(key: impl Into<Vec<u8>>) -> Result<Self> {
+        Ok(Self {
+            local: Some(LocalMasterKey::new(key)?),
+        })
+    }
+
+    /// Get the master key for the named provider.
+    fn master_key(&self, provider: &str) -> Result<&LocalMasterKey> {
+        match provider {
+            "local" => self
+                .local
+                .as_ref()
+                .ok_or_else(|| MongoError::encryption("no local KMS provider configured")),
+            other => Err(MongoError::encryption(format!(
+                "unsupported KMS provider: {}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Encryption algorithm for explicit encryption.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    /// Deterministic encryption: equal plaintexts yield equal ciphertexts, so
+    /// encrypted fields can be matched by equality.
+    AeadAes256CbcHmacSha512Deterministic,
+    /// Randomized encryption: every encryption yields a different ciphertext.
+    AeadAes256CbcHmacSha512Random,
+}
+
+impl Algorithm {
+    /// The algorithm name as used in key vault and schema documents.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Algorithm::AeadAes256CbcHmacSha512Deterministic => {
+                "AEAD_AES_256_CBC_HMAC_SHA_512-Deterministic"
+            }
+            Algorithm::AeadAes256CbcHmacSha512Random => "AEAD_AES_256_CBC_HMAC_SHA_512-Random",
+        }
+    }
+
+    /// Parse an algorithm name as used in schema documents.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "AEAD_AES_256_CBC_HMAC_SHA_512-Deterministic" => {
+                Some(Algorithm::AeadAes256CbcHmacSha512Deterministic)
+            }
+            "AEAD_AES_256_CBC_HMAC_SHA_512-Random" => {
+                Some(Algorithm::AeadAes256CbcHmacSha512Random)
+            }
+            _ => None,
+        }
+    }
+
+    /// Subtype byte stored at the start of the ciphertext.
+    fn blob_subtype(&self) -> u8 {
+        match self {
+            Algorithm::AeadAes256CbcHmacSha512Deterministic => 1,
+            Algorithm::AeadAes256CbcHmacSha512Random => 2,
+        }
+    }
+}
+
+/// The data key used to encrypt a value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EncryptKey {
+    /// Look up the data key by its `_id` (a UUID binary).
+    Id(Binary),
+    /// Look up the data key by one of its `keyAltNames`.
+    AltName(String),
+}
+
+/// Options for [`ClientEncryption::create_data_key`].
+#[derive(Debug, Clone, Default)]
+pub struct DataKeyOptions {
+    /// Alternate names the key can be looked up by.
+    pub key_alt_names: Option<Vec<String>>,
+    /// Key material to use instead of a randomly generated key.
+    pub key_material: Option<Vec<u8>>,
+}
+
+impl DataKeyOptions {
+    /// Create a new builder.
+    pub fn builder() -> DataKeyOptionsBuilder {
+        DataKeyOptionsBuilder::default()
+    }
+}
+
+/// Builder for DataKeyOptions.
+#[derive(Debug, Default)]
+pub struct DataKeyOptionsBuilder {
+    options: DataKeyOptions,
+}
+
+impl DataKeyOptionsBuilder {
+    /// Set the alternate names of the key.
+    pub fn key_alt_names(mut self, names: Vec<String>) -> Self {
+        self.options.key_alt_names = Some(names);
+        self
+    }
+
+    /// Set the key material (must be [`KEY_LEN`] bytes).
+    pub fn key_material(mut self, key_material: Vec<u8>) -> Self {
+        self.options.key_material = Some(key_material);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> DataKeyOptions {
+        self.options
+    }
+}
+
+/// Options for [`ClientEncryption::rewrap_many_data_key`].
+#[derive(Debug, Clone, Default)]
+pub struct RewrapManyDataKeyOptions {
+    /// Provider to re-wrap the keys with. Defaults to each key's current provider.
+    pub provider: Option<String>,
+    /// New local master key to re-wrap with, for rotating the `local` master key.
+    pub master_key: Option<LocalMasterKey>,
+}
+
+impl RewrapManyDataKeyOptions {
+    /// Create a new builder.
+    pub fn builder() -> RewrapManyDataKeyOptionsBuilder {
+        RewrapManyDataKeyOptionsBuilder::default()
+    }
+}
+
+/// Builder for RewrapManyDataKeyOptions.
+#[derive(Debug, Default)]
+pub struct RewrapManyDataKeyOptionsBuilder {
+    options: RewrapManyDataKeyOptions,
+}
+
+impl RewrapManyDataKeyOptionsBuilder {
+    /// Set the provider to re-wrap with.
+    pub fn provider(mut self, provider: impl Into<String>) -> Self {
+        self.options.provider = Some(provider.into());
+        self
+    }
+
+    /// Set the new local master key.
+    pub fn master_key(mut self, master_key: LocalMasterKey) -> Self {
+        self.options.master_key = Some(master_key);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> RewrapManyDataKeyOptions {
+        self.options
+    }
+}
+
+/// Result of [`ClientEncryption::rewrap_many_data_key`].
+#[derive(Debug, Clone, Default)]
+pub struct RewrapManyDataKeyResult {
+    /// Number of data keys that matched the filter.
+    pub matched_count: u64,
+    /// Number of data keys re-wrapped.
+    pub modified_count: u64,
+}
+
+/// How a queryable encrypted field can be queried.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum QueryType {
+    /// Equality matches on the exact value and BSON type.
+    Equality,
+    /// Range and equality matches over a bounded domain.
+    Range(RangeOptions),
+}
+
+/// Domain of a range-queryable field.
+///
+/// Bounds may be integers, doubles or dates; values and query bounds must be
+/// of the same kind (32 and 64-bit integers are interchangeable).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeOptions {
+    /// Smallest value that can be stored.
+    pub min: Bson,
+    /// Largest value that can be stored.
+    pub max: Bson,
+}
+
+impl RangeOptions {
+    /// Create range options for the domain `[min, max]`.
+    pub fn new(min: impl Into<Bson>, max: impl Into<Bson>) -> Self {
+        Self {
+            min: min.into(),
+            max: max.into(),
+        }
+    }
+
+    /// Get the encoded domain bounds, kind and bit width.
+    fn domain(&self) -> Result<(u64, u64, u8, u32)> {
+        let (kind, min) = range_encode(&self.min)?;
+        let (max_kind, max) = range_encode(&self.max)?;
+        if kind != max_kind || min > max {
+            return Err(MongoError::invalid_argument(
+                "range min and max must be of the same kind with min <= max",
+            ));
+        }
+        Ok((min, max, kind, 64 - (max - min).leading_zeros()))
+    }
+
+    /// Get the position of a value within the domain, and the domain's bit width.
+    fn offset(&self, value: &Bson) -> Result<(u64, u32)> {
+        let (min, max, kind, bits) = self.domain()?;
+        let (value_kind, encoded) = range_encode(value)?;
+        if value_kind != kind || encoded < min || encoded > max {
+            return Err(MongoError::invalid_argument(format!(
+                "value {} is outside the range domain",
+                value
+            )));
+        }
+        Ok((encoded - min, bits))
+    }
+
+    /// Translate `$gt`/`$gte`/`$lt`/`$lte` bounds into an inclusive offset range.
+    ///
+    /// Returns `None` when no value can match.
+    fn query_bounds(&self, bounds: &Document) -> Result<Option<(u64, u64, u32)>> {
+        let (min, max, kind, bits) = self.domain()?;
+        let (mut lower, mut upper) = (0i128, (max - min) as i128);
+
+        for (op, value) in bounds {
+            let (value_kind, encoded) = range_encode(value)?;
+            if value_kind != kind {
+                return Err(MongoError::invalid_argument(format!(
+                    "range bound {} is not of the domain's kind",
+                    value
+                )));
+            }
+            let relative = encoded as i128 - min as i128;
+            match op.as_str() {
+                "$gt" => lower = lower.max(relative + 1),
+                "$gte" => lower = lower.max(relative),
+                "$lt" => upper = upper.min(relative - 1),
+                "$lte" => upper = upper.min(relative),
+                other => {
+                    return Err(MongoError::invalid_argument(format!(
+                        "unsupported range operator: {}",
+                        other
+                    )))
+                }
+            }
+        }
+
+        if lower > upper {
+            return Ok(None);
+        }
+        Ok(Some((lower as u64, upper as u64, bits)))
+    }
+}
+
+/// A value encrypted for queryable encryption.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexedValue {
+    /// The randomly encrypted value, stored in place of the plaintext.
+    pub ciphertext: Binary,
+    /// Query tags, stored in [`SAFE_CONTENT_FIELD`].
+    pub tags: Vec<Binary>,
+}
+
+impl IndexedValue {
+    /// Store the value as the top-level `field` of `doc` and add its tags to
+    /// the document's [`SAFE_CONTENT_FIELD`].
+    pub fn insert_into(self, doc: &mut Document, field: &str) {
+        doc.insert(field, self.ciphertext);
+        let tags = self.tags.into_iter().map(Bson::Binary);
+        match doc.get_array_mut(SAFE_CONTENT_FIELD) {
+            Ok(safe_content) => safe_content.extend(tags),
+            Err(_) => {
+                doc.insert(SAFE_CONTENT_FIELD, tags.collect::<Vec<_>>());
+            }
+        }
+    }
+}
+
+/// Explicit encryption and decryption of field values.
+///
+/// Decrypted data keys are cached for the lifetime of this value.
+pub struct ClientEncryption {
+    /// The key vault collection holding data keys.
+    key_vault: Collection<Document>,
+    /// Master key providers.
+    kms_providers: KmsProviders,
+    /// Unwrapped data keys by key UUID.
+    key_cache: Mutex<HashMap<[u8; UUID_LEN], Arc<Vec<u8>>>>,
+}
+
+impl ClientEncryption {
+    /// Create a new encryption handle.
+    ///
+    /// # Arguments
+    ///
+    /// * `key_vault_client` - Client used to read data keys
+    /// * `key_vault_namespace` - Namespace of the key vault collection (`db.collection`)
+    /// * `kms_providers` - Master key providers used to unwrap data keys
+    pub fn new(
+        key_vault_client: &MongoClient,
+        key_vault_namespace: &str,
+        kms_providers: KmsProviders,
+    ) -> Result<Self> {
+        let (db_name, coll_name) = parse_namespace(key_vault_namespace)?;
+        Ok(Self {
+            key_vault: key_vault_client
+                .database(db_name)
+                .collection_with_doc(coll_name),
+            kms_providers,
+            key_cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Encrypt a value with the given data key.
+    ///
+    /// Returns a BSON binary of subtype 6 that can be stored in place of the value.
+    pub async fn encrypt(
+        &self,
+        value: impl Into<Bson>,
+        key: EncryptKey,
+        algorithm: Algorithm,
+    ) -> Result<Binary> {
+        let (key_id, data_key) = self.data_key(&key).await?;
+        encrypt_value(&value.into(), &key_id, &data_key, algorithm)
+    }
+
+    /// Decrypt a value previously produced by [`ClientEncryption::encrypt`].
+    pub async fn decrypt(&self, value: &Binary) -> Result<Bson> {
+        let key_id = ciphertext_key_id(value)?;
+        let data_key = self.key_by_id(key_id).await?;
+        decrypt_value(value, &data_key)
+    }
+
+    /// Encrypt a value so that `field` can still be queried as `query_type` allows.
+    ///
+    /// The value is encrypted with the randomized algorithm; equality or range
+    /// matches go through the returned tags instead.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let age = encryption
+    ///     .encrypt_indexed("age", 42, key.clone(), &QueryType::Range(RangeOptions::new(0, 150)))
+    ///     .await?;
+    /// let mut patient = doc! { "name": "John" };
+    /// age.insert_into(&mut patient, "age");
+    /// patients.insert_one(patient).await?;
+    /// ```
+    pub async fn encrypt_indexed(
+        &self,
+        field: &str,
+        value: impl Into<Bson>,
+        key: EncryptKey,
+        query_type: &QueryType,
+    ) -> Result<IndexedValue> {
+        let value = value.into();
+        let (key_id, data_key) = self.data_key(&key).await?;
+        let tags = match query_type {
+            QueryType::Equality => vec![equality_tag(&data_key, field, &value)?],
+            QueryType::Range(range) => {
+                let (offset, bits) = range.offset(&value)?;
+                range_edges(offset, bits)
+                    .into_iter()
+                    .map(|(len, prefix)| range_tag(&data_key, field, len, prefix))
+                    .collect()
+            }
+        };
+        let ciphertext = encrypt_value(
+            &value,
+            &key_id,
+            &data_key,
+            Algorithm::AeadAes256CbcHmacSha512Random,
+        )?;
+        Ok(IndexedValue { ciphertext, tags })
+    }
+
+    /// Build a filter matching documents whose equality-indexed `field` is `value`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let filter = encryption.equality_filter("ssn", "123-45-6789", key).await?;
+    /// let patient = patients.find_one(filter).await?;
+    /// ```
+    pub async fn equality_filter(
+        &self,
+        field: &str,
+        value: impl Into<Bson>,
+        key: EncryptKey,
+    ) -> Result<Document> {
+        let (_, data_key) = self.data_key(&key).await?;
+        Ok(doc! { SAFE_CONTENT_FIELD: equality_tag(&data_key, field, &value.into())? })
+    }
+
+    /// Build a filter matching documents whose range-indexed `field` satisfies
+    /// `bounds`, a document of `$gt`, `$gte`, `$lt` and `$lte` operators.
+    ///
+    /// Equality on a range-indexed field is `{ "$gte": v, "$lte": v }`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let range = RangeOptions::new(0, 150);
+    /// let filter = encryption
+    ///     .range_filter("age", key, &range, doc! { "$gte": 18, "$lt": 65 })
+    ///     .await?;
+    /// let adults = patients.find(filter).await?;
+    /// ```
+    pub async fn range_filter(
+        &self,
+        field: &str,
+        key: EncryptKey,
+        range: &RangeOptions,
+        bounds: Document,
+    ) -> Result<Document> {
+        let (_, data_key) = self.data_key(&key).await?;
+        let tags: Vec<Bson> = match range.query_bounds(&bounds)? {
+            Some((lower, upper, bits)) => range_cover(lower, upper, bits)
+                .into_iter()
+                .map(|(len, prefix)| Bson::Binary(range_tag(&data_key, field, len, prefix)))
+                .collect(),
+            None => Vec::new(),
+        };
+        Ok(doc! { SAFE_CONTENT_FIELD: { "$in": tags } })
+    }
+
+    /// Create a new data key and store it in the key vault.
+    ///
+    /// Returns the UUID `_id` of the new key.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = DataKeyOptions::builder()
+    ///     .key_alt_names(vec!["pii".to_string()])
+    ///     .build();
+    /// let key_id = encryption.create_data_key("local", options).await?;
+    /// ```
+    pub async fn create_data_key(
+        &self,
+        kms_provider: &str,
+        options: impl Into<Option<DataKeyOptions>>,
+    ) -> Result<Binary> {
+        let options = options.into().unwrap_or_default();
+        let data_key = match options.key_material {
+            Some(key_material) if key_material.len() != KEY_LEN => {
+                return Err(MongoError::invalid_argument(format!(
+                    "data key material must be {} bytes, got {}",
+                    KEY_LEN,
+                    key_material.len()
+                )));
+            }
+            Some(key_material) => key_material,
+            None => {
+                let mut key_material = vec![0u8; KEY_LEN];
+                rand::thread_rng().fill_bytes(&mut key_material);
+                key_material
+            }
+        };
+
+        let master_key = self.kms_providers.master_key(kms_provider)?;
+        let key_id = bson::Uuid::new().bytes();
+        let key_doc = data_key_document(
+            key_id,
+            wrap_data_key(master_key, &data_key),
+            kms_provider,
+            options.key_alt_names.unwrap_or_default(),
+        );
+        self.key_vault.insert_one(key_doc).await?;
+
+        self.key_cache().insert(key_id, Arc::new(data_key));
+        Ok(uuid_binary(key_id))
+    }
+
+    /// Get all data keys in the key vault.
+    pub async fn get_keys(&self) -> Result<Cursor<Document>> {
+        self.key_vault.find(None).await
+    }
+
+    /// Delete a data key from the key vault.
+    ///
+    /// Values encrypted with the key can no longer be decrypted.
+    pub async fn delete_key(&self, id: &Binary) -> Result<DeleteResult> {
+        let key_id = uuid_bytes(id)?;
+        self.key_cache().remove(&key_id);
+        self.key_vault
+            .delete_one(doc! { "_id": uuid_binary(key_id) })
+            .await
+    }
+
+    /// Re-wrap the data keys matching `filter` with a (possibly new) master key.
+    ///
+    /// The data keys themselves are unchanged, so existing ciphertexts remain
+    /// decryptable. When rotating the `local` master key, pass the new key in
+    /// the options and configure it on subsequent [`ClientEncryption`] handles.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let options = RewrapManyDataKeyOptions::builder()
+    ///     .provider("local")
+    ///     .master_key(LocalMasterKey::new(new_master_key)?)
+    ///     .build();
+    /// let result = encryption.rewrap_many_data_key(doc! {}, options).await?;
+    /// ```
+    pub async fn rewrap_many_data_key(
+        &self,
+        filter: Document,
+        options: impl Into<Option<RewrapManyDataKeyOptions>>,
+    ) -> Result<RewrapManyDataKeyResult> {
+        let options = options.into().unwrap_or_default();
+        let key_docs = self.key_vault.find(filter).await?.collect().await?;
+
+        let mut result = RewrapManyDataKeyResult {
+            matched_count: key_docs.len() as u64,
+            modified_count: 0,
+        };
+        for key_doc in key_docs {
+            let (key_id, current_provider, key_material) = key_document_parts(&key_doc)?;
+            let current_master_key = self.kms_providers.master_key(current_provider)?;
+            let data_key = unwrap_data_key(current_master_key, key_material)?;
+
+            let provider = options.provider.as_deref().unwrap_or(current_provider);
+            let master_key = match options.master_key {
+                Some(ref master_key) if provider == "local" => master_key,
+                _ => self.kms_providers.master_key(provider)?,
+            };
+            let update = doc! {
+                "$set": {
+                    "keyMaterial": Binary {
+                        subtype: BinarySubtype::Generic,
+                        bytes: wrap_data_key(master_key, &data_key),
+                    },
+                    "masterKey": { "provider": provider },
+                },
+                "$currentDate": { "updateDate": true },
+            };
+            let updated = self
+                .key_vault
+                .update_one(doc! { "_id": uuid_binary(key_id) }, update)
+                .await?;
+            result.modified_count += updated.modified_count;
+        }
+        Ok(result)
+    }
+
+    /// Resolve a data key by UUID, consulting the cache before the key vault.
+    async fn key_by_id(&self, key_id: [u8; UUID_LEN]) -> Result<Arc<Vec<u8>>> {
+        match self.cached_key(&key_id) {
+            Some(data_key) => Ok(data_key),
+            None => {
+                let filter = doc! { "_id": uuid_binary(key_id) };
+                Ok(self.load_key(filter).await?.1)
+            }
+        }
+    }
+
+    /// Resolve a data key, consulting the cache before the key vault.
+    async fn data_key(&self, key: &EncryptKey) -> Result<([u8; UUID_LEN], Arc<Vec<u8>>)> {
+        let filter = match key {
+            EncryptKey::Id(id) => {
+                let key_id = uuid_bytes(id)?;
+                if let Some(data_key) = self.cached_key(&key_id) {
+                    return Ok((key_id, data_key));
+                }
+                doc! { "_id": uuid_binary(key_id) }
+            }
+            EncryptKey::AltName(name) => doc! { "keyAltNames": name.as_str() },
+        };
+        self.load_key(filter).await
+    }
+
+    /// Fetch a data key document from the key vault and unwrap it.
+    async fn load_key(&self, filter: Document) -> Result<([u8; UUID_LEN], Arc<Vec<u8>>)> {
+        // Boxed because `find_one` may itself decrypt through this type.
+        let key_doc = Box::pin(self.key_vault.find_one(filter))
+            .await?
+            .ok_or_else(|| MongoError::encryption("data key not found in key vault"))?;
+
+        let (key_id, provider, key_material) = key_document_parts(&key_doc)?;
+        let master_key = self.kms_providers.master_key(provider)?;
+        let data_key = Arc::new(unwrap_data_key(master_key, key_material)?);
+        self.key_cache().insert(key_id, data_key.clone());
+        Ok((key_id, data_key))
+    }
+
+    /// Look up an already unwrapped data key.
+    fn cached_key(&self, key_id: &[u8; UUID_LEN]) -> Option<Arc<Vec<u8>>> {
+        self.key_cache().get(key_id).cloned()
+    }
+
+    /// Lock the key cache. Each write leaves it consistent, so a panic while
+    /// it was locked is ignored.
+    fn key_cache(&self) -> std::sync::MutexGuard<'_, HashMap<[u8; UUID_LEN], Arc<Vec<u8>>>> {
+        self.key_cache.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Options for automatic encryption, configured on [`ClientOptions`](crate::ClientOptions).
+///
+/// # Example
+///
+/// ```ignore
+/// let schema = doc! {
+///     "bsonType": "object",
+///     "properties": {
+///         "ssn": {
+///             "encrypt": {
+///                 "keyId": [key_id],
+///                 "bsonType": "string",
+///                 "algorithm": "AEAD_AES_256_CBC_HMAC_SHA_512-Deterministic",
+///             }
+///         }
+///     }
+/// };
+///
+/// let options = ClientOptions::builder()
+///     .auto_encryption_options(AutoEncryptionOptions {
+///         key_vault_namespace: "encryption.__keyVault".to_string(),
+///         kms_providers: KmsProviders::local(master_key)?,
+///         schema_map: HashMap::from([("mydb.users".to_string(), schema)]),
+///         bypass_auto_encryption: None,
+///     })
+///     .build();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct AutoEncryptionOptions {
+    /// Namespace of the key vault collection (`db.collection`).
+    pub key_vault_namespace: String,
+    /// Master key providers used to unwrap data keys.
+    pub kms_providers: KmsProviders,
+    /// JSON schemas by namespace, designating which fields to encrypt.
+    pub schema_map: HashMap<String, Document>,
+    /// Only decrypt on reads; never encrypt on writes.
+    pub bypass_auto_encryption: Option<bool>,
+}
+
+/// A field designated for encryption by a schema.
+#[derive(Debug, Clone, PartialEq)]
+struct EncryptedField {
+    /// Path of the field within the document.
+    path: Vec<String>,
+    /// Data key used for the field.
+    key: EncryptKey,
+    /// Encryption algorithm for the field.
+    algorithm: Algorithm,
+}
+
+/// Applies automatic encryption and decryption to documents on the wire.
+pub(crate) struct AutoEncrypter {
+    /// Explicit encryption handle backed by the key vault.
+    encryption: ClientEncryption,
+    /// Encrypted fields by namespace.
+    fields: HashMap<String, Vec<EncryptedField>>,
+    /// Whether to skip encryption on writes.
+    bypass: bool,
+}
+
+impl AutoEncrypter {
+    /// Create an auto encrypter reading data keys through `key_vault_client`.
+    pub(crate) fn new(key_vault_client: &MongoClient, options: &AutoEncryptionOptions) -> Result<Self> {
+        let encryption = ClientEncryption::new(
+            key_vault_client,
+            &options.key_vault_namespace,
+            options.kms_providers.clone(),
+        )?;
+
+        let mut fields = HashMap::new();
+        for (namespace, schema) in &options.schema_map {
+            let schema = schema.get_document("$jsonSchema").unwrap_or(schema);
+            let mut namespace_fields = Vec::new();
+            collect_schema_fields(schema, &mut Vec::new(), None, None, &mut namespace_fields)?;
+            fields.insert(namespace.clone(), namespace_fields);
+        }
+
+        Ok(Self {
+            encryption,
+            fields,
+            bypass: options.bypass_auto_encryption.unwrap_or(false),
+        })
+    }
+
+    /// Fields to encrypt for a namespace, if encryption applies.
+    fn fields_for(&self, namespace: &str) -> Option<&[EncryptedField]> {
+        if self.bypass {
+            return None;
+        }
+        self.fields
+            .get(namespace)
+            .map(|f| f.as_slice())
+            .filter(|f| !f.is_empty())
+    }
+
+    /// Encrypt the designated fields of a document in place.
+    pub(crate) async fn encrypt_document(&self, namespace: &str, doc: &mut JsonValue) -> Result<()> {
+        let Some(fields) = self.fields_for(namespace) else {
+            return Ok(());
+        };
+        for field in fields {
+            if let Some(value) = json_path_mut(doc, &field.path) {
+                self.encrypt_in_place(value, field).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Encrypt the designated fields touched by an update document in place.
+    ///
+    /// Values assigned by `$set` and `$setOnInsert` are encrypted; any other
+    /// operator touching an encrypted field (except `$unset`) is rejected, since
+    /// the server cannot apply it to ciphertext. A replacement document is
+    /// encrypted like an inserted document.
+    pub(crate) async fn encrypt_update(&self, namespace: &str, update: &mut JsonValue) -> Result<()> {
+        let Some(fields) = self.fields_for(namespace) else {
+            return Ok(());
+        };
+        let Some(ops) = update.as_object_mut() else {
+            return Ok(());
+        };
+        if !ops.keys().any(|k| k.starts_with('$')) {
+            return self.encrypt_document(namespace, update).await;
+        }
+
+        for (op, spec) in ops.iter_mut() {
+            let Some(spec) = spec.as_object_mut() else {
+                continue;
+            };
+            let assigns = op == "$set" || op == "$setOnInsert";
+            for (key, value) in spec.iter_mut() {
+                let path: Vec<String> = key.split('.').map(str::to_string).collect();
+                for field in fields {
+                    if !path.starts_with(&field.path) && !field.path.starts_with(&path) {
+                        continue;
+                    }
+                    if op == "$unset" {
+                        continue;
+                    }
+                    if !assigns || path.len() > field.path.len() {
+                        return Err(MongoError::encryption(format!(
+                            "cannot apply {} to encrypted field '{}'",
+                            op,
+                            field.path.join(".")
+                        )));
+                    }
+                    if let Some(target) = json_path_mut(value, &field.path[path.len()..]) {
+                        self.encrypt_in_place(target, field).await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Replace every encrypted value within `value` by its plaintext.
+    pub(crate) async fn decrypt(&self, value: &mut JsonValue) -> Result<()> {
+        let mut encrypted = Vec::new();
+        collect_encrypted(value, &mut encrypted);
+        if encrypted.is_empty() {
+            return Ok(());
+        }
+
+        let mut keys = HashMap::new();
+        for bin in &encrypted {
+            let key_id = ciphertext_key_id(bin)?;
+            if let Entry::Vacant(entry) = keys.entry(key_id) {
+                entry.insert(self.encryption.key_by_id(key_id).await?);
+            }
+        }
+        replace_encrypted(value, &keys)
+    }
+
+    /// Encrypt a single JSON value in place.
+    async fn encrypt_in_place(&self, value: &mut JsonValue, field: &EncryptedField) -> Result<()> {
+        if value.is_null() || as_encrypted_binary(value).is_some() {
+            return Ok(());
+        }
+        let bson = Bson::try_from(value.clone())
+            .map_err(|e| MongoError::encryption(format!("cannot encrypt field: {}", e)))?;
+        let encrypted = self
+            .encryption
+            .encrypt(bson, field.key.clone(), field.algorithm)
+            .await?;
+        *value = Bson::Binary(encrypted).into_canonical_extjson();
+        Ok(())
+    }
+}
+
+/// Walk a JSON schema collecting the fields marked with `encrypt`.
+fn collect_schema_fields(
+    schema: &Document,
+    path: &mut Vec<String>,
+    inherited_key: Option<&EncryptKey>,
+    inherited_algorithm: Option<Algorithm>,
+    out: &mut Vec<EncryptedField>,
+) -> Result<()> {
+    let metadata = schema.get_document("encryptMetadata").ok();
+    let key = match metadata.and_then(|m| m.get("keyId")) {
+        Some(key_id) => Some(parse_schema_key(key_id)?),
+        None => inherited_key.cloned(),
+    };
+    let algorithm = match metadata.and_then(|m| m.get_str("algorithm").ok()) {
+        Some(name) => Some(parse_schema_algorithm(name)?),
+        None => inherited_algorithm,
+    };
+
+    if let Ok(encrypt) = schema.get_document("encrypt") {
+        let key = match encrypt.get("keyId") {
+            Some(key_id) => parse_schema_key(key_id)?,
+            None => key.ok_or_else(|| {
+                MongoError::encryption(format!("no keyId for encrypted field '{}'", path.join(".")))
+            })?,
+        };
+        let algorithm = match encrypt.get_str("algorithm").ok() {
+            Some(name) => parse_schema_algorithm(name)?,
+            None => algorithm.ok_or_else(|| {
+                MongoError::encryption(format!(
+                    "no algorithm for encrypted field '{}'",
+                    path.join(".")
+                ))
+            })?,
+        };
+        out.push(EncryptedField {
+            path: path.clone(),
+            key,
+            algorithm,
+        });
+        return Ok(());
+    }
+
+    if let Ok(properties) = schema.get_document("properties") {
+        for (name, property) in properties {
+            if let Bson::Document(property) = property {
+                path.push(name.clone());
+                collect_schema_fields(property, path, key.as_ref(), algorithm, out)?;
+                path.pop();
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Parse a schema `keyId`: an array holding one UUID binary.
+fn parse_schema_key(key_id: &Bson) -> Result<EncryptKey> {
+    match key_id {
+        Bson::Array(ids) => match ids.as_slice() {
+            [Bson::Binary(id)] if id.subtype == BinarySubtype::Uuid => Ok(EncryptKey::Id(id.clone())),
+            _ => Err(MongoError::encryption("schema keyId must hold exactly one UUID")),
+        },
+        _ => Err(MongoError::encryption("schema keyId must be an array of UUIDs")),
+    }
+}
+
+fn parse_schema_algorithm(name: &str) -> Result<Algorithm> {
+    Algorithm::from_name(name)
+        .ok_or_else(|| MongoError::encryption(format!("unknown encryption algorithm: {}", name)))
+}
+
+/// Get a mutable reference to the value at `path` within a JSON document.
+fn json_path_mut<'a>(value: &'a mut JsonValue, path: &[String]) -> Option<&'a mut JsonValue> {
+    path.iter()
+        .try_fold(value, |current, key| current.as_object_mut()?.get_mut(key))
+}
+
+/// Parse a JSON value holding an encrypted binary (`$binary` with subtype 6).
+fn as_encrypted_binary(value: &JsonValue) -> Option<Binary> {
+    binary_from_json(value).filter(|bin| bin.subtype == BinarySubtype::Encrypted)
+}
+
+/// Collect every encrypted binary within a JSON value.
+fn collect_encrypted(value: &JsonValue, out: &mut Vec<Binary>) {
+    if let Some(bin) = as_encrypted_binary(value) {
+        out.push(bin);
+        return;
+    }
+    match value {
+        JsonValue::Object(map) => map.values().for_each(|v| collect_encrypted(v, out)),
+        JsonValue::Array(arr) => arr.iter().for_each(|v| collect_encrypted(v, out)),
+        _ => {}
+    }
+}
+
+/// Decrypt every encrypted binary within a JSON value using the given keys.
+fn replace_encrypted(
+    value: &mut JsonValue,
+    keys: &HashMap<[u8; UUID_LEN], Arc<Vec<u8>>>,
+) -> Result<()> {
+    if let Some(bin) = as_encrypted_binary(value) {
+        let data_key = keys
+            .get(&ciphertext_key_id(&bin)?)
+            .ok_or_else(|| MongoError::encryption("data key not found in key vault"))?;
+        *value = decrypt_value(&bin, data_key)?.into_relaxed_extjson();
+        return Ok(());
+    }
+    match value {
+        JsonValue::Object(map) => map
+            .values_mut()
+            .try_for_each(|v| replace_encrypted(v, keys)),
+        JsonValue::Array(arr) => arr.iter_mut().try_for_each(|v| replace_encrypted(v, keys)),
+        _ => Ok(()),
+    }
+}
+
+/// Split a `db.collection` namespace.
+fn parse_namespace(namespace: &str) -> Result<(&str, &str)> {
+    match namespace.split_once('.') {
+        Some((db, coll)) if !db.is_empty() && !coll.is_empty() => Ok((db, coll)),
+        _ => Err(MongoError::invalid_argument(format!(
+            "invalid key vault namespace: {}",
+            namespace
+        ))),
+    }
+}
+
+/// Build a UUID binary from raw bytes.
+fn uuid_binary(bytes: [u8; UUID_LEN]) -> Binary {
+    Binary {
+        subtype: BinarySubtype::Uuid,
+        bytes: bytes.to_vec(),
+    }
+}
+
+/// Extract the bytes of a UUID binary.
+fn uuid_bytes(bin: &Binary) -> Result<[u8; UUID_LEN]> {
+    if bin.subtype != BinarySubtype::Uuid {
+        return Err(MongoError::encryption("key id must be a UUID binary"));
+    }
+    bin.bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_| MongoError::encryption("key id must be 16 bytes"))
+}
+
+/// Build a key vault document for a new data key.
+fn data_key_document(
+    key_id: [u8; UUID_LEN],
+    key_material: Vec<u8>,
+    provider: &str,
+    key_alt_names: Vec<String>,
+) -> Document {
+    let now = bson::DateTime::now();
+    let mut key_doc = doc! {
+        "_id": uuid_binary(key_id),
+        "keyMaterial": Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: key_material,
+        },
+        "creationDate": now,
+        "updateDate": now,
+        "status": 0,
+        "masterKey": { "provider": provider },
+    };
+    if !key_alt_names.is_empty() {
+        key_doc.insert("keyAltNames", key_alt_names);
+    }
+    key_doc
+}
+
+/// Read the UUID, KMS provider and wrapped key material of a key vault document.
+fn key_document_parts(key_doc: &Document) -> Result<([u8; UUID_LEN], &str, &[u8])> {
+    let key_id = match key_doc.get("_id") {
+        Some(Bson::Binary(id)) => uuid_bytes(id)?,
+        _ => return Err(MongoError::encryption("data key has no UUID _id")),
+    };
+    let provider = key_doc
+        .get_document("masterKey")
+        .ok()
+        .and_then(|mk| mk.get_str("provider").ok())
+        .unwrap_or("local");
+    let key_material = match key_doc.get("keyMaterial") {
+        Some(Bson::Binary(bin)) => bin.bytes.as_slice(),
+        _ => return Err(MongoError::encryption("data key has no keyMaterial")),
+    };
+    Ok((key_id, provider, key_material))
+}
+
+/// Wrap a data key with a local master key.
+fn wrap_data_key(master_key: &LocalMasterKey, data_key: &[u8]) -> Vec<u8> {
+    aead_encrypt(&master_key.key, random_iv(), data_key, &[])
+}
+
+/// Compute a query tag for a field and token with a data key.
+fn query_tag(data_key: &[u8], field: &str, token: &[u8]) -> Binary {
+    let mut mac = HmacSha256::new_from_slice(data_key).expect("HMAC accepts any key length");
+    mac.update(field.as_bytes());
+    mac.update(&[0]);
+    mac.update(token);
+    Binary {
+        subtype: BinarySubtype::Generic,
+        bytes: mac.finalize().into_bytes().to_vec(),
+    }
+}
+
+/// Tag for an equality match on a value.
+fn equality_tag(data_key: &[u8], field: &str, value: &Bson) -> Result<Binary> {
+    let mut token = vec![b'e', value.element_type() as u8];
+    token.extend(value_to_bytes(value)?);
+    Ok(query_tag(data_key, field, &token))
+}
+
+/// Tag for a node of the range tree: the `len` leading bits `prefix`.
+fn range_tag(data_key: &[u8], field: &str, len: u32, prefix: u64) -> Binary {
+    let mut token = vec![b'r'];
+    token.extend_from_slice(&len.to_be_bytes());
+    token.extend_from_slice(&prefix.to_be_bytes());
+    query_tag(data_key, field, &token)
+}
+
+/// Encode a range value as an order-preserving `u64`, along with its kind.
+fn range_encode(value: &Bson) -> Result<(u8, u64)> {
+    const SIGN: u64 = 1 << 63;
+    match value {
+        Bson::Int32(v) => Ok((0, (*v as i64 as u64) ^ SIGN)),
+        Bson::Int64(v) => Ok((0, (*v as u64) ^ SIGN)),
+        Bson::Double(v) if !v.is_nan() => {
+            let bits = v.to_bits();
+            Ok((1, if bits & SIGN != 0 { !bits } else { bits | SIGN }))
+        }
+        Bson::DateTime(v) => Ok((2, (v.timestamp_millis() as u64) ^ SIGN)),
+        other => Err(MongoError::invalid_argument(format!(
+            "range queries support integers, doubles and dates, got {}",
+            other
+        ))),
+    }
+}
+
+/// Nodes of the range tree containing `offset`: one prefix per length.
+fn range_edges(offset: u64, bits: u32) -> Vec<(u32, u64)> {
+    (0..=bits)
+        .map(|len| (len, offset.checked_shr(bits - len).unwrap_or(0)))
+        .collect()
+}
+
+/// Minimal set of range tree nodes covering `[lower, upper]`.
+fn range_cover(lower: u64, upper: u64, bits: u32) -> Vec<(u32, u64)> {
+    let mut cover = Vec::new();
+    cover_node(0, 0, bits, lower as u128, upper as u128, &mut cover);
+    cover
+}
+
+fn cover_node(
+    len: u32,
+    prefix: u128,
+    bits: u32,
+    lower: u128,
+    upper: u128,
+    cover: &mut Vec<(u32, u64)>,
+) {
+    let span = bits - len;
+    let start = prefix << span;
+    let end = start + (1u128 << span) - 1;
+    if end < lower || start > upper {
+        return;
+    }
+    if lower <= start && end <= upper {
+        cover.push((len, prefix as u64));
+        return;
+    }
+    cover_node(len + 1, prefix << 1, bits, lower, upper, cover);
+    cover_node(len + 1, (prefix << 1) | 1, bits, lower, upper, cover);
+}
+
+/// Unwrap a data key's `keyMaterial` with a local master key.
+pub(crate) fn unwrap_data_key(master_key: &LocalMasterKey, key_material: &[u8]) -> Result<Vec<u8>> {
+    let data_key = aead_decrypt(&master_key.key, key_material, &[])?;
+    if data_key.len() != KEY_LEN {
+        return Err(MongoError::encryption("unwrapped data key has invalid length"));
+    }
+    Ok(data_key)
+}
+
+/// Read the data key UUID from an encrypted binary.
+fn ciphertext_key_id(value: &Binary) -> Result<[u8; UUID_LEN]> {
+    if value.subtype != BinarySubtype::Encrypted || value.bytes.len() < HEADER_LEN {
+        return Err(MongoError::encryption("value is not an encrypted binary"));
+    }
+    Ok(value.bytes[1..1 + UUID_LEN].try_into().unwrap())
+}
+
+/// Encrypt a BSON value into a subtype 6 binary.
+pub(crate) fn encrypt_value(
+    value: &Bson,
+    key_id: &[u8; UUID_LEN],
+    data_key: &[u8],
+    algorithm: Algorithm,
+) -> Result<Binary> {
+    let element_type = value.element_type();
+    if matches!(
+        element_type,
+        ElementType::Null | ElementType::Undefined | ElementType::MinKey | ElementType::MaxKey
+    ) {
+        return Err(MongoError::encryption(format!(
+            "cannot encrypt value of type {:?}",
+            element_type
+        )));
+    }
+
+    let mut header = Vec::with_capacity(HEADER_LEN);
+    header.push(algorithm.blob_subtype());
+    header.extend_from_slice(key_id);
+    header.push(element_type as u8);
+
+    let plaintext = value_to_bytes(value)?;
+    let iv = match algorithm {
+        Algorithm::AeadAes256CbcHmacSha512Deterministic => {
+            deterministic_iv(&data_key[64..], &header, &plaintext)
+        }
+        Algorithm::AeadAes256CbcHmacSha512Random => random_iv(),
+    };
+
+    let mut bytes = header.clone();
+    bytes.extend(aead_encrypt(data_key, iv, &plaintext, &header));
+    Ok(Binary {
+        subtype: BinarySubtype::Encrypted,
+        bytes,
+    })
+}
+
+/// Decrypt a subtype 6 binary back into the original BSON value.
+pub(crate) fn decrypt_value(value: &Binary, data_key: &[u8]) -> Result<Bson> {
+    ciphertext_key_id(value)?;
+    let header = &value.bytes[..HEADER_LEN];
+    let plaintext = aead_decrypt(data_key, &value.bytes[HEADER_LEN..], header)?;
+    bytes_to_value(header[HEADER_LEN - 1], &plaintext)
+}
+
+/// Serialize a BSON value to its raw element bytes (without type or key).
+fn value_to_bytes(value: &Bson) -> Result<Vec<u8>> {
+    // A document with a single empty key is: length (4), type (1), key
+    // terminator (1), the value, and the document terminator (1).
+    let bytes = bson::to_vec(&doc! { "": value.clone() })?;
+    Ok(bytes[6..bytes.len() - 1].to_vec())
+}
+
+/// Rebuild a BSON value from its element type and raw bytes.
+fn bytes_to_value(element_type: u8, value: &[u8]) -> Result<Bson> {
+    let len = (4 + 1 + 1 + value.len() + 1) as i32;
+    let mut bytes = Vec::with_capacity(len as usize);
+    bytes.extend_from_slice(&len.to_le_bytes());
+    bytes.push(element_type);
+    bytes.push(0);
+    bytes.extend_from_slice(value);
+    bytes.push(0);
+
+    let doc = Document::from_reader(bytes.as_slice())
+        .map_err(|e| MongoError::encryption(format!("invalid decrypted value: {}", e)))?;
+    doc.get("")
+        .cloned()
+        .ok_or_else(|| MongoError::encryption("invalid decrypted value"))
+}
+
+fn random_iv() -> [u8; IV_LEN] {
+    let mut iv = [0u8; IV_LEN];
+    rand::thread_rng().fill_bytes(&mut iv);
+    iv
+}
+
+fn deterministic_iv(iv_key: &[u8], associated_data: &[u8], plaintext: &[u8]) -> [u8; IV_LEN] {
+    let mut mac = HmacSha512::new_from_slice(iv_key).expect("HMAC accepts any key length");
+    mac.update(associated_data);
+    mac.update(&((associated_data.len() as u64) * 8).to_be_bytes());
+    mac.update(plaintext);
+    mac.finalize().into_bytes()[..IV_LEN].try_into().unwrap()
+}
+
+fn authentication_tag(mac_key: &[u8], associated_data: &[u8], ciphertext: &[u8]) -> [u8; TAG_LEN] {
+    let mut mac = HmacSha512::new_from_slice(mac_key).expect("HMAC accepts any key length");
+    mac.update(associated_data);
+    mac.update(ciphertext);
+    mac.update(&((associated_data.len() as u64) * 8).to_be_bytes());
+    mac.finalize().into_bytes()[..TAG_LEN].try_into().unwrap()
+}
+
+/// Encrypt with `AEAD_AES_256_CBC_HMAC_SHA_512`.
+///
+/// The 96-byte key is split into a MAC key, an encryption key and an IV key.
+/// The output is `IV || AES-256-CBC(plaintext) || tag`.
+fn aead_encrypt(key: &[u8], iv: [u8; IV_LEN], plaintext: &[u8], associated_data: &[u8]) -> Vec<u8> {
+    let (mac_key, enc_key) = (&key[..32], &key[32..64]);
+
+    let mut ciphertext = iv.to_vec();
+    ciphertext.extend(
+        Aes256CbcEnc::new(enc_key.into(), &iv.into()).encrypt_padded_vec_mut::<Pkcs7>(plaintext),
+    );
+    let tag = authentication_tag(mac_key, associated_data, &ciphertext);
+    ciphertext.extend_from_slice(&tag);
+    ciphertext
+}
+
+/// Verify and decrypt the output of [`aead_encrypt`].
+fn aead_decrypt(key: &[u8], data: &[u8], associated_data: &[u8]) -> Result<Vec<u8>> {
+    if key.len() != KEY_LEN {
+        return Err(MongoError::encryption("encryption key must be 96 bytes"));
+    }
+    if data.len() < IV_LEN + 16 + TAG_LEN {
+        return Err(MongoError::encryption("ciphertext is too short"));
+    }
+    let (mac_key, enc_key) = (&key[..32], &key[32..64]);
+    let (ciphertext, tag) = data.split_at(data.len() - TAG_LEN);
+
+    let mut mac = HmacSha512::new_from_slice(mac_key).expect("HMAC accepts any key length");
+    mac.update(associated_data);
+    mac.update(ciphertext);
+    mac.update(&((associated_data.len() as u64) * 8).to_be_bytes());
+    mac.verify_truncated_left(tag)
+        .map_err(|_| MongoError::encryption("ciphertext authentication failed"))?;
+
+    let (iv, body) = ciphertext.split_at(IV_LEN);
+    Aes256CbcDec::new(enc_key.into(), iv.into())
+        .decrypt_padded_vec_mut::<Pkcs7>(body)
+        .map_err(|_| MongoError::encryption("invalid ciphertext padding"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_key(seed: u8) -> Vec<u8> {
+        (0..KEY_LEN as u8).map(|i| i.wrapping_mul(7).wrapping_add(seed)).collect()
+    }
+
+    #[test]
+    fn test_local_master_key_length() {
+        assert!(LocalMasterKey::new(test_key(1)).is_ok());
+        let err = LocalMasterKey::new(vec![0u8; 32]).unwrap_err();
+        assert!(matches!(err, MongoError::InvalidArgument(_)));
+    }
+
+    #[test]
+    fn test_local_master_key_debug_redacted() {
+        let providers = KmsProviders::local(test_key(1)).unwrap();
+        assert!(format!("{:?}", providers).contains("<redacted>"));
+    }
+
+    #[test]
+    fn test_parse_namespace() {
+        assert_eq!(
+            parse_namespace("encryption.__keyVault").unwrap(),
+            ("encryption", "__keyVault")
+        );
+        assert!(parse_namespace("nodot").is_err());
+        assert!(parse_namespace(".coll").is_err());
+    }
+
+    #[test]
+    fn test_aead_round_trip() {
+        let key = test_key(3);
+        let ciphertext = aead_encrypt(&key, random_iv(), b"secret", b"ad");
+        assert_eq!(aead_decrypt(&key, &ciphertext, b"ad").unwrap(), b"secret");
+    }
+
+    #[test]
+    fn test_aead_rejects_tampering() {
+        let key = test_key(3);
+        let mut ciphertext = aead_encrypt(&key, random_iv(), b"secret", b"ad");
+        ciphertext[IV_LEN] ^= 1;
+        assert!(aead_decrypt(&key, &ciphertext, b"ad").is_err());
+
+        let ciphertext = aead_encrypt(&key, random_iv(), b"secret", b"ad");
+        assert!(aead_decrypt(&key, &ciphertext, b"other").is_err());
+    }
+
+    #[test]
+    fn test_encrypt_value_round_trip() {
+        let key = test_key(5);
+        let key_id = [9u8; UUID_LEN];
+        for value in [
+            Bson::String("123-45-6789".to_string()),
+            Bson::Int32(42),
+            Bson::Int64(1 << 40),
+            Bson::Double(2.5),
+            Bson::Boolean(true),
+            Bson::Document(doc! { "street": "Main", "no": 1 }),
+            Bson::Array(vec![Bson::Int32(1), Bson::String("a".to_string())]),
+        ] {
+            let encrypted =
+                encrypt_value(&value, &key_id, &key, Algorithm::AeadAes256CbcHmacSha512Random)
+                    .unwrap();
+            assert_eq!(encrypted.subtype, BinarySubtype::Encrypted);
+            assert_eq!(ciphertext_key_id(&encrypted).unwrap(), key_id);
+            assert_eq!(decrypt_value(&encrypted, &key).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_deterministic_encryption_is_stable() {
+        let key = test_key(5);
+        let key_id = [1u8; UUID_LEN];
+        let value = Bson::String("alice@example.com".to_string());
+        let algorithm = Algorithm::AeadAes256CbcHmacSha512Deterministic;
+
+        let a = encrypt_value(&value, &key_id, &key, algorithm).unwrap();
+        let b = encrypt_value(&value, &key_id, &key, algorithm).unwrap();
+        assert_eq!(a, b);
+
+        let random = Algorithm::AeadAes256CbcHmacSha512Random;
+        let c = encrypt_value(&value, &key_id, &key, random).unwrap();
+        let d = encrypt_value(&value, &key_id, &key, random).unwrap();
+        assert_ne!(c, d);
+    }
+
+    #[test]
+    fn test_encrypt_null_rejected() {
+        let result = encrypt_value(
+            &Bson::Null,
+            &[0u8; UUID_LEN],
+            &test_key(1),
+            Algorithm::AeadAes256CbcHmacSha512Random,
+        );
+        assert!(matches!(result, Err(MongoError::Encryption(_))));
+    }
+
+    #[test]
+    fn test_unwrap_data_key() {
+        let master_key = LocalMasterKey::new(test_key(1)).unwrap();
+        let data_key = test_key(2);
+        let key_material = aead_encrypt(&master_key.key, random_iv(), &data_key, &[]);
+        assert_eq!(unwrap_data_key(&master_key, &key_material).unwrap(), data_key);
+
+        let wrong = LocalMasterKey::new(test_key(9)).unwrap();
+        assert!(unwrap_data_key(&wrong, &key_material).is_err());
+    }
+
+    #[test]
+    fn test_data_key_document() {
+        let master_key = LocalMasterKey::new(test_key(1)).unwrap();
+        let data_key = test_key(2);
+        let key_doc = data_key_document(
+            [7u8; UUID_LEN],
+            wrap_data_key(&master_key, &data_key),
+            "local",
+            vec!["pii".to_string()],
+        );
+
+        assert_eq!(key_doc.get_array("keyAltNames").unwrap().len(), 1);
+        assert_eq!(key_doc.get_i32("status").unwrap(), 0);
+
+        let (key_id, provider, key_material) = key_document_parts(&key_doc).unwrap();
+        assert_eq!(key_id, [7u8; UUID_LEN]);
+        assert_eq!(provider, "local");
+        assert_eq!(unwrap_data_key(&master_key, key_material).unwrap(), data_key);
+    }
+
+    #[test]
+    fn test_data_key_document_without_alt_names() {
+        let master_key = LocalMasterKey::new(test_key(1)).unwrap();
+        let key_material = wrap_data_key(&master_key, &test_key(2));
+        let key_doc = data_key_document([7u8; UUID_LEN], key_material, "local", vec![]);
+        assert!(!key_doc.contains_key("keyAltNames"));
+    }
+
+    #[test]
+    fn test_range_encode_preserves_order() {
+        let ints = [
+            Bson::Int64(i64::MIN),
+            Bson::Int32(-5),
+            Bson::Int64(0),
+            Bson::Int32(3),
+            Bson::Int64(i64::MAX),
+        ];
+        let encoded: Vec<u64> = ints.iter().map(|v| range_encode(v).unwrap().1).collect();
+        assert!(encoded.windows(2).all(|w| w[0] < w[1]));
+
+        let doubles = [f64::NEG_INFINITY, -2.5, -0.5, 0.0, 1.5, f64::INFINITY];
+        let encoded: Vec<u64> = doubles
+            .iter()
+            .map(|v| range_encode(&Bson::Double(*v)).unwrap().1)
+            .collect();
+        assert!(encoded.windows(2).all(|w| w[0] < w[1]));
+
+        assert!(range_encode(&Bson::Double(f64::NAN)).is_err());
+        assert!(range_encode(&Bson::String("a".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_range_cover_matches_edges() {
+        let bits = 5;
+        for lower in 0..32u64 {
+            for upper in lower..32u64 {
+                let cover = range_cover(lower, upper, bits);
+                for value in 0..32u64 {
+                    let matches = range_edges(value, bits)
+                        .iter()
+                        .filter(|edge| cover.contains(edge))
+                        .count();
+                    let expected = usize::from(lower <= value && value <= upper);
+                    assert_eq!(matches, expected, "[{}, {}] {}", lower, upper, value);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_range_full_width_domain() {
+        let range = RangeOptions::new(i64::MIN, i64::MAX);
+        let (offset, bits) = range.offset(&Bson::Int64(-1)).unwrap();
+        assert_eq!(bits, 64);
+        assert_eq!(range_edges(offset, bits).len(), 65);
+        assert_eq!(range_cover(0, u64::MAX, bits), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn test_range_query_bounds() {
+        let range = RangeOptions::new(0, 100);
+        assert_eq!(range.offset(&Bson::Int64(42)).unwrap(), (42, 7));
+        assert!(range.offset(&Bson::Int32(101)).is_err());
+        assert!(range.offset(&Bson::Double(1.0)).is_err());
+
+        let bounds = range.query_bounds(&doc! { "$gt": 10, "$lte": 20 }).unwrap();
+        assert_eq!(bounds, Some((11, 20, 7)));
+        let bounds = range.query_bounds(&doc! { "$lt": 500 }).unwrap();
+        assert_eq!(bounds, Some((0, 100, 7)));
+        let bounds = range.query_bounds(&doc! { "$gt": 50, "$lt": 51 }).unwrap();
+        assert_eq!(bounds, None);
+        assert!(range.query_bounds(&doc! { "$ne": 5 }).is_err());
+    }
+
+    #[test]
+    fn test_query_tags() {
+        let data_key = test_key(3);
+        let tag = equality_tag(&data_key, "ssn", &Bson::String("123".to_string())).unwrap();
+        assert_eq!(tag, equality_tag(&data_key, "ssn", &Bson::String("123".to_string())).unwrap());
+        assert_ne!(tag, equality_tag(&data_key, "name", &Bson::String("123".to_string())).unwrap());
+        assert_ne!(tag, equality_tag(&test_key(4), "ssn", &Bson::String("123".to_string())).unwrap());
+        assert_ne!(range_tag(&data_key, "age", 1, 0), range_tag(&data_key, "age", 2, 0));
+    }
+
+    #[test]
+    fn test_indexed_value_insert_into() {
+        let tag = |b: u8| Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: vec![b],
+        };
+        let ciphertext = Binary {
+            subtype: BinarySubtype::Encrypted,
+            bytes: vec![0],
+        };
+        let mut doc = doc! {};
+        let a = IndexedValue {
+            ciphertext: ciphertext.clone(),
+            tags: vec![tag(1)],
+        };
+        a.insert_into(&mut doc, "a");
+        let b = IndexedValue {
+            ciphertext,
+            tags: vec![tag(2), tag(3)],
+        };
+        b.insert_into(&mut doc, "b");
+
+        assert!(matches!(doc.get("a"), Some(Bson::Binary(_))));
+        assert_eq!(doc.get_array(SAFE_CONTENT_FIELD).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_algorithm_names() {
+        for algorithm in [
+            Algorithm::AeadAes256CbcHmacSha512Deterministic,
+            Algorithm::AeadAes256CbcHmacSha512Random,
+        ] {
+            assert_eq!(Algorithm::from_name(algorithm.as_str()), Some(algorithm));
+        }
+        assert_eq!(Algorithm::from_name("AES"), None);
+    }
+
+    #[test]
+    fn test_collect_schema_fields() {
+        let key_id = uuid_binary([7u8; UUID_LEN]);
+        let schema = doc! {
+            "bsonType": "object",
+            "encryptMetadata": {
+                "keyId": [key_id.clone()],
+                "algorithm": "AEAD_AES_256_CBC_HMAC_SHA_512-Random",
+            },
+            "properties": {
+                "ssn": {
+                    "encrypt": {
+                        "bsonType": "string",
+                        "algorithm": "AEAD_AES_256_CBC_HMAC_SHA_512-Deterministic",
+                    }
+                },
+                "address": {
+                    "bsonType": "object",
+                    "properties": {
+                        "street": { "encrypt": { "bsonType": "string" } },
+                        "city": { "bsonType": "string" },
+                    }
+                },
+                "name": { "bsonType": "string" },
+            }
+        };
+
+        let mut fields = Vec::new();
+        collect_schema_fields(&schema, &mut Vec::new(), None, None, &mut fields).unwrap();
+        assert_eq!(
+            fields,
+            vec![
+                EncryptedField {
+                    path: vec!["ssn".to_string()],
+                    key: EncryptKey::Id(key_id.clone()),
+                    algorithm: Algorithm::AeadAes256CbcHmacSha512Deterministic,
+                },
+                EncryptedField {
+                    path: vec!["address".to_string(), "street".to_string()],
+                    key: EncryptKey::Id(key_id),
+                    algorithm: Algorithm::AeadAes256CbcHmacSha512Random,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collect_schema_fields_requires_key() {
+        let schema = doc! {
+            "properties": {
+                "ssn": { "encrypt": { "algorithm": "AEAD_AES_256_CBC_HMAC_SHA_512-Random" } }
+            }
+        };
+        let mut fields = Vec::new();
+        let result = collect_schema_fields(&schema, &mut Vec::new(), None, None, &mut fields);
+        assert!(matches!(result, Err(MongoError::Encryption(_))));
+    }
+
+    #[test]
+    fn test_json_path_mut() {
+        let mut value = serde_json::json!({ "a": { "b": 1 } });
+        *json_path_mut(&mut value, &["a".to_string(), "b".to_string()]).unwrap() =
+            serde_json::json!(2);
+        assert_eq!(value, serde_json::json!({ "a": { "b": 2 } }));
+        assert!(json_path_mut(&mut value, &["a".to_string(), "c".to_string()]).is_none());
+    }
+
+    #[test]
+    fn test_replace_encrypted() {
+        let key = test_key(4);
+        let key_id = [3u8; UUID_LEN];
+        let encrypted = encrypt_value(
+            &Bson::String("secret".to_string()),
+            &key_id,
+            &key,
+            Algorithm::AeadAes256CbcHmacSha512Random,
+        )
+        .unwrap();
+        let mut value = serde_json::json!({
+            "name": "John",
+            "ssn": Bson::Binary(encrypted).into_canonical_extjson(),
+            "tags": [{ "$binary": { "base64": "AQID", "subType": "00" } }],
+        });
+
+        let mut found = Vec::new();
+        collect_encrypted(&value, &mut found);
+        assert_eq!(found.len(), 1);
+
+        let keys = HashMap::from([(key_id, Arc::new(key))]);
+        replace_encrypted(&mut value, &keys).unwrap();
+        assert_eq!(value["ssn"], serde_json::json!("secret"));
+        assert_eq!(value["name"], serde_json::json!("John"));
+    }
+
+    #[test]
+    fn test_decrypt_rejects_non_encrypted_binary() {
+        let bin = Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: vec![0; 64],
+        };
+        assert!(decrypt_value(&bin, &test_key(1)).is_err());
+    }
+}
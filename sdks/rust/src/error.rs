@@ -1,8 +1,18 @@
 //! Error types for MongoDB operations.
 
+use crate::rpc::Method;
+use bson::{oid::ObjectId, Bson, Document};
+use std::collections::HashMap;
 use std::fmt;
 use thiserror::Error;
 
+/// Server error code for a duplicate key.
+pub const DUPLICATE_KEY_CODE: i32 = 11000;
+
+/// Longest response excerpt kept in a [`MongoError::Protocol`], in bytes.
+const PROTOCOL_EXCERPT_LEN: usize = 200;
+
+
 /// All errors that can occur during MongoDB operations.
 #[derive(Debug, Error)]
 pub enum MongoError {
@@ -23,10 +33,18 @@ pub enum MongoError {
         message: String,
     },
 
+    /// A write violated a unique index.
+    #[error("duplicate key error: {0}")]
+    DuplicateKey(DuplicateKeyError),
+
     /// Bulk write error.
     #[error("bulk write error: {0} errors")]
     BulkWrite(usize),
 
+    /// Some documents of an insert_many failed.
+    #[error("insert many error: {0}")]
+    InsertMany(InsertManyError),
+
     /// Command error.
     #[error("command error: {message}")]
     Command {
@@ -79,6 +97,23 @@ pub enum MongoError {
     /// BSON error.
     #[error("bson error: {0}")]
     Bson(String),
+
+    /// Client-side encryption error.
+    #[error("encryption error: {0}")]
+    Encryption(String),
+
+    /// The server does not implement an RPC method.
+    #[error("unknown RPC method: {0}")]
+    UnknownMethod(String),
+
+    /// A response did not have the expected shape.
+    #[error("protocol error: {message} in response {excerpt}")]
+    Protocol {
+        /// What was expected.
+        message: String,
+        /// The start of the offending response.
+        excerpt: String,
+    },
 }
 
 impl MongoError {
@@ -118,6 +153,28 @@ impl MongoError {
         MongoError::InvalidArgument(msg.into())
     }
 
+    /// Create an encryption error.
+    pub fn encryption(msg: impl Into<String>) -> Self {
+        MongoError::Encryption(msg.into())
+    }
+
+    /// Create a protocol error for an unexpected `response`.
+    pub fn protocol(message: impl Into<String>, response: &serde_json::Value) -> Self {
+        let mut excerpt = response.to_string();
+        if excerpt.len() > PROTOCOL_EXCERPT_LEN {
+            let mut end = PROTOCOL_EXCERPT_LEN;
+            while !excerpt.is_char_boundary(end) {
+                end -= 1;
+            }
+            excerpt.truncate(end);
+            excerpt.push_str("...");
+        }
+        MongoError::Protocol {
+            message: message.into(),
+            excerpt,
+        }
+    }
+
     /// Check if this is a connection error.
     pub fn is_connection_error(&self) -> bool {
         matches!(self, MongoError::Connection(_) | MongoError::Network(_))
@@ -133,10 +190,44 @@ impl MongoError {
         matches!(self, MongoError::Timeout)
     }
 
+    /// Get the duplicate key details, if this is a duplicate key error.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// match users.insert_one(user).await {
+    ///     Err(err) if err.duplicate_key().is_some_and(|dup| dup.index_name == "email_1") => {
+    ///         return Err(AppError::EmailTaken);
+    ///     }
+    ///     result => result?,
+    /// };
+    /// ```
+    pub fn duplicate_key(&self) -> Option<&DuplicateKeyError> {
+        match self {
+            MongoError::DuplicateKey(err) => Some(err),
+            _ => None,
+        }
+    }
+
+    /// Convert the RPC error of a call to `method`, recognizing duplicate key
+    /// and unknown method errors in its message.
+    pub(crate) fn from_rpc(method: Method, err: rpc_do::RpcError) -> Self {
+        let message = err.to_string();
+        if let Some(dup) = DuplicateKeyError::parse(&message) {
+            return MongoError::DuplicateKey(dup);
+        }
+        if is_unknown_method(&message) {
+            return MongoError::UnknownMethod(method.as_str().to_string());
+        }
+        MongoError::Rpc(err)
+    }
+
     /// Get the error code if available.
     pub fn code(&self) -> Option<i32> {
         match self {
             MongoError::Write { code, .. } => *code,
+            MongoError::DuplicateKey(_) => Some(DUPLICATE_KEY_CODE),
+            MongoError::InsertMany(err) => err.write_errors.first().map(|e| e.code),
             MongoError::Command { code, .. } => Some(*code),
             _ => None,
         }
@@ -148,6 +239,14 @@ impl MongoError {
     }
 }
 
+/// Whether an RPC error message says the server does not implement the method.
+fn is_unknown_method(message: &str) -> bool {
+    let message = message.to_ascii_lowercase();
+    ["unknown method", "method not found", "no such method"]
+        .iter()
+        .any(|pattern| message.contains(pattern))
+}
+
 impl From<serde_json::Error> for MongoError {
     fn from(err: serde_json::Error) -> Self {
         MongoError::Serialization(err.to_string())
@@ -166,6 +265,173 @@ impl From<bson::de::Error> for MongoError {
     }
 }
 
+/// Details of a write that violated a unique index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DuplicateKeyError {
+    /// Name of the unique index, e.g. `email_1`.
+    pub index_name: String,
+    /// The duplicated key, e.g. `{ "email": "a@example.com" }`.
+    pub key_values: Document,
+}
+
+impl DuplicateKeyError {
+    /// Parse a server message such as `E11000 duplicate key error collection:
+    /// app.users index: email_1 dup key: { email: "a@example.com" }`.
+    pub fn parse(message: &str) -> Option<Self> {
+        if !message.contains("E11000") && !message.contains("duplicate key error") {
+            return None;
+        }
+        let index_name = message
+            .split("index: ")
+            .nth(1)?
+            .split_whitespace()
+            .next()?
+            .to_string();
+        let key_values = message
+            .split("dup key: ")
+            .nth(1)
+            .map(parse_dup_key)
+            .unwrap_or_default();
+        Some(Self {
+            index_name,
+            key_values,
+        })
+    }
+}
+
+impl fmt::Display for DuplicateKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "index {} dup key {}", self.index_name, self.key_values)
+    }
+}
+
+/// One failed document of a multi-document write.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WriteError {
+    /// Position of the document in the request.
+    pub index: usize,
+    /// Error code from server.
+    pub code: i32,
+    /// Error message.
+    pub message: String,
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "document {}: {} ({})", self.index, self.message, self.code)
+    }
+}
+
+/// The outcome of an insert_many in which some documents failed.
+///
+/// An ordered insert stops at the first failure; an unordered one tries
+/// every document.
+///
+/// # Example
+///
+/// ```ignore
+/// let options = InsertManyOptions::builder().ordered(false).build();
+/// match events.insert_many_with_options(&batch, options).await {
+///     Err(MongoError::InsertMany(err)) => {
+///         println!("{} inserted, {} failed", err.inserted_ids.len(), err.write_errors.len());
+///     }
+///     result => {
+///         result?;
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct InsertManyError {
+    /// The documents that failed, in request order.
+    pub write_errors: Vec<WriteError>,
+    /// IDs of the documents that were inserted, by position in the request.
+    pub inserted_ids: HashMap<usize, Bson>,
+}
+
+impl fmt::Display for InsertManyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (failed, inserted) = (self.write_errors.len(), self.inserted_ids.len());
+        write!(f, "{} documents failed, {} inserted", failed, inserted)?;
+        if let Some(first) = self.write_errors.first() {
+            write!(f, "; first failure at {}", first)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse the shell-style `{ field: value, ... }` key of a duplicate key message.
+fn parse_dup_key(text: &str) -> Document {
+    let text = text.trim();
+    let inner = text
+        .strip_prefix('{')
+        .and_then(|rest| rest.rfind('}').map(|end| &rest[..end]))
+        .unwrap_or(text);
+    split_top_level(inner)
+        .into_iter()
+        .filter_map(|field| {
+            let (name, value) = field.split_once(':')?;
+            Some((name.trim().to_string(), parse_shell_value(value.trim())))
+        })
+        .collect()
+}
+
+/// Split on commas outside quotes and brackets.
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let (mut depth, mut quote, mut escaped, mut start) = (0i32, None, false, 0);
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(_), _) if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '{' | '[' | '(') => depth += 1,
+            (None, '}' | ']' | ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                parts.push(&text[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if !text[start..].trim().is_empty() {
+        parts.push(&text[start..]);
+    }
+    parts
+}
+
+/// Parse a shell-style value, keeping anything unrecognized as its text.
+fn parse_shell_value(value: &str) -> Bson {
+    if let Some(string) = value.strip_prefix('"').and_then(|v| v.strip_suffix('"')) {
+        return Bson::String(string.replace("\\\"", "\"").replace("\\\\", "\\"));
+    }
+    if let Some(hex) = value
+        .strip_prefix("ObjectId(")
+        .and_then(|v| v.strip_suffix(')'))
+        .map(|v| v.trim_matches(|c| c == '\'' || c == '"'))
+    {
+        if let Ok(oid) = ObjectId::parse_str(hex) {
+            return Bson::ObjectId(oid);
+        }
+    }
+    match value {
+        "null" => return Bson::Null,
+        "true" => return Bson::Boolean(true),
+        "false" => return Bson::Boolean(false),
+        _ => {}
+    }
+    if let Ok(n) = value.parse::<i32>() {
+        Bson::Int32(n)
+    } else if let Ok(n) = value.parse::<i64>() {
+        Bson::Int64(n)
+    } else if let Ok(n) = value.parse::<f64>() {
+        Bson::Double(n)
+    } else {
+        Bson::String(value.to_string())
+    }
+}
+
 /// Result type alias for MongoDB operations.
 pub type Result<T> = std::result::Result<T, MongoError>;
 
@@ -190,6 +456,8 @@ pub enum ErrorKind {
     Internal,
     /// Network error.
     Network,
+    /// Client-side encryption error.
+    Encryption,
 }
 
 impl MongoError {
@@ -198,18 +466,23 @@ impl MongoError {
         match self {
             MongoError::Connection(_) => ErrorKind::Connection,
             MongoError::Authentication(_) => ErrorKind::Authentication,
-            MongoError::Write { .. } | MongoError::BulkWrite(_) => ErrorKind::Write,
+            MongoError::Write { .. }
+            | MongoError::DuplicateKey(_)
+            | MongoError::BulkWrite(_)
+            | MongoError::InsertMany(_) => ErrorKind::Write,
             MongoError::Query(_) => ErrorKind::Query,
-            MongoError::Command { .. } => ErrorKind::Command,
+            MongoError::Command { .. } | MongoError::UnknownMethod(_) => ErrorKind::Command,
             MongoError::Timeout => ErrorKind::Timeout,
             MongoError::Serialization(_) | MongoError::Deserialization(_) | MongoError::Bson(_) => {
                 ErrorKind::Serialization
             }
             MongoError::Network(_) => ErrorKind::Network,
+            MongoError::Encryption(_) => ErrorKind::Encryption,
             MongoError::InvalidArgument(_)
             | MongoError::CursorExhausted
             | MongoError::ServerSelection(_)
             | MongoError::Internal(_)
+            | MongoError::Protocol { .. }
             | MongoError::Rpc(_) => ErrorKind::Internal,
         }
     }
@@ -225,6 +498,35 @@ mod tests {
         assert_eq!(err.to_string(), "connection error: failed to connect");
     }
 
+    #[test]
+    fn test_protocol_error() {
+        let response = serde_json::json!({ "ok": 1 });
+        let err = MongoError::protocol("expected a count in `n`", &response);
+        assert_eq!(
+            err.to_string(),
+            r#"protocol error: expected a count in `n` in response {"ok":1}"#
+        );
+        assert_eq!(err.kind(), ErrorKind::Internal);
+
+        let long = serde_json::json!({ "note": "é".repeat(200) });
+        let MongoError::Protocol { excerpt, .. } = MongoError::protocol("too long", &long) else {
+            panic!("expected a protocol error");
+        };
+        assert!(excerpt.len() <= PROTOCOL_EXCERPT_LEN + 3);
+        assert!(excerpt.ends_with("..."));
+    }
+
+    #[test]
+    fn test_unknown_method_error() {
+        assert!(is_unknown_method("Method not found: mongo.hello"));
+        assert!(is_unknown_method("unknown method 'mongo.hello'"));
+        assert!(!is_unknown_method("socket closed"));
+
+        let err = MongoError::UnknownMethod(Method::Hello.to_string());
+        assert_eq!(err.to_string(), "unknown RPC method: mongo.hello");
+        assert_eq!(err.kind(), ErrorKind::Command);
+    }
+
     #[test]
     fn test_write_error() {
         let err = MongoError::write(Some(11000), "duplicate key error");
@@ -232,6 +534,59 @@ mod tests {
         assert_eq!(err.code(), Some(11000));
     }
 
+    #[test]
+    fn test_duplicate_key_error() {
+        let message = "E11000 duplicate key error collection: app.users index: email_1 \
+                       dup key: { email: \"a@example.com\", tenant: 7 }";
+        let dup = DuplicateKeyError::parse(message).unwrap();
+        assert_eq!(dup.index_name, "email_1");
+        assert_eq!(dup.key_values, bson::doc! { "email": "a@example.com", "tenant": 7 });
+
+        let err = MongoError::DuplicateKey(dup);
+        assert_eq!(err.code(), Some(DUPLICATE_KEY_CODE));
+        assert_eq!(err.kind(), ErrorKind::Write);
+        assert_eq!(err.duplicate_key().unwrap().index_name, "email_1");
+        assert!(MongoError::Timeout.duplicate_key().is_none());
+    }
+
+    #[test]
+    fn test_duplicate_key_values() {
+        let oid = ObjectId::new();
+        let message = format!(
+            "E11000 duplicate key error index: _id_ dup key: {{ _id: ObjectId('{}') }}",
+            oid
+        );
+        let dup = DuplicateKeyError::parse(&message).unwrap();
+        assert_eq!(dup.index_name, "_id_");
+        assert_eq!(dup.key_values, bson::doc! { "_id": oid });
+
+        let message = "E11000 duplicate key error index: name_1_tag_1 \
+                       dup key: { name: \"a, \\\"b\\\"\", tag: null }";
+        let dup = DuplicateKeyError::parse(message).unwrap();
+        assert_eq!(dup.key_values, bson::doc! { "name": "a, \"b\"", "tag": null });
+
+        assert!(DuplicateKeyError::parse("connection reset").is_none());
+    }
+
+    #[test]
+    fn test_insert_many_error() {
+        let err = MongoError::InsertMany(InsertManyError {
+            write_errors: vec![WriteError {
+                index: 1,
+                code: DUPLICATE_KEY_CODE,
+                message: "E11000 duplicate key error".to_string(),
+            }],
+            inserted_ids: HashMap::from([(0, Bson::Int32(1)), (2, Bson::Int32(3))]),
+        });
+        assert_eq!(err.code(), Some(DUPLICATE_KEY_CODE));
+        assert_eq!(err.kind(), ErrorKind::Write);
+        assert_eq!(
+            err.to_string(),
+            "insert many error: 1 documents failed, 2 inserted; \
+             first failure at document 1: E11000 duplicate key error (11000)"
+        );
+    }
+
     #[test]
     fn test_command_error() {
         let err = MongoError::command(59, "command not found");
@@ -283,6 +638,13 @@ mod tests {
         assert!(err.to_string().contains("field cannot be empty"));
     }
 
+    #[test]
+    fn test_encryption_error() {
+        let err = MongoError::encryption("data key not found");
+        assert_eq!(err.to_string(), "encryption error: data key not found");
+        assert_eq!(err.kind(), ErrorKind::Encryption);
+    }
+
     #[test]
     fn test_from_serde_json_error() {
         let json_err = serde_json::from_str::<String>("invalid").unwrap_err();
@@ -0,0 +1,281 @@
+//! Connection lifecycle events.
+//!
+//! Subscribe with [`MongoClient::events`](crate::MongoClient::events) to
+//! pause work or flip health checks while the transport recovers.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use futures::StreamExt;
+//! use mongo_do::events::ConnectionEvent;
+//!
+//! let mut events = client.events();
+//! while let Some(event) = events.next().await {
+//!     match event {
+//!         ConnectionEvent::Disconnected { cause } => health.set_unhealthy(cause),
+//!         ConnectionEvent::Connected => health.set_healthy(),
+//!         _ => {}
+//!     }
+//! }
+//! ```
+
+use crate::monitoring::{
+    CmapEventHandler, ConnectionCheckedInEvent, ConnectionCheckedOutEvent, ConnectionClosedEvent,
+    ConnectionClosedReason, ConnectionCreatedEvent, ConnectionReconnectingEvent,
+};
+use futures::Stream;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// Number of events kept for subscribers that fall behind.
+const EVENT_CAPACITY: usize = 64;
+
+/// A change in the state of a client's connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// A lazy client connected, or a lost connection recovered.
+    Connected,
+    /// The connection was lost.
+    Disconnected {
+        /// The error that revealed the lost connection.
+        cause: String,
+    },
+    /// An operation is being sent over a lost connection, which makes the
+    /// transport try to reconnect.
+    Reconnecting {
+        /// Attempts since the connection was lost, starting at 1.
+        attempt: u32,
+    },
+    /// The client was closed.
+    Closed,
+}
+
+/// Connection state and event channel shared by every transport created
+/// from one client.
+#[derive(Debug)]
+pub(crate) struct ConnectionEvents {
+    sender: broadcast::Sender<ConnectionEvent>,
+    /// Reconnect attempts since the connection was lost, or `None` while connected.
+    lost: Mutex<Option<u32>>,
+    /// Calls in flight.
+    in_use: AtomicUsize,
+    /// Told about connection events, with the configured pool size.
+    handler: Option<(Arc<dyn CmapEventHandler>, Option<u32>)>,
+}
+
+impl Default for ConnectionEvents {
+    fn default() -> Self {
+        Self {
+            sender: broadcast::channel(EVENT_CAPACITY).0,
+            lost: Mutex::new(None),
+            in_use: AtomicUsize::new(0),
+            handler: None,
+        }
+    }
+}
+
+impl ConnectionEvents {
+    /// Create connection events also reported to `handler`.
+    pub(crate) fn with_handler(
+        handler: Option<Arc<dyn CmapEventHandler>>,
+        max_pool_size: Option<u32>,
+    ) -> Self {
+        Self {
+            handler: handler.map(|handler| (handler, max_pool_size)),
+            ..Self::default()
+        }
+    }
+
+    /// Stream events sent from now on. Events missed by a slow subscriber are skipped.
+    pub(crate) fn subscribe(&self) -> impl Stream<Item = ConnectionEvent> {
+        futures::stream::unfold(self.sender.subscribe(), |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => return Some((event, receiver)),
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+
+    /// Record that a lazy client connected.
+    pub(crate) fn connected(&self) {
+        self.created();
+        self.send(ConnectionEvent::Connected);
+    }
+
+    /// Record that a connection was created.
+    pub(crate) fn created(&self) {
+        if let Some((handler, _)) = &self.handler {
+            handler.connection_created(&ConnectionCreatedEvent {});
+        }
+    }
+
+    /// Record that a call started using the connection.
+    pub(crate) fn checked_out(&self) {
+        let in_use = self.in_use.fetch_add(1, Ordering::Relaxed) + 1;
+        if let Some((handler, max_pool_size)) = &self.handler {
+            handler.connection_checked_out(&ConnectionCheckedOutEvent {
+                in_use,
+                max_pool_size: *max_pool_size,
+            });
+        }
+    }
+
+    /// Record that a call that took `duration` finished using the connection.
+    pub(crate) fn checked_in(&self, duration: Duration) {
+        let in_use = self.in_use.fetch_sub(1, Ordering::Relaxed).saturating_sub(1);
+        if let Some((handler, _)) = &self.handler {
+            handler.connection_checked_in(&ConnectionCheckedInEvent { in_use, duration });
+        }
+    }
+
+    /// Record that a call is about to be sent.
+    pub(crate) fn before_call(&self) {
+        let mut lost = self.lost();
+        if let Some(attempts) = lost.as_mut() {
+            *attempts += 1;
+            let attempt = *attempts;
+            drop(lost);
+            if let Some((handler, _)) = &self.handler {
+                handler.connection_reconnecting(&ConnectionReconnectingEvent { attempt });
+            }
+            self.send(ConnectionEvent::Reconnecting { attempt });
+        }
+    }
+
+    /// Record that a call succeeded, returning whether it recovered a lost connection.
+    pub(crate) fn call_succeeded(&self) -> bool {
+        let recovered = self.lost().take().is_some();
+        if recovered {
+            self.send(ConnectionEvent::Connected);
+        }
+        recovered
+    }
+
+    /// Record that a call failed because the connection was lost.
+    pub(crate) fn connection_lost(&self, cause: String) {
+        let mut lost = self.lost();
+        if lost.is_none() {
+            *lost = Some(0);
+            drop(lost);
+            self.report_closed(ConnectionClosedReason::Error(cause.clone()));
+            self.send(ConnectionEvent::Disconnected { cause });
+        }
+    }
+
+    /// Record that the client was closed.
+    pub(crate) fn closed(&self) {
+        self.report_closed(ConnectionClosedReason::ClientClosed);
+        self.send(ConnectionEvent::Closed);
+    }
+
+    fn report_closed(&self, reason: ConnectionClosedReason) {
+        if let Some((handler, _)) = &self.handler {
+            handler.connection_closed(&ConnectionClosedEvent { reason });
+        }
+    }
+
+    fn lost(&self) -> std::sync::MutexGuard<'_, Option<u32>> {
+        self.lost.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn send(&self, event: ConnectionEvent) {
+        // Sending only fails when nobody is subscribed.
+        let _ = self.sender.send(event);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_connection_events() {
+        let events = ConnectionEvents::default();
+        let stream = events.subscribe();
+
+        events.before_call();
+        events.connection_lost("socket closed".to_string());
+        events.connection_lost("socket closed".to_string());
+        events.before_call();
+        events.before_call();
+        assert!(events.call_succeeded());
+        assert!(!events.call_succeeded());
+        events.closed();
+        drop(events);
+
+        let received: Vec<_> = stream.collect().await;
+        assert_eq!(
+            received,
+            vec![
+                ConnectionEvent::Disconnected {
+                    cause: "socket closed".to_string()
+                },
+                ConnectionEvent::Reconnecting { attempt: 1 },
+                ConnectionEvent::Reconnecting { attempt: 2 },
+                ConnectionEvent::Connected,
+                ConnectionEvent::Closed,
+            ]
+        );
+    }
+
+    #[derive(Debug, Default)]
+    struct Recorder(Mutex<Vec<String>>);
+
+    impl CmapEventHandler for Recorder {
+        fn connection_created(&self, _event: &ConnectionCreatedEvent) {
+            self.0.lock().unwrap().push("created".to_string());
+        }
+
+        fn connection_checked_out(&self, event: &ConnectionCheckedOutEvent) {
+            let line = format!("out {}/{:?}", event.in_use, event.max_pool_size);
+            self.0.lock().unwrap().push(line);
+        }
+
+        fn connection_checked_in(&self, event: &ConnectionCheckedInEvent) {
+            self.0.lock().unwrap().push(format!("in {}", event.in_use));
+        }
+
+        fn connection_closed(&self, event: &ConnectionClosedEvent) {
+            self.0.lock().unwrap().push(format!("closed {:?}", event.reason));
+        }
+
+        fn connection_reconnecting(&self, event: &ConnectionReconnectingEvent) {
+            self.0.lock().unwrap().push(format!("reconnecting {}", event.attempt));
+        }
+    }
+
+    #[test]
+    fn test_cmap_events() {
+        let recorder = Arc::new(Recorder::default());
+        let events = ConnectionEvents::with_handler(Some(recorder.clone()), Some(2));
+
+        events.connected();
+        events.checked_out();
+        events.checked_out();
+        events.checked_in(Duration::from_millis(1));
+        events.connection_lost("socket closed".to_string());
+        events.before_call();
+        events.checked_in(Duration::from_millis(1));
+        events.closed();
+
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                "created",
+                "out 1/Some(2)",
+                "out 2/Some(2)",
+                "in 1",
+                "closed Error(\"socket closed\")",
+                "reconnecting 1",
+                "in 0",
+                "closed ClientClosed",
+            ]
+        );
+    }
+}
@@ -0,0 +1,284 @@
+//! Running one query over many databases or deployments.
+//!
+//! [`aggregate_across`] runs the same pipeline on a collection of every
+//! given database at once and merges the results, e.g. to roll up a report
+//! over one database per tenant or per region. With a
+//! [`sort`](FanOutOptions::sort), the merged results are in that order, and
+//! [`skip`](FanOutOptions::skip) and [`limit`](FanOutOptions::limit) apply
+//! to the merged results. The sort and limit are also appended to every
+//! database's pipeline, so each returns no more documents than needed.
+//!
+//! [`find_all`] runs a find on the same namespace of several clients, e.g.
+//! one per cluster of a fleet, and streams the documents as they arrive,
+//! each tagged with the client it came from.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::fanout::{aggregate_across, FanOutOptions};
+//!
+//! let tenants = ["acme", "globex"].map(|name| client.database(name));
+//! let top = aggregate_across(
+//!     tenants,
+//!     "orders",
+//!     [doc! { "$group": { "_id": "$sku", "sold": { "$sum": "$quantity" } } }],
+//!     FanOutOptions::builder()
+//!         .sort(doc! { "sold": -1 })
+//!         .limit(10)
+//!         .database_field("tenant")
+//!         .build(),
+//! )
+//! .await?;
+//!
+//! let mut stuck = find_all(&clusters, "admin.jobs", doc! { "state": "stuck" }, None)?;
+//! while let Some(job) = stuck.next().await {
+//!     let job = job?;
+//!     println!("{}: {}", job.source, job.document);
+//! }
+//! ```
+
+use crate::client::MongoClient;
+use crate::collection::FindOptions;
+use crate::db::Database;
+use crate::error::{MongoError, Result};
+use crate::util::{compare_bson, get_path};
+use bson::{doc, Bson, Document};
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use std::cmp::Ordering;
+
+/// Options for [`aggregate_across`].
+#[derive(Debug, Clone, Default)]
+pub struct FanOutOptions {
+    /// Order of the merged results, e.g. `{ "total": -1 }`. Without one,
+    /// results follow the order of the databases.
+    pub sort: Option<Document>,
+    /// Merged results to skip.
+    pub skip: Option<u64>,
+    /// Most merged results to return.
+    pub limit: Option<u64>,
+    /// Field set to the name of the database each result came from.
+    pub database_field: Option<String>,
+}
+
+impl FanOutOptions {
+    /// Create a builder.
+    pub fn builder() -> FanOutOptionsBuilder {
+        FanOutOptionsBuilder::default()
+    }
+
+    /// The stages appended to each database's pipeline.
+    fn pushdown(&self) -> Vec<Document> {
+        let mut stages = Vec::new();
+        if let Some(ref sort) = self.sort {
+            stages.push(doc! { "$sort": sort.clone() });
+        }
+        if let Some(limit) = self.limit {
+            let limit = limit.saturating_add(self.skip.unwrap_or(0));
+            stages.push(doc! { "$limit": i64::try_from(limit).unwrap_or(i64::MAX) });
+        }
+        stages
+    }
+
+    /// Merge the results of each database, given in database order.
+    fn merge(&self, results: Vec<Vec<Document>>) -> Result<Vec<Document>> {
+        let mut merged: Vec<Document> = results.into_iter().flatten().collect();
+        if let Some(ref sort) = self.sort {
+            let keys = sort_keys(sort)?;
+            merged.sort_by(|a, b| {
+                keys.iter()
+                    .map(|(path, descending)| {
+                        let order = compare_bson(
+                            get_path(a, path).unwrap_or(&Bson::Null),
+                            get_path(b, path).unwrap_or(&Bson::Null),
+                        );
+                        if *descending {
+                            order.reverse()
+                        } else {
+                            order
+                        }
+                    })
+                    .find(|order| order.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        let skip = self.skip.unwrap_or(0).try_into().unwrap_or(usize::MAX);
+        let limit = self.limit.map_or(usize::MAX, |n| n.try_into().unwrap_or(usize::MAX));
+        Ok(merged.into_iter().skip(skip).take(limit).collect())
+    }
+}
+
+/// Builder for FanOutOptions.
+#[derive(Debug, Clone, Default)]
+pub struct FanOutOptionsBuilder {
+    options: FanOutOptions,
+}
+
+impl FanOutOptionsBuilder {
+    /// Set the order of the merged results.
+    pub fn sort(mut self, sort: Document) -> Self {
+        self.options.sort = Some(sort);
+        self
+    }
+
+    /// Set the number of merged results to skip.
+    pub fn skip(mut self, skip: u64) -> Self {
+        self.options.skip = Some(skip);
+        self
+    }
+
+    /// Set the most merged results to return.
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.options.limit = Some(limit);
+        self
+    }
+
+    /// Set the field recording which database each result came from.
+    pub fn database_field(mut self, field: impl Into<String>) -> Self {
+        self.options.database_field = Some(field.into());
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> FanOutOptions {
+        self.options
+    }
+}
+
+/// Run `pipeline` on the collection `collection` of every database
+/// concurrently and merge the results.
+///
+/// Fails if the pipeline fails on any database.
+pub async fn aggregate_across(
+    databases: impl IntoIterator<Item = Database>,
+    collection: &str,
+    pipeline: impl IntoIterator<Item = Document>,
+    options: FanOutOptions,
+) -> Result<Vec<Document>> {
+    if let Some(ref sort) = options.sort {
+        sort_keys(sort)?;
+    }
+    let mut pipeline: Vec<Document> = pipeline.into_iter().collect();
+    pipeline.extend(options.pushdown());
+
+    let runs = databases.into_iter().map(|db| {
+        let pipeline = pipeline.clone();
+        let database_field = options.database_field.clone();
+        async move {
+            let coll = db.collection::<Document>(collection);
+            let mut documents = coll.aggregate(pipeline).await?.collect().await?;
+            if let Some(field) = database_field {
+                for document in &mut documents {
+                    document.insert(field.as_str(), db.name());
+                }
+            }
+            Ok::<_, MongoError>(documents)
+        }
+    });
+    let results = futures::future::try_join_all(runs).await?;
+    options.merge(results)
+}
+
+/// A document returned by [`find_all`], with the client it came from.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourcedDocument {
+    /// Position of the client in the clients given to [`find_all`].
+    pub index: usize,
+    /// The client's connection URI, with credentials redacted.
+    pub source: String,
+    /// The document.
+    pub document: Document,
+}
+
+/// Find the documents matching `filter` in the namespace `ns`, such as
+/// `app.users`, of every client concurrently.
+///
+/// Documents are streamed as each client returns them, so documents of
+/// different clients are interleaved in no particular order. An error from
+/// one client is yielded in the stream without ending the others.
+pub fn find_all<'a>(
+    clients: impl IntoIterator<Item = &'a MongoClient>,
+    ns: &str,
+    filter: impl Into<Option<Document>>,
+    options: impl Into<Option<FindOptions>>,
+) -> Result<BoxStream<'static, Result<SourcedDocument>>> {
+    let (db, coll) = ns
+        .split_once('.')
+        .filter(|(db, coll)| !db.is_empty() && !coll.is_empty())
+        .ok_or_else(|| MongoError::invalid_argument(format!("invalid namespace {}", ns)))?;
+    let filter = filter.into().unwrap_or_default();
+    let options = options.into().unwrap_or_default();
+
+    let streams = clients.into_iter().enumerate().map(|(index, client)| {
+        let source = client.uri_redacted();
+        let collection = client.database(db).collection::<Document>(coll);
+        let (filter, options) = (filter.clone(), options.clone());
+        let find = async move { collection.find_with_options(filter, options).await };
+        futures::stream::once(find)
+            .try_flatten()
+            .map_ok(move |document| SourcedDocument {
+                index,
+                source: source.clone(),
+                document,
+            })
+            .boxed()
+    });
+    Ok(futures::stream::select_all(streams).boxed())
+}
+
+/// The fields of a sort specification, each with whether it is descending.
+fn sort_keys(sort: &Document) -> Result<Vec<(String, bool)>> {
+    sort.iter()
+        .map(|(path, direction)| match direction {
+            Bson::Int32(1) | Bson::Int64(1) => Ok((path.clone(), false)),
+            Bson::Int32(-1) | Bson::Int64(-1) => Ok((path.clone(), true)),
+            Bson::Double(d) if *d == 1.0 || *d == -1.0 => Ok((path.clone(), *d < 0.0)),
+            _ => Err(MongoError::invalid_argument(format!(
+                "sort direction of {} must be 1 or -1",
+                path
+            ))),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_sorted_results() {
+        let options = FanOutOptions::builder()
+            .sort(doc! { "total": -1, "name": 1 })
+            .skip(1)
+            .limit(2)
+            .build();
+        assert_eq!(
+            options.pushdown(),
+            vec![doc! { "$sort": { "total": -1, "name": 1 } }, doc! { "$limit": 3_i64 }]
+        );
+        let results = vec![
+            vec![doc! { "name": "a", "total": 9 }, doc! { "name": "c", "total": 4 }],
+            vec![doc! { "name": "b", "total": 7.5 }, doc! { "name": "d", "total": 4 }],
+        ];
+        let merged = options.merge(results).unwrap();
+        let expected = vec![doc! { "name": "b", "total": 7.5 }, doc! { "name": "c", "total": 4 }];
+        assert_eq!(merged, expected);
+    }
+
+    #[tokio::test]
+    async fn test_find_all_namespace() {
+        assert!(find_all(std::iter::empty(), "users", None, None).is_err());
+        assert!(find_all(std::iter::empty(), "app.", None, None).is_err());
+        let mut none = find_all(std::iter::empty(), "app.users", None, None).unwrap();
+        assert!(none.next().await.is_none());
+    }
+
+    #[test]
+    fn test_merge_unsorted_results() {
+        let options = FanOutOptions::default();
+        assert!(options.pushdown().is_empty());
+        let results = vec![vec![doc! { "n": 2 }], vec![], vec![doc! { "n": 1 }]];
+        assert_eq!(options.merge(results).unwrap(), vec![doc! { "n": 2 }, doc! { "n": 1 }]);
+        assert!(sort_keys(&doc! { "n": "asc" }).is_err());
+    }
+}
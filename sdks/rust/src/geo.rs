@@ -0,0 +1,359 @@
+//! Typed geospatial queries.
+//!
+//! GeoJSON geometries can be stored directly in documents and used to build
+//! `$near`, `$geoWithin` and `$geoIntersects` filters. Coordinates are
+//! `[longitude, latitude]`, as in GeoJSON.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::geo::{self, NearOptions, Point};
+//!
+//! places.create_2dsphere_index("location").await?;
+//!
+//! let here = Point::new(-73.9667, 40.78);
+//! let options = NearOptions::builder().max_distance(1000.0).build();
+//! let nearby = places.find(geo::near("location", &here, options)).await?;
+//! ```
+
+use bson::{doc, Bson, Document};
+use serde::{Deserialize, Serialize};
+
+/// Mean radius of the earth in meters, as used by `$centerSphere`.
+pub const EARTH_RADIUS_METERS: f64 = 6_378_100.0;
+
+/// A `[longitude, latitude]` coordinate pair.
+pub type Position = [f64; 2];
+
+/// A GeoJSON point.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct Point {
+    /// The point's position.
+    pub coordinates: Position,
+}
+
+impl Point {
+    /// Create a point from a longitude and latitude.
+    pub fn new(longitude: f64, latitude: f64) -> Self {
+        Self {
+            coordinates: [longitude, latitude],
+        }
+    }
+
+    /// Get the longitude.
+    pub fn longitude(&self) -> f64 {
+        self.coordinates[0]
+    }
+
+    /// Get the latitude.
+    pub fn latitude(&self) -> f64 {
+        self.coordinates[1]
+    }
+}
+
+/// A GeoJSON line string.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct LineString {
+    /// The positions along the line.
+    pub coordinates: Vec<Position>,
+}
+
+impl LineString {
+    /// Create a line string through the given positions.
+    pub fn new(positions: impl IntoIterator<Item = Position>) -> Self {
+        Self {
+            coordinates: positions.into_iter().collect(),
+        }
+    }
+}
+
+/// A GeoJSON polygon.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub struct Polygon {
+    /// Linear rings: the exterior ring followed by any holes.
+    pub coordinates: Vec<Vec<Position>>,
+}
+
+impl Polygon {
+    /// Create a polygon from its exterior ring.
+    ///
+    /// The ring is closed automatically if its last position differs from the first.
+    pub fn new(exterior: impl IntoIterator<Item = Position>) -> Self {
+        Self {
+            coordinates: vec![close_ring(exterior)],
+        }
+    }
+
+    /// Add a hole to the polygon.
+    pub fn with_hole(mut self, hole: impl IntoIterator<Item = Position>) -> Self {
+        self.coordinates.push(close_ring(hole));
+        self
+    }
+}
+
+/// Any supported GeoJSON geometry.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Geometry {
+    /// A point.
+    Point(Point),
+    /// A line string.
+    LineString(LineString),
+    /// A polygon.
+    Polygon(Polygon),
+}
+
+impl Geometry {
+    /// Convert the geometry to a GeoJSON document.
+    pub fn to_document(&self) -> Document {
+        match self {
+            Geometry::Point(point) => doc! {
+                "type": "Point",
+                "coordinates": position_bson(&point.coordinates),
+            },
+            Geometry::LineString(line) => doc! {
+                "type": "LineString",
+                "coordinates": ring_bson(&line.coordinates),
+            },
+            Geometry::Polygon(polygon) => doc! {
+                "type": "Polygon",
+                "coordinates": polygon
+                    .coordinates
+                    .iter()
+                    .map(|ring| ring_bson(ring))
+                    .collect::<Vec<_>>(),
+            },
+        }
+    }
+}
+
+impl From<Point> for Geometry {
+    fn from(point: Point) -> Self {
+        Geometry::Point(point)
+    }
+}
+
+impl From<LineString> for Geometry {
+    fn from(line: LineString) -> Self {
+        Geometry::LineString(line)
+    }
+}
+
+impl From<Polygon> for Geometry {
+    fn from(polygon: Polygon) -> Self {
+        Geometry::Polygon(polygon)
+    }
+}
+
+impl From<Geometry> for Bson {
+    fn from(geometry: Geometry) -> Self {
+        Bson::Document(geometry.to_document())
+    }
+}
+
+impl From<Point> for Bson {
+    fn from(point: Point) -> Self {
+        Geometry::from(point).into()
+    }
+}
+
+impl From<LineString> for Bson {
+    fn from(line: LineString) -> Self {
+        Geometry::from(line).into()
+    }
+}
+
+impl From<Polygon> for Bson {
+    fn from(polygon: Polygon) -> Self {
+        Geometry::from(polygon).into()
+    }
+}
+
+/// Options for [`near`].
+#[derive(Debug, Clone, Default)]
+pub struct NearOptions {
+    /// Maximum distance in meters.
+    pub max_distance: Option<f64>,
+    /// Minimum distance in meters.
+    pub min_distance: Option<f64>,
+}
+
+impl NearOptions {
+    /// Create a new builder.
+    pub fn builder() -> NearOptionsBuilder {
+        NearOptionsBuilder::default()
+    }
+}
+
+/// Builder for NearOptions.
+#[derive(Debug, Default)]
+pub struct NearOptionsBuilder {
+    options: NearOptions,
+}
+
+impl NearOptionsBuilder {
+    /// Set the maximum distance in meters.
+    pub fn max_distance(mut self, meters: f64) -> Self {
+        self.options.max_distance = Some(meters);
+        self
+    }
+
+    /// Set the minimum distance in meters.
+    pub fn min_distance(mut self, meters: f64) -> Self {
+        self.options.min_distance = Some(meters);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> NearOptions {
+        self.options
+    }
+}
+
+/// Filter for documents whose `field` is near `point`, sorted nearest first.
+///
+/// Requires a `2dsphere` index on `field`.
+pub fn near(field: &str, point: &Point, options: impl Into<Option<NearOptions>>) -> Document {
+    let options = options.into().unwrap_or_default();
+    let mut near = doc! { "$geometry": *point };
+    if let Some(max_distance) = options.max_distance {
+        near.insert("$maxDistance", max_distance);
+    }
+    if let Some(min_distance) = options.min_distance {
+        near.insert("$minDistance", min_distance);
+    }
+    doc! { field: { "$near": near } }
+}
+
+/// Filter for documents whose `field` lies entirely within `geometry`.
+pub fn geo_within(field: &str, geometry: impl Into<Geometry>) -> Document {
+    doc! { field: { "$geoWithin": { "$geometry": geometry.into() } } }
+}
+
+/// Filter for documents whose `field` lies within `radius` meters of `center`.
+///
+/// Unlike [`near`], this does not require an index and does not sort results.
+pub fn geo_within_radius(field: &str, center: &Point, radius: f64) -> Document {
+    doc! {
+        field: {
+            "$geoWithin": {
+                "$centerSphere": [position_bson(&center.coordinates), radius / EARTH_RADIUS_METERS],
+            }
+        }
+    }
+}
+
+/// Filter for documents whose `field` intersects `geometry`.
+pub fn geo_intersects(field: &str, geometry: impl Into<Geometry>) -> Document {
+    doc! { field: { "$geoIntersects": { "$geometry": geometry.into() } } }
+}
+
+/// Close a linear ring by repeating its first position if needed.
+fn close_ring(ring: impl IntoIterator<Item = Position>) -> Vec<Position> {
+    let mut ring: Vec<Position> = ring.into_iter().collect();
+    if let (Some(first), Some(last)) = (ring.first(), ring.last()) {
+        if first != last {
+            ring.push(*first);
+        }
+    }
+    ring
+}
+
+fn position_bson(position: &Position) -> Bson {
+    Bson::Array(vec![Bson::Double(position[0]), Bson::Double(position[1])])
+}
+
+fn ring_bson(ring: &[Position]) -> Bson {
+    Bson::Array(ring.iter().map(position_bson).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_point_serialization() {
+        let point = Point::new(-73.97, 40.77);
+        let json = serde_json::to_value(point).unwrap();
+        assert_eq!(json, serde_json::json!({ "type": "Point", "coordinates": [-73.97, 40.77] }));
+
+        let parsed: Point = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, point);
+        assert_eq!(parsed.longitude(), -73.97);
+        assert_eq!(parsed.latitude(), 40.77);
+    }
+
+    #[test]
+    fn test_point_rejects_other_types() {
+        let json = serde_json::json!({ "type": "LineString", "coordinates": [[0.0, 0.0]] });
+        assert!(serde_json::from_value::<Point>(json).is_err());
+    }
+
+    #[test]
+    fn test_geometry_deserialization() {
+        let json = serde_json::json!({ "type": "LineString", "coordinates": [[0.0, 0.0], [1.0, 1.0]] });
+        let geometry: Geometry = serde_json::from_value(json).unwrap();
+        assert_eq!(geometry, Geometry::LineString(LineString::new([[0.0, 0.0], [1.0, 1.0]])));
+    }
+
+    #[test]
+    fn test_polygon_closes_ring() {
+        let polygon = Polygon::new([[0.0, 0.0], [4.0, 0.0], [4.0, 4.0]])
+            .with_hole([[1.0, 1.0], [2.0, 1.0], [2.0, 2.0], [1.0, 1.0]]);
+        assert_eq!(polygon.coordinates[0].len(), 4);
+        assert_eq!(polygon.coordinates[0][0], polygon.coordinates[0][3]);
+        assert_eq!(polygon.coordinates[1].len(), 4);
+    }
+
+    #[test]
+    fn test_geometry_document_matches_serde() {
+        let polygon = Polygon::new([[0.0, 0.0], [4.0, 0.0], [4.0, 4.0]]);
+        let from_serde = bson::to_document(&polygon).unwrap();
+        assert_eq!(Geometry::from(polygon).to_document(), from_serde);
+    }
+
+    #[test]
+    fn test_near_filter() {
+        let options = NearOptions::builder().max_distance(500.0).build();
+        let filter = near("location", &Point::new(1.0, 2.0), options);
+        let spec = filter.get_document("location").unwrap().get_document("$near").unwrap();
+        assert_eq!(spec.get_f64("$maxDistance").unwrap(), 500.0);
+        assert!(!spec.contains_key("$minDistance"));
+        assert_eq!(spec.get_document("$geometry").unwrap().get_str("type").unwrap(), "Point");
+
+        let filter = near("location", &Point::new(1.0, 2.0), None);
+        let spec = filter.get_document("location").unwrap().get_document("$near").unwrap();
+        assert_eq!(spec.len(), 1);
+    }
+
+    #[test]
+    fn test_geo_within_and_intersects_filters() {
+        let polygon = Polygon::new([[0.0, 0.0], [4.0, 0.0], [4.0, 4.0]]);
+        let filter = geo_within("location", polygon.clone());
+        assert!(filter
+            .get_document("location")
+            .unwrap()
+            .get_document("$geoWithin")
+            .unwrap()
+            .contains_key("$geometry"));
+
+        let filter = geo_intersects("route", polygon);
+        assert!(filter.get_document("route").unwrap().contains_key("$geoIntersects"));
+    }
+
+    #[test]
+    fn test_geo_within_radius_filter() {
+        let filter = geo_within_radius("location", &Point::new(1.0, 2.0), EARTH_RADIUS_METERS);
+        let center_sphere = filter
+            .get_document("location")
+            .unwrap()
+            .get_document("$geoWithin")
+            .unwrap()
+            .get_array("$centerSphere")
+            .unwrap();
+        assert_eq!(center_sphere[1], Bson::Double(1.0));
+    }
+}
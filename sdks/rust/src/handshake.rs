@@ -0,0 +1,181 @@
+//! Protocol version negotiation with the .do backend.
+//!
+//! When a client connects it sends `mongo.hello` with the SDK's
+//! [`PROTOCOL_VERSION`] and a description of the client. The server answers
+//! with its own protocol version and the capabilities it supports, and
+//! operations that need a newer RPC feature check for it first, failing with
+//! a clear [`MongoError::Command`] instead of an "unknown method" error or
+//! an option the server silently ignores.
+//!
+//! Backends that predate the handshake are treated as protocol version 0
+//! with no capabilities.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let hello = client.hello().await?;
+//! if !hello.supports(capability::TAILABLE_CURSORS) {
+//!     eprintln!("server {} cannot tail collections", hello.server_version.unwrap_or_default());
+//! }
+//! ```
+
+use crate::client::DriverInfo;
+use crate::error::{MongoError, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+
+/// The RPC protocol version this SDK speaks.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// The platform reported in the handshake.
+const PLATFORM: &str = "Rust, tokio";
+
+/// Server error code for a command the server does not support.
+pub const COMMAND_NOT_SUPPORTED_CODE: i32 = 115;
+
+/// Capabilities a server can report in its hello reply.
+///
+/// Everything the SDK did before the handshake existed needs no capability.
+pub mod capability {
+    /// Tailable and `awaitData` find cursors.
+    pub const TAILABLE_CURSORS: &str = "tailableCursors";
+}
+
+/// The server's reply to the handshake.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerHello {
+    /// Highest protocol version the server speaks.
+    #[serde(default)]
+    pub protocol_version: u32,
+    /// Server version, for logs.
+    #[serde(default)]
+    pub server_version: Option<String>,
+    /// Capabilities the server supports, see [`capability`].
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+}
+
+impl ServerHello {
+    /// Whether the server supports `capability`.
+    pub fn supports(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
+    /// Fail unless the server supports `capability`.
+    pub fn require(&self, capability: &str) -> Result<()> {
+        if self.supports(capability) {
+            Ok(())
+        } else {
+            Err(MongoError::command(
+                COMMAND_NOT_SUPPORTED_CODE,
+                format!(
+                    "the server (protocol version {}) does not support {}",
+                    self.protocol_version, capability
+                ),
+            ))
+        }
+    }
+}
+
+/// The arguments of `mongo.hello`.
+pub(crate) fn hello_args(client_metadata: &JsonValue) -> Vec<JsonValue> {
+    vec![serde_json::json!({
+        "protocolVersion": PROTOCOL_VERSION,
+        "client": client_metadata,
+    })]
+}
+
+/// Describe this client to the server, in the shape of MongoDB's client
+/// metadata: `application`, `driver`, `os` and `platform`.
+///
+/// A wrapping library is appended to the driver name, version and platform
+/// after a `|`.
+pub(crate) fn client_metadata(
+    app_name: Option<&str>,
+    driver_info: Option<&DriverInfo>,
+) -> JsonValue {
+    let mut name = env!("CARGO_PKG_NAME").to_string();
+    let mut version = env!("CARGO_PKG_VERSION").to_string();
+    let mut platform = PLATFORM.to_string();
+    if let Some(info) = driver_info {
+        for (field, value) in [
+            (&mut name, Some(&info.name)),
+            (&mut version, info.version.as_ref()),
+            (&mut platform, info.platform.as_ref()),
+        ] {
+            if let Some(value) = value {
+                field.push('|');
+                field.push_str(value);
+            }
+        }
+    }
+
+    let mut metadata = serde_json::json!({
+        "driver": { "name": name, "version": version },
+        "os": { "type": std::env::consts::OS, "architecture": std::env::consts::ARCH },
+        "platform": platform,
+    });
+    if let Some(name) = app_name {
+        metadata["application"] = serde_json::json!({ "name": name });
+    }
+    metadata
+}
+
+/// Read the reply to `mongo.hello`, treating a server without the method as
+/// protocol version 0.
+pub(crate) fn parse_hello(reply: Result<JsonValue>) -> Result<ServerHello> {
+    match reply {
+        Ok(reply) => serde_json::from_value(reply)
+            .map_err(|e| MongoError::Deserialization(format!("invalid hello reply: {}", e))),
+        Err(MongoError::UnknownMethod(_)) => Ok(ServerHello::default()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_metadata() {
+        let metadata = client_metadata(Some("checkout"), None);
+        assert_eq!(metadata["driver"]["name"], "mongo-do");
+        assert_eq!(metadata["driver"]["version"], crate::version());
+        assert_eq!(metadata["os"]["type"], std::env::consts::OS);
+        assert_eq!(metadata["platform"], PLATFORM);
+        assert_eq!(metadata["application"]["name"], "checkout");
+        assert!(client_metadata(None, None).get("application").is_none());
+
+        let odm = DriverInfo::builder().name("edge-odm").version("2.1.0").build();
+        let wrapped = client_metadata(None, Some(&odm));
+        assert_eq!(wrapped["driver"]["name"], "mongo-do|edge-odm");
+        assert_eq!(wrapped["driver"]["version"], format!("{}|2.1.0", crate::version()));
+        assert_eq!(wrapped["platform"], PLATFORM);
+
+        let args = hello_args(&metadata);
+        assert_eq!(args[0]["protocolVersion"], PROTOCOL_VERSION);
+        assert_eq!(args[0]["client"], metadata);
+    }
+
+    #[test]
+    fn test_parse_hello() {
+        let hello = parse_hello(Ok(serde_json::json!({
+            "protocolVersion": 1,
+            "serverVersion": "2.3.0",
+            "capabilities": ["tailableCursors"],
+        })))
+        .unwrap();
+        assert_eq!(hello.protocol_version, 1);
+        assert!(hello.supports(capability::TAILABLE_CURSORS));
+        assert!(!hello.supports("columnstore"));
+        assert!(hello.require(capability::TAILABLE_CURSORS).is_ok());
+        let err = hello.require("columnstore").unwrap_err();
+        assert_eq!(err.code(), Some(COMMAND_NOT_SUPPORTED_CODE));
+
+        // Old backends do not know the method.
+        let old = parse_hello(Err(MongoError::UnknownMethod("mongo.hello".to_string()))).unwrap();
+        assert_eq!(old, ServerHello::default());
+        assert!(parse_hello(Err(MongoError::Timeout)).is_err());
+    }
+}
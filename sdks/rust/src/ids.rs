@@ -0,0 +1,389 @@
+//! Time-sortable document IDs.
+//!
+//! A [`Ulid`] or [`UuidV7`] starts with its creation time in milliseconds, so
+//! IDs sort in insertion order like object IDs, and a time range of
+//! documents is a range of `_id`s that the `_id` index answers without a
+//! separate timestamp field.
+//!
+//! ULIDs are stored as 26-character strings and UUIDv7s as binary subtype
+//! `04`, the UUID representation of every MongoDB driver. Both sort by time
+//! in the server's comparison order.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::ids::Ulid;
+//!
+//! events.insert_one(doc! { "_id": Ulid::new(), "kind": "login" }).await?;
+//!
+//! let now = SystemTime::now();
+//! let last_hour = Ulid::ids_between(now - Duration::from_secs(3600), now);
+//! let recent = events.find(last_hour).await?;
+//! ```
+
+use crate::error::{MongoError, Result};
+use bson::oid::ObjectId;
+use bson::{doc, Bson, DateTime, Document};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::hash_map::RandomState;
+use std::fmt::{self, Write};
+use std::hash::{BuildHasher, Hasher};
+use std::str::FromStr;
+
+/// Crockford's base32 alphabet, used by ULIDs.
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Length of a ULID string.
+const ULID_LEN: usize = 26;
+
+/// Largest timestamp an ID can hold: 48 bits of milliseconds.
+const MAX_TIMESTAMP_MS: u64 = (1 << 48) - 1;
+
+/// A ULID: 48 bits of milliseconds since the Unix epoch followed by 80
+/// random bits, written as 26 characters of Crockford base32.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ulid(u128);
+
+impl Ulid {
+    /// Create a ULID for the current time.
+    pub fn new() -> Self {
+        Self::at(DateTime::now())
+    }
+
+    /// Create a ULID for `time`.
+    pub fn at(time: impl Into<DateTime>) -> Self {
+        Self::from_parts(timestamp_ms(time.into()), random_bits())
+    }
+
+    /// Create a ULID from a millisecond timestamp and random bits. Only the
+    /// low 48 bits of the timestamp and 80 bits of the randomness are used.
+    pub fn from_parts(timestamp_ms: u64, random: u128) -> Self {
+        let timestamp = u128::from(timestamp_ms & MAX_TIMESTAMP_MS);
+        Ulid(timestamp << 80 | random & ((1 << 80) - 1))
+    }
+
+    /// The smallest ULID at `time`, for range queries.
+    pub fn min_at(time: impl Into<DateTime>) -> Self {
+        Self::from_parts(timestamp_ms(time.into()), 0)
+    }
+
+    /// When the ULID was created.
+    pub fn timestamp(&self) -> DateTime {
+        DateTime::from_millis((self.0 >> 80) as i64)
+    }
+
+    /// A filter on `_id` matching ULIDs created from `start` up to, but
+    /// excluding, `end`.
+    pub fn ids_between(start: impl Into<DateTime>, end: impl Into<DateTime>) -> Document {
+        doc! {
+            "_id": {
+                "$gte": Ulid::min_at(start),
+                "$lt": Ulid::min_at(end),
+            }
+        }
+    }
+
+    /// The ULID as a 128-bit number.
+    pub fn to_u128(self) -> u128 {
+        self.0
+    }
+}
+
+impl Default for Ulid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for Ulid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in (0..ULID_LEN).rev() {
+            f.write_char(CROCKFORD[(self.0 >> (5 * i)) as usize & 0x1f] as char)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Ulid {
+    type Err = MongoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || MongoError::invalid_argument(format!("invalid ULID '{}'", s));
+        if s.len() != ULID_LEN {
+            return Err(invalid());
+        }
+        // 26 characters hold 130 bits; the first may only use the low 3.
+        if s.as_bytes()[0] > b'7' {
+            return Err(invalid());
+        }
+        let mut value: u128 = 0;
+        for c in s.bytes() {
+            let digit = CROCKFORD
+                .iter()
+                .position(|&d| d == c.to_ascii_uppercase())
+                .ok_or_else(invalid)?;
+            value = value << 5 | digit as u128;
+        }
+        Ok(Ulid(value))
+    }
+}
+
+impl From<Ulid> for Bson {
+    fn from(id: Ulid) -> Self {
+        Bson::String(id.to_string())
+    }
+}
+
+impl TryFrom<&Bson> for Ulid {
+    type Error = MongoError;
+
+    fn try_from(value: &Bson) -> Result<Self> {
+        match value {
+            Bson::String(s) => s.parse(),
+            other => Err(MongoError::invalid_argument(format!(
+                "expected a ULID string, got {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+impl Serialize for Ulid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Ulid {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A version 7 UUID: 48 bits of milliseconds since the Unix epoch, the
+/// version and variant, and 74 random bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UuidV7([u8; 16]);
+
+impl UuidV7 {
+    /// Create a UUIDv7 for the current time.
+    pub fn new() -> Self {
+        Self::at(DateTime::now())
+    }
+
+    /// Create a UUIDv7 for `time`.
+    pub fn at(time: impl Into<DateTime>) -> Self {
+        Self::from_parts(timestamp_ms(time.into()), random_bits())
+    }
+
+    /// Create a UUIDv7 from a millisecond timestamp and random bits. Only
+    /// the low 48 bits of the timestamp and 74 bits of the randomness are
+    /// used.
+    pub fn from_parts(timestamp_ms: u64, random: u128) -> Self {
+        let timestamp = u128::from(timestamp_ms & MAX_TIMESTAMP_MS);
+        let rand_a = (random >> 62) & 0xfff;
+        let rand_b = random & ((1 << 62) - 1);
+        let value = timestamp << 80 | 0x7 << 76 | rand_a << 64 | 0b10 << 62 | rand_b;
+        UuidV7(value.to_be_bytes())
+    }
+
+    /// The smallest UUIDv7 at `time`, for range queries.
+    pub fn min_at(time: impl Into<DateTime>) -> Self {
+        Self::from_parts(timestamp_ms(time.into()), 0)
+    }
+
+    /// When the UUID was created.
+    pub fn timestamp(&self) -> DateTime {
+        let mut millis = [0u8; 8];
+        millis[2..].copy_from_slice(&self.0[..6]);
+        DateTime::from_millis(i64::from_be_bytes(millis))
+    }
+
+    /// A filter on `_id` matching UUIDv7s created from `start` up to, but
+    /// excluding, `end`.
+    pub fn ids_between(start: impl Into<DateTime>, end: impl Into<DateTime>) -> Document {
+        doc! {
+            "_id": {
+                "$gte": UuidV7::min_at(start),
+                "$lt": UuidV7::min_at(end),
+            }
+        }
+    }
+
+    /// The UUID's bytes.
+    pub fn bytes(&self) -> [u8; 16] {
+        self.0
+    }
+
+    /// Read a UUID, failing unless it is version 7.
+    fn from_uuid(uuid: bson::Uuid) -> Result<Self> {
+        let bytes = uuid.bytes();
+        if bytes[6] >> 4 != 7 {
+            return Err(MongoError::invalid_argument(format!(
+                "UUID {} is not version 7",
+                uuid
+            )));
+        }
+        Ok(UuidV7(bytes))
+    }
+}
+
+impl Default for UuidV7 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for UuidV7 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        bson::Uuid::from_bytes(self.0).fmt(f)
+    }
+}
+
+impl FromStr for UuidV7 {
+    type Err = MongoError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let uuid = bson::Uuid::parse_str(s)
+            .map_err(|_| MongoError::invalid_argument(format!("invalid UUID '{}'", s)))?;
+        Self::from_uuid(uuid)
+    }
+}
+
+impl From<UuidV7> for bson::Uuid {
+    fn from(id: UuidV7) -> Self {
+        bson::Uuid::from_bytes(id.0)
+    }
+}
+
+impl From<UuidV7> for Bson {
+    fn from(id: UuidV7) -> Self {
+        Bson::Binary(bson::Uuid::from(id).into())
+    }
+}
+
+impl TryFrom<&Bson> for UuidV7 {
+    type Error = MongoError;
+
+    fn try_from(value: &Bson) -> Result<Self> {
+        match value {
+            Bson::Binary(binary) => {
+                let uuid = binary
+                    .to_uuid()
+                    .map_err(|e| MongoError::invalid_argument(e.to_string()))?;
+                Self::from_uuid(uuid)
+            }
+            Bson::String(s) => s.parse(),
+            other => Err(MongoError::invalid_argument(format!(
+                "expected a UUID, got {:?}",
+                other
+            ))),
+        }
+    }
+}
+
+/// Serialized as binary subtype `04`, like other UUIDs.
+impl Serialize for UuidV7 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        bson::Binary::from(bson::Uuid::from(*self)).serialize(serializer)
+    }
+}
+
+/// Reads binary subtype `04` or a string.
+impl<'de> Deserialize<'de> for UuidV7 {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let uuid = bson::Uuid::deserialize(deserializer)?;
+        Self::from_uuid(uuid).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Milliseconds since the epoch, clamped to what an ID can hold.
+fn timestamp_ms(time: DateTime) -> u64 {
+    (time.timestamp_millis().max(0) as u64).min(MAX_TIMESTAMP_MS)
+}
+
+/// 80 bits that differ between calls in this process and between processes.
+///
+/// An object ID contributes a per-process random value and a counter, and a
+/// randomly keyed hash of the time the rest.
+fn random_bits() -> u128 {
+    let oid = ObjectId::new().bytes();
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_i64(DateTime::now().timestamp_millis());
+    let mut bits = [0u8; 16];
+    bits[6..14].copy_from_slice(&oid[4..]);
+    bits[14..].copy_from_slice(&hasher.finish().to_be_bytes()[..2]);
+    u128::from_be_bytes(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ulid() {
+        let ulid = Ulid::from_parts(1_469_918_176_385, 0);
+        assert_eq!(ulid.to_string(), "01ARYZ6S410000000000000000");
+        assert_eq!(ulid.timestamp(), DateTime::from_millis(1_469_918_176_385));
+        assert_eq!("01aryz6s410000000000000000".parse::<Ulid>().unwrap(), ulid);
+        let max = Ulid::from_parts(MAX_TIMESTAMP_MS, u128::MAX);
+        assert_eq!(max.to_string(), format!("7{}", "Z".repeat(25)));
+        assert!("81ARYZ6S410000000000000000".parse::<Ulid>().is_err());
+        assert!("01ARYZ6S41000000000000000U".parse::<Ulid>().is_err());
+        assert!("01ARYZ".parse::<Ulid>().is_err());
+
+        let (a, b) = (Ulid::new(), Ulid::new());
+        assert_ne!(a, b);
+        assert_eq!(a.to_string().parse::<Ulid>().unwrap(), a);
+        let earlier = Ulid::at(DateTime::from_millis(1));
+        let later = Ulid::at(DateTime::from_millis(2));
+        assert!(earlier < later);
+        assert!(earlier.to_string() < later.to_string());
+
+        assert_eq!(Bson::from(a), Bson::String(a.to_string()));
+        assert_eq!(Ulid::try_from(&Bson::from(a)).unwrap(), a);
+        let json = serde_json::to_value(a).unwrap();
+        assert_eq!(json, serde_json::json!(a.to_string()));
+        assert_eq!(serde_json::from_value::<Ulid>(json).unwrap(), a);
+    }
+
+    #[test]
+    fn test_uuid_v7() {
+        let uuid = UuidV7::from_parts(0x0189_7f3e_2b00, u128::MAX);
+        let text = uuid.to_string();
+        assert!(text.starts_with("01897f3e-2b00-7fff-bfff-"), "{}", text);
+        assert_eq!(uuid.timestamp(), DateTime::from_millis(0x0189_7f3e_2b00));
+        assert_eq!(text.parse::<UuidV7>().unwrap(), uuid);
+        assert!("550e8400-e29b-41d4-a716-446655440000".parse::<UuidV7>().is_err());
+
+        let (a, b) = (UuidV7::new(), UuidV7::new());
+        assert_ne!(a, b);
+        assert!(UuidV7::at(DateTime::from_millis(1)) < UuidV7::at(DateTime::from_millis(2)));
+
+        let bson = Bson::from(a);
+        match bson {
+            Bson::Binary(ref binary) => {
+                assert_eq!(binary.subtype, bson::spec::BinarySubtype::Uuid)
+            }
+            ref other => panic!("expected binary, got {:?}", other),
+        }
+        assert_eq!(UuidV7::try_from(&bson).unwrap(), a);
+        let json = serde_json::to_value(a).unwrap();
+        assert_eq!(json["$binary"]["subType"], "04");
+    }
+
+    #[test]
+    fn test_ids_between() {
+        let (start, end) = (DateTime::from_millis(1_000), DateTime::from_millis(2_000));
+        assert_eq!(
+            Ulid::ids_between(start, end),
+            doc! { "_id": { "$gte": Ulid::min_at(start), "$lt": Ulid::min_at(end) } }
+        );
+        let filter = UuidV7::ids_between(start, end);
+        let range = filter.get_document("_id").unwrap();
+        assert_eq!(UuidV7::try_from(range.get("$gte").unwrap()).unwrap().timestamp(), start);
+        assert_eq!(UuidV7::try_from(range.get("$lt").unwrap()).unwrap().timestamp(), end);
+        assert_eq!(Ulid::min_at(DateTime::from_millis(-5)).timestamp(), DateTime::from_millis(0));
+    }
+}
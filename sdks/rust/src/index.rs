@@ -0,0 +1,280 @@
+//! Index declarations.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::index::{IndexModel, IndexOptions};
+//!
+//! let indexes = [
+//!     IndexModel::new(doc! { "email": 1 }, IndexOptions::builder().unique(true).build()),
+//!     IndexModel::new(doc! { "createdAt": -1 }, None),
+//! ];
+//! users.ensure_indexes(&indexes, false).await?;
+//! ```
+
+use crate::collation::Collation;
+use bson::{Bson, Document};
+use std::time::Duration;
+
+/// Name of the index MongoDB creates on `_id` for every collection.
+pub const ID_INDEX_NAME: &str = "_id_";
+
+/// Options for creating an index.
+#[derive(Debug, Clone, Default)]
+pub struct IndexOptions {
+    /// Index name. Defaults to a name derived from the keys.
+    pub name: Option<String>,
+    /// Whether the index rejects duplicate keys.
+    pub unique: Option<bool>,
+    /// Whether the index skips documents missing the indexed fields.
+    pub sparse: Option<bool>,
+    /// Only index documents matching this filter.
+    pub partial_filter_expression: Option<Document>,
+    /// Fields included in or excluded from a wildcard (`$**`) index.
+    pub wildcard_projection: Option<Document>,
+    /// Whether the index is hidden from the query planner.
+    pub hidden: Option<bool>,
+    /// How long documents live after the indexed date, for TTL indexes.
+    pub expire_after: Option<Duration>,
+    /// How strings are compared in the index.
+    pub collation: Option<Collation>,
+}
+
+impl IndexOptions {
+    /// Create a new builder.
+    pub fn builder() -> IndexOptionsBuilder {
+        IndexOptionsBuilder::default()
+    }
+
+    /// Convert to the options document sent to the server.
+    pub fn to_document(&self) -> Document {
+        let mut options = Document::new();
+        if let Some(ref name) = self.name {
+            options.insert("name", name.as_str());
+        }
+        if let Some(unique) = self.unique {
+            options.insert("unique", unique);
+        }
+        if let Some(sparse) = self.sparse {
+            options.insert("sparse", sparse);
+        }
+        if let Some(ref filter) = self.partial_filter_expression {
+            options.insert("partialFilterExpression", filter.clone());
+        }
+        if let Some(ref projection) = self.wildcard_projection {
+            options.insert("wildcardProjection", projection.clone());
+        }
+        if let Some(hidden) = self.hidden {
+            options.insert("hidden", hidden);
+        }
+        if let Some(expire_after) = self.expire_after {
+            options.insert("expireAfterSeconds", expire_after.as_secs() as i64);
+        }
+        if let Some(ref collation) = self.collation {
+            options.insert("collation", collation.to_document());
+        }
+        options
+    }
+}
+
+impl From<IndexOptions> for Option<Document> {
+    fn from(options: IndexOptions) -> Self {
+        Some(options.to_document())
+    }
+}
+
+/// Builder for IndexOptions.
+#[derive(Debug, Default)]
+pub struct IndexOptionsBuilder {
+    options: IndexOptions,
+}
+
+impl IndexOptionsBuilder {
+    /// Set the index name.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.options.name = Some(name.into());
+        self
+    }
+
+    /// Set whether the index is unique.
+    pub fn unique(mut self, unique: bool) -> Self {
+        self.options.unique = Some(unique);
+        self
+    }
+
+    /// Set whether the index is sparse.
+    pub fn sparse(mut self, sparse: bool) -> Self {
+        self.options.sparse = Some(sparse);
+        self
+    }
+
+    /// Only index documents matching `filter`.
+    pub fn partial_filter_expression(mut self, filter: Document) -> Self {
+        self.options.partial_filter_expression = Some(filter);
+        self
+    }
+
+    /// Set the fields covered by a wildcard index.
+    pub fn wildcard_projection(mut self, projection: Document) -> Self {
+        self.options.wildcard_projection = Some(projection);
+        self
+    }
+
+    /// Set whether the index is hidden from the query planner.
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.options.hidden = Some(hidden);
+        self
+    }
+
+    /// Expire documents this long after the indexed date.
+    pub fn expire_after(mut self, expire_after: Duration) -> Self {
+        self.options.expire_after = Some(expire_after);
+        self
+    }
+
+    /// Set the collation.
+    pub fn collation(mut self, collation: Collation) -> Self {
+        self.options.collation = Some(collation);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> IndexOptions {
+        self.options
+    }
+}
+
+/// An index to create: its keys and options.
+#[derive(Debug, Clone)]
+pub struct IndexModel {
+    /// Index keys, e.g. `{ "email": 1 }`.
+    pub keys: Document,
+    /// Index options.
+    pub options: Option<IndexOptions>,
+}
+
+impl IndexModel {
+    /// Create an index model.
+    pub fn new(keys: Document, options: impl Into<Option<IndexOptions>>) -> Self {
+        Self {
+            keys,
+            options: options.into(),
+        }
+    }
+
+    /// The index name: the explicit name, or the name MongoDB derives from the keys.
+    pub fn name(&self) -> String {
+        self.options
+            .as_ref()
+            .and_then(|options| options.name.clone())
+            .unwrap_or_else(|| default_index_name(&self.keys))
+    }
+}
+
+/// Indexes created and dropped by
+/// [`Collection::ensure_indexes`](crate::Collection::ensure_indexes).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EnsureIndexesResult {
+    /// Names of the indexes created.
+    pub created: Vec<String>,
+    /// Names of the indexes dropped.
+    pub dropped: Vec<String>,
+}
+
+/// The name MongoDB gives an index with these keys, e.g. `email_1_createdAt_-1`.
+pub fn default_index_name(keys: &Document) -> String {
+    keys.iter()
+        .map(|(field, direction)| format!("{}_{}", field, key_value_name(direction)))
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+fn key_value_name(value: &Bson) -> String {
+    match value {
+        Bson::Int32(n) => n.to_string(),
+        Bson::Int64(n) => n.to_string(),
+        Bson::Double(n) if n.fract() == 0.0 => (*n as i64).to_string(),
+        Bson::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collation::CollationStrength;
+    use bson::doc;
+
+    #[test]
+    fn test_default_index_name() {
+        assert_eq!(default_index_name(&doc! { "email": 1 }), "email_1");
+        assert_eq!(
+            default_index_name(&doc! { "user": 1, "createdAt": -1_i64 }),
+            "user_1_createdAt_-1"
+        );
+        assert_eq!(default_index_name(&doc! { "location": "2dsphere" }), "location_2dsphere");
+        assert_eq!(default_index_name(&doc! { "score": -1.0 }), "score_-1");
+    }
+
+    #[test]
+    fn test_index_model_name() {
+        let model = IndexModel::new(doc! { "email": 1 }, None);
+        assert_eq!(model.name(), "email_1");
+
+        let options = IndexOptions::builder().name("by_email").unique(true).build();
+        let model = IndexModel::new(doc! { "email": 1 }, options);
+        assert_eq!(model.name(), "by_email");
+    }
+
+    #[test]
+    fn test_index_options_document() {
+        let options = IndexOptions::builder().unique(true).sparse(false).build();
+        assert_eq!(options.to_document(), doc! { "unique": true, "sparse": false });
+        assert!(IndexOptions::default().to_document().is_empty());
+    }
+
+    #[test]
+    fn test_partial_and_ttl_index_options() {
+        let options = IndexOptions::builder()
+            .partial_filter_expression(doc! { "status": "active" })
+            .expire_after(Duration::from_secs(3600))
+            .hidden(true)
+            .build();
+        assert_eq!(
+            options.to_document(),
+            doc! {
+                "partialFilterExpression": { "status": "active" },
+                "hidden": true,
+                "expireAfterSeconds": 3600_i64,
+            }
+        );
+    }
+
+    #[test]
+    fn test_wildcard_index_options() {
+        let options = IndexOptions::builder()
+            .name("attrs")
+            .wildcard_projection(doc! { "attributes": 1, "attributes.internal": 0 })
+            .build();
+        let model = IndexModel::new(doc! { "$**": 1 }, options.clone());
+        assert_eq!(model.name(), "attrs");
+        assert_eq!(
+            options.to_document(),
+            doc! {
+                "name": "attrs",
+                "wildcardProjection": { "attributes": 1, "attributes.internal": 0 },
+            }
+        );
+    }
+
+    #[test]
+    fn test_collation_index_options() {
+        let options = IndexOptions::builder()
+            .collation(Collation::new("de").strength(CollationStrength::Primary))
+            .build();
+        assert_eq!(
+            options.to_document(),
+            doc! { "collation": { "locale": "de", "strength": 1 } }
+        );
+    }
+}
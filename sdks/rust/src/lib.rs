@@ -11,8 +11,32 @@
 //! - Async/await support with tokio
 //! - Promise pipelining for reduced round trips
 //! - Full CRUD operations
-//! - Aggregation pipelines
-//! - Cursor-based iteration
+//! - Aggregation pipelines, with a typed pipeline builder
+//! - One pipeline run across many databases, with merged sorting and limits
+//! - Scatter-gather finds across several clients
+//! - Locale-aware collations for queries, updates, deletes and indexes
+//! - Cursor-based iteration, with results as Arrow record batches (`arrow` feature)
+//! - Raw BSON results, for forwarding documents untouched
+//! - CSV export of query results, and Parquet export (`parquet` feature)
+//! - Change streams, and tailing capped collections
+//! - Write auditing
+//! - Operation statistics and connection events
+//! - A client-wide retry budget that dampens retry storms
+//! - Per-operation round-trip time, server time and payload sizes
+//! - Metrics with latency histograms, exported for Prometheus (`prometheus` feature)
+//! - Command and connection monitoring
+//! - Declarative index management, with `#[derive(Model)]` (`derive` feature)
+//! - Typed geospatial queries
+//! - UUIDs stored as BSON binary subtype 4 (`uuid` feature)
+//! - Time-sortable ULID and UUIDv7 document IDs
+//! - Rolling collections bucketed by day, month or year
+//! - Archiving old documents to another collection or a JSON file
+//! - Hot/cold tiered reads that fall back to the archive
+//! - Full-text search helpers and Atlas Search stages
+//! - Client-side field level encryption (`encryption` feature)
+//! - Tracing spans for every operation (`tracing` feature)
+//! - Deterministic test data generation, with `fake` values (`fake` feature)
+//! - Collections of documents that are not `Send` or `Sync` (`local` feature)
 //!
 //! ## Quick Start
 //!
@@ -59,32 +83,134 @@
 //! }
 //! ```
 
+pub mod archive;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod audit;
+pub mod change_stream;
 pub mod client;
+pub mod collation;
 pub mod collection;
+pub mod convert;
+pub mod csv;
 pub mod cursor;
 pub mod db;
+#[cfg(feature = "encryption")]
+pub mod encryption;
 pub mod error;
+pub mod events;
+pub mod fanout;
+pub mod geo;
+pub mod handshake;
+pub mod ids;
+pub mod index;
+#[cfg(feature = "local")]
+pub mod local;
+#[cfg(test)]
+mod mock;
+pub mod model;
+pub mod monitoring;
+#[cfg(feature = "parquet")]
+pub mod parquet;
+pub mod pipeline;
+pub mod progress;
+pub mod rolling;
+pub mod rpc;
+pub mod scan;
+pub mod search;
+pub mod stats;
+pub mod tail;
+pub mod testgen;
+pub mod text;
+pub mod tiered;
+pub mod timing;
+mod transport;
+pub mod util;
 
 // Re-export main types
-pub use client::{Client, ClientOptions, ClientOptionsBuilder, ClientSession, MongoClient};
+pub use archive::{ArchiveOptions, ArchiveOptionsBuilder, ArchiveResult, ArchiveTarget};
+pub use audit::AuditOptions;
+pub use change_stream::{
+    ChangeEvent, ChangeStream, ChangeStreamOptions, ChangeStreamOptionsBuilder, Checkpoint,
+    CheckpointInterval, CollectionTokenStore, FileTokenStore, FullDocument, MemoryTokenStore,
+    OperationType, ResumeTokenStore,
+};
+pub use client::{
+    Client, ClientOptions, ClientOptionsBuilder, ClientSession, DatabaseSpecification, DriverInfo,
+    DriverInfoBuilder, MongoClient,
+};
+pub use collation::{
+    Collation, CollationAlternate, CollationCaseFirst, CollationMaxVariable, CollationStrength,
+};
 pub use collection::{
-    Collection, DeleteResult, FindOptions, FindOptionsBuilder, InsertManyResult, InsertOneResult,
-    UpdateOptions, UpdateOptionsBuilder, UpdateResult,
+    AggregateOptions, AggregateOptionsBuilder, BatchUpdateResult, Collection, CompactResult,
+    CountOptions, CountOptionsBuilder, CursorType, DeleteOptions, DeleteOptionsBuilder,
+    DeleteResult, EstimatedDocumentCountOptions, EstimatedDocumentCountOptionsBuilder,
+    FindOneAndUpdateOptions, FindOneAndUpdateOptionsBuilder, FindOptions, FindOptionsBuilder,
+    Hint, InsertManyOptions, InsertManyOptionsBuilder, InsertManyResult, InsertOneOptions,
+    InsertOneOptionsBuilder, InsertOneResult, ModifyOptions, ModifyOptionsBuilder,
+    ReplaceOptions, ReplaceOptionsBuilder, ReturnDocument, SaveResult, SortOrder,
+    UpdateModifications, UpdateOptions, UpdateOptionsBuilder, UpdateResult, ValidateResult,
+};
+pub use convert::ValueCodec;
+pub use cursor::{Cursor, RawCursor};
+pub use db::{
+    Acknowledgment, CollModOptions, CollModOptionsBuilder, CollectionSpecification,
+    CollectionSpecificationInfo, CollectionType, ConnectionStats, CreateCollectionOptions,
+    CreateCollectionOptionsBuilder, Database, DatabaseOptions, DatabaseOptionsBuilder, DbStats,
+    OpCounters, ReadConcern, ReadConcernLevel, ServerStatus, ValidationAction, ValidationLevel,
+    WriteConcern, WriteConcernBuilder,
+};
+#[cfg(feature = "encryption")]
+pub use encryption::{
+    Algorithm, ClientEncryption, DataKeyOptions, EncryptKey, IndexedValue, KmsProviders, QueryType,
+    RangeOptions, RewrapManyDataKeyOptions, RewrapManyDataKeyResult,
+};
+pub use error::{DuplicateKeyError, ErrorKind, InsertManyError, MongoError, Result, WriteError};
+pub use events::ConnectionEvent;
+pub use fanout::{aggregate_across, find_all, FanOutOptions, FanOutOptionsBuilder, SourcedDocument};
+pub use handshake::ServerHello;
+pub use ids::{Ulid, UuidV7};
+pub use index::{EnsureIndexesResult, IndexModel, IndexOptions, IndexOptionsBuilder};
+#[cfg(feature = "local")]
+pub use local::{LocalClient, LocalCollection, LocalDatabase};
+pub use model::Model;
+#[cfg(feature = "derive")]
+pub use mongo_do_derive::Model;
+pub use monitoring::{
+    CmapEventHandler, CommandEventHandler, CommandFailedEvent, CommandStartedEvent,
+    CommandSucceededEvent, ConnectionCheckedInEvent, ConnectionCheckedOutEvent,
+    ConnectionClosedEvent, ConnectionClosedReason, ConnectionCreatedEvent,
+    ConnectionReconnectingEvent,
+};
+pub use pipeline::{
+    validate_pipeline, Accumulator, Joined, OutputPipeline, OutputSummary, PercentileMethod,
+    PipelineBuilder, WhenMatched, WhenNotMatched,
 };
-pub use cursor::Cursor;
-pub use db::{CreateCollectionOptions, CreateCollectionOptionsBuilder, Database};
-pub use error::{ErrorKind, MongoError, Result};
+pub use progress::Progress;
+pub use rolling::{BucketPeriod, RollingCollection, RollingOptions, RollingOptionsBuilder};
+pub use rpc::Method;
+pub use scan::Scan;
+pub use search::{Compound, Search, SearchHit, SearchOperator};
+pub use stats::{ClientMetrics, ClientStats, LatencyHistogram, RetryBudget, RetryBudgetBuilder};
+pub use tail::{TailOptions, TailOptionsBuilder};
+pub use tiered::{TieredCollection, TieredOptions, TieredOptionsBuilder};
+pub use timing::{measure, OperationMetrics};
 
 // Re-export bson for convenience
 pub use bson;
 pub use bson::doc;
+#[cfg(feature = "arrow")]
+pub use {arrow_array, arrow_schema};
+#[cfg(feature = "uuid")]
+pub use uuid;
 
 /// Prelude module for common imports.
 pub mod prelude {
     pub use super::client::{Client, ClientOptions, MongoClient};
     pub use super::collection::{
-        Collection, DeleteResult, FindOptions, InsertManyResult, InsertOneResult, UpdateOptions,
-        UpdateResult,
+        Collection, DeleteOptions, DeleteResult, FindOptions, InsertManyResult, InsertOneResult,
+        UpdateOptions, UpdateResult,
     };
     pub use super::cursor::Cursor;
     pub use super::db::Database;
@@ -126,7 +252,7 @@ mod tests {
         };
         assert_eq!(document.get_str("name").unwrap(), "John");
         assert_eq!(document.get_i32("age").unwrap(), 30);
-        assert_eq!(document.get_bool("active").unwrap(), true);
+        assert!(document.get_bool("active").unwrap());
     }
 
     #[test]
@@ -150,5 +276,6 @@ mod tests {
         let _ = ErrorKind::Serialization;
         let _ = ErrorKind::Internal;
         let _ = ErrorKind::Network;
+        let _ = ErrorKind::Encryption;
     }
 }
@@ -0,0 +1,257 @@
+//! Collections of document types that are not `Send` or `Sync`.
+//!
+//! [`Collection<T>`] requires `T: Send + Sync + Unpin + 'static` so its
+//! futures and cursors can move between threads. On a single-threaded
+//! executor, such as a wasm event loop or a tokio `LocalSet`, that rules out
+//! documents holding `Rc`, `Cell` or `RefCell` values for no benefit.
+//!
+//! A [`LocalCollection<T>`], obtained through a [`LocalClient`], only
+//! requires `T: Serialize` for writes and `T: DeserializeOwned` for reads.
+//! Documents travel as JSON values and are converted at the edges, through
+//! the client's [`ValueCodec`](crate::ValueCodec) like any typed document. The
+//! connection itself is shared with the wrapped [`MongoClient`] and still
+//! needs its runtime.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::local::LocalClient;
+//!
+//! #[derive(Serialize, Deserialize)]
+//! struct Draft {
+//!     title: String,
+//!     edits: Cell<u32>,
+//! }
+//!
+//! let client = LocalClient::new(MongoClient::new("mongodb://localhost").await?);
+//! let drafts = client.database("app").collection::<Draft>("drafts");
+//! let local = tokio::task::LocalSet::new();
+//! local
+//!     .run_until(async {
+//!         let draft = drafts.find_one(doc! { "title": "Intro" }).await?;
+//!         Ok::<_, MongoError>(())
+//!     })
+//!     .await?;
+//! ```
+
+use crate::client::MongoClient;
+use crate::collection::{
+    Collection, DeleteResult, FindOptions, InsertManyResult, InsertOneResult, UpdateModifications,
+    UpdateResult,
+};
+use crate::cursor::deserialize;
+use crate::db::Database;
+use crate::error::Result;
+use bson::Document;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::borrow::Borrow;
+use std::fmt;
+use std::marker::PhantomData;
+
+/// A client handing out [`LocalCollection`]s.
+#[derive(Debug, Clone)]
+pub struct LocalClient {
+    client: MongoClient,
+}
+
+impl LocalClient {
+    /// Share the connection of `client`.
+    pub fn new(client: MongoClient) -> Self {
+        Self { client }
+    }
+
+    /// Get a database handle.
+    pub fn database(&self, name: &str) -> LocalDatabase {
+        LocalDatabase {
+            db: self.client.database(name),
+        }
+    }
+
+    /// The wrapped client.
+    pub fn client(&self) -> &MongoClient {
+        &self.client
+    }
+}
+
+/// A database handing out [`LocalCollection`]s.
+#[derive(Debug, Clone)]
+pub struct LocalDatabase {
+    db: Database,
+}
+
+impl LocalDatabase {
+    /// Get a collection of documents of type `T`.
+    pub fn collection<T>(&self, name: &str) -> LocalCollection<T> {
+        LocalCollection {
+            collection: self.db.collection(name),
+            _marker: PhantomData,
+        }
+    }
+
+    /// The wrapped database.
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+}
+
+/// A collection of documents of type `T`, with no thread-safety bounds.
+pub struct LocalCollection<T> {
+    /// The collection, read and written as JSON.
+    collection: Collection<JsonValue>,
+    /// Type marker.
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> LocalCollection<T> {
+    /// Get the collection name.
+    pub fn name(&self) -> &str {
+        self.collection.name()
+    }
+
+    /// Get the namespace (database.collection).
+    pub fn namespace(&self) -> String {
+        self.collection.namespace()
+    }
+
+    /// The collection with untyped documents, for operations not offered here.
+    pub fn collection(&self) -> &Collection<JsonValue> {
+        &self.collection
+    }
+
+    /// Update a single document.
+    pub async fn update_one(
+        &self,
+        filter: Document,
+        update: impl Into<UpdateModifications>,
+    ) -> Result<UpdateResult> {
+        self.collection.update_one(filter, update).await
+    }
+
+    /// Update all documents matching a filter.
+    pub async fn update_many(
+        &self,
+        filter: Document,
+        update: impl Into<UpdateModifications>,
+    ) -> Result<UpdateResult> {
+        self.collection.update_many(filter, update).await
+    }
+
+    /// Delete a single document.
+    pub async fn delete_one(&self, filter: Document) -> Result<DeleteResult> {
+        self.collection.delete_one(filter).await
+    }
+
+    /// Delete all documents matching a filter.
+    pub async fn delete_many(&self, filter: Document) -> Result<DeleteResult> {
+        self.collection.delete_many(filter).await
+    }
+
+    /// Count documents matching a filter.
+    pub async fn count_documents(&self, filter: impl Into<Option<Document>>) -> Result<u64> {
+        self.collection.count_documents(filter).await
+    }
+}
+
+impl<T: Serialize> LocalCollection<T> {
+    /// Insert a single document.
+    pub async fn insert_one(&self, doc: &T) -> Result<InsertOneResult> {
+        self.collection.insert_one(self.collection.rpc_client.encode_typed(doc)?).await
+    }
+
+    /// Insert multiple documents.
+    pub async fn insert_many<I>(&self, docs: I) -> Result<InsertManyResult>
+    where
+        I: IntoIterator,
+        I::Item: Borrow<T>,
+    {
+        let docs = docs
+            .into_iter()
+            .map(|doc| self.collection.rpc_client.encode_typed(doc.borrow()))
+            .collect::<Result<Vec<_>>>()?;
+        self.collection.insert_many(docs).await
+    }
+
+    /// Replace a single document.
+    pub async fn replace_one(&self, filter: Document, replacement: &T) -> Result<UpdateResult> {
+        let replacement = self.collection.rpc_client.encode_typed(replacement)?;
+        self.collection.replace_one(filter, replacement).await
+    }
+}
+
+impl<T: DeserializeOwned> LocalCollection<T> {
+    /// Find a single document.
+    pub async fn find_one(&self, filter: impl Into<Option<Document>>) -> Result<Option<T>> {
+        self.collection.find_one(filter).await?.map(|doc| deserialize(&doc, None)).transpose()
+    }
+
+    /// Find all documents matching a filter.
+    pub async fn find(&self, filter: impl Into<Option<Document>>) -> Result<Vec<T>> {
+        self.find_with_options(filter, None).await
+    }
+
+    /// Find all documents matching a filter, with options.
+    pub async fn find_with_options(
+        &self,
+        filter: impl Into<Option<Document>>,
+        options: impl Into<Option<FindOptions>>,
+    ) -> Result<Vec<T>> {
+        let mut cursor = self.collection.find_with_options(filter, options).await?;
+        let mut docs = Vec::new();
+        while let Some(doc) = cursor.try_next().await? {
+            docs.push(deserialize(&doc, None)?);
+        }
+        Ok(docs)
+    }
+}
+
+impl<T> Clone for LocalCollection<T> {
+    fn clone(&self) -> Self {
+        Self {
+            collection: self.collection.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for LocalCollection<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocalCollection")
+            .field("namespace", &self.collection.namespace())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+    use std::cell::Cell;
+
+    #[derive(Debug, Serialize, Deserialize)]
+    struct Draft {
+        title: String,
+        edits: Cell<u32>,
+    }
+
+    #[test]
+    fn test_local_collection() {
+        let client = LocalClient::new(MongoClient::new_lazy("mongodb://localhost"));
+        let drafts = client.database("app").collection::<Draft>("drafts");
+        assert_eq!(drafts.namespace(), "app.drafts");
+        assert_eq!(
+            format!("{:?}", drafts.clone()),
+            "LocalCollection { namespace: \"app.drafts\" }"
+        );
+
+        // Futures for `!Sync` documents build, and need not be `Send`.
+        let draft = Draft { title: "Intro".into(), edits: Cell::new(1) };
+        let _insert = drafts.insert_one(&draft);
+        let _find = drafts.find(None);
+
+        let json = serde_json::json!({ "title": "Intro", "edits": 2 });
+        let doc: Draft = deserialize(&json, None).unwrap();
+        assert_eq!(doc.edits.get(), 2);
+    }
+}
@@ -0,0 +1,68 @@
+//! A server stand-in answering calls in memory, for unit tests.
+
+use crate::client::ClientOptions;
+use crate::error::Result;
+use crate::rpc::Method;
+use crate::transport::Transport;
+use serde_json::Value as JsonValue;
+use std::sync::{Arc, Mutex};
+
+/// How a [`MockServer`] answers a call.
+type Reply = Box<dyn Fn(Method, &[JsonValue]) -> Result<JsonValue> + Send + Sync>;
+
+/// Records every call sent to it and answers with a reply function.
+pub(crate) struct MockServer {
+    calls: Mutex<Vec<(Method, Vec<JsonValue>)>>,
+    reply: Reply,
+}
+
+impl MockServer {
+    /// Answer each call with `reply(method, args)`. The arguments include
+    /// the trailing metadata argument, if any.
+    pub(crate) fn new(
+        reply: impl Fn(Method, &[JsonValue]) -> Result<JsonValue> + Send + Sync + 'static,
+    ) -> Arc<Self> {
+        Arc::new(Self {
+            calls: Mutex::new(Vec::new()),
+            reply: Box::new(reply),
+        })
+    }
+
+    /// A transport sending its calls here.
+    pub(crate) fn transport(self: &Arc<Self>) -> Transport {
+        Transport::lazy("mongodb://mock".to_string(), ClientOptions::default())
+            .with_mock(self.clone())
+    }
+
+    /// The calls received so far, in order.
+    pub(crate) fn calls(&self) -> Vec<(Method, Vec<JsonValue>)> {
+        self.calls.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone()
+    }
+
+    /// The calls of `method` received so far, in order.
+    pub(crate) fn calls_of(&self, method: Method) -> Vec<Vec<JsonValue>> {
+        self.calls()
+            .into_iter()
+            .filter(|(m, _)| *m == method)
+            .map(|(_, args)| args)
+            .collect()
+    }
+
+    /// Record a call and answer it, after letting other tasks run so
+    /// concurrent calls interleave.
+    pub(crate) async fn call(&self, method: Method, args: Vec<JsonValue>) -> Result<JsonValue> {
+        let reply = (self.reply)(method, &args);
+        self.calls
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push((method, args));
+        tokio::task::yield_now().await;
+        reply
+    }
+}
+
+impl std::fmt::Debug for MockServer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MockServer").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,51 @@
+//! Types bound to a collection.
+//!
+//! A [`Model`] names its collection, exposes its `_id` and declares its
+//! indexes, so [`Database::model_collection`](crate::Database::model_collection)
+//! and [`Database::sync_indexes`](crate::Database::sync_indexes) need no
+//! collection name. With the `derive` feature, `#[derive(Model)]` implements
+//! it from `#[mongo(...)]` attributes.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::Model;
+//!
+//! #[derive(Serialize, Deserialize, Model)]
+//! #[mongo(collection = "users", index(fields(email), unique))]
+//! struct User {
+//!     #[serde(rename = "_id")]
+//!     id: ObjectId,
+//!     email: String,
+//! }
+//!
+//! db.sync_indexes::<User>().await?;
+//! let users = db.model_collection::<User>();
+//! ```
+
+use crate::index::IndexModel;
+use bson::Bson;
+
+/// A document type stored in a known collection.
+pub trait Model {
+    /// Type of the `_id` field.
+    type Id;
+
+    /// Name of the collection the documents are stored in.
+    const COLLECTION: &'static str;
+
+    /// Get the `_id` of this document.
+    fn id(&self) -> &Self::Id;
+
+    /// Indexes the collection should have.
+    fn indexes() -> Vec<IndexModel> {
+        Vec::new()
+    }
+
+    /// Store the `_id` generated when this document was inserted without
+    /// one, see [`Collection::insert_one_mut`](crate::Collection::insert_one_mut).
+    ///
+    /// Does nothing by default. The derived implementation sets an `_id`
+    /// field of type `Option<_>`, such as `Option<ObjectId>`.
+    fn set_id(&mut self, _id: Bson) {}
+}
@@ -0,0 +1,183 @@
+//! Command and connection monitoring.
+//!
+//! A [`CommandEventHandler`] set with
+//! [`ClientOptionsBuilder::command_event_handler`](crate::ClientOptionsBuilder::command_event_handler)
+//! is told when each RPC call starts and how it ended. The events of one
+//! call share a request ID.
+//!
+//! A [`CmapEventHandler`] set with
+//! [`ClientOptionsBuilder::cmap_event_handler`](crate::ClientOptionsBuilder::cmap_event_handler)
+//! follows the connection itself: when it is created and closed, when calls
+//! check it out and back in, and each reconnect attempt. A client shares one
+//! RPC connection between its calls, so "in use" counts calls in flight.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::monitoring::{CommandEventHandler, CommandSucceededEvent};
+//!
+//! #[derive(Debug)]
+//! struct Latency;
+//!
+//! impl CommandEventHandler for Latency {
+//!     fn command_succeeded(&self, event: &CommandSucceededEvent) {
+//!         metrics::histogram!("mongo.latency", event.duration, "op" => event.command_name.clone());
+//!     }
+//! }
+//!
+//! let options = ClientOptions::builder().command_event_handler(Latency).build();
+//! ```
+
+use crate::rpc::Method;
+use serde_json::Value as JsonValue;
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Source of request IDs, unique within the process.
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A call about to be sent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandStartedEvent {
+    /// ID shared by the events of this call.
+    pub request_id: u64,
+    /// The RPC method called.
+    pub method: Method,
+    /// Name of the operation, e.g. `find`.
+    pub command_name: String,
+    /// Database the call operates on, if it names one.
+    pub database_name: Option<String>,
+    /// Collection the call operates on, if it names one.
+    pub collection_name: Option<String>,
+    /// Arguments of the call, without the metadata the transport attaches.
+    pub command: JsonValue,
+}
+
+/// A call that succeeded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandSucceededEvent {
+    /// ID shared by the events of this call.
+    pub request_id: u64,
+    /// The RPC method called.
+    pub method: Method,
+    /// Name of the operation, e.g. `find`.
+    pub command_name: String,
+    /// Time from sending the call to receiving the reply.
+    pub duration: Duration,
+    /// The server's reply.
+    pub reply: JsonValue,
+}
+
+/// A call that failed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommandFailedEvent {
+    /// ID shared by the events of this call.
+    pub request_id: u64,
+    /// The RPC method called.
+    pub method: Method,
+    /// Name of the operation, e.g. `find`.
+    pub command_name: String,
+    /// Time from sending the call to the failure.
+    pub duration: Duration,
+    /// Why the call failed.
+    pub failure: String,
+}
+
+/// Receives an event for every call a client sends.
+///
+/// Handlers are called inline, so they should return quickly.
+pub trait CommandEventHandler: Debug + Send + Sync {
+    /// Called before a call is sent.
+    fn command_started(&self, _event: &CommandStartedEvent) {}
+
+    /// Called when a call succeeds.
+    fn command_succeeded(&self, _event: &CommandSucceededEvent) {}
+
+    /// Called when a call fails.
+    fn command_failed(&self, _event: &CommandFailedEvent) {}
+}
+
+/// The client connected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionCreatedEvent {}
+
+/// A call started using the connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionCheckedOutEvent {
+    /// Calls in flight, including this one.
+    pub in_use: usize,
+    /// The configured `max_pool_size`, to compare `in_use` against.
+    pub max_pool_size: Option<u32>,
+}
+
+/// A call finished using the connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionCheckedInEvent {
+    /// Calls still in flight.
+    pub in_use: usize,
+    /// How long the call held the connection.
+    pub duration: Duration,
+}
+
+/// Why a connection closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionClosedReason {
+    /// The connection was lost.
+    Error(String),
+    /// The client was closed.
+    ClientClosed,
+}
+
+/// The connection closed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionClosedEvent {
+    /// Why it closed.
+    pub reason: ConnectionClosedReason,
+}
+
+/// A call is being sent over a lost connection, which makes the transport
+/// try to reconnect.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionReconnectingEvent {
+    /// Attempts since the connection was lost, starting at 1.
+    pub attempt: u32,
+}
+
+/// Receives connection events, in the style of the official driver's
+/// connection pool monitoring.
+///
+/// Handlers are called inline, so they should return quickly.
+pub trait CmapEventHandler: Debug + Send + Sync {
+    /// Called when the client connects.
+    fn connection_created(&self, _event: &ConnectionCreatedEvent) {}
+
+    /// Called when a call starts using the connection.
+    fn connection_checked_out(&self, _event: &ConnectionCheckedOutEvent) {}
+
+    /// Called when a call finishes using the connection.
+    fn connection_checked_in(&self, _event: &ConnectionCheckedInEvent) {}
+
+    /// Called when the connection is lost or the client is closed.
+    fn connection_closed(&self, _event: &ConnectionClosedEvent) {}
+
+    /// Called for each reconnect attempt after the connection was lost.
+    fn connection_reconnecting(&self, _event: &ConnectionReconnectingEvent) {}
+}
+
+/// Allocate the ID for a new call.
+pub(crate) fn next_request_id() -> u64 {
+    NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_request_id() {
+        let first = next_request_id();
+        let second = next_request_id();
+        assert!(second > first);
+    }
+}
@@ -0,0 +1,182 @@
+//! Parquet export of query results.
+//!
+//! [`Collection::export_parquet`] streams the documents matching a filter
+//! into a Parquet file, converting each fetched batch to Arrow with the given
+//! schema (see the [`arrow`](crate::arrow) module for how values are coerced)
+//! so only one batch and the current row group are held in memory. Files are
+//! Snappy-compressed.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use arrow_schema::{DataType, Field, Schema, TimeUnit};
+//!
+//! let schema = Arc::new(Schema::new(vec![
+//!     Field::new("_id", DataType::Utf8, false),
+//!     Field::new("at", DataType::Timestamp(TimeUnit::Millisecond, None), false),
+//!     Field::new("reading", DataType::Float64, true),
+//! ]));
+//! let rows = readings
+//!     .export_parquet_file("readings-2024-06.parquet", schema, doc! { "month": "2024-06" })
+//!     .await?;
+//! ```
+
+use crate::arrow::documents_to_record_batch;
+use crate::collection::Collection;
+use crate::cursor::Cursor;
+use crate::error::{MongoError, Result};
+use crate::progress::{Progress, ProgressTracker};
+use ::parquet::arrow::ArrowWriter;
+use ::parquet::basic::Compression;
+use ::parquet::file::properties::WriterProperties;
+use arrow_schema::SchemaRef;
+use bson::Document;
+use serde::de::DeserializeOwned;
+use std::io::Write;
+use std::path::Path;
+
+impl<T: DeserializeOwned + Send + Sync + Unpin + 'static> Collection<T> {
+    /// Write the documents matching `filter` to `writer` as Parquet with
+    /// `schema`, and return the number of rows written.
+    pub async fn export_parquet<W: Write + Send>(
+        &self,
+        writer: W,
+        schema: SchemaRef,
+        filter: impl Into<Option<Document>>,
+    ) -> Result<u64> {
+        self.export_parquet_with_progress(writer, schema, filter, |_| {})
+            .await
+    }
+
+    /// [`Collection::export_parquet`], calling `progress` after each batch is
+    /// written.
+    pub async fn export_parquet_with_progress<W: Write + Send>(
+        &self,
+        writer: W,
+        schema: SchemaRef,
+        filter: impl Into<Option<Document>>,
+        progress: impl FnMut(&Progress) + Send,
+    ) -> Result<u64> {
+        self.find(filter)
+            .await?
+            .write_parquet_with_progress(writer, schema, progress)
+            .await
+    }
+
+    /// Write the documents matching `filter` to a new Parquet file at `path`,
+    /// replacing any existing file, and return the number of rows written.
+    pub async fn export_parquet_file(
+        &self,
+        path: impl AsRef<Path>,
+        schema: SchemaRef,
+        filter: impl Into<Option<Document>>,
+    ) -> Result<u64> {
+        let file = std::fs::File::create(path.as_ref()).map_err(|e| {
+            MongoError::Internal(format!(
+                "failed to create {}: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })?;
+        self.export_parquet(std::io::BufWriter::new(file), schema, filter)
+            .await
+    }
+}
+
+impl<T: DeserializeOwned + Send + Unpin + 'static> Cursor<T> {
+    /// Write the remaining documents to `writer` as Parquet with `schema`,
+    /// and return the number of rows written.
+    pub async fn write_parquet<W: Write + Send>(self, writer: W, schema: SchemaRef) -> Result<u64> {
+        self.write_parquet_with_progress(writer, schema, |_| {})
+            .await
+    }
+
+    /// [`Cursor::write_parquet`], calling `progress` after each batch is
+    /// written.
+    pub async fn write_parquet_with_progress<W: Write + Send>(
+        mut self,
+        writer: W,
+        schema: SchemaRef,
+        mut progress: impl FnMut(&Progress) + Send,
+    ) -> Result<u64> {
+        let properties = WriterProperties::builder()
+            .set_compression(Compression::SNAPPY)
+            .build();
+        let mut writer =
+            ArrowWriter::try_new(writer, schema.clone(), Some(properties)).map_err(write_error)?;
+        let mut tracker = ProgressTracker::start();
+        let mut rows = 0;
+        while let Some(documents) = self.next_documents().await? {
+            let batch = documents_to_record_batch(&documents, schema.clone())?;
+            writer.write(&batch).map_err(write_error)?;
+            rows += documents.len() as u64;
+            progress(&tracker.record(documents.len() as u64, &documents));
+        }
+        writer.close().map_err(write_error)?;
+        Ok(rows)
+    }
+}
+
+fn write_error(e: ::parquet::errors::ParquetError) -> MongoError {
+    MongoError::Internal(format!("failed to write parquet: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+    use arrow_array::{Array, Int64Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_cursor_write_parquet() {
+        let cursor: Cursor<Document> = Cursor::new(
+            "edge.readings".to_string(),
+            vec![
+                serde_json::json!({ "sensor": "a", "value": 1 }),
+                serde_json::json!({ "sensor": "b" }),
+            ],
+            None,
+        );
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("sensor", DataType::Utf8, false),
+            Field::new("value", DataType::Int64, true),
+        ]));
+        let path = std::env::temp_dir().join(format!(
+            "mongo-do-parquet-{}.parquet",
+            bson::oid::ObjectId::new()
+        ));
+        let file = std::fs::File::create(&path).unwrap();
+        let mut documents = 0;
+        let rows = cursor
+            .write_parquet_with_progress(file, schema, |progress| documents = progress.documents)
+            .await
+            .unwrap();
+        assert_eq!(rows, 2);
+        assert_eq!(documents, 2);
+
+        let file = std::fs::File::open(&path).unwrap();
+        let batches = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(batches.len(), 1);
+        let sensors = batches[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(sensors.value(1), "b");
+        let values = batches[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(values.value(0), 1);
+        assert!(values.is_null(1));
+    }
+}
@@ -0,0 +1,915 @@
+//! Typed aggregation pipeline builder.
+//!
+//! [`PipelineBuilder`] assembles aggregation stages while tracking the type of
+//! the documents the pipeline produces, so results deserialize directly into
+//! the right type.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::pipeline::Joined;
+//!
+//! let pipeline = users
+//!     .pipeline()
+//!     .filter(doc! { "active": true })
+//!     .lookup_typed(&orders, "_id", "userId", "orders");
+//!
+//! let cursor = users.aggregate_pipeline(pipeline).await?;
+//! let results: Vec<Joined<User, Order>> = cursor.collect().await?;
+//! ```
+
+use crate::collection::Collection;
+use crate::db::validate_collection_name;
+use crate::error::{MongoError, Result};
+use crate::search::{self, Search, SearchHit};
+use bson::{doc, Bson, Document};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::marker::PhantomData;
+
+/// Aggregation stages recognized by [`validate_pipeline`].
+const KNOWN_STAGES: &[&str] = &[
+    "$addFields",
+    "$bucket",
+    "$bucketAuto",
+    "$changeStream",
+    "$collStats",
+    "$count",
+    "$currentOp",
+    "$densify",
+    "$documents",
+    "$facet",
+    "$fill",
+    "$geoNear",
+    "$graphLookup",
+    "$group",
+    "$indexStats",
+    "$limit",
+    "$listLocalSessions",
+    "$listSearchIndexes",
+    "$listSessions",
+    "$lookup",
+    "$match",
+    "$merge",
+    "$out",
+    "$planCacheStats",
+    "$project",
+    "$redact",
+    "$replaceRoot",
+    "$replaceWith",
+    "$sample",
+    "$search",
+    "$searchMeta",
+    "$set",
+    "$setWindowFields",
+    "$skip",
+    "$sort",
+    "$sortByCount",
+    "$unionWith",
+    "$unset",
+    "$unwind",
+    "$vectorSearch",
+];
+
+/// Check a pipeline for mistakes the server would reject, without running it.
+///
+/// Each stage must have exactly one field naming a known stage, `$out` and
+/// `$merge` must be the last stage, and `$match` must not be empty.
+pub fn validate_pipeline(stages: &[Document]) -> Result<()> {
+    for (i, stage) in stages.iter().enumerate() {
+        let invalid = |msg: String| MongoError::invalid_argument(format!("stage {}: {}", i, msg));
+        let mut fields = stage.iter();
+        let (name, value) = match (fields.next(), fields.next()) {
+            (Some(field), None) => field,
+            _ => {
+                return Err(invalid(format!(
+                    "a stage must have exactly one field, found {}",
+                    stage.len()
+                )))
+            }
+        };
+        if !KNOWN_STAGES.contains(&name.as_str()) {
+            return Err(invalid(format!("unknown stage {}", name)));
+        }
+        if (name == "$out" || name == "$merge") && i + 1 != stages.len() {
+            return Err(invalid(format!("{} must be the last stage", name)));
+        }
+        if name == "$match" && value.as_document().is_none_or(Document::is_empty) {
+            return Err(invalid("$match must be a non-empty document".to_string()));
+        }
+    }
+    Ok(())
+}
+
+/// A document joined with the matching documents of another collection.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Joined<T, F> {
+    /// The original document.
+    pub doc: T,
+    /// The joined documents from the foreign collection.
+    pub joined: Vec<F>,
+}
+
+/// What `$merge` does when an output document matches an existing document.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WhenMatched {
+    /// Replace the existing document.
+    Replace,
+    /// Keep the existing document.
+    KeepExisting,
+    /// Merge the output document into the existing document.
+    Merge,
+    /// Stop the aggregation with an error.
+    Fail,
+    /// Update the existing document with an update pipeline.
+    Pipeline(Vec<Document>),
+}
+
+impl From<WhenMatched> for Bson {
+    fn from(when_matched: WhenMatched) -> Self {
+        match when_matched {
+            WhenMatched::Replace => Bson::from("replace"),
+            WhenMatched::KeepExisting => Bson::from("keepExisting"),
+            WhenMatched::Merge => Bson::from("merge"),
+            WhenMatched::Fail => Bson::from("fail"),
+            WhenMatched::Pipeline(pipeline) => Bson::from(pipeline),
+        }
+    }
+}
+
+/// What `$merge` does when an output document matches no existing document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WhenNotMatched {
+    /// Insert the output document.
+    Insert,
+    /// Discard the output document.
+    Discard,
+    /// Stop the aggregation with an error.
+    Fail,
+}
+
+impl WhenNotMatched {
+    fn as_str(&self) -> &'static str {
+        match self {
+            WhenNotMatched::Insert => "insert",
+            WhenNotMatched::Discard => "discard",
+            WhenNotMatched::Fail => "fail",
+        }
+    }
+}
+
+/// How `$percentile` and `$median` compute their result.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PercentileMethod {
+    /// Estimate the percentile, the only method the server supports.
+    #[default]
+    Approximate,
+}
+
+impl PercentileMethod {
+    fn as_str(&self) -> &'static str {
+        match self {
+            PercentileMethod::Approximate => "approximate",
+        }
+    }
+}
+
+/// An accumulator for a `$group` stage or an output field of a
+/// `$setWindowFields` stage.
+///
+/// Converts into [`Bson`], so it can also be used in a `doc!` passed to
+/// [`Collection::group_by`].
+///
+/// # Example
+///
+/// ```ignore
+/// let pipeline = requests.pipeline().group(
+///     "$route",
+///     [
+///         ("p99", Accumulator::percentile("$latencyMs", [0.99], PercentileMethod::Approximate)?),
+///         ("median", Accumulator::median("$latencyMs", PercentileMethod::Approximate)),
+///     ],
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Accumulator(Document);
+
+impl Accumulator {
+    /// `$sum` of `expr`.
+    pub fn sum(expr: impl Into<Bson>) -> Self {
+        Self(doc! { "$sum": expr.into() })
+    }
+
+    /// `$avg` of `expr`.
+    pub fn avg(expr: impl Into<Bson>) -> Self {
+        Self(doc! { "$avg": expr.into() })
+    }
+
+    /// `$min` of `expr`.
+    pub fn min(expr: impl Into<Bson>) -> Self {
+        Self(doc! { "$min": expr.into() })
+    }
+
+    /// `$max` of `expr`.
+    pub fn max(expr: impl Into<Bson>) -> Self {
+        Self(doc! { "$max": expr.into() })
+    }
+
+    /// `$percentile` of `input`: an array with the value at each percentile in `p`.
+    ///
+    /// Fails if `p` is empty or a percentile is outside `0.0..=1.0`.
+    pub fn percentile(
+        input: impl Into<Bson>,
+        p: impl IntoIterator<Item = f64>,
+        method: PercentileMethod,
+    ) -> Result<Self> {
+        let p: Vec<f64> = p.into_iter().collect();
+        if p.is_empty() {
+            return Err(MongoError::invalid_argument("percentile needs at least one p value"));
+        }
+        if let Some(bad) = p.iter().find(|p| !(0.0..=1.0).contains(*p)) {
+            return Err(MongoError::invalid_argument(format!(
+                "percentile p value {} is not between 0 and 1",
+                bad
+            )));
+        }
+        Ok(Self(doc! {
+            "$percentile": { "input": input.into(), "p": p, "method": method.as_str() }
+        }))
+    }
+
+    /// `$median` of `input`.
+    pub fn median(input: impl Into<Bson>, method: PercentileMethod) -> Self {
+        Self(doc! { "$median": { "input": input.into(), "method": method.as_str() } })
+    }
+
+    /// Restrict the accumulator to a window, for `$setWindowFields`.
+    ///
+    /// `window` holds `documents` or `range` bounds, e.g.
+    /// `doc! { "documents": ["unbounded", "current"] }`.
+    pub fn window(mut self, window: Document) -> Self {
+        self.0.insert("window", window);
+        self
+    }
+
+    /// Get the accumulator expression.
+    pub fn into_document(self) -> Document {
+        self.0
+    }
+}
+
+impl From<Accumulator> for Bson {
+    fn from(accumulator: Accumulator) -> Self {
+        Bson::Document(accumulator.0)
+    }
+}
+
+/// The terminal stage of an [`OutputPipeline`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputStage {
+    /// `$out`: replace the target collection with the results.
+    Out,
+    /// `$merge`: merge the results into the target collection.
+    Merge,
+}
+
+/// A pipeline that writes its results to a collection, run with
+/// [`Collection::aggregate_output`].
+#[derive(Debug, Clone)]
+pub struct OutputPipeline {
+    /// Stages before the terminal stage.
+    pub(crate) stages: Vec<Document>,
+    /// The terminal `$out` or `$merge` stage.
+    pub(crate) terminal: Document,
+    /// Kind of terminal stage.
+    pub(crate) kind: OutputStage,
+    /// Target database, if different from the source database.
+    pub(crate) target_db: Option<String>,
+    /// Target collection.
+    pub(crate) target_coll: String,
+}
+
+impl OutputPipeline {
+    /// Get the kind of terminal stage.
+    pub fn kind(&self) -> OutputStage {
+        self.kind
+    }
+
+    /// Get the full pipeline, including the terminal stage.
+    pub fn build(self) -> Vec<Document> {
+        let mut stages = self.stages;
+        stages.push(self.terminal);
+        stages
+    }
+}
+
+/// Summary of a pipeline run with [`Collection::aggregate_output`].
+#[derive(Debug, Clone, Default)]
+pub struct OutputSummary {
+    /// Number of documents inserted, modified or upserted in the target
+    /// collection, as reported by the server. `None` if the server does not
+    /// report it.
+    pub documents_written: Option<u64>,
+}
+
+/// Builder for aggregation pipelines producing documents of type `T`.
+pub struct PipelineBuilder<T = Document> {
+    /// Pipeline stages.
+    stages: Vec<Document>,
+    /// Database of the collection the pipeline runs on, if known.
+    database: Option<String>,
+    /// Output type marker.
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> PipelineBuilder<T> {
+    /// Create an empty pipeline.
+    pub fn new() -> Self {
+        Self {
+            stages: Vec::new(),
+            database: None,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Create an empty pipeline over a collection of `database`.
+    pub(crate) fn for_database(database: &str) -> Self {
+        Self {
+            database: Some(database.to_string()),
+            ..Self::new()
+        }
+    }
+
+    /// Append a raw stage.
+    pub fn stage(mut self, stage: Document) -> Self {
+        self.stages.push(stage);
+        self
+    }
+
+    /// Append a `$match` stage.
+    pub fn filter(self, filter: Document) -> Self {
+        self.stage(doc! { "$match": filter })
+    }
+
+    /// Append a `$sort` stage.
+    pub fn sort(self, sort: Document) -> Self {
+        self.stage(doc! { "$sort": sort })
+    }
+
+    /// Append a `$skip` stage.
+    pub fn skip(self, skip: u64) -> Self {
+        self.stage(doc! { "$skip": skip as i64 })
+    }
+
+    /// Append a `$limit` stage.
+    pub fn limit(self, limit: i64) -> Self {
+        self.stage(doc! { "$limit": limit })
+    }
+
+    /// Append a `$search` stage, and the score and highlights of each result.
+    ///
+    /// The pipeline then produces [`SearchHit`] values. `$search` must be the
+    /// first stage of a pipeline.
+    pub fn search(self, search: Search) -> PipelineBuilder<SearchHit<T>> {
+        let mut meta = doc! { search::SCORE_FIELD: search::search_score() };
+        if search.has_highlight() {
+            meta.insert(search::HIGHLIGHTS_FIELD, search::search_highlights());
+        }
+        self.stage(search.build()).stage(doc! { "$addFields": meta }).output()
+    }
+
+    /// Append a `$group` stage grouping by `id` and computing `fields`.
+    ///
+    /// Call [`PipelineBuilder::output`] afterwards to deserialize the groups.
+    pub fn group<'a>(
+        self,
+        id: impl Into<Bson>,
+        fields: impl IntoIterator<Item = (&'a str, Accumulator)>,
+    ) -> Self {
+        let mut group = doc! { "_id": id.into() };
+        for (name, accumulator) in fields {
+            group.insert(name, accumulator);
+        }
+        self.stage(doc! { "$group": group })
+    }
+
+    /// Append a `$setWindowFields` stage adding `output` fields computed over
+    /// windows of each partition, ordered by `sort_by`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let pipeline = requests.pipeline().set_window_fields(
+    ///     Some("$route".into()),
+    ///     doc! { "at": 1 },
+    ///     [(
+    ///         "rollingMedian",
+    ///         Accumulator::median("$latencyMs", PercentileMethod::Approximate)
+    ///             .window(doc! { "documents": [-99, 0] }),
+    ///     )],
+    /// );
+    /// ```
+    pub fn set_window_fields<'a>(
+        self,
+        partition_by: Option<Bson>,
+        sort_by: Document,
+        output: impl IntoIterator<Item = (&'a str, Accumulator)>,
+    ) -> Self {
+        let mut stage = Document::new();
+        if let Some(partition_by) = partition_by {
+            stage.insert("partitionBy", partition_by);
+        }
+        if !sort_by.is_empty() {
+            stage.insert("sortBy", sort_by);
+        }
+        let output: Document = output
+            .into_iter()
+            .map(|(name, accumulator)| (name.to_string(), Bson::from(accumulator)))
+            .collect();
+        stage.insert("output", output);
+        self.stage(doc! { "$setWindowFields": stage })
+    }
+
+    /// Append a `$lookup` stage embedding matches from `from` as the array `as_`.
+    pub fn lookup(self, from: &str, local_field: &str, foreign_field: &str, as_: &str) -> Self {
+        self.stage(doc! {
+            "$lookup": {
+                "from": from,
+                "localField": local_field,
+                "foreignField": foreign_field,
+                "as": as_,
+            }
+        })
+    }
+
+    /// Append a `$unionWith` stage adding the documents of `other`, after
+    /// running them through `pipeline`.
+    ///
+    /// Fails if `other` is in a different database than the collection this
+    /// pipeline was started from, has an invalid name, or if `pipeline` is
+    /// invalid or writes its results with `$out` or `$merge`. Documents of
+    /// `other` must deserialize as `T`, or call [`PipelineBuilder::output`].
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let pipeline = orders
+    ///     .pipeline()
+    ///     .union_with(&archived_orders, [doc! { "$match": { "year": 2024 } }])?
+    ///     .group("$region", [("revenue", Accumulator::sum("$amount"))]);
+    /// ```
+    pub fn union_with<U>(
+        self,
+        other: &Collection<U>,
+        pipeline: impl IntoIterator<Item = Document>,
+    ) -> Result<Self> {
+        let pipeline: Vec<Document> = pipeline.into_iter().collect();
+        self.union_with_namespace(other.database_name(), other.name(), pipeline)
+    }
+
+    /// Append the stage of [`PipelineBuilder::union_with`].
+    fn union_with_namespace(self, db: &str, coll: &str, pipeline: Vec<Document>) -> Result<Self> {
+        if let Some(ref database) = self.database {
+            if database != db {
+                return Err(MongoError::invalid_argument(format!(
+                    "$unionWith collection '{}.{}' is not in database '{}'",
+                    db, coll, database
+                )));
+            }
+        }
+        validate_collection_name(db, coll)?;
+        validate_pipeline(&pipeline)?;
+        if let Some(stage) = pipeline
+            .iter()
+            .find(|stage| stage.contains_key("$out") || stage.contains_key("$merge"))
+        {
+            return Err(MongoError::invalid_argument(format!(
+                "$unionWith pipeline cannot contain {}",
+                stage.keys().next().map(String::as_str).unwrap_or_default()
+            )));
+        }
+        let union = if pipeline.is_empty() {
+            Bson::from(coll)
+        } else {
+            Bson::from(doc! { "coll": coll, "pipeline": pipeline })
+        };
+        Ok(self.stage(doc! { "$unionWith": union }))
+    }
+
+    /// Join each document with the matching documents of `from`.
+    ///
+    /// The pipeline then produces [`Joined`] values: the original document in
+    /// `doc` and the matches in `joined`. `as_` names the intermediate array
+    /// and must not clash with a field of `T`. The foreign collection must be
+    /// in the same database.
+    pub fn lookup_typed<F>(
+        self,
+        from: &Collection<F>,
+        local_field: &str,
+        foreign_field: &str,
+        as_: &str,
+    ) -> PipelineBuilder<Joined<T, F>> {
+        self.lookup_joined(from.name(), local_field, foreign_field, as_)
+    }
+
+    /// Append the stages of [`PipelineBuilder::lookup_typed`].
+    fn lookup_joined<F>(
+        self,
+        from: &str,
+        local_field: &str,
+        foreign_field: &str,
+        as_: &str,
+    ) -> PipelineBuilder<Joined<T, F>> {
+        self.lookup(from, local_field, foreign_field, as_)
+            .stage(doc! {
+                "$replaceWith": { "doc": "$$ROOT", "joined": format!("${}", as_) }
+            })
+            .stage(doc! { "$unset": format!("doc.{}", as_) })
+            .output()
+    }
+
+    /// Terminate the pipeline with a `$merge` into `db.coll`.
+    ///
+    /// `on` lists the fields identifying matching documents; empty means `_id`.
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let pipeline = orders
+    ///     .pipeline()
+    ///     .stage(doc! { "$group": { "_id": "$userId", "total": { "$sum": "$amount" } } })
+    ///     .merge_into("reports", "totals", &[], WhenMatched::Replace, WhenNotMatched::Insert);
+    /// let summary = orders.aggregate_output(pipeline).await?;
+    /// ```
+    pub fn merge_into(
+        self,
+        db: &str,
+        coll: &str,
+        on: &[&str],
+        when_matched: WhenMatched,
+        when_not_matched: WhenNotMatched,
+    ) -> OutputPipeline {
+        let mut merge = doc! {
+            "into": { "db": db, "coll": coll },
+            "whenMatched": when_matched,
+            "whenNotMatched": when_not_matched.as_str(),
+        };
+        if !on.is_empty() {
+            merge.insert("on", on.to_vec());
+        }
+        OutputPipeline {
+            stages: self.stages,
+            terminal: doc! { "$merge": merge },
+            kind: OutputStage::Merge,
+            target_db: Some(db.to_string()),
+            target_coll: coll.to_string(),
+        }
+    }
+
+    /// Terminate the pipeline with an `$out` replacing `coll` in the same database.
+    ///
+    /// [`Collection::aggregate_output`] refuses to run it when `coll` is the
+    /// source collection.
+    pub fn out_to(self, coll: &str) -> OutputPipeline {
+        OutputPipeline {
+            stages: self.stages,
+            terminal: doc! { "$out": coll },
+            kind: OutputStage::Out,
+            target_db: None,
+            target_coll: coll.to_string(),
+        }
+    }
+
+    /// Change the output type, after stages that reshape documents such as
+    /// `$project` or `$group`.
+    pub fn output<U>(self) -> PipelineBuilder<U> {
+        PipelineBuilder {
+            stages: self.stages,
+            database: self.database,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get the stages built so far.
+    pub fn stages(&self) -> &[Document] {
+        &self.stages
+    }
+
+    /// Build the pipeline stages.
+    pub fn build(self) -> Vec<Document> {
+        self.stages
+    }
+
+    /// Check the stages built so far with [`validate_pipeline`].
+    ///
+    /// # Example
+    ///
+    /// ```ignore
+    /// let pipeline = users.pipeline().stage(doc! { "$macth": { "active": true } });
+    /// assert!(pipeline.validate().is_err());
+    /// ```
+    pub fn validate(&self) -> Result<()> {
+        validate_pipeline(&self.stages)
+    }
+}
+
+impl<T> Default for PipelineBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Clone for PipelineBuilder<T> {
+    fn clone(&self) -> Self {
+        Self {
+            stages: self.stages.clone(),
+            database: self.database.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for PipelineBuilder<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PipelineBuilder")
+            .field("stages", &self.stages)
+            .finish()
+    }
+}
+
+impl<T> IntoIterator for PipelineBuilder<T> {
+    type Item = Document;
+    type IntoIter = std::vec::IntoIter<Document>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.stages.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct User {
+        name: String,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Order {
+        total: i32,
+    }
+
+    #[test]
+    fn test_pipeline_stages() {
+        let pipeline = PipelineBuilder::<Document>::new()
+            .filter(doc! { "active": true })
+            .sort(doc! { "name": 1 })
+            .skip(5)
+            .limit(10)
+            .build();
+        assert_eq!(
+            pipeline,
+            vec![
+                doc! { "$match": { "active": true } },
+                doc! { "$sort": { "name": 1 } },
+                doc! { "$skip": 5_i64 },
+                doc! { "$limit": 10_i64 },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_percentiles() {
+        let p95 = Accumulator::percentile("$latency", [0.5, 0.95], PercentileMethod::Approximate);
+        let pipeline = PipelineBuilder::<Document>::new()
+            .group(
+                "$route",
+                [
+                    ("count", Accumulator::sum(1)),
+                    ("p", p95.unwrap()),
+                    ("median", Accumulator::median("$latency", PercentileMethod::default())),
+                ],
+            )
+            .build();
+        assert_eq!(
+            pipeline,
+            vec![doc! {
+                "$group": {
+                    "_id": "$route",
+                    "count": { "$sum": 1 },
+                    "p": {
+                        "$percentile": {
+                            "input": "$latency",
+                            "p": [0.5, 0.95],
+                            "method": "approximate",
+                        }
+                    },
+                    "median": { "$median": { "input": "$latency", "method": "approximate" } },
+                }
+            }]
+        );
+    }
+
+    #[test]
+    fn test_percentile_p_values() {
+        let method = PercentileMethod::Approximate;
+        assert!(Accumulator::percentile("$x", [], method).is_err());
+        let err = Accumulator::percentile("$x", [0.5, 1.5], method).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid argument: percentile p value 1.5 is not between 0 and 1"
+        );
+        assert!(Accumulator::percentile("$x", [0.0, 1.0], method).is_ok());
+    }
+
+    #[test]
+    fn test_set_window_fields() {
+        let median = Accumulator::median("$latency", PercentileMethod::Approximate)
+            .window(doc! { "documents": [-9, 0] });
+        let pipeline = PipelineBuilder::<Document>::new()
+            .set_window_fields(Some("$route".into()), doc! { "at": 1 }, [("rolling", median)])
+            .build();
+        assert_eq!(
+            pipeline,
+            vec![doc! {
+                "$setWindowFields": {
+                    "partitionBy": "$route",
+                    "sortBy": { "at": 1 },
+                    "output": {
+                        "rolling": {
+                            "$median": { "input": "$latency", "method": "approximate" },
+                            "window": { "documents": [-9, 0] },
+                        }
+                    },
+                }
+            }]
+        );
+    }
+
+    #[test]
+    fn test_union_with() {
+        let pipeline = PipelineBuilder::<Document>::for_database("shop")
+            .union_with_namespace("shop", "archive", vec![])
+            .unwrap()
+            .union_with_namespace("shop", "returns", vec![doc! { "$match": { "year": 2024 } }])
+            .unwrap()
+            .build();
+        assert_eq!(
+            pipeline,
+            vec![
+                doc! { "$unionWith": "archive" },
+                doc! {
+                    "$unionWith": {
+                        "coll": "returns",
+                        "pipeline": [{ "$match": { "year": 2024 } }],
+                    }
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_union_with_invalid() {
+        let pipeline = PipelineBuilder::<Document>::for_database("shop");
+        let err = pipeline.clone().union_with_namespace("crm", "users", vec![]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid argument: $unionWith collection 'crm.users' is not in database 'shop'"
+        );
+        assert!(pipeline.clone().union_with_namespace("shop", "a$b", vec![]).is_err());
+        assert!(pipeline
+            .clone()
+            .union_with_namespace("shop", "archive", vec![doc! { "$macth": {} }])
+            .is_err());
+        let err = pipeline
+            .union_with_namespace("shop", "archive", vec![doc! { "$out": "copy" }])
+            .unwrap_err();
+        assert_eq!(err.to_string(), "invalid argument: $unionWith pipeline cannot contain $out");
+
+        // Without a known database, any database is accepted.
+        assert!(PipelineBuilder::<Document>::new()
+            .union_with_namespace("crm", "users", vec![])
+            .is_ok());
+    }
+
+    #[test]
+    fn test_search_stages() {
+        let operator = crate::search::SearchOperator::text("espresso", ["body"]);
+        let pipeline: PipelineBuilder<SearchHit<Document>> = PipelineBuilder::<Document>::new()
+            .search(Search::new(operator.clone()).highlight(["body"]));
+        assert_eq!(
+            pipeline.stages()[1],
+            doc! {
+                "$addFields": {
+                    "searchScore": { "$meta": "searchScore" },
+                    "searchHighlights": { "$meta": "searchHighlights" },
+                }
+            }
+        );
+
+        let pipeline = PipelineBuilder::<Document>::new().search(Search::new(operator));
+        assert_eq!(
+            pipeline.stages()[1],
+            doc! { "$addFields": { "searchScore": { "$meta": "searchScore" } } }
+        );
+    }
+
+    #[test]
+    fn test_lookup_joined_stages() {
+        let pipeline: PipelineBuilder<Joined<User, Order>> = PipelineBuilder::<User>::new()
+            .lookup_joined("orders", "_id", "userId", "orders");
+        assert_eq!(
+            pipeline.stages(),
+            &[
+                doc! {
+                    "$lookup": {
+                        "from": "orders",
+                        "localField": "_id",
+                        "foreignField": "userId",
+                        "as": "orders",
+                    }
+                },
+                doc! { "$replaceWith": { "doc": "$$ROOT", "joined": "$orders" } },
+                doc! { "$unset": "doc.orders" },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_into() {
+        let pipeline = PipelineBuilder::<Document>::new()
+            .filter(doc! { "active": true })
+            .merge_into("reports", "totals", &["userId"], WhenMatched::Merge, WhenNotMatched::Discard);
+        assert_eq!(pipeline.kind(), OutputStage::Merge);
+        assert_eq!(
+            pipeline.build()[1],
+            doc! {
+                "$merge": {
+                    "into": { "db": "reports", "coll": "totals" },
+                    "whenMatched": "merge",
+                    "whenNotMatched": "discard",
+                    "on": ["userId"],
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_merge_into_pipeline_when_matched() {
+        let update = vec![doc! { "$set": { "seen": true } }];
+        let pipeline = PipelineBuilder::<Document>::new().merge_into(
+            "db",
+            "coll",
+            &[],
+            WhenMatched::Pipeline(update),
+            WhenNotMatched::Insert,
+        );
+        let merge = pipeline.terminal.get_document("$merge").unwrap();
+        assert!(!merge.contains_key("on"));
+        assert_eq!(merge.get_array("whenMatched").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_validate() {
+        let pipeline = PipelineBuilder::<Document>::new()
+            .filter(doc! { "active": true })
+            .stage(doc! { "$group": { "_id": "$region" } });
+        assert!(pipeline.validate().is_ok());
+        assert!(validate_pipeline(&pipeline.clone().out_to("archive").build()).is_ok());
+
+        let err = pipeline.clone().stage(doc! { "$macth": {} }).validate().unwrap_err();
+        assert_eq!(err.to_string(), "invalid argument: stage 2: unknown stage $macth");
+
+        let empty_match = PipelineBuilder::<Document>::new().filter(doc! {});
+        assert!(empty_match.validate().is_err());
+
+        let two_fields = PipelineBuilder::<Document>::new().stage(doc! { "$skip": 1, "$limit": 1 });
+        assert!(two_fields.validate().is_err());
+    }
+
+    #[test]
+    fn test_validate_out_not_last() {
+        let stages = vec![doc! { "$out": "archive" }, doc! { "$limit": 1 }];
+        let err = validate_pipeline(&stages).unwrap_err();
+        assert_eq!(err.to_string(), "invalid argument: stage 0: $out must be the last stage");
+    }
+
+    #[test]
+    fn test_out_to() {
+        let pipeline = PipelineBuilder::<Document>::new().out_to("archive");
+        assert_eq!(pipeline.kind(), OutputStage::Out);
+        assert_eq!(pipeline.build(), vec![doc! { "$out": "archive" }]);
+    }
+
+    #[test]
+    fn test_joined_deserialization() {
+        let json = serde_json::json!({
+            "doc": { "name": "John" },
+            "joined": [{ "total": 5 }, { "total": 7 }],
+        });
+        let joined: Joined<User, Order> = serde_json::from_value(json).unwrap();
+        assert_eq!(joined.doc.name, "John");
+        assert_eq!(joined.joined, vec![Order { total: 5 }, Order { total: 7 }]);
+    }
+}
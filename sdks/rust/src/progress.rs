@@ -0,0 +1,108 @@
+//! Progress reporting for long-running bulk jobs.
+//!
+//! Batched jobs call back with a [`Progress`] after each batch: see
+//! [`Scan::on_progress`](crate::Scan::on_progress),
+//! [`Collection::update_in_batches_with_progress`][update],
+//! [`Collection::archive_with_progress`][archive],
+//! [`Cursor::write_csv_with_progress`][csv] and, with the `parquet` feature,
+//! `Collection::export_parquet_with_progress` and
+//! `Cursor::write_parquet_with_progress`.
+//!
+//! [update]: crate::Collection::update_in_batches_with_progress
+//! [archive]: crate::Collection::archive_with_progress
+//! [csv]: crate::Cursor::write_csv_with_progress
+//!
+//! # Example
+//!
+//! ```ignore
+//! let mut scan = events.scan(1000).on_progress(|progress| {
+//!     println!("{} docs, {:.0} docs/s", progress.documents, progress.rate());
+//! });
+//! ```
+
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// How far a bulk job has come.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Documents processed so far.
+    pub documents: u64,
+    /// Bytes of documents read so far, measured as JSON.
+    pub bytes: u64,
+    /// Time since the job started.
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Documents processed per second.
+    pub fn rate(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.documents as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A callback receiving progress updates.
+pub(crate) type ProgressFn = Box<dyn FnMut(&Progress) + Send>;
+
+/// Accumulates the progress of a job from its batches.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ProgressTracker {
+    started: Instant,
+    documents: u64,
+    bytes: u64,
+}
+
+impl ProgressTracker {
+    /// Start tracking a job.
+    pub(crate) fn start() -> Self {
+        Self {
+            started: Instant::now(),
+            documents: 0,
+            bytes: 0,
+        }
+    }
+
+    /// Count `documents` processed after reading `batch`.
+    pub(crate) fn record(&mut self, documents: u64, batch: &[impl Serialize]) -> Progress {
+        self.documents += documents;
+        self.bytes += crate::transport::json_len(&batch) as u64;
+        Progress {
+            documents: self.documents,
+            bytes: self.bytes,
+            elapsed: self.started.elapsed(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_rate() {
+        let progress = Progress {
+            documents: 500,
+            bytes: 0,
+            elapsed: Duration::from_secs(2),
+        };
+        assert_eq!(progress.rate(), 250.0);
+        assert_eq!(Progress::default().rate(), 0.0);
+    }
+
+    #[test]
+    fn test_progress_tracker() {
+        let batch = vec![serde_json::json!({ "_id": 1 }), serde_json::json!({ "_id": 2 })];
+        let mut tracker = ProgressTracker::start();
+        let first = tracker.record(2, &batch);
+        let second = tracker.record(2, &batch);
+        assert_eq!(first.documents, 2);
+        assert_eq!(second.documents, 4);
+        assert_eq!(second.bytes, 2 * serde_json::to_vec(&batch).unwrap().len() as u64);
+        assert!(second.elapsed >= first.elapsed);
+    }
+}
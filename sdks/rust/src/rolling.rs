@@ -0,0 +1,503 @@
+//! Time-bucketed collections.
+//!
+//! A [`RollingCollection`] spreads a stream of timestamped documents over
+//! one collection per period, named after the period: `events_2025_06` for
+//! June 2025. Writes go to the bucket of their time, reads over a time range
+//! visit only the buckets it overlaps, and old data is removed by dropping
+//! whole buckets, which is cheaper than a TTL index deleting documents one by
+//! one.
+//!
+//! Documents must store their time in the
+//! [`time_field`](RollingOptions::time_field), which is indexed in every
+//! bucket the first time it is written to.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::rolling::{BucketPeriod, RollingOptions};
+//!
+//! let options = RollingOptions::builder()
+//!     .period(BucketPeriod::Day)
+//!     .time_field("at")
+//!     .retention(30)
+//!     .build();
+//! let events = db.rolling_collection::<Event>("events", options);
+//!
+//! events.insert_one(Event { at: DateTime::now(), kind: "login".into() }).await?;
+//! let logins = events.find_between(midnight, DateTime::now(), doc! { "kind": "login" }).await?;
+//! let dropped = events.drop_expired().await?;
+//! ```
+
+use crate::collection::{and_filter, Collection, InsertOneResult};
+use crate::db::Database;
+use crate::error::Result;
+use bson::{doc, DateTime, Document};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashSet;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+
+/// Milliseconds in a day.
+const DAY_MS: i64 = 86_400_000;
+
+/// How much time one bucket of a [`RollingCollection`] covers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BucketPeriod {
+    /// One bucket per UTC day, e.g. `events_2025_06_30`.
+    Day,
+    /// One bucket per UTC month, e.g. `events_2025_06`.
+    #[default]
+    Month,
+    /// One bucket per UTC year, e.g. `events_2025`.
+    Year,
+}
+
+impl BucketPeriod {
+    /// The start of the bucket containing `time`.
+    fn start(self, time: DateTime) -> DateTime {
+        let (year, month, day) = civil_from_days(time.timestamp_millis().div_euclid(DAY_MS));
+        let (month, day) = match self {
+            BucketPeriod::Day => (month, day),
+            BucketPeriod::Month => (month, 1),
+            BucketPeriod::Year => (1, 1),
+        };
+        DateTime::from_millis(days_from_civil(year, month, day) * DAY_MS)
+    }
+
+    /// The start of the bucket after the one starting at `start`.
+    fn next(self, start: DateTime) -> DateTime {
+        let (year, month, day) = civil_from_days(start.timestamp_millis().div_euclid(DAY_MS));
+        let days = match self {
+            BucketPeriod::Day => days_from_civil(year, month, day) + 1,
+            BucketPeriod::Month if month == 12 => days_from_civil(year + 1, 1, 1),
+            BucketPeriod::Month => days_from_civil(year, month + 1, 1),
+            BucketPeriod::Year => days_from_civil(year + 1, 1, 1),
+        };
+        DateTime::from_millis(days * DAY_MS)
+    }
+
+    /// The start of the bucket `n` buckets before the one starting at `start`.
+    fn back(self, start: DateTime, n: u32) -> DateTime {
+        let (year, month, day) = civil_from_days(start.timestamp_millis().div_euclid(DAY_MS));
+        let n = i64::from(n);
+        let days = match self {
+            BucketPeriod::Day => days_from_civil(year, month, day) - n,
+            BucketPeriod::Month => {
+                let months = year * 12 + i64::from(month) - 1 - n;
+                days_from_civil(months.div_euclid(12), months.rem_euclid(12) as u32 + 1, 1)
+            }
+            BucketPeriod::Year => days_from_civil(year - n, 1, 1),
+        };
+        DateTime::from_millis(days * DAY_MS)
+    }
+
+    /// The collection name suffix of the bucket starting at `start`.
+    fn suffix(self, start: DateTime) -> String {
+        let (year, month, day) = civil_from_days(start.timestamp_millis().div_euclid(DAY_MS));
+        match self {
+            BucketPeriod::Day => format!("{:04}_{:02}_{:02}", year, month, day),
+            BucketPeriod::Month => format!("{:04}_{:02}", year, month),
+            BucketPeriod::Year => format!("{:04}", year),
+        }
+    }
+
+    /// The start of the bucket a collection name suffix names, if it names one.
+    fn parse_suffix(self, suffix: &str) -> Option<DateTime> {
+        let parts = suffix
+            .split('_')
+            .map(|part| match part.bytes().all(|b| b.is_ascii_digit()) {
+                true => part.parse::<u32>().ok(),
+                false => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let (year, month, day) = match (self, parts.as_slice()) {
+            (BucketPeriod::Day, &[year, month, day]) => (year, month, day),
+            (BucketPeriod::Month, &[year, month]) => (year, month, 1),
+            (BucketPeriod::Year, &[year]) => (year, 1, 1),
+            _ => return None,
+        };
+        let days = days_from_civil(i64::from(year), month, day);
+        // Reject dates like the 31st of April by round-tripping them.
+        (civil_from_days(days) == (i64::from(year), month, day) && (1..=12).contains(&month))
+            .then(|| DateTime::from_millis(days * DAY_MS))
+    }
+}
+
+/// Options for a [`RollingCollection`].
+#[derive(Debug, Clone)]
+pub struct RollingOptions {
+    /// Time covered by each bucket. Defaults to a month.
+    pub period: BucketPeriod,
+    /// Field holding each document's time. Defaults to `timestamp`.
+    pub time_field: String,
+    /// Number of buckets to keep, including the current one. Older buckets
+    /// are dropped by [`RollingCollection::drop_expired`]. Keeps all by
+    /// default.
+    pub retention: Option<u32>,
+}
+
+impl Default for RollingOptions {
+    fn default() -> Self {
+        Self {
+            period: BucketPeriod::default(),
+            time_field: "timestamp".to_string(),
+            retention: None,
+        }
+    }
+}
+
+impl RollingOptions {
+    /// Create a builder.
+    pub fn builder() -> RollingOptionsBuilder {
+        RollingOptionsBuilder::default()
+    }
+}
+
+/// Builder for RollingOptions.
+#[derive(Debug, Clone, Default)]
+pub struct RollingOptionsBuilder {
+    options: RollingOptions,
+}
+
+impl RollingOptionsBuilder {
+    /// Set the time covered by each bucket.
+    pub fn period(mut self, period: BucketPeriod) -> Self {
+        self.options.period = period;
+        self
+    }
+
+    /// Set the field holding each document's time.
+    pub fn time_field(mut self, field: impl Into<String>) -> Self {
+        self.options.time_field = field.into();
+        self
+    }
+
+    /// Set the number of buckets to keep.
+    pub fn retention(mut self, buckets: u32) -> Self {
+        self.options.retention = Some(buckets);
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> RollingOptions {
+        self.options
+    }
+}
+
+/// Documents of type `T` stored in one collection per time period.
+pub struct RollingCollection<T> {
+    /// Database holding the buckets.
+    db: Database,
+    /// Bucket name prefix.
+    prefix: String,
+    /// Period, time field and retention.
+    options: RollingOptions,
+    /// Buckets whose time field index this handle has created.
+    indexed: Arc<Mutex<HashSet<String>>>,
+    /// Type marker.
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> RollingCollection<T> {
+    /// Create a handle to the buckets named `{prefix}_{period}` in `db`.
+    pub(crate) fn new(db: Database, prefix: &str, options: RollingOptions) -> Self {
+        Self {
+            db,
+            prefix: prefix.to_string(),
+            options,
+            indexed: Arc::new(Mutex::new(HashSet::new())),
+            _marker: PhantomData,
+        }
+    }
+
+    /// The options of this collection.
+    pub fn options(&self) -> &RollingOptions {
+        &self.options
+    }
+
+    /// The name of the bucket holding documents at `time`.
+    pub fn bucket_name(&self, time: impl Into<DateTime>) -> String {
+        let start = self.options.period.start(time.into());
+        self.name_of(start)
+    }
+
+    /// The names of the buckets overlapping `start` up to, but excluding,
+    /// `end`, oldest first.
+    pub fn buckets_between(
+        &self,
+        start: impl Into<DateTime>,
+        end: impl Into<DateTime>,
+    ) -> Vec<String> {
+        let period = self.options.period;
+        let end = end.into();
+        let mut names = Vec::new();
+        let mut bucket = period.start(start.into());
+        while bucket < end {
+            names.push(self.name_of(bucket));
+            bucket = period.next(bucket);
+        }
+        names
+    }
+
+    /// The name of the bucket starting at `start`.
+    fn name_of(&self, start: DateTime) -> String {
+        format!("{}_{}", self.prefix, self.options.period.suffix(start))
+    }
+
+    /// The filter selecting documents of `filter` from `start` up to `end`.
+    fn range_filter(&self, filter: &Document, start: DateTime, end: DateTime) -> Document {
+        let range = doc! { self.options.time_field.as_str(): { "$gte": start, "$lt": end } };
+        and_filter(filter, range)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> RollingCollection<T> {
+    /// The bucket holding documents at `time`.
+    pub fn bucket(&self, time: impl Into<DateTime>) -> Collection<T> {
+        self.db.collection(&self.bucket_name(time))
+    }
+
+    /// Insert a document into the current bucket.
+    pub async fn insert_one(&self, doc: impl Into<T>) -> Result<InsertOneResult> {
+        self.insert_one_at(doc, DateTime::now()).await
+    }
+
+    /// Insert a document into the bucket of `time`, which should be the
+    /// document's time field.
+    pub async fn insert_one_at(
+        &self,
+        doc: impl Into<T>,
+        time: impl Into<DateTime>,
+    ) -> Result<InsertOneResult> {
+        let bucket = self.bucket(time);
+        self.ensure_index(&bucket).await?;
+        bucket.insert_one(doc).await
+    }
+
+    /// Index the time field of `bucket` if this handle has not yet.
+    async fn ensure_index(&self, bucket: &Collection<T>) -> Result<()> {
+        let indexed = |name: &str| {
+            self.indexed
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .contains(name)
+        };
+        if indexed(bucket.name()) {
+            return Ok(());
+        }
+        bucket
+            .create_index(doc! { self.options.time_field.as_str(): 1 }, None)
+            .await?;
+        self.indexed
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .insert(bucket.name().to_string());
+        Ok(())
+    }
+
+    /// Find the documents matching `filter` from `start` up to, but
+    /// excluding, `end`, reading only the buckets the range overlaps, oldest
+    /// bucket first.
+    pub async fn find_between(
+        &self,
+        start: impl Into<DateTime>,
+        end: impl Into<DateTime>,
+        filter: impl Into<Option<Document>>,
+    ) -> Result<Vec<T>> {
+        let (start, end) = (start.into(), end.into());
+        let filter = self.range_filter(&filter.into().unwrap_or_default(), start, end);
+        let mut documents = Vec::new();
+        for name in self.buckets_between(start, end) {
+            let cursor = self.db.collection::<T>(&name).find(filter.clone()).await?;
+            documents.extend(cursor.collect().await?);
+        }
+        Ok(documents)
+    }
+
+    /// Count the documents matching `filter` from `start` up to, but
+    /// excluding, `end`.
+    pub async fn count_between(
+        &self,
+        start: impl Into<DateTime>,
+        end: impl Into<DateTime>,
+        filter: impl Into<Option<Document>>,
+    ) -> Result<u64> {
+        let (start, end) = (start.into(), end.into());
+        let filter = self.range_filter(&filter.into().unwrap_or_default(), start, end);
+        let mut count = 0;
+        for name in self.buckets_between(start, end) {
+            count += self.db.collection::<T>(&name).count_documents(filter.clone()).await?;
+        }
+        Ok(count)
+    }
+
+    /// Drop the buckets older than the retention, returning their names.
+    ///
+    /// Does nothing without a retention.
+    pub async fn drop_expired(&self) -> Result<Vec<String>> {
+        let Some(retention) = self.options.retention else {
+            return Ok(Vec::new());
+        };
+        let period = self.options.period;
+        let current = period.start(DateTime::now());
+        let oldest_kept = period.back(current, retention.saturating_sub(1));
+
+        let mut dropped = Vec::new();
+        for name in self.db.list_collection_names().await? {
+            let expired = name
+                .strip_prefix(&self.prefix)
+                .and_then(|rest| rest.strip_prefix('_'))
+                .and_then(|suffix| period.parse_suffix(suffix))
+                .is_some_and(|start| start < oldest_kept);
+            if expired {
+                self.db.collection::<T>(&name).drop().await?;
+                self.indexed
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .remove(&name);
+                dropped.push(name);
+            }
+        }
+        Ok(dropped)
+    }
+}
+
+impl<T> Clone for RollingCollection<T> {
+    fn clone(&self) -> Self {
+        Self {
+            db: self.db.clone(),
+            prefix: self.prefix.clone(),
+            options: self.options.clone(),
+            indexed: self.indexed.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> fmt::Debug for RollingCollection<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RollingCollection")
+            .field("database", &self.db.name())
+            .field("prefix", &self.prefix)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year.rem_euclid(400);
+    let month = i64::from(month);
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5
+        + i64::from(day)
+        - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// The proleptic Gregorian date `days` after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Midnight UTC of a date.
+    fn date(year: i64, month: u32, day: u32) -> DateTime {
+        DateTime::from_millis(days_from_civil(year, month, day) * DAY_MS)
+    }
+
+    #[test]
+    fn test_civil_dates() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(days_from_civil(2000, 3, 1) - days_from_civil(2000, 2, 28), 2);
+        let june = DateTime::parse_rfc3339_str("2025-06-15T13:45:00Z").unwrap();
+        let days = june.timestamp_millis().div_euclid(DAY_MS);
+        assert_eq!(civil_from_days(days), (2025, 6, 15));
+        assert_eq!(days_from_civil(2025, 6, 15), days);
+    }
+
+    #[test]
+    fn test_bucket_periods() {
+        let time = DateTime::parse_rfc3339_str("2025-06-15T13:45:00Z").unwrap();
+        assert_eq!(BucketPeriod::Day.start(time), date(2025, 6, 15));
+        assert_eq!(BucketPeriod::Month.start(time), date(2025, 6, 1));
+        assert_eq!(BucketPeriod::Year.start(time), date(2025, 1, 1));
+
+        assert_eq!(BucketPeriod::Day.next(date(2024, 2, 28)), date(2024, 2, 29));
+        assert_eq!(BucketPeriod::Month.next(date(2025, 12, 1)), date(2026, 1, 1));
+        assert_eq!(BucketPeriod::Month.back(date(2025, 2, 1), 3), date(2024, 11, 1));
+        assert_eq!(BucketPeriod::Year.back(date(2025, 1, 1), 2), date(2023, 1, 1));
+
+        assert_eq!(BucketPeriod::Day.suffix(date(2025, 6, 5)), "2025_06_05");
+        assert_eq!(BucketPeriod::Month.suffix(date(2025, 6, 1)), "2025_06");
+        assert_eq!(BucketPeriod::Year.suffix(date(2025, 1, 1)), "2025");
+
+        assert_eq!(BucketPeriod::Month.parse_suffix("2025_06"), Some(date(2025, 6, 1)));
+        assert_eq!(BucketPeriod::Day.parse_suffix("2024_02_29"), Some(date(2024, 2, 29)));
+        assert_eq!(BucketPeriod::Day.parse_suffix("2025_04_31"), None);
+        assert_eq!(BucketPeriod::Month.parse_suffix("2025_13"), None);
+        assert_eq!(BucketPeriod::Month.parse_suffix("2025_06_01"), None);
+        assert_eq!(BucketPeriod::Month.parse_suffix("archive"), None);
+    }
+
+    #[test]
+    fn test_bucket_names() {
+        let transport = crate::transport::Transport::lazy(
+            "mongodb://localhost".to_string(),
+            crate::ClientOptions::default(),
+        );
+        let db = Database::new("telemetry".to_string(), transport);
+        let events = db.rolling_collection::<Document>("events", RollingOptions::default());
+        let time = DateTime::parse_rfc3339_str("2025-06-15T13:45:00Z").unwrap();
+        assert_eq!(events.bucket_name(time), "events_2025_06");
+        assert_eq!(events.bucket(time).namespace(), "telemetry.events_2025_06");
+        assert_eq!(
+            events.buckets_between(date(2025, 5, 20), date(2025, 8, 1)),
+            vec!["events_2025_05", "events_2025_06", "events_2025_07"]
+        );
+        assert!(events.buckets_between(date(2025, 8, 1), date(2025, 8, 1)).is_empty());
+        assert_eq!(
+            events.range_filter(&doc! { "kind": "login" }, date(2025, 6, 1), date(2025, 6, 2)),
+            doc! { "$and": [
+                { "kind": "login" },
+                { "timestamp": { "$gte": date(2025, 6, 1), "$lt": date(2025, 6, 2) } },
+            ] }
+        );
+    }
+
+    #[test]
+    fn test_rolling_options() {
+        let options = RollingOptions::builder()
+            .period(BucketPeriod::Day)
+            .time_field("at")
+            .retention(30)
+            .build();
+        assert_eq!(options.period, BucketPeriod::Day);
+        assert_eq!(options.time_field, "at");
+        assert_eq!(options.retention, Some(30));
+
+        let default = RollingOptions::default();
+        assert_eq!(default.period, BucketPeriod::Month);
+        assert_eq!(default.time_field, "timestamp");
+        assert!(default.retention.is_none());
+    }
+}
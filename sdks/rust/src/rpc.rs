@@ -0,0 +1,276 @@
+//! The RPC methods of the .do backend.
+//!
+//! Every call the SDK sends names one [`Method`]. Command monitoring events
+//! carry it, so handlers can match on the operation instead of its name:
+//!
+//! ```ignore
+//! impl CommandEventHandler for SlowWrites {
+//!     fn command_succeeded(&self, event: &CommandSucceededEvent) {
+//!         if event.method.is_write() && event.duration > Duration::from_millis(100) {
+//!             warn!("slow {} took {:?}", event.method, event.duration);
+//!         }
+//!     }
+//! }
+//! ```
+
+use crate::error::MongoError;
+use std::fmt;
+use std::str::FromStr;
+
+/// An RPC method of the .do backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Method {
+    /// Protocol handshake.
+    Hello,
+    /// Check the connection.
+    Ping,
+    /// List databases.
+    ListDatabases,
+    /// Start a session.
+    StartSession,
+    /// End a session.
+    EndSession,
+    /// Start a transaction in a session.
+    StartTransaction,
+    /// Commit a session's transaction.
+    CommitTransaction,
+    /// Abort a session's transaction.
+    AbortTransaction,
+    /// Insert a document.
+    InsertOne,
+    /// Insert documents.
+    InsertMany,
+    /// Find documents.
+    Find,
+    /// Find a document.
+    FindOne,
+    /// Fetch the next batch of a cursor.
+    GetMore,
+    /// Update a document.
+    UpdateOne,
+    /// Update documents.
+    UpdateMany,
+    /// Replace a document.
+    ReplaceOne,
+    /// Delete a document.
+    DeleteOne,
+    /// Delete documents.
+    DeleteMany,
+    /// Update a document and return it.
+    FindOneAndUpdate,
+    /// Replace a document and return it.
+    FindOneAndReplace,
+    /// Delete a document and return it.
+    FindOneAndDelete,
+    /// Run several writes.
+    BulkWrite,
+    /// Count matching documents.
+    CountDocuments,
+    /// Estimate the number of documents from metadata.
+    EstimatedDocumentCount,
+    /// Run a collection aggregation.
+    Aggregate,
+    /// Run a database aggregation.
+    AggregateDb,
+    /// Get the distinct values of a field.
+    Distinct,
+    /// Create an index.
+    CreateIndex,
+    /// Drop an index.
+    DropIndex,
+    /// List a collection's indexes.
+    ListIndexes,
+    /// List collection names.
+    ListCollections,
+    /// Create a collection.
+    CreateCollection,
+    /// Drop a collection.
+    DropCollection,
+    /// Drop a database.
+    DropDatabase,
+    /// Run a database command.
+    RunCommand,
+    /// Open a change stream.
+    Watch,
+    /// Get the next change stream event.
+    ChangeStreamNext,
+    /// Close a change stream.
+    ChangeStreamClose,
+}
+
+impl Method {
+    /// Every method.
+    pub const ALL: &'static [Method] = &[
+        Method::Hello,
+        Method::Ping,
+        Method::ListDatabases,
+        Method::StartSession,
+        Method::EndSession,
+        Method::StartTransaction,
+        Method::CommitTransaction,
+        Method::AbortTransaction,
+        Method::InsertOne,
+        Method::InsertMany,
+        Method::Find,
+        Method::FindOne,
+        Method::GetMore,
+        Method::UpdateOne,
+        Method::UpdateMany,
+        Method::ReplaceOne,
+        Method::DeleteOne,
+        Method::DeleteMany,
+        Method::FindOneAndUpdate,
+        Method::FindOneAndReplace,
+        Method::FindOneAndDelete,
+        Method::BulkWrite,
+        Method::CountDocuments,
+        Method::EstimatedDocumentCount,
+        Method::Aggregate,
+        Method::AggregateDb,
+        Method::Distinct,
+        Method::CreateIndex,
+        Method::DropIndex,
+        Method::ListIndexes,
+        Method::ListCollections,
+        Method::CreateCollection,
+        Method::DropCollection,
+        Method::DropDatabase,
+        Method::RunCommand,
+        Method::Watch,
+        Method::ChangeStreamNext,
+        Method::ChangeStreamClose,
+    ];
+
+    /// The method name sent to the server, e.g. `mongo.insertOne`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Hello => "mongo.hello",
+            Method::Ping => "mongo.ping",
+            Method::ListDatabases => "mongo.listDatabases",
+            Method::StartSession => "mongo.startSession",
+            Method::EndSession => "mongo.endSession",
+            Method::StartTransaction => "mongo.startTransaction",
+            Method::CommitTransaction => "mongo.commitTransaction",
+            Method::AbortTransaction => "mongo.abortTransaction",
+            Method::InsertOne => "mongo.insertOne",
+            Method::InsertMany => "mongo.insertMany",
+            Method::Find => "mongo.find",
+            Method::FindOne => "mongo.findOne",
+            Method::GetMore => "mongo.getMore",
+            Method::UpdateOne => "mongo.updateOne",
+            Method::UpdateMany => "mongo.updateMany",
+            Method::ReplaceOne => "mongo.replaceOne",
+            Method::DeleteOne => "mongo.deleteOne",
+            Method::DeleteMany => "mongo.deleteMany",
+            Method::FindOneAndUpdate => "mongo.findOneAndUpdate",
+            Method::FindOneAndReplace => "mongo.findOneAndReplace",
+            Method::FindOneAndDelete => "mongo.findOneAndDelete",
+            Method::BulkWrite => "mongo.bulkWrite",
+            Method::CountDocuments => "mongo.countDocuments",
+            Method::EstimatedDocumentCount => "mongo.estimatedDocumentCount",
+            Method::Aggregate => "mongo.aggregate",
+            Method::AggregateDb => "mongo.aggregateDb",
+            Method::Distinct => "mongo.distinct",
+            Method::CreateIndex => "mongo.createIndex",
+            Method::DropIndex => "mongo.dropIndex",
+            Method::ListIndexes => "mongo.listIndexes",
+            Method::ListCollections => "mongo.listCollections",
+            Method::CreateCollection => "mongo.createCollection",
+            Method::DropCollection => "mongo.dropCollection",
+            Method::DropDatabase => "mongo.dropDatabase",
+            Method::RunCommand => "mongo.runCommand",
+            Method::Watch => "mongo.watch",
+            Method::ChangeStreamNext => "mongo.changeStreamNext",
+            Method::ChangeStreamClose => "mongo.changeStreamClose",
+        }
+    }
+
+    /// The operation the method performs, e.g. `insertOne`.
+    pub fn operation(self) -> &'static str {
+        self.as_str().trim_start_matches("mongo.")
+    }
+
+    /// Whether the method modifies documents.
+    pub fn is_write(self) -> bool {
+        matches!(
+            self,
+            Method::InsertOne
+                | Method::InsertMany
+                | Method::UpdateOne
+                | Method::UpdateMany
+                | Method::ReplaceOne
+                | Method::DeleteOne
+                | Method::DeleteMany
+                | Method::FindOneAndUpdate
+                | Method::FindOneAndReplace
+                | Method::FindOneAndDelete
+                | Method::BulkWrite
+        )
+    }
+
+    /// Whether the method's arguments start with a database and collection.
+    pub(crate) fn has_namespace(self) -> bool {
+        !matches!(
+            self,
+            Method::Hello
+                | Method::Ping
+                | Method::ListDatabases
+                | Method::StartSession
+                | Method::EndSession
+                | Method::StartTransaction
+                | Method::CommitTransaction
+                | Method::AbortTransaction
+                | Method::GetMore
+                | Method::ChangeStreamNext
+                | Method::ChangeStreamClose
+        )
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Parses a method name, with or without the `mongo.` prefix.
+impl FromStr for Method {
+    type Err = MongoError;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let operation = name.strip_prefix("mongo.").unwrap_or(name);
+        Method::ALL
+            .iter()
+            .copied()
+            .find(|method| method.operation() == operation)
+            .ok_or_else(|| MongoError::UnknownMethod(name.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_method_names() {
+        assert_eq!(Method::InsertOne.as_str(), "mongo.insertOne");
+        assert_eq!(Method::InsertOne.operation(), "insertOne");
+        assert_eq!(Method::EstimatedDocumentCount.to_string(), "mongo.estimatedDocumentCount");
+        for method in Method::ALL {
+            assert_eq!(method.as_str().parse::<Method>().unwrap(), *method);
+            assert_eq!(method.operation().parse::<Method>().unwrap(), *method);
+        }
+        let err = "mongo.explode".parse::<Method>().unwrap_err();
+        assert!(matches!(err, MongoError::UnknownMethod(ref name) if name == "mongo.explode"));
+    }
+
+    #[test]
+    fn test_method_kinds() {
+        assert!(Method::UpdateMany.is_write());
+        assert!(!Method::Find.is_write());
+        assert!(Method::Find.has_namespace());
+        assert!(!Method::GetMore.has_namespace());
+        assert!(!Method::Ping.has_namespace());
+    }
+}
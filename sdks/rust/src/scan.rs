@@ -0,0 +1,245 @@
+//! Full collection scans in `_id` order.
+//!
+//! A [`Scan`] reads a collection in batches of ascending `_id`, each batch
+//! a separate range query, so no server cursor is held open for the length
+//! of the job. With a checkpoint, the last `_id` of each finished batch is
+//! saved to a [`ResumeTokenStore`] and a restarted job continues after it.
+//!
+//! The scan is not a snapshot: documents inserted during the scan are read
+//! if their `_id` sorts after the current position.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let store = Arc::new(CollectionTokenStore::new(db.collection_with_doc("checkpoints")));
+//! let mut scan = users.scan(500).with_checkpoint(store, "reindex-users");
+//! while let Some(batch) = scan.next_batch().await? {
+//!     for user in batch {
+//!         reindex(&user).await?;
+//!     }
+//! }
+//! ```
+
+use crate::change_stream::ResumeTokenStore;
+use crate::collection::{Collection, FindOptions};
+use crate::error::{MongoError, Result};
+use crate::progress::{Progress, ProgressFn, ProgressTracker};
+use bson::{doc, Bson, Document};
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
+use std::fmt;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// Field of the checkpoint document holding the last `_id` scanned.
+const LAST_ID_FIELD: &str = "lastId";
+
+/// A batched scan over a whole collection in `_id` order.
+///
+/// Created by [`Collection::scan`].
+pub struct Scan<T> {
+    /// Collection being scanned, read as raw documents to see each `_id`.
+    collection: Collection<JsonValue>,
+    /// Documents per batch.
+    batch_size: u32,
+    /// `_id` of the last document returned.
+    last_id: Option<Bson>,
+    /// Where progress is saved, and under which name.
+    checkpoint: Option<(Arc<dyn ResumeTokenStore>, String)>,
+    /// Whether the first batch has been requested.
+    started: bool,
+    /// Whether the scan reached the end of the collection.
+    done: bool,
+    /// Called after each batch.
+    on_progress: Option<ProgressFn>,
+    /// Progress since the first batch.
+    tracker: ProgressTracker,
+    /// Type marker.
+    _marker: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Scan<T> {
+    /// Create a scan over `collection`.
+    pub(crate) fn new(collection: Collection<JsonValue>, batch_size: u32) -> Self {
+        Self {
+            collection,
+            batch_size: batch_size.max(1),
+            last_id: None,
+            checkpoint: None,
+            started: false,
+            done: false,
+            on_progress: None,
+            tracker: ProgressTracker::start(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Start after the document with this `_id`.
+    pub fn resume_after(mut self, id: impl Into<Bson>) -> Self {
+        self.last_id = Some(id.into());
+        self
+    }
+
+    /// Save progress to `store` under `name`, resuming from it if saved before.
+    ///
+    /// A batch is saved when the next one is requested, so a job that stops
+    /// while processing a batch processes it again on restart.
+    pub fn with_checkpoint(
+        mut self,
+        store: Arc<dyn ResumeTokenStore>,
+        name: impl Into<String>,
+    ) -> Self {
+        self.checkpoint = Some((store, name.into()));
+        self
+    }
+
+    /// Call `progress` after each batch is read.
+    pub fn on_progress(mut self, progress: impl FnMut(&Progress) + Send + 'static) -> Self {
+        self.on_progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Get the `_id` of the last document returned.
+    pub fn last_id(&self) -> Option<&Bson> {
+        self.last_id.as_ref()
+    }
+
+    /// Get the next batch, or `None` once the whole collection was read.
+    pub async fn next_batch(&mut self) -> Result<Option<Vec<T>>> {
+        if self.done {
+            return Ok(None);
+        }
+        if self.started {
+            self.save().await?;
+        } else {
+            self.started = true;
+            self.tracker = ProgressTracker::start();
+            self.load().await?;
+        }
+
+        let options = FindOptions::builder()
+            .sort(doc! { "_id": 1 })
+            .limit(i64::from(self.batch_size))
+            .batch_size(self.batch_size)
+            .build();
+        let documents = self
+            .collection
+            .find_with_options(scan_filter(self.last_id.as_ref()), options)
+            .await?
+            .collect()
+            .await?;
+
+        let Some(last) = documents.last() else {
+            self.done = true;
+            return Ok(None);
+        };
+        let last_id = last
+            .get("_id")
+            .ok_or_else(|| MongoError::Deserialization("scanned document has no _id".into()))?;
+        self.last_id = Some(self.collection.rpc_client.decode(last_id));
+        let progress = self.tracker.record(documents.len() as u64, &documents);
+        if let Some(ref mut on_progress) = self.on_progress {
+            on_progress(&progress);
+        }
+
+        documents
+            .into_iter()
+            .map(|doc| self.collection.rpc_client.decode_typed(&doc))
+            .collect::<Result<_>>()
+            .map(Some)
+    }
+
+    /// Load the saved position, unless a position was given explicitly.
+    async fn load(&mut self) -> Result<()> {
+        if let (None, Some((store, name))) = (&self.last_id, &self.checkpoint) {
+            if let Some(saved) = store.load(name).await? {
+                self.last_id = saved.get(LAST_ID_FIELD).cloned();
+            }
+        }
+        Ok(())
+    }
+
+    /// Save the current position, if checkpointing.
+    async fn save(&self) -> Result<()> {
+        if let (Some(last_id), Some((store, name))) = (&self.last_id, &self.checkpoint) {
+            store.save(name, &doc! { LAST_ID_FIELD: last_id.clone() }).await?;
+        }
+        Ok(())
+    }
+}
+
+impl<T> fmt::Debug for Scan<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Scan")
+            .field("namespace", &self.collection.namespace())
+            .field("batch_size", &self.batch_size)
+            .field("last_id", &self.last_id)
+            .field("checkpoint", &self.checkpoint.as_ref().map(|(_, name)| name))
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+/// The filter selecting documents after `last_id`.
+fn scan_filter(last_id: Option<&Bson>) -> Document {
+    match last_id {
+        Some(id) => doc! { "_id": { "$gt": id.clone() } },
+        None => Document::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct MemoryStore(Mutex<HashMap<String, Document>>);
+
+    #[async_trait]
+    impl ResumeTokenStore for MemoryStore {
+        async fn load(&self, name: &str) -> Result<Option<Document>> {
+            Ok(self.0.lock().unwrap().get(name).cloned())
+        }
+
+        async fn save(&self, name: &str, token: &Document) -> Result<()> {
+            self.0.lock().unwrap().insert(name.to_string(), token.clone());
+            Ok(())
+        }
+    }
+
+    fn users_scan() -> Scan<Document> {
+        let transport = crate::transport::Transport::lazy(
+            "mongodb://localhost".to_string(),
+            crate::ClientOptions::default(),
+        );
+        Scan::new(Collection::new("db".into(), "users".into(), transport), 100)
+    }
+
+    #[test]
+    fn test_scan_filter() {
+        assert!(scan_filter(None).is_empty());
+        assert_eq!(scan_filter(Some(&Bson::Int32(7))), doc! { "_id": { "$gt": 7 } });
+    }
+
+    #[tokio::test]
+    async fn test_scan_checkpoint() {
+        let store = Arc::new(MemoryStore::default());
+        store.save("job", &doc! { "lastId": 42 }).await.unwrap();
+
+        let mut scan = users_scan().with_checkpoint(store.clone(), "job");
+        scan.load().await.unwrap();
+        assert_eq!(scan.last_id(), Some(&Bson::Int32(42)));
+
+        scan.last_id = Some(Bson::Int32(50));
+        scan.save().await.unwrap();
+        assert_eq!(store.load("job").await.unwrap(), Some(doc! { "lastId": 50 }));
+
+        // An explicit position wins over the saved one.
+        let mut scan = users_scan().resume_after(7).with_checkpoint(store, "job");
+        scan.load().await.unwrap();
+        assert_eq!(scan.last_id(), Some(&Bson::Int32(7)));
+    }
+}
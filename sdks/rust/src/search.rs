@@ -0,0 +1,371 @@
+//! Atlas Search `$search` stages.
+//!
+//! [`Search`] builds a `$search` stage from [`SearchOperator`]s. Appending it
+//! with [`PipelineBuilder::search`](crate::PipelineBuilder::search) also adds
+//! each result's score and highlights, read back as a [`SearchHit`].
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::search::{Compound, Search, SearchHit, SearchOperator};
+//!
+//! let search = Search::new(
+//!     Compound::new()
+//!         .must(SearchOperator::text("espresso", ["title", "body"]).fuzzy(1))
+//!         .should(SearchOperator::text("italian", ["tags"]).boost(2.0)),
+//! )
+//! .index("articles")
+//! .highlight(["body"]);
+//!
+//! let pipeline = articles.pipeline().search(search).limit(10);
+//! let hits: Vec<SearchHit<Article>> =
+//!     articles.aggregate_pipeline(pipeline).await?.collect().await?;
+//! for hit in hits {
+//!     println!("{} ({:.2}): {:?}", hit.doc.title, hit.score, hit.highlight_hits("body"));
+//! }
+//! ```
+
+use bson::{doc, Bson, Document};
+use serde::{Deserialize, Serialize};
+
+/// Field a [`SearchHit`] reads the score from.
+pub(crate) const SCORE_FIELD: &str = "searchScore";
+
+/// Field a [`SearchHit`] reads the highlights from.
+pub(crate) const HIGHLIGHTS_FIELD: &str = "searchHighlights";
+
+/// A `$search` operator, such as `text` or `compound`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchOperator {
+    /// Operator name.
+    name: &'static str,
+    /// Operator options.
+    body: Document,
+}
+
+impl SearchOperator {
+    /// Match `query` as analyzed text in the fields `path`.
+    pub fn text<P: Into<String>>(
+        query: impl Into<String>,
+        path: impl IntoIterator<Item = P>,
+    ) -> Self {
+        Self {
+            name: "text",
+            body: doc! { "query": query.into(), "path": search_path(path) },
+        }
+    }
+
+    /// Match `query` as a prefix of words in the field `path`, which needs an
+    /// `autocomplete` mapping in the search index.
+    pub fn autocomplete(query: impl Into<String>, path: impl Into<String>) -> Self {
+        Self {
+            name: "autocomplete",
+            body: doc! { "query": query.into(), "path": path.into() },
+        }
+    }
+
+    /// Combine operators with a [`Compound`].
+    pub fn compound(compound: Compound) -> Self {
+        Self {
+            name: "compound",
+            body: compound.build(),
+        }
+    }
+
+    /// Accept matches up to `max_edits` single-character edits away, for
+    /// `text` and `autocomplete`.
+    pub fn fuzzy(mut self, max_edits: u32) -> Self {
+        self.body.insert("fuzzy", doc! { "maxEdits": max_edits });
+        self
+    }
+
+    /// Multiply the score of matches by `factor`.
+    pub fn boost(mut self, factor: f64) -> Self {
+        self.body.insert("score", doc! { "boost": { "value": factor } });
+        self
+    }
+
+    /// Build the operator document, e.g. `{ "text": { ... } }`.
+    pub fn build(self) -> Document {
+        doc! { self.name: self.body }
+    }
+}
+
+impl From<Compound> for SearchOperator {
+    fn from(compound: Compound) -> Self {
+        SearchOperator::compound(compound)
+    }
+}
+
+/// A `compound` operator combining other operators.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Compound {
+    must: Vec<Document>,
+    must_not: Vec<Document>,
+    should: Vec<Document>,
+    filter: Vec<Document>,
+    minimum_should_match: Option<u32>,
+}
+
+impl Compound {
+    /// Create an empty compound operator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require `operator` to match, adding to the score.
+    pub fn must(mut self, operator: SearchOperator) -> Self {
+        self.must.push(operator.build());
+        self
+    }
+
+    /// Exclude documents matching `operator`.
+    pub fn must_not(mut self, operator: SearchOperator) -> Self {
+        self.must_not.push(operator.build());
+        self
+    }
+
+    /// Prefer documents matching `operator`, adding to the score.
+    pub fn should(mut self, operator: SearchOperator) -> Self {
+        self.should.push(operator.build());
+        self
+    }
+
+    /// Require `operator` to match, without affecting the score.
+    pub fn filter(mut self, operator: SearchOperator) -> Self {
+        self.filter.push(operator.build());
+        self
+    }
+
+    /// Require at least `n` of the `should` operators to match.
+    pub fn minimum_should_match(mut self, n: u32) -> Self {
+        self.minimum_should_match = Some(n);
+        self
+    }
+
+    fn build(self) -> Document {
+        let mut compound = Document::new();
+        for (clause, operators) in [
+            ("must", self.must),
+            ("mustNot", self.must_not),
+            ("should", self.should),
+            ("filter", self.filter),
+        ] {
+            if !operators.is_empty() {
+                compound.insert(clause, operators);
+            }
+        }
+        if let Some(n) = self.minimum_should_match {
+            compound.insert("minimumShouldMatch", n);
+        }
+        compound
+    }
+}
+
+/// A `$search` stage.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Search {
+    operator: SearchOperator,
+    index: Option<String>,
+    highlight: Option<Vec<String>>,
+}
+
+impl Search {
+    /// Create a search running `operator`.
+    pub fn new(operator: impl Into<SearchOperator>) -> Self {
+        Self {
+            operator: operator.into(),
+            index: None,
+            highlight: None,
+        }
+    }
+
+    /// Use the search index `name` instead of `default`.
+    pub fn index(mut self, name: impl Into<String>) -> Self {
+        self.index = Some(name.into());
+        self
+    }
+
+    /// Return highlighted passages from the fields `path`.
+    pub fn highlight<P: Into<String>>(mut self, path: impl IntoIterator<Item = P>) -> Self {
+        self.highlight = Some(path.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Whether highlights were requested.
+    pub(crate) fn has_highlight(&self) -> bool {
+        self.highlight.is_some()
+    }
+
+    /// Build the `$search` stage.
+    pub fn build(self) -> Document {
+        let mut search = Document::new();
+        if let Some(index) = self.index {
+            search.insert("index", index);
+        }
+        search.extend(self.operator.build());
+        if let Some(highlight) = self.highlight {
+            search.insert("highlight", doc! { "path": search_path(highlight) });
+        }
+        doc! { "$search": search }
+    }
+}
+
+impl From<Search> for Document {
+    fn from(search: Search) -> Self {
+        search.build()
+    }
+}
+
+/// The `{ "$meta": "searchScore" }` expression.
+pub fn search_score() -> Document {
+    doc! { "$meta": "searchScore" }
+}
+
+/// The `{ "$meta": "searchHighlights" }` expression.
+pub fn search_highlights() -> Document {
+    doc! { "$meta": "searchHighlights" }
+}
+
+/// A search result with its score and highlights.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SearchHit<T> {
+    /// The matching document.
+    #[serde(flatten)]
+    pub doc: T,
+    /// Relevance score.
+    #[serde(rename = "searchScore", default)]
+    pub score: f64,
+    /// Highlighted passages, if highlights were requested.
+    #[serde(rename = "searchHighlights", default)]
+    pub highlights: Vec<Highlight>,
+}
+
+impl<T> SearchHit<T> {
+    /// Get the matched terms highlighted in the field `path`.
+    pub fn highlight_hits(&self, path: &str) -> Vec<&str> {
+        self.highlights
+            .iter()
+            .filter(|highlight| highlight.path == path)
+            .flat_map(|highlight| &highlight.texts)
+            .filter(|text| text.kind == HighlightKind::Hit)
+            .map(|text| text.value.as_str())
+            .collect()
+    }
+}
+
+/// A highlighted passage of a search result.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Highlight {
+    /// Field the passage is from.
+    pub path: String,
+    /// The passage, split into matched and surrounding text.
+    pub texts: Vec<HighlightText>,
+    /// Relevance of the passage.
+    #[serde(default)]
+    pub score: f64,
+}
+
+/// A piece of a highlighted passage.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HighlightText {
+    /// The text.
+    pub value: String,
+    /// Whether the text matched the query.
+    #[serde(rename = "type")]
+    pub kind: HighlightKind,
+}
+
+/// Whether a piece of a highlighted passage matched the query.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HighlightKind {
+    /// Text matching the query.
+    Hit,
+    /// Text around a match.
+    Text,
+}
+
+/// A search path: a single field, or an array of fields.
+fn search_path<P: Into<String>>(path: impl IntoIterator<Item = P>) -> Bson {
+    let mut fields: Vec<String> = path.into_iter().map(Into::into).collect();
+    if fields.len() == 1 {
+        Bson::String(fields.remove(0))
+    } else {
+        Bson::from(fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_text() {
+        let search = Search::new(SearchOperator::text("espresso", ["title"]).fuzzy(1))
+            .index("articles")
+            .build();
+        assert_eq!(
+            search,
+            doc! {
+                "$search": {
+                    "index": "articles",
+                    "text": { "query": "espresso", "path": "title", "fuzzy": { "maxEdits": 1 } },
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_search_compound() {
+        let compound = Compound::new()
+            .must(SearchOperator::autocomplete("esp", "title"))
+            .should(SearchOperator::text("italian", ["tags", "body"]).boost(2.0))
+            .minimum_should_match(1);
+        let search = Search::new(compound).highlight(["body"]).build();
+        assert_eq!(
+            search,
+            doc! {
+                "$search": {
+                    "compound": {
+                        "must": [{ "autocomplete": { "query": "esp", "path": "title" } }],
+                        "should": [{
+                            "text": {
+                                "query": "italian",
+                                "path": ["tags", "body"],
+                                "score": { "boost": { "value": 2.0 } },
+                            }
+                        }],
+                        "minimumShouldMatch": 1,
+                    },
+                    "highlight": { "path": "body" },
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_search_hit() {
+        let json = serde_json::json!({
+            "title": "Espresso guide",
+            "searchScore": 1.5,
+            "searchHighlights": [{
+                "path": "body",
+                "score": 0.8,
+                "texts": [
+                    { "value": "a good ", "type": "text" },
+                    { "value": "espresso", "type": "hit" },
+                ],
+            }],
+        });
+        let hit: SearchHit<Document> = serde_json::from_value(json).unwrap();
+        assert_eq!(hit.doc.get_str("title").unwrap(), "Espresso guide");
+        assert_eq!(hit.score, 1.5);
+        assert_eq!(hit.highlight_hits("body"), vec!["espresso"]);
+        assert!(hit.highlight_hits("title").is_empty());
+
+        let hit: SearchHit<Document> =
+            serde_json::from_value(serde_json::json!({ "title": "x" })).unwrap();
+        assert!(hit.highlights.is_empty());
+    }
+}
@@ -0,0 +1,626 @@
+//! Client operation statistics.
+//!
+//! Every call through a [`MongoClient`](crate::MongoClient), and the
+//! databases, collections and cursors obtained from it, is counted. Read the
+//! counters with [`MongoClient::stats`](crate::MongoClient::stats), or with
+//! latency histograms and open cursors via
+//! [`MongoClient::metrics`](crate::MongoClient::metrics). With the
+//! `prometheus` feature, [`ClientMetrics::to_prometheus`] renders them in the
+//! Prometheus text format.
+//!
+//! A [`RetryBudget`], set with
+//! [`ClientOptionsBuilder::retry_budget`](crate::ClientOptionsBuilder::retry_budget),
+//! limits the silent reconnects of
+//! [`Collection::tail`](crate::Collection::tail) to a share of the client's
+//! calls, so they die down during a backend incident instead of adding to
+//! the load.
+//!
+//! # Example
+//!
+//! ```ignore
+//! let stats = client.stats();
+//! println!(
+//!     "{} ops, {:?} average latency, {} reconnects",
+//!     stats.total_operations(),
+//!     stats.average_latency,
+//!     stats.reconnects,
+//! );
+//! ```
+
+use crate::rpc::Method;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Upper bounds of the latency histogram buckets.
+pub const LATENCY_BUCKETS: [Duration; 12] = [
+    Duration::from_millis(1),
+    Duration::from_millis(2),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+
+/// Cumulative counters for a client, from when it was created.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientStats {
+    /// Number of calls per operation, e.g. `"find"` or `"insertOne"`.
+    pub operations: HashMap<String, u64>,
+    /// Number of calls that failed.
+    pub failures: u64,
+    /// Bytes of arguments sent, measured as JSON.
+    pub bytes_sent: u64,
+    /// Bytes of replies received, measured as JSON.
+    pub bytes_received: u64,
+    /// Operations retried by the client, e.g. after a write conflict.
+    pub retries: u64,
+    /// Retries not made because the retry budget was spent.
+    pub retries_denied: u64,
+    /// Audit entries that could not be recorded. The writes they describe
+    /// were still made.
+    pub audit_failures: u64,
+    /// Times a call succeeded after the connection had been lost.
+    pub reconnects: u64,
+    /// Average time a call took, including failed calls.
+    pub average_latency: Duration,
+}
+
+impl ClientStats {
+    /// Total number of calls.
+    pub fn total_operations(&self) -> u64 {
+        self.operations.values().sum()
+    }
+}
+
+/// Distribution of call latencies over [`LATENCY_BUCKETS`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyHistogram {
+    /// Calls per bucket: `counts[i]` took at most `LATENCY_BUCKETS[i]` and
+    /// more than the bound before it. The last count is for slower calls.
+    pub counts: [u64; LATENCY_BUCKETS.len() + 1],
+    /// Total time of all calls.
+    pub sum: Duration,
+}
+
+impl Default for LatencyHistogram {
+    fn default() -> Self {
+        Self {
+            counts: [0; LATENCY_BUCKETS.len() + 1],
+            sum: Duration::ZERO,
+        }
+    }
+}
+
+impl LatencyHistogram {
+    /// Number of calls.
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Estimate the `q` quantile, e.g. `0.99`, as the upper bound of the
+    /// bucket it falls in. `None` without calls or when it falls in the
+    /// last, unbounded bucket.
+    pub fn quantile(&self, q: f64) -> Option<Duration> {
+        let count = self.count();
+        if count == 0 {
+            return None;
+        }
+        let rank = (q.clamp(0.0, 1.0) * count as f64).ceil().max(1.0) as u64;
+        let mut seen = 0;
+        for (bound, n) in LATENCY_BUCKETS.iter().zip(self.counts) {
+            seen += n;
+            if seen >= rank {
+                return Some(*bound);
+            }
+        }
+        None
+    }
+
+    fn record(&mut self, latency: Duration) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| latency <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.counts[bucket] += 1;
+        self.sum += latency;
+    }
+}
+
+/// Statistics with latency histograms and open cursors.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientMetrics {
+    /// Counters, as returned by [`MongoClient::stats`](crate::MongoClient::stats).
+    pub stats: ClientStats,
+    /// Latency per operation, e.g. `"find"`.
+    pub latency: HashMap<String, LatencyHistogram>,
+    /// Cursors holding a server cursor open.
+    pub open_cursors: u64,
+    /// Retries the retry budget allows right now, if the client has one.
+    pub retry_budget: Option<u64>,
+}
+
+impl ClientMetrics {
+    /// Render the metrics in the Prometheus text exposition format.
+    #[cfg(feature = "prometheus")]
+    pub fn to_prometheus(&self) -> String {
+        use std::fmt::Write;
+
+        let mut out = String::new();
+        let mut operations: Vec<_> = self.latency.iter().collect();
+        operations.sort_by(|a, b| a.0.cmp(b.0));
+
+        let _ = writeln!(out, "# HELP mongo_do_operations_total Calls per operation.");
+        let _ = writeln!(out, "# TYPE mongo_do_operations_total counter");
+        for (operation, histogram) in &operations {
+            let _ = writeln!(
+                out,
+                "mongo_do_operations_total{{operation=\"{}\"}} {}",
+                operation,
+                histogram.count()
+            );
+        }
+        let counters = [
+            ("failures_total", "Calls that failed.", self.stats.failures),
+            ("bytes_sent_total", "Bytes of arguments sent.", self.stats.bytes_sent),
+            ("bytes_received_total", "Bytes of replies received.", self.stats.bytes_received),
+            ("retries_total", "Operations retried.", self.stats.retries),
+            ("retries_denied_total", "Retries denied by the budget.", self.stats.retries_denied),
+            ("audit_failures_total", "Audit entries not recorded.", self.stats.audit_failures),
+            ("reconnects_total", "Recovered connections.", self.stats.reconnects),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(out, "# HELP mongo_do_{} {}", name, help);
+            let _ = writeln!(out, "# TYPE mongo_do_{} counter", name);
+            let _ = writeln!(out, "mongo_do_{} {}", name, value);
+        }
+        let _ = writeln!(out, "# HELP mongo_do_open_cursors Cursors holding a server cursor.");
+        let _ = writeln!(out, "# TYPE mongo_do_open_cursors gauge");
+        let _ = writeln!(out, "mongo_do_open_cursors {}", self.open_cursors);
+        if let Some(available) = self.retry_budget {
+            let _ = writeln!(out, "# HELP mongo_do_retry_budget Retries currently allowed.");
+            let _ = writeln!(out, "# TYPE mongo_do_retry_budget gauge");
+            let _ = writeln!(out, "mongo_do_retry_budget {}", available);
+        }
+
+        let name = "mongo_do_operation_duration_seconds";
+        let _ = writeln!(out, "# HELP {} Call latency per operation.", name);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (operation, histogram) in &operations {
+            let mut cumulative = 0;
+            for (bound, n) in LATENCY_BUCKETS.iter().zip(histogram.counts) {
+                cumulative += n;
+                let _ = writeln!(
+                    out,
+                    "{}_bucket{{operation=\"{}\",le=\"{}\"}} {}",
+                    name,
+                    operation,
+                    bound.as_secs_f64(),
+                    cumulative
+                );
+            }
+            let count = histogram.count();
+            let _ = writeln!(
+                out,
+                "{}_bucket{{operation=\"{}\",le=\"+Inf\"}} {}",
+                name, operation, count
+            );
+            let sum = histogram.sum.as_secs_f64();
+            let _ = writeln!(out, "{}_sum{{operation=\"{}\"}} {}", name, operation, sum);
+            let _ = writeln!(out, "{}_count{{operation=\"{}\"}} {}", name, operation, count);
+        }
+        out
+    }
+}
+
+/// A limit on the retries of a client, relative to the calls it makes.
+///
+/// Over the last [`window`](RetryBudget::window), retries may number at most
+/// [`ratio`](RetryBudget::ratio) times the calls made, plus
+/// [`min_retries`](RetryBudget::min_retries) so a quiet client can still
+/// retry. Once spent, operations fail with the error they would have retried.
+///
+/// The budget only governs the reconnects
+/// [`Collection::tail`](crate::Collection::tail) makes after a transient
+/// error. Other retries do not spend it: the RPC layer's own
+/// connection retries, and [`Collection::modify`](crate::Collection::modify)
+/// retrying a write conflict, which is bounded by
+/// [`ModifyOptions::max_retries`](crate::collection::ModifyOptions::max_retries).
+///
+/// # Example
+///
+/// ```ignore
+/// let options = ClientOptions::builder()
+///     .retry_budget(RetryBudget::builder().ratio(0.2).build())
+///     .build();
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryBudget {
+    /// Retries allowed per call, e.g. `0.1` for one retry per ten calls.
+    pub ratio: f64,
+    /// Retries allowed per window regardless of the calls made.
+    pub min_retries: u32,
+    /// How far back calls and retries are counted.
+    pub window: Duration,
+}
+
+impl Default for RetryBudget {
+    fn default() -> Self {
+        Self {
+            ratio: 0.1,
+            min_retries: 10,
+            window: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryBudget {
+    /// Create a builder.
+    pub fn builder() -> RetryBudgetBuilder {
+        RetryBudgetBuilder::default()
+    }
+}
+
+/// Builder for RetryBudget.
+#[derive(Debug, Clone, Default)]
+pub struct RetryBudgetBuilder {
+    budget: RetryBudget,
+}
+
+impl RetryBudgetBuilder {
+    /// Set the retries allowed per call.
+    pub fn ratio(mut self, ratio: f64) -> Self {
+        self.budget.ratio = ratio;
+        self
+    }
+
+    /// Set the retries allowed per window regardless of the calls made.
+    pub fn min_retries(mut self, min_retries: u32) -> Self {
+        self.budget.min_retries = min_retries;
+        self
+    }
+
+    /// Set how far back calls and retries are counted.
+    pub fn window(mut self, window: Duration) -> Self {
+        self.budget.window = window;
+        self
+    }
+
+    /// Build the budget.
+    pub fn build(self) -> RetryBudget {
+        self.budget
+    }
+}
+
+/// The calls and retries a [`RetryBudget`] counts.
+#[derive(Debug)]
+struct BudgetState {
+    budget: RetryBudget,
+    counts: Mutex<BudgetCounts>,
+}
+
+/// Calls and retries of the current window and the one before it.
+#[derive(Debug)]
+struct BudgetCounts {
+    started: Instant,
+    calls: [u64; 2],
+    retries: [u64; 2],
+}
+
+impl BudgetState {
+    fn new(budget: RetryBudget, now: Instant) -> Self {
+        Self {
+            budget,
+            counts: Mutex::new(BudgetCounts {
+                started: now,
+                calls: [0; 2],
+                retries: [0; 2],
+            }),
+        }
+    }
+
+    /// Lock the counts, moving on to a new window once the current one ends.
+    fn counts(&self, now: Instant) -> std::sync::MutexGuard<'_, BudgetCounts> {
+        let mut counts = self.counts.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let elapsed = now.saturating_duration_since(counts.started);
+        if elapsed >= self.budget.window * 2 {
+            *counts = BudgetCounts {
+                started: now,
+                calls: [0; 2],
+                retries: [0; 2],
+            };
+        } else if elapsed >= self.budget.window {
+            counts.started += self.budget.window;
+            counts.calls = [0, counts.calls[0]];
+            counts.retries = [0, counts.retries[0]];
+        }
+        counts
+    }
+
+    fn record_call(&self, now: Instant) {
+        self.counts(now).calls[0] += 1;
+    }
+
+    /// Retries allowed by the counts.
+    fn available(&self, counts: &BudgetCounts) -> u64 {
+        let calls = (counts.calls[0] + counts.calls[1]) as f64;
+        let allowed = f64::from(self.budget.min_retries) + self.budget.ratio.max(0.0) * calls;
+        (allowed as u64).saturating_sub(counts.retries[0] + counts.retries[1])
+    }
+
+    /// Count a retry if the budget allows one.
+    fn withdraw(&self, now: Instant) -> bool {
+        let mut counts = self.counts(now);
+        if self.available(&counts) == 0 {
+            return false;
+        }
+        counts.retries[0] += 1;
+        true
+    }
+}
+
+/// Counters shared by every transport created from one client.
+#[derive(Debug, Default)]
+pub(crate) struct StatsRecorder {
+    /// Latency per operation, which also counts the calls.
+    operations: Mutex<HashMap<String, LatencyHistogram>>,
+    failures: AtomicU64,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+    retries: AtomicU64,
+    retries_denied: AtomicU64,
+    audit_failures: AtomicU64,
+    reconnects: AtomicU64,
+    total_latency_micros: AtomicU64,
+    open_cursors: AtomicU64,
+    /// Limit on retries, if any.
+    retry_budget: Option<BudgetState>,
+}
+
+/// Counts a cursor as open until dropped.
+#[derive(Debug)]
+pub(crate) struct OpenCursor(Arc<StatsRecorder>);
+
+impl Drop for OpenCursor {
+    fn drop(&mut self) {
+        self.0.open_cursors.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl StatsRecorder {
+    /// Create counters that also limit retries to `budget`.
+    pub(crate) fn with_retry_budget(budget: RetryBudget) -> Self {
+        Self {
+            retry_budget: Some(BudgetState::new(budget, Instant::now())),
+            ..Self::default()
+        }
+    }
+
+    /// Record a call that sent `bytes_sent` bytes and took `latency`.
+    pub(crate) fn record_call(&self, method: Method, bytes_sent: usize, latency: Duration) {
+        self.operations
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .entry(method.operation().to_string())
+            .or_default()
+            .record(latency);
+        self.bytes_sent.fetch_add(bytes_sent as u64, Ordering::Relaxed);
+        self.total_latency_micros
+            .fetch_add(latency.as_micros() as u64, Ordering::Relaxed);
+        if let Some(ref budget) = self.retry_budget {
+            budget.record_call(Instant::now());
+        }
+    }
+
+    /// Record a reply of `bytes_received` bytes.
+    pub(crate) fn record_success(&self, bytes_received: usize) {
+        self.bytes_received
+            .fetch_add(bytes_received as u64, Ordering::Relaxed);
+    }
+
+    /// Record a failed call.
+    pub(crate) fn record_failure(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record a call succeeding after the connection was lost.
+    pub(crate) fn record_reconnect(&self) {
+        self.reconnects.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record an operation being retried.
+    pub(crate) fn record_retry(&self) {
+        self.retries.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record an audit entry that could not be recorded.
+    pub(crate) fn record_audit_failure(&self) {
+        self.audit_failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Record an operation about to be retried, unless the retry budget is
+    /// spent. Returns whether to retry.
+    pub(crate) fn try_retry(&self) -> bool {
+        if let Some(ref budget) = self.retry_budget {
+            if !budget.withdraw(Instant::now()) {
+                self.retries_denied.fetch_add(1, Ordering::Relaxed);
+                return false;
+            }
+        }
+        self.record_retry();
+        true
+    }
+
+    /// Count a cursor as open until the returned guard is dropped.
+    pub(crate) fn open_cursor(self: &Arc<Self>) -> OpenCursor {
+        self.open_cursors.fetch_add(1, Ordering::Relaxed);
+        OpenCursor(self.clone())
+    }
+
+    /// Read the counters.
+    pub(crate) fn snapshot(&self) -> ClientStats {
+        let operations: HashMap<String, u64> = self
+            .operations
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .iter()
+            .map(|(operation, histogram)| (operation.clone(), histogram.count()))
+            .collect();
+        let calls: u64 = operations.values().sum();
+        let total_latency = self.total_latency_micros.load(Ordering::Relaxed);
+        ClientStats {
+            operations,
+            failures: self.failures.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
+            retries: self.retries.load(Ordering::Relaxed),
+            retries_denied: self.retries_denied.load(Ordering::Relaxed),
+            audit_failures: self.audit_failures.load(Ordering::Relaxed),
+            reconnects: self.reconnects.load(Ordering::Relaxed),
+            average_latency: Duration::from_micros(total_latency.checked_div(calls).unwrap_or(0)),
+        }
+    }
+
+    /// Read the counters, latency histograms and open cursors.
+    pub(crate) fn metrics(&self) -> ClientMetrics {
+        let stats = self.snapshot();
+        let latency = self
+            .operations
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone();
+        ClientMetrics {
+            stats,
+            latency,
+            open_cursors: self.open_cursors.load(Ordering::Relaxed),
+            retry_budget: self.retry_budget.as_ref().map(|budget| {
+                let counts = budget.counts(Instant::now());
+                budget.available(&counts)
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_recorder() {
+        let recorder = StatsRecorder::default();
+        assert_eq!(recorder.snapshot(), ClientStats::default());
+
+        recorder.record_call(Method::Find, 100, Duration::from_millis(10));
+        recorder.record_success(400);
+        recorder.record_call(Method::Find, 100, Duration::from_millis(30));
+        recorder.record_success(200);
+        recorder.record_call(Method::InsertOne, 50, Duration::from_millis(20));
+        recorder.record_failure();
+        recorder.record_retry();
+        recorder.record_reconnect();
+
+        let stats = recorder.snapshot();
+        assert_eq!(stats.operations["find"], 2);
+        assert_eq!(stats.operations["insertOne"], 1);
+        assert_eq!(stats.total_operations(), 3);
+        assert_eq!(stats.failures, 1);
+        assert_eq!(stats.bytes_sent, 250);
+        assert_eq!(stats.bytes_received, 600);
+        assert_eq!(stats.retries, 1);
+        assert_eq!(stats.reconnects, 1);
+        assert_eq!(stats.average_latency, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn test_latency_histogram() {
+        let mut histogram = LatencyHistogram::default();
+        assert_eq!(histogram.quantile(0.5), None);
+        for ms in [1, 3, 3, 40, 90] {
+            histogram.record(Duration::from_millis(ms));
+        }
+        assert_eq!(histogram.count(), 5);
+        assert_eq!(histogram.sum, Duration::from_millis(137));
+        assert_eq!(histogram.quantile(0.0), Some(Duration::from_millis(1)));
+        assert_eq!(histogram.quantile(0.5), Some(Duration::from_millis(5)));
+        assert_eq!(histogram.quantile(1.0), Some(Duration::from_millis(100)));
+
+        histogram.record(Duration::from_secs(60));
+        assert_eq!(histogram.counts[LATENCY_BUCKETS.len()], 1);
+        assert_eq!(histogram.quantile(1.0), None);
+    }
+
+    #[test]
+    fn test_metrics_open_cursors() {
+        let recorder = Arc::new(StatsRecorder::default());
+        recorder.record_call(Method::Find, 10, Duration::from_millis(3));
+        let first = recorder.open_cursor();
+        let second = recorder.open_cursor();
+        assert_eq!(recorder.metrics().open_cursors, 2);
+        drop(first);
+        drop(second);
+
+        let metrics = recorder.metrics();
+        assert_eq!(metrics.open_cursors, 0);
+        assert_eq!(metrics.stats.operations["find"], 1);
+        assert_eq!(metrics.latency["find"].counts[2], 1);
+    }
+
+    #[test]
+    fn test_retry_budget() {
+        let budget = RetryBudget::builder().ratio(0.5).min_retries(1).build();
+        assert_eq!(budget.window, Duration::from_secs(10));
+        let start = Instant::now();
+        let state = BudgetState::new(budget, start);
+        assert!(state.withdraw(start));
+        assert!(!state.withdraw(start));
+        for _ in 0..4 {
+            state.record_call(start);
+        }
+        assert!(state.withdraw(start));
+        assert!(state.withdraw(start));
+        assert!(!state.withdraw(start));
+
+        // The previous window still counts; the one before it does not.
+        let later = start + Duration::from_secs(15);
+        assert_eq!(state.available(&state.counts(later)), 0);
+        let much_later = start + Duration::from_secs(25);
+        assert_eq!(state.available(&state.counts(much_later)), 1);
+
+        let budget = RetryBudget::builder().min_retries(1).build();
+        let recorder = StatsRecorder::with_retry_budget(budget);
+        assert_eq!(recorder.metrics().retry_budget, Some(1));
+        assert!(recorder.try_retry());
+        assert!(!recorder.try_retry());
+        let stats = recorder.snapshot();
+        assert_eq!((stats.retries, stats.retries_denied), (1, 1));
+        assert!(StatsRecorder::default().try_retry());
+        assert_eq!(StatsRecorder::default().metrics().retry_budget, None);
+    }
+
+    #[cfg(feature = "prometheus")]
+    #[test]
+    fn test_to_prometheus() {
+        let recorder = StatsRecorder::default();
+        recorder.record_call(Method::Find, 10, Duration::from_millis(3));
+        recorder.record_failure();
+        let text = recorder.metrics().to_prometheus();
+        assert!(text.contains("mongo_do_operations_total{operation=\"find\"} 1\n"));
+        assert!(text.contains("mongo_do_failures_total 1\n"));
+        assert!(text.contains("mongo_do_open_cursors 0\n"));
+        assert!(!text.contains("mongo_do_retry_budget"));
+        assert!(text.contains(
+            "mongo_do_operation_duration_seconds_bucket{operation=\"find\",le=\"0.002\"} 0\n"
+        ));
+        assert!(text.contains(
+            "mongo_do_operation_duration_seconds_bucket{operation=\"find\",le=\"0.005\"} 1\n"
+        ));
+        assert!(text.contains("mongo_do_operation_duration_seconds_count{operation=\"find\"} 1\n"));
+    }
+}
@@ -0,0 +1,294 @@
+//! Tailing capped collections.
+//!
+//! [`Collection::tail`] follows a capped collection like `tail -f` follows a
+//! file: it returns the matching documents in insertion order, then waits
+//! for new ones, forever. Under the hood it reads a tailable `awaitData`
+//! cursor. When the cursor dies, because the connection dropped or the
+//! collection wrapped around past the cursor's position, the tail reconnects
+//! after a delay and continues after the `_id` of the last document
+//! returned, so nothing is returned twice. Documents must have increasing
+//! `_id`s, such as the default object IDs.
+//!
+//! Connection errors and timeouts are retried silently, while the client's
+//! [retry budget](crate::RetryBudget) lasts. Other errors are returned from
+//! the stream, which then reconnects as well; stop reading from the stream
+//! to give up. Servers that do not report the
+//! [`tailableCursors`](crate::handshake::capability::TAILABLE_CURSORS)
+//! capability return a command error.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use futures::StreamExt;
+//!
+//! let events = db.collection::<Event>("events");
+//! let mut tail = Box::pin(events.tail(doc! { "level": "error" }));
+//! while let Some(event) = tail.next().await {
+//!     alert(&event?).await;
+//! }
+//! ```
+
+use crate::collection::{and_filter, Collection, CursorType, FindOptions};
+use crate::cursor::{self, Cursor};
+use crate::error::{MongoError, Result};
+use bson::{doc, Bson, Document};
+use futures::Stream;
+use serde::de::DeserializeOwned;
+use serde_json::Value as JsonValue;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+/// Options for [`Collection::tail_with_options`].
+#[derive(Debug, Clone)]
+pub struct TailOptions {
+    /// How long the server waits for new documents before returning an
+    /// empty batch.
+    pub max_await_time: Option<Duration>,
+    /// Batch size for the cursor.
+    pub batch_size: Option<u32>,
+    /// How long to wait before reconnecting after the cursor dies.
+    pub reconnect_delay: Duration,
+    /// Continue after the document with this `_id`.
+    pub resume_after: Option<Bson>,
+}
+
+impl Default for TailOptions {
+    fn default() -> Self {
+        Self {
+            max_await_time: None,
+            batch_size: None,
+            reconnect_delay: Duration::from_secs(1),
+            resume_after: None,
+        }
+    }
+}
+
+impl TailOptions {
+    /// Create new tail options.
+    pub fn builder() -> TailOptionsBuilder {
+        TailOptionsBuilder::default()
+    }
+
+    /// The options of the tailable find.
+    fn find_options(&self) -> FindOptions {
+        let mut options = FindOptions::builder()
+            .cursor_type(CursorType::TailableAwait)
+            .sort(doc! { "$natural": 1 })
+            .build();
+        options.max_await_time = self.max_await_time;
+        options.batch_size = self.batch_size;
+        options
+    }
+}
+
+/// Builder for TailOptions.
+#[derive(Debug, Clone, Default)]
+pub struct TailOptionsBuilder {
+    options: TailOptions,
+}
+
+impl TailOptionsBuilder {
+    /// Set how long the server waits for new documents.
+    pub fn max_await_time(mut self, max_await_time: Duration) -> Self {
+        self.options.max_await_time = Some(max_await_time);
+        self
+    }
+
+    /// Set the batch size.
+    pub fn batch_size(mut self, batch_size: u32) -> Self {
+        self.options.batch_size = Some(batch_size);
+        self
+    }
+
+    /// Set how long to wait before reconnecting.
+    pub fn reconnect_delay(mut self, reconnect_delay: Duration) -> Self {
+        self.options.reconnect_delay = reconnect_delay;
+        self
+    }
+
+    /// Continue after the document with this `_id`.
+    pub fn resume_after(mut self, id: impl Into<Bson>) -> Self {
+        self.options.resume_after = Some(id.into());
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> TailOptions {
+        self.options
+    }
+}
+
+/// The state of a tail between documents.
+struct Tail<T> {
+    /// Collection being tailed, read as raw documents to see each `_id`.
+    collection: Collection<JsonValue>,
+    /// Filter selecting the documents to return.
+    filter: Document,
+    /// Cursor and reconnect options.
+    options: TailOptions,
+    /// The open tailable cursor, if any.
+    cursor: Option<Cursor<JsonValue>>,
+    /// `_id` of the last document returned.
+    last_id: Option<Bson>,
+    /// Type marker.
+    _marker: PhantomData<T>,
+}
+
+impl<T: DeserializeOwned> Tail<T> {
+    /// Get the next document, reconnecting until there is one or an error
+    /// that is not retried.
+    async fn next(&mut self) -> Result<T> {
+        loop {
+            let cursor = match self.cursor {
+                Some(ref mut cursor) => cursor,
+                None => {
+                    let filter = tail_filter(&self.filter, self.last_id.as_ref());
+                    match self
+                        .collection
+                        .find_with_options(filter, self.options.find_options())
+                        .await
+                    {
+                        Ok(cursor) => self.cursor.insert(cursor),
+                        Err(e) => {
+                            self.reconnect_after(e).await?;
+                            continue;
+                        }
+                    }
+                }
+            };
+
+            match cursor.try_next().await {
+                Ok(Some(doc)) => {
+                    if let Some(id) = doc.get("_id") {
+                        self.last_id = Some(self.collection.rpc_client.decode(id));
+                    }
+                    return cursor::deserialize(&doc, None);
+                }
+                // An empty batch from a live cursor: the server waited and
+                // nothing arrived.
+                Ok(None) if !cursor.is_exhausted().await => {}
+                Ok(None) => self.reconnect().await,
+                Err(e) => self.reconnect_after(e).await?,
+            }
+        }
+    }
+
+    /// Drop the cursor and wait before the next one is opened.
+    async fn reconnect(&mut self) {
+        self.cursor = None;
+        tokio::time::sleep(self.options.reconnect_delay).await;
+    }
+
+    /// Reconnect after `e`, returning it first unless it is retried silently.
+    async fn reconnect_after(&mut self, e: MongoError) -> Result<()> {
+        let transient = e.is_connection_error() || e.is_timeout();
+        if transient && self.collection.rpc_client.stats.try_retry() {
+            self.reconnect().await;
+            return Ok(());
+        }
+        // The delay is taken when the next document is requested.
+        self.cursor = None;
+        Err(e)
+    }
+}
+
+/// The filter selecting documents of `filter` after `last_id`.
+fn tail_filter(filter: &Document, last_id: Option<&Bson>) -> Document {
+    match last_id {
+        Some(id) => and_filter(filter, doc! { "_id": { "$gt": id.clone() } }),
+        None => filter.clone(),
+    }
+}
+
+/// Tail `collection` as a stream of `T`.
+pub(crate) fn tail<T>(
+    collection: Collection<JsonValue>,
+    filter: Document,
+    options: TailOptions,
+) -> impl Stream<Item = Result<T>> + Send + 'static
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let tail = Tail {
+        collection,
+        filter,
+        last_id: options.resume_after.clone(),
+        options,
+        cursor: None,
+        _marker: PhantomData,
+    };
+    futures::stream::unfold((tail, false), |(mut tail, failed)| async move {
+        // A bad document leaves the cursor open; anything else closed it.
+        if failed && tail.cursor.is_none() {
+            tokio::time::sleep(tail.options.reconnect_delay).await;
+        }
+        let item = tail.next().await;
+        let failed = item.is_err();
+        Some((item, (tail, failed)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail_filter() {
+        let filter = doc! { "level": "error" };
+        assert_eq!(tail_filter(&filter, None), filter);
+        assert_eq!(
+            tail_filter(&filter, Some(&Bson::Int32(7))),
+            doc! { "$and": [{ "level": "error" }, { "_id": { "$gt": 7 } }] }
+        );
+        assert_eq!(
+            tail_filter(&Document::new(), Some(&Bson::Int32(7))),
+            doc! { "_id": { "$gt": 7 } }
+        );
+    }
+
+    #[test]
+    fn test_tail_options() {
+        let options = TailOptions::builder()
+            .max_await_time(Duration::from_millis(500))
+            .batch_size(10)
+            .reconnect_delay(Duration::from_millis(50))
+            .resume_after(3)
+            .build();
+        assert_eq!(options.reconnect_delay, Duration::from_millis(50));
+        assert_eq!(options.resume_after, Some(Bson::Int32(3)));
+
+        let find = options.find_options();
+        assert_eq!(find.cursor_type, Some(CursorType::TailableAwait));
+        assert_eq!(find.max_await_time, Some(Duration::from_millis(500)));
+        assert_eq!(find.batch_size, Some(10));
+        assert_eq!(find.sort, Some(doc! { "$natural": 1 }));
+        assert_eq!(TailOptions::default().reconnect_delay, Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn test_tail_reconnects_spend_retry_budget() {
+        use crate::mock::MockServer;
+        use crate::rpc::Method;
+        use crate::RetryBudget;
+        use futures::StreamExt;
+
+        let server = MockServer::new(|method, _| match method {
+            Method::Hello => Ok(serde_json::json!({ "capabilities": ["tailableCursors"] })),
+            _ => Err(MongoError::connection("connection reset")),
+        });
+        // No retries for free: only the calls made earn any.
+        let budget = RetryBudget::builder().ratio(0.5).min_retries(0).build();
+        let transport = server.transport().with_retry_budget(Some(budget));
+        let events = Collection::new("app".to_string(), "events".to_string(), transport);
+        let options = TailOptions::builder().reconnect_delay(Duration::from_millis(1)).build();
+        let mut tail = Box::pin(tail::<JsonValue>(events.clone(), doc! {}, options));
+
+        // The handshake and the first find earn one silent reconnect, then
+        // the budget is spent and the error surfaces.
+        assert!(tail.next().await.unwrap().unwrap_err().is_connection_error());
+        assert_eq!(server.calls_of(Method::Find).len(), 2);
+        let stats = events.rpc_client.stats.snapshot();
+        assert_eq!((stats.retries, stats.retries_denied), (1, 1));
+        assert_eq!(stats.failures, 2);
+    }
+}
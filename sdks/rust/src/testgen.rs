@@ -0,0 +1,260 @@
+//! Deterministic test data for load tests and benchmarks.
+//!
+//! The same seed always produces the same documents, on every platform and
+//! version of this crate, so benchmark runs compare like with like. Types
+//! describe how to generate themselves with [`Generate`], or documents come
+//! from a closure with [`generate_with`]. With the `fake` feature, [`TestRng`]
+//! is a `rand` generator, so `fake`'s `Faker` can fill in realistic values
+//! and [`generate_fake`] generates any type implementing `Dummy<Faker>`.
+//!
+//! [`insert`] and [`insert_with`] insert generated documents in chunks,
+//! without holding them all in memory.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::testgen::{self, Generate, TestRng};
+//!
+//! impl Generate for Order {
+//!     fn generate(rng: &mut TestRng) -> Self {
+//!         Order {
+//!             region: rng.pick(&["eu", "us", "apac"]).to_string(),
+//!             amount: rng.range(1, 500) as f64,
+//!             express: rng.bool(0.1),
+//!         }
+//!     }
+//! }
+//!
+//! let sample: Vec<Order> = testgen::generate(10, 42);
+//! testgen::insert(&orders, 1_000_000, 42, 10_000).await?;
+//! ```
+
+use crate::collection::Collection;
+use crate::error::{MongoError, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A small, seeded pseudo-random number generator (SplitMix64).
+///
+/// Not suitable for anything but test data.
+#[derive(Debug, Clone)]
+pub struct TestRng {
+    state: u64,
+}
+
+impl TestRng {
+    /// Create a generator from `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Get the next random `u64`.
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Get a random integer in `low..high`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `low >= high`.
+    pub fn range(&mut self, low: i64, high: i64) -> i64 {
+        assert!(low < high, "empty range {}..{}", low, high);
+        let span = high.abs_diff(low);
+        low.wrapping_add((self.next_u64() % span) as i64)
+    }
+
+    /// Get a random float in `0.0..1.0`.
+    pub fn f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Get `true` with probability `p`.
+    pub fn bool(&mut self, p: f64) -> bool {
+        self.f64() < p
+    }
+
+    /// Pick a random element of `items`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `items` is empty.
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        assert!(!items.is_empty(), "cannot pick from an empty slice");
+        &items[(self.next_u64() % items.len() as u64) as usize]
+    }
+
+    /// Get a random string of `len` lowercase letters.
+    pub fn string(&mut self, len: usize) -> String {
+        (0..len)
+            .map(|_| (b'a' + (self.next_u64() % 26) as u8) as char)
+            .collect()
+    }
+}
+
+#[cfg(feature = "fake")]
+impl rand::RngCore for TestRng {
+    fn next_u32(&mut self) -> u32 {
+        (TestRng::next_u64(self) >> 32) as u32
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        TestRng::next_u64(self)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(8) {
+            let bytes = TestRng::next_u64(self).to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> std::result::Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+/// A type that can generate random values of itself.
+pub trait Generate {
+    /// Generate a value from `rng`.
+    fn generate(rng: &mut TestRng) -> Self;
+}
+
+/// Generate `n` values of `T` from `seed`.
+pub fn generate<T: Generate>(n: usize, seed: u64) -> Vec<T> {
+    generate_with(n, seed, |rng, _| T::generate(rng))
+}
+
+/// Generate `n` values with `f`, called with the generator and the index of
+/// the value.
+pub fn generate_with<T>(
+    n: usize,
+    seed: u64,
+    mut f: impl FnMut(&mut TestRng, usize) -> T,
+) -> Vec<T> {
+    let mut rng = TestRng::new(seed);
+    (0..n).map(|i| f(&mut rng, i)).collect()
+}
+
+/// Generate `n` values of `T` with `fake`'s `Faker` from `seed`.
+#[cfg(feature = "fake")]
+pub fn generate_fake<T: fake::Dummy<fake::Faker>>(n: usize, seed: u64) -> Vec<T> {
+    generate_with(n, seed, |rng, _| fake::Fake::fake_with_rng(&fake::Faker, rng))
+}
+
+/// Insert `n` values of `T` generated from `seed` into `collection`, in
+/// `insert_many` calls of `chunk_size` documents, and return the number
+/// inserted.
+///
+/// The documents are the ones [`generate`] returns for the same `n` and
+/// `seed`.
+pub async fn insert<T>(
+    collection: &Collection<T>,
+    n: usize,
+    seed: u64,
+    chunk_size: usize,
+) -> Result<u64>
+where
+    T: Generate + Serialize + DeserializeOwned + Send + Sync + Unpin + 'static,
+{
+    insert_with(collection, n, seed, chunk_size, |rng, _| T::generate(rng)).await
+}
+
+/// Insert `n` values generated with `f` from `seed` into `collection`, in
+/// `insert_many` calls of `chunk_size` documents, and return the number
+/// inserted.
+pub async fn insert_with<T>(
+    collection: &Collection<T>,
+    n: usize,
+    seed: u64,
+    chunk_size: usize,
+    mut f: impl FnMut(&mut TestRng, usize) -> T,
+) -> Result<u64>
+where
+    T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static,
+{
+    if chunk_size == 0 {
+        return Err(MongoError::invalid_argument("chunk_size must be positive"));
+    }
+    let mut rng = TestRng::new(seed);
+    let mut inserted = 0;
+    let mut chunk = Vec::with_capacity(chunk_size.min(n));
+    for i in 0..n {
+        chunk.push(f(&mut rng, i));
+        if chunk.len() == chunk_size || i + 1 == n {
+            inserted += collection.insert_many(chunk.drain(..)).await?.inserted_ids.len() as u64;
+        }
+    }
+    Ok(inserted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    struct Reading {
+        sensor: String,
+        value: i64,
+    }
+
+    impl Generate for Reading {
+        fn generate(rng: &mut TestRng) -> Self {
+            Reading {
+                sensor: rng.pick(&["a", "b", "c"]).to_string(),
+                value: rng.range(-10, 10),
+            }
+        }
+    }
+
+    #[test]
+    fn test_rng_is_deterministic() {
+        let mut rng = TestRng::new(7);
+        let first: Vec<u64> = (0..3).map(|_| rng.next_u64()).collect();
+        let mut again = TestRng::new(7);
+        assert_eq!(first, (0..3).map(|_| again.next_u64()).collect::<Vec<_>>());
+        assert_ne!(TestRng::new(8).next_u64(), first[0]);
+        // Pinned so the sequence cannot change between releases.
+        assert_eq!(TestRng::new(0).next_u64(), 0xe220_a839_7b1d_cdaf);
+    }
+
+    #[test]
+    fn test_rng_ranges() {
+        let mut rng = TestRng::new(1);
+        for _ in 0..1000 {
+            assert!((-3..3).contains(&rng.range(-3, 3)));
+            assert!((0.0..1.0).contains(&rng.f64()));
+        }
+        assert!(!rng.bool(0.0));
+        assert!(rng.bool(1.0));
+        let s = rng.string(12);
+        assert_eq!(s.len(), 12);
+        assert!(s.bytes().all(|b| b.is_ascii_lowercase()));
+        // The full range does not overflow.
+        rng.range(i64::MIN, i64::MAX);
+    }
+
+    #[test]
+    fn test_generate() {
+        let readings: Vec<Reading> = generate(50, 42);
+        assert_eq!(readings.len(), 50);
+        assert_eq!(readings, generate::<Reading>(50, 42));
+        assert_ne!(readings, generate::<Reading>(50, 43));
+
+        let ids = generate_with(3, 42, |_, i| i);
+        assert_eq!(ids, vec![0, 1, 2]);
+    }
+
+    #[cfg(feature = "fake")]
+    #[test]
+    fn test_generate_fake() {
+        let values: Vec<(u8, String)> = generate_fake(20, 42);
+        assert_eq!(values.len(), 20);
+        assert_eq!(values, generate_fake::<(u8, String)>(20, 42));
+    }
+}
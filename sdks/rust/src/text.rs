@@ -0,0 +1,193 @@
+//! Full-text search helpers.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::text::{TextIndexOptions, TextSearch};
+//!
+//! let options = TextIndexOptions::builder().default_language("english").build();
+//! articles.create_text_index([("title", 10), ("body", 1)], options).await?;
+//!
+//! let filter = TextSearch::new("coffee -decaf").language("english").build();
+//! let options = FindOptions::text_score_projection("score");
+//! let results = articles.find_with_options(filter, options).await?;
+//! ```
+
+use bson::{doc, Document};
+
+/// A `$text` query filter.
+#[derive(Debug, Clone, Default)]
+pub struct TextSearch {
+    search: String,
+    language: Option<String>,
+    case_sensitive: Option<bool>,
+    diacritic_sensitive: Option<bool>,
+}
+
+impl TextSearch {
+    /// Create a text search for the given terms.
+    ///
+    /// Terms follow MongoDB's syntax: quoted phrases and `-` negations are supported.
+    pub fn new(search: impl Into<String>) -> Self {
+        Self {
+            search: search.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Set the language used for stop words and stemming.
+    pub fn language(mut self, language: impl Into<String>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Set whether the search is case sensitive.
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.case_sensitive = Some(case_sensitive);
+        self
+    }
+
+    /// Set whether the search is diacritic sensitive.
+    pub fn diacritic_sensitive(mut self, diacritic_sensitive: bool) -> Self {
+        self.diacritic_sensitive = Some(diacritic_sensitive);
+        self
+    }
+
+    /// Build the filter document.
+    pub fn build(self) -> Document {
+        let mut text = doc! { "$search": self.search };
+        if let Some(language) = self.language {
+            text.insert("$language", language);
+        }
+        if let Some(case_sensitive) = self.case_sensitive {
+            text.insert("$caseSensitive", case_sensitive);
+        }
+        if let Some(diacritic_sensitive) = self.diacritic_sensitive {
+            text.insert("$diacriticSensitive", diacritic_sensitive);
+        }
+        doc! { "$text": text }
+    }
+}
+
+impl From<TextSearch> for Document {
+    fn from(search: TextSearch) -> Self {
+        search.build()
+    }
+}
+
+/// The `{ "$meta": "textScore" }` expression.
+pub fn text_score() -> Document {
+    doc! { "$meta": "textScore" }
+}
+
+/// Options for creating a text index.
+#[derive(Debug, Clone, Default)]
+pub struct TextIndexOptions {
+    /// Index name.
+    pub name: Option<String>,
+    /// Default language for stop words and stemming.
+    pub default_language: Option<String>,
+    /// Document field that overrides the language per document.
+    pub language_override: Option<String>,
+}
+
+impl TextIndexOptions {
+    /// Create a new builder.
+    pub fn builder() -> TextIndexOptionsBuilder {
+        TextIndexOptionsBuilder::default()
+    }
+
+    /// Convert to index options, with the given field weights.
+    pub(crate) fn to_document(&self, weights: Document) -> Document {
+        let mut options = doc! { "weights": weights };
+        if let Some(ref name) = self.name {
+            options.insert("name", name.as_str());
+        }
+        if let Some(ref default_language) = self.default_language {
+            options.insert("default_language", default_language.as_str());
+        }
+        if let Some(ref language_override) = self.language_override {
+            options.insert("language_override", language_override.as_str());
+        }
+        options
+    }
+}
+
+/// Builder for TextIndexOptions.
+#[derive(Debug, Default)]
+pub struct TextIndexOptionsBuilder {
+    options: TextIndexOptions,
+}
+
+impl TextIndexOptionsBuilder {
+    /// Set the index name.
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.options.name = Some(name.into());
+        self
+    }
+
+    /// Set the default language.
+    pub fn default_language(mut self, language: impl Into<String>) -> Self {
+        self.options.default_language = Some(language.into());
+        self
+    }
+
+    /// Set the field that overrides the language per document.
+    pub fn language_override(mut self, field: impl Into<String>) -> Self {
+        self.options.language_override = Some(field.into());
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> TextIndexOptions {
+        self.options
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_search_minimal() {
+        let filter = TextSearch::new("coffee").build();
+        assert_eq!(filter, doc! { "$text": { "$search": "coffee" } });
+    }
+
+    #[test]
+    fn test_text_search_options() {
+        let filter: Document = TextSearch::new("café")
+            .language("french")
+            .case_sensitive(true)
+            .diacritic_sensitive(false)
+            .into();
+        assert_eq!(
+            filter,
+            doc! {
+                "$text": {
+                    "$search": "café",
+                    "$language": "french",
+                    "$caseSensitive": true,
+                    "$diacriticSensitive": false,
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_text_index_options() {
+        let options = TextIndexOptions::builder()
+            .name("search")
+            .default_language("english")
+            .build();
+        let doc = options.to_document(doc! { "title": 10 });
+        assert_eq!(
+            doc,
+            doc! {
+                "weights": { "title": 10 },
+                "name": "search",
+                "default_language": "english",
+            }
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! Reading across a hot and a cold collection.
+//!
+//! A [`TieredCollection`] pairs a hot collection holding recent documents
+//! with a cold one holding older documents, typically moved there by
+//! [`Collection::archive`]. Reads check the hot collection first and fall
+//! back to the cold one, so callers need not know where a document lives.
+//! With [`rehydrate`](TieredOptions::rehydrate), documents read from the
+//! cold collection are moved back to the hot one.
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::tiered::{TieredCollection, TieredOptions};
+//!
+//! let orders = TieredCollection::new(
+//!     db.collection::<Order>("orders"),
+//!     archive_db.collection::<Order>("orders"),
+//!     TieredOptions::builder().rehydrate(true).build(),
+//! );
+//! let order = orders.find_one(doc! { "_id": order_id }).await?;
+//! ```
+
+use crate::collection::{and_filter, Collection, InsertManyOptions, InsertOneResult};
+use crate::cursor::deserialize;
+use crate::error::{MongoError, Result, DUPLICATE_KEY_CODE};
+use bson::{doc, Bson, Document};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::fmt;
+
+/// Options for a [`TieredCollection`].
+#[derive(Debug, Clone, Default)]
+pub struct TieredOptions {
+    /// Move documents read from the cold collection back to the hot one.
+    pub rehydrate: bool,
+}
+
+impl TieredOptions {
+    /// Create a builder.
+    pub fn builder() -> TieredOptionsBuilder {
+        TieredOptionsBuilder::default()
+    }
+}
+
+/// Builder for TieredOptions.
+#[derive(Debug, Clone, Default)]
+pub struct TieredOptionsBuilder {
+    options: TieredOptions,
+}
+
+impl TieredOptionsBuilder {
+    /// Set whether to move cold documents back to the hot collection on read.
+    pub fn rehydrate(mut self, rehydrate: bool) -> Self {
+        self.options.rehydrate = rehydrate;
+        self
+    }
+
+    /// Build the options.
+    pub fn build(self) -> TieredOptions {
+        self.options
+    }
+}
+
+/// Documents of type `T` split between a hot and a cold collection.
+pub struct TieredCollection<T> {
+    /// Collection read first and written to.
+    hot: Collection<T>,
+    /// Collection read when the hot one has no match.
+    cold: Collection<T>,
+    /// Whether to rehydrate.
+    options: TieredOptions,
+}
+
+impl<T> TieredCollection<T> {
+    /// Read `hot` first, then `cold`.
+    pub fn new(hot: Collection<T>, cold: Collection<T>, options: TieredOptions) -> Self {
+        Self { hot, cold, options }
+    }
+
+    /// The hot collection.
+    pub fn hot(&self) -> &Collection<T> {
+        &self.hot
+    }
+
+    /// The cold collection.
+    pub fn cold(&self) -> &Collection<T> {
+        &self.cold
+    }
+
+    /// The options of this collection.
+    pub fn options(&self) -> &TieredOptions {
+        &self.options
+    }
+}
+
+impl<T: Serialize + DeserializeOwned + Send + Sync + Unpin + 'static> TieredCollection<T> {
+    /// Insert a document into the hot collection.
+    pub async fn insert_one(&self, doc: impl Into<T>) -> Result<InsertOneResult> {
+        self.hot.insert_one(doc).await
+    }
+
+    /// Find a document matching `filter` in the hot collection, or else in
+    /// the cold one.
+    pub async fn find_one(&self, filter: impl Into<Option<Document>>) -> Result<Option<T>> {
+        let filter = filter.into().unwrap_or_default();
+        if let Some(doc) = self.hot.find_one(filter.clone()).await? {
+            return Ok(Some(doc));
+        }
+        let cold = self.cold.clone_with_type::<JsonValue>();
+        let Some(doc) = cold.find_one(filter).await? else {
+            return Ok(None);
+        };
+        if self.options.rehydrate {
+            self.rehydrate(std::slice::from_ref(&doc)).await?;
+        }
+        deserialize(&doc, None).map(Some)
+    }
+
+    /// Find the documents matching `filter` in both collections, hot
+    /// documents first. A document in both is returned once, from the hot
+    /// collection.
+    pub async fn find(&self, filter: impl Into<Option<Document>>) -> Result<Vec<T>> {
+        let filter = filter.into().unwrap_or_default();
+        let hot = self.hot.clone_with_type::<JsonValue>();
+        let cold = self.cold.clone_with_type::<JsonValue>();
+
+        let hot_docs = hot.find(filter.clone()).await?.collect().await?;
+        let hot_ids = document_ids(&hot, &hot_docs)?;
+        let cold_filter = and_filter(&filter, doc! { "_id": { "$nin": hot_ids } });
+        let cold_docs = cold.find(cold_filter).await?.collect().await?;
+        if self.options.rehydrate && !cold_docs.is_empty() {
+            self.rehydrate(&cold_docs).await?;
+        }
+        hot_docs.into_iter().chain(cold_docs).map(|doc| deserialize(&doc, None)).collect()
+    }
+
+    /// Count the documents matching `filter` in both collections.
+    ///
+    /// A document being rehydrated may briefly be counted twice.
+    pub async fn count_documents(&self, filter: impl Into<Option<Document>>) -> Result<u64> {
+        let filter = filter.into().unwrap_or_default();
+        let hot = self.hot.count_documents(filter.clone()).await?;
+        Ok(hot + self.cold.count_documents(filter).await?)
+    }
+
+    /// Move `docs` from the cold collection to the hot one.
+    ///
+    /// Documents already in the hot collection, e.g. rehydrated by a
+    /// concurrent read, are only deleted from the cold one.
+    async fn rehydrate(&self, docs: &[JsonValue]) -> Result<()> {
+        let hot = self.hot.clone_with_type::<JsonValue>();
+        let ids = document_ids(&hot, docs)?;
+        let options = InsertManyOptions::builder().ordered(false).build();
+        match hot.insert_many_with_options(docs, options).await {
+            Ok(_) => {}
+            Err(MongoError::InsertMany(err))
+                if err.write_errors.iter().all(|e| e.code == DUPLICATE_KEY_CODE) => {}
+            Err(e) => return Err(e),
+        }
+        self.cold.delete_many(doc! { "_id": { "$in": ids } }).await?;
+        Ok(())
+    }
+}
+
+/// The `_id`s of `docs` read from `collection`.
+fn document_ids(collection: &Collection<JsonValue>, docs: &[JsonValue]) -> Result<Vec<Bson>> {
+    docs.iter()
+        .map(|doc| {
+            doc.get("_id")
+                .map(|id| collection.rpc_client.decode(id))
+                .ok_or_else(|| MongoError::Deserialization("document has no _id".into()))
+        })
+        .collect()
+}
+
+impl<T> Clone for TieredCollection<T> {
+    fn clone(&self) -> Self {
+        Self {
+            hot: self.hot.clone_with_type(),
+            cold: self.cold.clone_with_type(),
+            options: self.options.clone(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for TieredCollection<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TieredCollection")
+            .field("hot", &self.hot.namespace())
+            .field("cold", &self.cold.namespace())
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Database;
+    use crate::transport::Transport;
+
+    #[test]
+    fn test_tiered_collection() {
+        let transport =
+            Transport::lazy("mongodb://localhost".to_string(), crate::ClientOptions::default());
+        let db = Database::new("app".to_string(), transport);
+        let orders = TieredCollection::new(
+            db.collection::<Document>("orders"),
+            db.collection::<Document>("orders_archive"),
+            TieredOptions::builder().rehydrate(true).build(),
+        );
+        assert!(orders.options().rehydrate);
+        assert!(!TieredOptions::default().rehydrate);
+        assert_eq!(orders.cold().name(), "orders_archive");
+        assert_eq!(
+            format!("{:?}", orders.clone()),
+            "TieredCollection { hot: \"app.orders\", cold: \"app.orders_archive\", \
+             options: TieredOptions { rehydrate: true } }"
+        );
+
+        let docs = [serde_json::json!({ "_id": "a" }), serde_json::json!({ "name": "x" })];
+        let hot = orders.hot().clone_with_type::<JsonValue>();
+        assert_eq!(document_ids(&hot, &docs[..1]).unwrap(), vec![Bson::String("a".into())]);
+        assert!(document_ids(&hot, &docs).is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! Timing and payload sizes of individual operations.
+//!
+//! [`measure`] runs an operation and returns what its calls to the server
+//! cost: round-trip time, the execution time the server reports, and bytes
+//! sent and received. Unlike the client-wide [`ClientStats`], this is per
+//! operation, so latency and size SLIs can be computed per endpoint or per
+//! tenant without wrapping every call with timers.
+//!
+//! Calls are attributed to the innermost `measure` of the task making them.
+//! Work spawned onto other tasks, e.g. by
+//! [`Collection::parallel_collect`](crate::Collection::parallel_collect), is
+//! not counted.
+//!
+//! [`ClientStats`]: crate::ClientStats
+//!
+//! # Example
+//!
+//! ```ignore
+//! use mongo_do::timing::measure;
+//!
+//! let (user, metrics) = measure(users.find_one(doc! { "_id": id })).await;
+//! histogram.observe(metrics.round_trip.as_secs_f64());
+//! let user = user?;
+//! ```
+
+use crate::error::Result;
+use serde_json::Value as JsonValue;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+tokio::task_local! {
+    /// Where the calls of the current task are added up.
+    static CURRENT: Arc<Mutex<OperationMetrics>>;
+}
+
+/// What the server calls of an operation cost.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OperationMetrics {
+    /// Server calls made, including `getMore`s and retries.
+    pub calls: u32,
+    /// Time from sending each call to receiving its reply, summed.
+    pub round_trip: Duration,
+    /// Execution time reported by the server, summed, if any reply reported it.
+    pub server_time: Option<Duration>,
+    /// Bytes of requests sent, measured as JSON.
+    pub bytes_sent: u64,
+    /// Bytes of replies received, measured as JSON.
+    pub bytes_received: u64,
+}
+
+impl OperationMetrics {
+    /// Time spent outside the server, e.g. on the network, if the server
+    /// reported its execution time.
+    pub fn overhead(&self) -> Option<Duration> {
+        self.server_time.map(|server| self.round_trip.saturating_sub(server))
+    }
+}
+
+/// Run `operation`, returning its output and what its server calls cost.
+///
+/// # Example
+///
+/// ```ignore
+/// let (result, metrics) = measure(async {
+///     let order = orders.find_one(filter).await?;
+///     payments.insert_one(payment).await
+/// })
+/// .await;
+/// println!("{} calls, {:?}", metrics.calls, metrics.round_trip);
+/// ```
+pub async fn measure<F: Future>(operation: F) -> (F::Output, OperationMetrics) {
+    let metrics = Arc::new(Mutex::new(OperationMetrics::default()));
+    let output = CURRENT.scope(metrics.clone(), operation).await;
+    let metrics = *metrics.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    (output, metrics)
+}
+
+/// Count a call in the metrics of the enclosing [`measure`], if any.
+pub(crate) fn record(
+    round_trip: Duration,
+    bytes_sent: usize,
+    bytes_received: usize,
+    result: &Result<JsonValue>,
+) {
+    let _ = CURRENT.try_with(|metrics| {
+        let mut metrics = metrics.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        metrics.calls += 1;
+        metrics.round_trip += round_trip;
+        metrics.bytes_sent += bytes_sent as u64;
+        metrics.bytes_received += bytes_received as u64;
+        if let Some(server) = result.as_ref().ok().and_then(server_time) {
+            metrics.server_time = Some(metrics.server_time.unwrap_or_default() + server);
+        }
+    });
+}
+
+/// The execution time a reply reports in `$metadata.executionTimeMS`.
+fn server_time(reply: &JsonValue) -> Option<Duration> {
+    let millis = reply.get("$metadata")?.get("executionTimeMS")?.as_f64()?;
+    (millis.is_finite() && millis >= 0.0).then(|| Duration::from_secs_f64(millis / 1000.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_measure() {
+        let reply = serde_json::json!({ "ok": 1, "$metadata": { "executionTimeMS": 1.5 } });
+        let (output, metrics) = measure(async {
+            record(Duration::from_millis(4), 100, 20, &Ok(reply));
+            record(Duration::from_millis(6), 50, 0, &Ok(serde_json::json!(null)));
+            "done"
+        })
+        .await;
+        assert_eq!(output, "done");
+        assert_eq!(metrics.calls, 2);
+        assert_eq!(metrics.round_trip, Duration::from_millis(10));
+        assert_eq!(metrics.server_time, Some(Duration::from_micros(1500)));
+        assert_eq!(metrics.overhead(), Some(Duration::from_micros(8500)));
+        assert_eq!((metrics.bytes_sent, metrics.bytes_received), (150, 20));
+
+        // Calls outside a measure are not counted anywhere.
+        record(Duration::from_millis(1), 1, 1, &Ok(JsonValue::Null));
+        let (_, metrics) = measure(async {}).await;
+        assert_eq!(metrics, OperationMetrics::default());
+        assert_eq!(metrics.overhead(), None);
+    }
+}
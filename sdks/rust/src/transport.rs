@@ -0,0 +1,797 @@
+//! RPC transport shared by clients, databases, collections and cursors.
+
+use crate::audit::AuditOptions;
+use crate::client::{connect, ClientOptions};
+use crate::convert::{self, ValueCodec};
+use crate::error::{MongoError, Result};
+use crate::events::ConnectionEvents;
+use crate::handshake::{self, ServerHello};
+use crate::monitoring::{self, CmapEventHandler, CommandEventHandler};
+use crate::rpc::Method;
+use crate::stats::{RetryBudget, StatsRecorder};
+use bson::{Bson, Document};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::OnceCell;
+
+/// An RPC client plus the per-client metadata sent with every call.
+///
+/// Metadata travels as a trailing `{ "$metadata": { ... } }` argument after
+/// the method's own positional arguments, so servers that do not read it
+/// can ignore it.
+#[derive(Clone)]
+pub(crate) struct Transport {
+    /// Where calls are sent, set on first use for lazy transports.
+    pub(crate) client: Arc<OnceCell<Connection>>,
+    /// How a lazy transport connects.
+    connector: Option<Arc<(String, ClientOptions)>>,
+    /// Tag identifying the application feature issuing the calls.
+    pub(crate) operation_tag: Option<Arc<str>>,
+    /// Application name, so server logs and `$currentOp` attribute each call.
+    app_name: Option<Arc<str>>,
+    /// Where writes are audited, when enabled.
+    pub(crate) audit: Option<Arc<AuditOptions>>,
+    /// When every call must have completed by.
+    pub(crate) deadline: Option<Instant>,
+    /// Operation counters, shared with every copy of this transport.
+    pub(crate) stats: Arc<StatsRecorder>,
+    /// Connection state and lifecycle events, shared with every copy of this transport.
+    pub(crate) events: Arc<ConnectionEvents>,
+    /// Why every call is refused without being sent, e.g. an invalid namespace.
+    rejection: Option<Arc<str>>,
+    /// Custom mapping between BSON values and the wire representation.
+    pub(crate) codec: Option<Arc<dyn ValueCodec>>,
+    /// Session every call is made in.
+    pub(crate) session_id: Option<Arc<str>>,
+    /// Told when each call starts and ends.
+    pub(crate) command_events: Option<Arc<dyn CommandEventHandler>>,
+    /// Whether responses missing expected fields are errors.
+    pub(crate) strict_responses: bool,
+    /// Describes the client in the handshake.
+    client_metadata: Arc<JsonValue>,
+    /// The server's handshake reply, once exchanged.
+    hello: Arc<OnceCell<ServerHello>>,
+}
+
+/// Where a transport sends its calls.
+pub(crate) enum Connection {
+    /// An RPC client connected to the server.
+    Rpc(Arc<rpc_do::RpcClient>),
+    /// A server stand-in answering calls in memory.
+    #[cfg(test)]
+    Mock(Arc<crate::mock::MockServer>),
+}
+
+impl Connection {
+    /// Send a call.
+    async fn call(&self, method: Method, args: Vec<JsonValue>) -> Result<JsonValue> {
+        match self {
+            Connection::Rpc(client) => client
+                .call_raw(method.as_str(), args)
+                .await
+                .map_err(|e| MongoError::from_rpc(method, e)),
+            #[cfg(test)]
+            Connection::Mock(server) => server.call(method, args).await,
+        }
+    }
+
+    /// Check whether the connection is up.
+    async fn is_connected(&self) -> bool {
+        match self {
+            Connection::Rpc(client) => client.is_connected().await,
+            #[cfg(test)]
+            Connection::Mock(_) => true,
+        }
+    }
+
+    /// The RPC client, if calls go to one.
+    pub(crate) fn rpc_client(&self) -> Option<&Arc<rpc_do::RpcClient>> {
+        match self {
+            Connection::Rpc(client) => Some(client),
+            #[cfg(test)]
+            Connection::Mock(_) => None,
+        }
+    }
+}
+
+impl Transport {
+    /// Create a transport without metadata.
+    pub(crate) fn new(client: Arc<rpc_do::RpcClient>) -> Self {
+        Self {
+            client: Arc::new(OnceCell::new_with(Some(Connection::Rpc(client)))),
+            connector: None,
+            operation_tag: None,
+            app_name: None,
+            audit: None,
+            deadline: None,
+            stats: Arc::default(),
+            events: Arc::default(),
+            rejection: None,
+            codec: None,
+            session_id: None,
+            command_events: None,
+            strict_responses: false,
+            client_metadata: Arc::new(handshake::client_metadata(None, None)),
+            hello: Arc::default(),
+        }
+    }
+
+    /// Create a transport that connects to `uri` on its first call.
+    pub(crate) fn lazy(uri: String, options: ClientOptions) -> Self {
+        Self {
+            client: Arc::new(OnceCell::new()),
+            connector: Some(Arc::new((uri, options))),
+            operation_tag: None,
+            app_name: None,
+            audit: None,
+            deadline: None,
+            stats: Arc::default(),
+            events: Arc::default(),
+            rejection: None,
+            codec: None,
+            session_id: None,
+            command_events: None,
+            strict_responses: false,
+            client_metadata: Arc::new(handshake::client_metadata(None, None)),
+            hello: Arc::default(),
+        }
+    }
+
+    /// Return a copy of this transport that refuses every call with an
+    /// invalid argument error carrying `reason`.
+    pub(crate) fn rejecting(&self, reason: impl Into<String>) -> Self {
+        Self {
+            rejection: Some(Arc::from(reason.into())),
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this transport with a different operation tag.
+    pub(crate) fn with_operation_tag(&self, tag: Option<&str>) -> Self {
+        Self {
+            operation_tag: tag.map(Arc::from),
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this transport that audits writes.
+    pub(crate) fn with_audit(&self, audit: Option<AuditOptions>) -> Self {
+        Self {
+            audit: audit.map(Arc::new),
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this transport whose calls must complete by `deadline`.
+    pub(crate) fn with_deadline(&self, deadline: Instant) -> Self {
+        Self {
+            deadline: Some(deadline),
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this transport that converts values with `codec`.
+    pub(crate) fn with_codec(&self, codec: Option<Arc<dyn ValueCodec>>) -> Self {
+        Self {
+            codec,
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this transport with its own connection events, also
+    /// reported to `handler`.
+    ///
+    /// Only for building a client: copies made earlier keep the old events.
+    pub(crate) fn with_cmap_events(
+        &self,
+        handler: Option<Arc<dyn CmapEventHandler>>,
+        max_pool_size: Option<u32>,
+    ) -> Self {
+        Self {
+            events: Arc::new(ConnectionEvents::with_handler(handler, max_pool_size)),
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this transport with its own counters, which limit
+    /// retries to `budget`.
+    ///
+    /// Only for building a client: copies made earlier keep the old counters.
+    pub(crate) fn with_retry_budget(&self, budget: Option<RetryBudget>) -> Self {
+        match budget {
+            Some(budget) => Self {
+                stats: Arc::new(StatsRecorder::with_retry_budget(budget)),
+                ..self.clone()
+            },
+            None => self.clone(),
+        }
+    }
+
+    /// Return a copy of this transport that reports its calls to `handler`.
+    pub(crate) fn with_command_events(
+        &self,
+        handler: Option<Arc<dyn CommandEventHandler>>,
+    ) -> Self {
+        Self {
+            command_events: handler,
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this transport that checks responses for expected fields.
+    pub(crate) fn with_strict_responses(&self, strict_responses: bool) -> Self {
+        Self {
+            strict_responses,
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this transport that describes the client as
+    /// `metadata` in the handshake, and names `app_name` in every call.
+    pub(crate) fn with_client_metadata(&self, metadata: JsonValue, app_name: Option<&str>) -> Self {
+        Self {
+            client_metadata: Arc::new(metadata),
+            app_name: app_name.map(Arc::from),
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this transport whose calls are made in a session.
+    pub(crate) fn with_session(&self, session_id: &str) -> Self {
+        Self {
+            session_id: Some(Arc::from(session_id)),
+            ..self.clone()
+        }
+    }
+
+    /// Return a copy of this transport whose calls are answered by `server`.
+    #[cfg(test)]
+    pub(crate) fn with_mock(&self, server: Arc<crate::mock::MockServer>) -> Self {
+        Self {
+            client: Arc::new(OnceCell::new_with(Some(Connection::Mock(server)))),
+            connector: None,
+            ..self.clone()
+        }
+    }
+
+    /// Convert a document to its wire representation.
+    pub(crate) fn encode(&self, doc: &Document) -> Result<JsonValue> {
+        convert::encode_document(doc, self.codec.as_deref())
+    }
+
+    /// Convert a value received from the server to BSON.
+    pub(crate) fn decode(&self, json: &JsonValue) -> Bson {
+        convert::decode(json, self.codec.as_deref())
+    }
+
+    /// Convert a document received from the server to BSON.
+    pub(crate) fn decode_document(&self, json: &JsonValue) -> Result<Document> {
+        convert::decode_document(json, self.codec.as_deref())
+    }
+
+    /// Convert a typed document to its wire representation.
+    pub(crate) fn encode_typed<T: Serialize + ?Sized>(&self, value: &T) -> Result<JsonValue> {
+        convert::encode_typed(value, self.codec.as_deref())
+    }
+
+    /// Convert a typed document received from the server.
+    pub(crate) fn decode_typed<T: DeserializeOwned>(&self, json: &JsonValue) -> Result<T> {
+        convert::decode_typed(json, self.codec.as_deref())
+    }
+
+    /// Exchange protocol versions with the server, once per client.
+    pub(crate) async fn hello(&self) -> Result<&ServerHello> {
+        self.hello
+            .get_or_try_init(|| async {
+                let args = handshake::hello_args(&self.client_metadata);
+                handshake::parse_hello(self.call_raw(Method::Hello, args).await)
+            })
+            .await
+    }
+
+    /// Read the count `field` of a write response.
+    ///
+    /// A missing or non-numeric count is 0, unless responses are strict and
+    /// the write was acknowledged.
+    pub(crate) fn response_count(&self, response: &JsonValue, field: &str) -> Result<u64> {
+        match response.get(field).and_then(|v| v.as_u64()) {
+            Some(count) => Ok(count),
+            None if self.checks(response) => Err(MongoError::protocol(
+                format!("expected a count in `{}`", field),
+                response,
+            )),
+            None => Ok(0),
+        }
+    }
+
+    /// Read the `field` holding an `_id` of a write response.
+    ///
+    /// A missing `_id` is null, unless responses are strict and the write
+    /// was acknowledged.
+    pub(crate) fn response_id(&self, response: &JsonValue, field: &str) -> Result<Bson> {
+        match response.get(field) {
+            Some(id) => Ok(self.decode(id)),
+            None if self.checks(response) => Err(MongoError::protocol(
+                format!("expected an _id in `{}`", field),
+                response,
+            )),
+            None => Ok(Bson::Null),
+        }
+    }
+
+    /// Whether `response` is checked for expected fields. Unacknowledged
+    /// writes have none.
+    pub(crate) fn checks(&self, response: &JsonValue) -> bool {
+        self.strict_responses
+            && response.get("acknowledged").and_then(|v| v.as_bool()) != Some(false)
+    }
+
+    /// Call an RPC method, attaching metadata to the arguments.
+    ///
+    /// Writes are recorded in the audit collection, if auditing is enabled.
+    /// The audit entry is sent in the same batch as the write, and failing to
+    /// record it does not fail the write: it is counted in
+    /// [`ClientStats::audit_failures`](crate::ClientStats::audit_failures)
+    /// instead. With a deadline, the remaining time is sent as `maxTimeMS` and
+    /// the call fails with [`MongoError::Timeout`] once it passes.
+    pub(crate) async fn call_raw(&self, method: Method, args: Vec<JsonValue>) -> Result<JsonValue> {
+        if let Some(ref reason) = self.rejection {
+            return Err(MongoError::invalid_argument(reason.as_ref()));
+        }
+        let operation_tag = self.operation_tag.as_deref();
+        let codec = self.codec.as_deref();
+        let audit_entry = self
+            .audit
+            .as_ref()
+            .and_then(|audit| Some((audit, audit.entry(method, &args, operation_tag, codec)?)));
+        let Some((audit, entry)) = audit_entry else {
+            return self.call_with_metadata(method, args).await;
+        };
+
+        let audit_args = vec![
+            serde_json::json!(audit.database),
+            serde_json::json!(audit.collection),
+            entry,
+        ];
+        // Polling both calls together sends them without waiting for either
+        // reply, so they travel in one pipelined batch.
+        let (result, audited) = futures::future::join(
+            self.call_with_metadata(method, args),
+            self.call_with_metadata(Method::InsertOne, audit_args),
+        )
+        .await;
+        if audited.is_err() {
+            self.stats.record_audit_failure();
+        }
+        #[cfg(feature = "tracing")]
+        if let Err(ref error) = audited {
+            tracing::warn!(error = %error, "failed to record audit entry");
+        }
+        result
+    }
+
+    /// Send a single call with this transport's metadata, within the deadline,
+    /// and count it in the transport's statistics.
+    async fn call_with_metadata(&self, method: Method, args: Vec<JsonValue>) -> Result<JsonValue> {
+        let remaining = match self.deadline {
+            Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                Some(remaining) if !remaining.is_zero() => Some(remaining),
+                _ => return Err(MongoError::Timeout),
+            },
+            None => None,
+        };
+
+        #[cfg(feature = "tracing")]
+        let span = operation_span(method, &args);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            parent: &span,
+            payload = %RedactedArgs(&args, self.codec.as_deref()),
+            "sending command",
+        );
+
+        let client = self.client().await?;
+        let monitored = self
+            .command_events
+            .as_ref()
+            .map(|handler| (handler, command_started(handler.as_ref(), method, &args)));
+        self.events.before_call();
+        let args = with_metadata(
+            args,
+            self.operation_tag.as_deref(),
+            self.app_name.as_deref(),
+            self.session_id.as_deref(),
+            remaining,
+        );
+        let bytes_sent = json_len(&args);
+        let started = Instant::now();
+        self.events.checked_out();
+        let call = client.call(method, args);
+        #[cfg(feature = "tracing")]
+        let call = tracing::Instrument::instrument(call, span.clone());
+        let result = match remaining {
+            Some(remaining) => tokio::time::timeout(remaining, call)
+                .await
+                .unwrap_or(Err(MongoError::Timeout)),
+            None => call.await,
+        };
+
+        self.events.checked_in(started.elapsed());
+        self.stats.record_call(method, bytes_sent, started.elapsed());
+        #[cfg(feature = "tracing")]
+        record_outcome(&span, started.elapsed(), &result);
+        if let Some((handler, request_id)) = monitored {
+            command_finished(handler.as_ref(), request_id, method, started.elapsed(), &result);
+        }
+        let bytes_received = result.as_ref().map_or(0, json_len);
+        crate::timing::record(started.elapsed(), bytes_sent, bytes_received, &result);
+        match &result {
+            Ok(_) => {
+                self.stats.record_success(bytes_received);
+                if self.events.call_succeeded() {
+                    self.stats.record_reconnect();
+                }
+            }
+            Err(error) => {
+                self.stats.record_failure();
+                if matches!(error, MongoError::Rpc(_)) && !client.is_connected().await {
+                    self.events.connection_lost(error.to_string());
+                }
+            }
+        }
+        result
+    }
+
+    /// Get the connection, connecting a lazy transport on first use.
+    async fn client(&self) -> Result<&Connection> {
+        self.client
+            .get_or_try_init(|| async {
+                let (uri, options) = self
+                    .connector
+                    .as_deref()
+                    .ok_or_else(|| MongoError::connection("transport has no client"))?;
+                let client = connect(uri, options).await?;
+                self.events.connected();
+                Ok(Connection::Rpc(Arc::new(client)))
+            })
+            .await
+    }
+
+    /// Check whether the underlying client is connected. A lazy transport
+    /// that has not made a call yet is not connected.
+    pub(crate) async fn is_connected(&self) -> bool {
+        match self.client.get() {
+            Some(client) => client.is_connected().await,
+            None => false,
+        }
+    }
+}
+
+/// The database and collection a call operates on, where its arguments name them.
+fn call_namespace(method: Method, args: &[JsonValue]) -> (Option<&str>, Option<&str>) {
+    if method == Method::GetMore {
+        return match args.get(1).and_then(|ns| ns.as_str()) {
+            Some(ns) => match ns.split_once('.') {
+                Some((db, coll)) => (Some(db), Some(coll)),
+                None => (Some(ns), None),
+            },
+            None => (None, None),
+        };
+    }
+    if !method.has_namespace() {
+        return (None, None);
+    }
+    let db = args.first().and_then(|v| v.as_str());
+    let coll = args.get(1).and_then(|v| v.as_str());
+    (db, coll)
+}
+
+/// Open the span covering one call.
+#[cfg(feature = "tracing")]
+fn operation_span(method: Method, args: &[JsonValue]) -> tracing::Span {
+    let span = tracing::info_span!(
+        "mongo",
+        db.name = tracing::field::Empty,
+        db.collection = tracing::field::Empty,
+        db.operation = method.operation(),
+        duration_ms = tracing::field::Empty,
+        error = tracing::field::Empty,
+    );
+    let (db, coll) = call_namespace(method, args);
+    if let Some(db) = db {
+        span.record("db.name", db);
+    }
+    if let Some(coll) = coll {
+        span.record("db.collection", coll);
+    }
+    span
+}
+
+/// Call arguments shown by shape only, redacted when formatted.
+#[cfg(feature = "tracing")]
+struct RedactedArgs<'a>(&'a [JsonValue], Option<&'a dyn ValueCodec>);
+
+#[cfg(feature = "tracing")]
+impl std::fmt::Display for RedactedArgs<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut list = f.debug_list();
+        for arg in self.0 {
+            list.entry(&format_args!("{}", crate::util::redact_json(arg, self.1)));
+        }
+        list.finish()
+    }
+}
+
+/// Record how long a call took and whether it failed on its span.
+#[cfg(feature = "tracing")]
+fn record_outcome(span: &tracing::Span, elapsed: Duration, result: &Result<JsonValue>) {
+    span.record("duration_ms", elapsed.as_secs_f64() * 1000.0);
+    if let Err(ref error) = result {
+        span.record("error", tracing::field::display(error));
+    }
+}
+
+/// Report a call about to be sent, returning its request ID.
+fn command_started(handler: &dyn CommandEventHandler, method: Method, args: &[JsonValue]) -> u64 {
+    let request_id = monitoring::next_request_id();
+    let (db, coll) = call_namespace(method, args);
+    handler.command_started(&monitoring::CommandStartedEvent {
+        request_id,
+        method,
+        command_name: method.operation().to_string(),
+        database_name: db.map(str::to_string),
+        collection_name: coll.map(str::to_string),
+        command: JsonValue::Array(args.to_vec()),
+    });
+    request_id
+}
+
+/// Report how a call ended.
+fn command_finished(
+    handler: &dyn CommandEventHandler,
+    request_id: u64,
+    method: Method,
+    duration: Duration,
+    result: &Result<JsonValue>,
+) {
+    let command_name = method.operation().to_string();
+    match result {
+        Ok(reply) => handler.command_succeeded(&monitoring::CommandSucceededEvent {
+            request_id,
+            method,
+            command_name,
+            duration,
+            reply: reply.clone(),
+        }),
+        Err(error) => handler.command_failed(&monitoring::CommandFailedEvent {
+            request_id,
+            method,
+            command_name,
+            duration,
+            failure: error.to_string(),
+        }),
+    }
+}
+
+/// Size of a value serialized as JSON, counted without buffering the output.
+pub(crate) fn json_len(value: &impl serde::Serialize) -> usize {
+    let mut counter = ByteCounter(0);
+    serde_json::to_writer(&mut counter, value).map_or(0, |()| counter.0)
+}
+
+/// A writer that only counts the bytes written to it.
+struct ByteCounter(usize);
+
+impl std::io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Append the metadata argument, if there is any metadata to send.
+fn with_metadata(
+    mut args: Vec<JsonValue>,
+    operation_tag: Option<&str>,
+    app_name: Option<&str>,
+    session_id: Option<&str>,
+    remaining: Option<Duration>,
+) -> Vec<JsonValue> {
+    let mut metadata = serde_json::Map::new();
+    if let Some(tag) = operation_tag {
+        metadata.insert("operationTag".to_string(), serde_json::json!(tag));
+    }
+    if let Some(app_name) = app_name {
+        metadata.insert("appName".to_string(), serde_json::json!(app_name));
+    }
+    if let Some(session_id) = session_id {
+        metadata.insert("sessionId".to_string(), serde_json::json!(session_id));
+    }
+    if let Some(remaining) = remaining {
+        // Round up so a sub-millisecond budget is not sent as "no limit".
+        let max_time_ms = remaining.as_micros().div_ceil(1000) as u64;
+        metadata.insert("maxTimeMS".to_string(), serde_json::json!(max_time_ms));
+    }
+    if !metadata.is_empty() {
+        args.push(serde_json::json!({ "$metadata": metadata }));
+    }
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_metadata() {
+        let args = vec![serde_json::json!("db"), serde_json::json!("users")];
+        assert_eq!(with_metadata(args.clone(), None, None, None, None), args);
+
+        let tagged = with_metadata(args.clone(), Some("checkout"), None, None, None);
+        assert_eq!(tagged.len(), 3);
+        assert_eq!(
+            tagged[2],
+            serde_json::json!({ "$metadata": { "operationTag": "checkout" } })
+        );
+
+        let in_session = with_metadata(args.clone(), None, None, Some("s1"), None);
+        assert_eq!(in_session[2], serde_json::json!({ "$metadata": { "sessionId": "s1" } }));
+
+        let named = with_metadata(args, None, Some("billing"), None, None);
+        assert_eq!(named[2], serde_json::json!({ "$metadata": { "appName": "billing" } }));
+    }
+
+    #[test]
+    fn test_strict_responses() {
+        let lax = Transport::lazy("mongodb://localhost".to_string(), ClientOptions::default());
+        let strict = lax.with_strict_responses(true);
+        let response = serde_json::json!({ "matchedCount": 1, "acknowledged": true });
+
+        assert_eq!(strict.response_count(&response, "matchedCount").unwrap(), 1);
+        assert_eq!(lax.response_count(&response, "modifiedCount").unwrap(), 0);
+        assert_eq!(lax.response_id(&response, "insertedId").unwrap(), Bson::Null);
+
+        let err = strict.response_count(&response, "modifiedCount").unwrap_err();
+        assert!(matches!(err, MongoError::Protocol { .. }));
+        assert!(err.to_string().contains("modifiedCount"), "{}", err);
+        assert!(err.to_string().contains(r#""matchedCount":1"#), "{}", err);
+        assert!(strict.response_id(&response, "insertedId").is_err());
+
+        // Unacknowledged writes report nothing.
+        let unacknowledged = serde_json::json!({ "acknowledged": false });
+        assert_eq!(strict.response_count(&unacknowledged, "deletedCount").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_call_namespace() {
+        let args = vec![serde_json::json!("shop"), serde_json::json!("orders")];
+        assert_eq!(call_namespace(Method::Find, &args), (Some("shop"), Some("orders")));
+
+        let args = vec![serde_json::json!("shop"), serde_json::json!({ "ping": 1 })];
+        assert_eq!(call_namespace(Method::RunCommand, &args), (Some("shop"), None));
+
+        let args = vec![serde_json::json!("c1"), serde_json::json!("shop.orders")];
+        assert_eq!(call_namespace(Method::GetMore, &args), (Some("shop"), Some("orders")));
+
+        let args = vec![serde_json::json!("session1")];
+        assert_eq!(call_namespace(Method::CommitTransaction, &args), (None, None));
+    }
+
+    #[derive(Debug, Default)]
+    struct Recorder(std::sync::Mutex<Vec<String>>);
+
+    impl CommandEventHandler for Recorder {
+        fn command_started(&self, event: &monitoring::CommandStartedEvent) {
+            let ns = (event.database_name.as_deref(), event.collection_name.as_deref());
+            self.0.lock().unwrap().push(format!("started {} {:?}", event.command_name, ns));
+        }
+
+        fn command_succeeded(&self, event: &monitoring::CommandSucceededEvent) {
+            self.0.lock().unwrap().push(format!("succeeded {}", event.command_name));
+        }
+
+        fn command_failed(&self, event: &monitoring::CommandFailedEvent) {
+            let line = format!("failed {}: {}", event.command_name, event.failure);
+            self.0.lock().unwrap().push(line);
+        }
+    }
+
+    #[test]
+    fn test_command_events() {
+        let recorder = Recorder::default();
+        let args = vec![serde_json::json!("shop"), serde_json::json!("orders")];
+        let id = command_started(&recorder, Method::Find, &args);
+        let elapsed = Duration::from_millis(3);
+        command_finished(&recorder, id, Method::Find, elapsed, &Ok(serde_json::json!([])));
+        command_finished(&recorder, id, Method::Find, elapsed, &Err(MongoError::Timeout));
+        assert_eq!(
+            *recorder.0.lock().unwrap(),
+            vec![
+                "started find (Some(\"shop\"), Some(\"orders\"))".to_string(),
+                "succeeded find".to_string(),
+                format!("failed find: {}", MongoError::Timeout),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_json_len() {
+        let value = serde_json::json!({ "name": "caf\u{e9}", "tags": ["a", "b"], "n": 1.5 });
+        assert_eq!(json_len(&value), serde_json::to_vec(&value).unwrap().len());
+    }
+
+    #[test]
+    fn test_with_metadata_deadline() {
+        let args = with_metadata(vec![], None, None, None, Some(Duration::from_micros(1500)));
+        assert_eq!(args, vec![serde_json::json!({ "$metadata": { "maxTimeMS": 2 } })]);
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn test_redacted_args() {
+        let args = [serde_json::json!("shop"), serde_json::json!({ "email": "a@example.com" })];
+        assert_eq!(
+            RedactedArgs(&args, None).to_string(),
+            r#"["string", {"email":"string"}]"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_audit_entry_does_not_fail_write() {
+        let server = crate::mock::MockServer::new(|method, args| match method {
+            Method::InsertOne if args[0] == "audit" => Err(MongoError::connection("audit down")),
+            _ => Ok(serde_json::json!({ "acknowledged": true, "insertedId": 1 })),
+        });
+        let transport = server
+            .transport()
+            .with_audit(Some(AuditOptions::new("audit", "writes")));
+        let args = vec![
+            serde_json::json!("shop"),
+            serde_json::json!("orders"),
+            serde_json::json!({ "_id": 1 }),
+        ];
+
+        let reply = transport.call_raw(Method::InsertOne, args).await.unwrap();
+        assert_eq!(reply["insertedId"], 1);
+        assert_eq!(transport.stats.snapshot().audit_failures, 1);
+
+        // The entry went out alongside the write.
+        let calls = server.calls_of(Method::InsertOne);
+        assert_eq!(calls.len(), 2);
+        assert_eq!((calls[0][0].as_str(), calls[1][0].as_str()), (Some("shop"), Some("audit")));
+        assert_eq!(calls[1][2]["ns"], "shop.orders");
+    }
+
+    #[tokio::test]
+    async fn test_mock_calls_are_accounted() {
+        let server = crate::mock::MockServer::new(|method, _| match method {
+            Method::Ping => Ok(serde_json::json!({ "ok": 1 })),
+            _ => Err(MongoError::connection("down")),
+        });
+        let transport = server.transport();
+
+        // Mock calls go through the same bookkeeping as calls to a server.
+        let (result, metrics) = crate::timing::measure(async {
+            transport.call_raw(Method::Ping, vec![]).await.unwrap();
+            transport.call_raw(Method::CountDocuments, vec![]).await
+        })
+        .await;
+        assert!(result.unwrap_err().is_connection_error());
+        assert_eq!(metrics.calls, 2);
+        assert_eq!(metrics.bytes_received, json_len(&serde_json::json!({ "ok": 1 })) as u64);
+        let stats = transport.stats.snapshot();
+        assert_eq!((stats.total_operations(), stats.failures), (2, 1));
+
+        // An expired deadline fails the call before it is sent.
+        let expired = transport.with_deadline(Instant::now());
+        assert!(matches!(expired.call_raw(Method::Ping, vec![]).await, Err(MongoError::Timeout)));
+        assert_eq!(server.calls().len(), 2);
+    }
+}
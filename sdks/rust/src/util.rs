@@ -0,0 +1,279 @@
+//! Utilities for working with documents and queries.
+
+use crate::convert::{self, ValueCodec};
+use crate::error::Result;
+use bson::{Bson, Document};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value as JsonValue;
+use std::cmp::Ordering;
+
+/// Convert a value to a BSON document.
+///
+/// # Example
+///
+/// ```ignore
+/// use mongo_do::util::to_document;
+///
+/// let mut update = to_document(&profile)?;
+/// update.remove("_id");
+/// users.update_one(doc! { "_id": id }, doc! { "$set": update }).await?;
+/// ```
+pub fn to_document<T: Serialize + ?Sized>(value: &T) -> Result<Document> {
+    Ok(bson::to_document(value)?)
+}
+
+/// Convert a BSON document to a value.
+///
+/// # Example
+///
+/// ```ignore
+/// use mongo_do::util::from_document;
+///
+/// let raw = db.run_command(doc! { "buildInfo": 1 }).await?;
+/// let info: BuildInfo = from_document(raw)?;
+/// ```
+pub fn from_document<T: DeserializeOwned>(document: Document) -> Result<T> {
+    Ok(bson::from_document(document)?)
+}
+
+/// Replace every value in a filter by its BSON type name, keeping field names
+/// and operators.
+///
+/// The result shows the shape of a query without its data, so it can be
+/// logged or attached to errors without leaking personal information.
+///
+/// # Example
+///
+/// ```ignore
+/// use mongo_do::util::redact;
+///
+/// let shape = redact(&doc! { "email": "a@example.com", "age": { "$gte": 21 } });
+/// assert_eq!(shape, doc! { "email": "string", "age": { "$gte": "int" } });
+/// ```
+pub fn redact(filter: &Document) -> Document {
+    filter
+        .iter()
+        .map(|(key, value)| (key.clone(), redact_value(value)))
+        .collect()
+}
+
+fn redact_value(value: &Bson) -> Bson {
+    match value {
+        Bson::Document(doc) => Bson::Document(redact(doc)),
+        Bson::Array(values) => Bson::Array(values.iter().map(redact_value).collect()),
+        other => Bson::String(type_name(other).to_string()),
+    }
+}
+
+/// Redact a value in its wire representation, as [`redact`] does, for audit
+/// entries and logs of calls.
+pub(crate) fn redact_json(value: &JsonValue, codec: Option<&dyn ValueCodec>) -> JsonValue {
+    // A shape holds only strings, arrays and documents, which always encode.
+    convert::encode(&redact_value(&convert::decode(value, codec)), None).unwrap_or_default()
+}
+
+/// Get the value at a dotted path such as `address.city`.
+pub(crate) fn get_path<'a>(doc: &'a Document, path: &str) -> Option<&'a Bson> {
+    let mut parts = path.split('.');
+    let mut value = doc.get(parts.next()?)?;
+    for part in parts {
+        value = value.as_document()?.get(part)?;
+    }
+    Some(value)
+}
+
+/// Compare two values in the server's sort order: first by type, with all
+/// numbers comparing as one type, then by value.
+pub(crate) fn compare_bson(a: &Bson, b: &Bson) -> Ordering {
+    match (a, b) {
+        (Bson::Int32(_) | Bson::Int64(_), Bson::Int32(_) | Bson::Int64(_)) => {
+            as_i64(a).cmp(&as_i64(b))
+        }
+        _ if sort_rank(a) != sort_rank(b) => sort_rank(a).cmp(&sort_rank(b)),
+        (Bson::String(a) | Bson::Symbol(a), Bson::String(b) | Bson::Symbol(b)) => a.cmp(b),
+        (Bson::Document(a), Bson::Document(b)) => a
+            .iter()
+            .zip(b.iter())
+            .map(|((ka, va), (kb, vb))| ka.cmp(kb).then_with(|| compare_bson(va, vb)))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Bson::Array(a), Bson::Array(b)) => a
+            .iter()
+            .zip(b.iter())
+            .map(|(va, vb)| compare_bson(va, vb))
+            .find(|order| order.is_ne())
+            .unwrap_or_else(|| a.len().cmp(&b.len())),
+        (Bson::Binary(a), Bson::Binary(b)) => a.bytes.cmp(&b.bytes),
+        (Bson::ObjectId(a), Bson::ObjectId(b)) => a.bytes().cmp(&b.bytes()),
+        (Bson::Boolean(a), Bson::Boolean(b)) => a.cmp(b),
+        (Bson::DateTime(a), Bson::DateTime(b)) => a.cmp(b),
+        (Bson::Timestamp(a), Bson::Timestamp(b)) => {
+            (a.time, a.increment).cmp(&(b.time, b.increment))
+        }
+        _ if sort_rank(a) == 3 => as_f64(a).total_cmp(&as_f64(b)),
+        _ => Ordering::Equal,
+    }
+}
+
+/// The position of a value's type in the server's sort order.
+fn sort_rank(value: &Bson) -> u8 {
+    match value {
+        Bson::MinKey => 0,
+        Bson::Null | Bson::Undefined => 1,
+        Bson::Double(_) | Bson::Int32(_) | Bson::Int64(_) | Bson::Decimal128(_) => 3,
+        Bson::String(_) | Bson::Symbol(_) => 4,
+        Bson::Document(_) => 5,
+        Bson::Array(_) => 6,
+        Bson::Binary(_) => 7,
+        Bson::ObjectId(_) => 8,
+        Bson::Boolean(_) => 9,
+        Bson::DateTime(_) => 10,
+        Bson::Timestamp(_) => 11,
+        Bson::RegularExpression(_) => 12,
+        Bson::DbPointer(_) => 13,
+        Bson::JavaScriptCode(_) => 14,
+        Bson::JavaScriptCodeWithScope(_) => 15,
+        Bson::MaxKey => 255,
+    }
+}
+
+/// An integer value as an i64.
+fn as_i64(value: &Bson) -> i64 {
+    match value {
+        Bson::Int32(n) => i64::from(*n),
+        Bson::Int64(n) => *n,
+        _ => 0,
+    }
+}
+
+/// A numeric value as an f64, with decimals parsed from their text.
+fn as_f64(value: &Bson) -> f64 {
+    match value {
+        Bson::Double(n) => *n,
+        Bson::Int32(n) => f64::from(*n),
+        Bson::Int64(n) => *n as f64,
+        Bson::Decimal128(n) => n.to_string().parse().unwrap_or(f64::NAN),
+        _ => f64::NAN,
+    }
+}
+
+/// The `$type` alias of a value's BSON type.
+fn type_name(value: &Bson) -> &'static str {
+    match value {
+        Bson::Double(_) => "double",
+        Bson::String(_) => "string",
+        Bson::Array(_) => "array",
+        Bson::Document(_) => "object",
+        Bson::Boolean(_) => "bool",
+        Bson::Null => "null",
+        Bson::RegularExpression(_) => "regex",
+        Bson::JavaScriptCode(_) | Bson::JavaScriptCodeWithScope(_) => "javascript",
+        Bson::Int32(_) => "int",
+        Bson::Int64(_) => "long",
+        Bson::Timestamp(_) => "timestamp",
+        Bson::Binary(_) => "binData",
+        Bson::ObjectId(_) => "objectId",
+        Bson::DateTime(_) => "date",
+        Bson::Symbol(_) => "symbol",
+        Bson::Decimal128(_) => "decimal",
+        Bson::Undefined => "undefined",
+        Bson::MaxKey => "maxKey",
+        Bson::MinKey => "minKey",
+        Bson::DbPointer(_) => "dbPointer",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bson::{doc, oid::ObjectId};
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct User {
+        name: String,
+        age: i32,
+    }
+
+    #[test]
+    fn test_document_round_trip() {
+        let user = User {
+            name: "Ada".to_string(),
+            age: 36,
+        };
+        let document = to_document(&user).unwrap();
+        assert_eq!(document, doc! { "name": "Ada", "age": 36 });
+        assert_eq!(from_document::<User>(document).unwrap(), user);
+
+        assert!(to_document(&42).is_err());
+        let err = from_document::<User>(doc! { "name": "Ada" }).unwrap_err();
+        assert!(matches!(err, crate::MongoError::Bson(_)));
+    }
+
+    #[test]
+    fn test_redact() {
+        let filter = doc! {
+            "_id": ObjectId::new(),
+            "email": "a@example.com",
+            "age": { "$gte": 21, "$lt": 65_i64 },
+            "tags": { "$in": ["x", "y"] },
+            "$or": [{ "deleted": null }, { "score": 1.5 }],
+        };
+        assert_eq!(
+            redact(&filter),
+            doc! {
+                "_id": "objectId",
+                "email": "string",
+                "age": { "$gte": "int", "$lt": "long" },
+                "tags": { "$in": ["string", "string"] },
+                "$or": [{ "deleted": "null" }, { "score": "double" }],
+            }
+        );
+    }
+
+    #[test]
+    fn test_redact_json() {
+        let filter = serde_json::json!({
+            "_id": { "$oid": "507f1f77bcf86cd799439011" },
+            "email": "a@example.com",
+            "age": { "$gte": 21 },
+            "deleted": null,
+        });
+        assert_eq!(
+            redact_json(&filter, None),
+            serde_json::json!({
+                "_id": "objectId",
+                "email": "string",
+                "age": { "$gte": "long" },
+                "deleted": "null",
+            })
+        );
+    }
+
+    #[test]
+    fn test_compare_bson() {
+        let ascending = [
+            Bson::MinKey,
+            Bson::Null,
+            Bson::Int32(-3),
+            Bson::Double(1.5),
+            Bson::Int64(2),
+            Bson::String("a".into()),
+            Bson::String("b".into()),
+            Bson::Document(doc! { "a": 1 }),
+            Bson::Array(vec![Bson::Int32(1)]),
+            Bson::ObjectId(ObjectId::from_bytes([0; 12])),
+            Bson::Boolean(false),
+            Bson::Boolean(true),
+            Bson::DateTime(bson::DateTime::from_millis(0)),
+            Bson::MaxKey,
+        ];
+        for pair in ascending.windows(2) {
+            assert_eq!(compare_bson(&pair[0], &pair[1]), Ordering::Less, "{:?}", pair);
+            assert_eq!(compare_bson(&pair[1], &pair[0]), Ordering::Greater, "{:?}", pair);
+        }
+        assert_eq!(compare_bson(&Bson::Int32(7), &Bson::Double(7.0)), Ordering::Equal);
+        let (max, below) = (Bson::Int64(i64::MAX), Bson::Int64(i64::MAX - 1));
+        assert_eq!(compare_bson(&max, &below), Ordering::Greater);
+    }
+}
@@ -3,15 +3,15 @@
 //! These tests verify the MongoDB-compatible API works correctly
 //! by testing all major functionality with mocked RPC responses.
 
-use bson::{doc, oid::ObjectId, Document};
+use bson::{doc, oid::ObjectId};
 use mongo_do::{
     client::{ClientOptions, ClientOptionsBuilder},
     collection::{
-        DeleteResult, FindOptions, FindOptionsBuilder, InsertManyResult, InsertOneResult,
-        UpdateOptions, UpdateOptionsBuilder, UpdateResult,
+        DeleteResult, FindOptions, InsertManyResult, InsertOneResult, UpdateOptions,
+        UpdateResult,
     },
     cursor::Cursor,
-    db::{CreateCollectionOptions, CreateCollectionOptionsBuilder},
+    db::CreateCollectionOptions,
     error::{ErrorKind, MongoError},
     prelude::*,
 };
@@ -216,9 +216,8 @@ mod collection_tests {
     #[test]
     fn test_insert_one_result() {
         let oid = ObjectId::new();
-        let result = InsertOneResult {
-            inserted_id: bson::Bson::ObjectId(oid),
-        };
+        let mut result = InsertOneResult::default();
+        result.inserted_id = bson::Bson::ObjectId(oid);
         assert_eq!(result.inserted_id.as_object_id().unwrap(), oid);
     }
 
@@ -237,11 +236,8 @@ mod collection_tests {
     #[test]
     fn test_update_result_with_upsert() {
         let oid = ObjectId::new();
-        let result = UpdateResult {
-            matched_count: 0,
-            modified_count: 0,
-            upserted_id: Some(bson::Bson::ObjectId(oid)),
-        };
+        let mut result = UpdateResult::default();
+        result.upserted_id = Some(bson::Bson::ObjectId(oid));
 
         assert_eq!(result.matched_count, 0);
         assert_eq!(result.modified_count, 0);
@@ -250,11 +246,9 @@ mod collection_tests {
 
     #[test]
     fn test_update_result_no_upsert() {
-        let result = UpdateResult {
-            matched_count: 5,
-            modified_count: 3,
-            upserted_id: None,
-        };
+        let mut result = UpdateResult::default();
+        result.matched_count = 5;
+        result.modified_count = 3;
 
         assert_eq!(result.matched_count, 5);
         assert_eq!(result.modified_count, 3);
@@ -263,7 +257,8 @@ mod collection_tests {
 
     #[test]
     fn test_delete_result() {
-        let result = DeleteResult { deleted_count: 42 };
+        let mut result = DeleteResult::default();
+        result.deleted_count = 42;
         assert_eq!(result.deleted_count, 42);
     }
 }
@@ -521,15 +516,15 @@ mod bson_tests {
         let document = doc! {
             "string": "value",
             "number": 42,
-            "float": 3.14,
+            "float": 2.5,
             "boolean": true,
             "null": null,
         };
 
         assert_eq!(document.get_str("string").unwrap(), "value");
         assert_eq!(document.get_i32("number").unwrap(), 42);
-        assert_eq!(document.get_f64("float").unwrap(), 3.14);
-        assert_eq!(document.get_bool("boolean").unwrap(), true);
+        assert_eq!(document.get_f64("float").unwrap(), 2.5);
+        assert!(document.get_bool("boolean").unwrap());
         assert!(document.get("null").unwrap().as_null().is_some());
     }
 
@@ -750,7 +745,7 @@ mod integration_tests {
 
     #[test]
     fn test_aggregation_pipeline_doc() {
-        let pipeline = vec![
+        let pipeline = [
             doc! { "$match": { "status": "active" } },
             doc! { "$group": { "_id": "$category", "count": { "$sum": 1 } } },
             doc! { "$sort": { "count": -1 } },
@@ -801,7 +796,7 @@ mod prelude_tests {
     #[test]
     fn test_prelude_result_type() {
         let result: Result<i32> = Ok(42);
-        assert_eq!(result.unwrap(), 42);
+        assert_eq!(result.ok(), Some(42));
     }
 
     #[test]
@@ -0,0 +1,125 @@
+//! Tests for `#[derive(Model)]`.
+
+use bson::{doc, oid::ObjectId};
+use mongo_do::{IndexModel, Model};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Serialize, Deserialize, Model)]
+#[mongo(collection = "users", index(fields(email), unique))]
+#[mongo(index(fields(team, created_at = -1), name = "team_recent", sparse))]
+struct User {
+    #[serde(rename = "_id")]
+    key: ObjectId,
+    email: String,
+    team: String,
+    created_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Model)]
+#[mongo(collection = "sessions", index(fields(seen), expire_after_secs = 3600))]
+struct Session {
+    #[serde(rename = "_id")]
+    id: String,
+    seen: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Model)]
+#[serde(rename_all = "camelCase")]
+#[mongo(collection = "orders", index(fields(customer_id, placed_at = -1)))]
+#[mongo(index(fields(r#ref), unique))]
+struct Order {
+    #[serde(rename = "_id")]
+    id: ObjectId,
+    customer_id: String,
+    placed_at: i64,
+    #[serde(rename = "reference")]
+    r#ref: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Model)]
+#[mongo(collection = "audit")]
+struct AuditEntry {
+    #[mongo(id)]
+    #[serde(rename = "_id")]
+    sequence: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Model)]
+#[mongo(collection = "drafts")]
+struct Draft {
+    #[serde(rename = "_id")]
+    id: Option<ObjectId>,
+}
+
+fn keys(indexes: &[IndexModel]) -> Vec<bson::Document> {
+    indexes.iter().map(|index| index.keys.clone()).collect()
+}
+
+#[test]
+fn test_derive_collection_and_id() {
+    let key = ObjectId::new();
+    let user = User {
+        key,
+        email: "ada@example.com".to_string(),
+        team: "core".to_string(),
+        created_at: 0,
+    };
+    assert_eq!(User::COLLECTION, "users");
+    assert_eq!(user.id(), &key);
+
+    let session = Session {
+        id: "s1".to_string(),
+        seen: 0,
+    };
+    assert_eq!(Session::COLLECTION, "sessions");
+    assert_eq!(session.id(), "s1");
+
+    assert_eq!(*AuditEntry { sequence: 9 }.id(), 9);
+    assert!(AuditEntry::indexes().is_empty());
+}
+
+#[test]
+fn test_derive_indexes() {
+    let indexes = User::indexes();
+    assert_eq!(
+        keys(&indexes),
+        vec![doc! { "email": 1 }, doc! { "team": 1, "created_at": -1 }]
+    );
+    let unique = indexes[0].options.as_ref().unwrap();
+    assert_eq!(unique.unique, Some(true));
+    assert_eq!(indexes[0].name(), "email_1");
+    let team = indexes[1].options.as_ref().unwrap();
+    assert_eq!(team.sparse, Some(true));
+    assert_eq!(indexes[1].name(), "team_recent");
+
+    // Keys are the names serde stores the fields under.
+    assert_eq!(
+        keys(&Order::indexes()),
+        vec![doc! { "customerId": 1, "placedAt": -1 }, doc! { "reference": 1 }]
+    );
+
+    let indexes = Session::indexes();
+    let ttl = indexes[0].options.as_ref().unwrap();
+    assert_eq!(ttl.expire_after, Some(Duration::from_secs(3600)));
+}
+
+#[test]
+fn test_derive_set_id() {
+    let id = ObjectId::new();
+    let mut draft = Draft { id: None };
+    draft.set_id(bson::Bson::ObjectId(id));
+    assert_eq!(draft.id, Some(id));
+
+    // The ID is kept when the generated one does not fit its type.
+    draft.set_id(bson::Bson::Int32(1));
+    assert_eq!(draft.id, Some(id));
+
+    // Without an `Option` ID there is nothing to set.
+    let mut session = Session {
+        id: "s1".to_string(),
+        seen: 0,
+    };
+    session.set_id(bson::Bson::ObjectId(id));
+    assert_eq!(session.id, "s1");
+}